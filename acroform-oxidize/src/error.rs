@@ -9,6 +9,16 @@ use std::io;
 pub enum PdfError {
     /// Error parsing or reading PDF
     ParseError(String),
+    /// Error reported by the oxidize-pdf parser, with where it occurred
+    Parse {
+        location: Location,
+        source: oxidize_pdf::parser::ParseError,
+    },
+    /// Error reported by oxidize-pdf, with where it occurred
+    Pdf {
+        location: Location,
+        source: oxidize_pdf::error::PdfError,
+    },
     /// Missing required entry in a PDF dictionary
     MissingEntry {
         typ: &'static str,
@@ -20,10 +30,57 @@ pub enum PdfError {
     Other(String),
 }
 
+/// Where in the document an error occurred, as far as it is known.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    /// Object number and generation of the object that failed to load
+    pub object: Option<(u32, u16)>,
+    /// Dictionary keys leading to the value
+    pub path: Vec<String>,
+    /// Byte offset in the file
+    pub offset: Option<usize>,
+}
+
+impl PdfError {
+    /// Where in the document this error occurred, if known.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            PdfError::Parse { location, .. } | PdfError::Pdf { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut details = vec![];
+        if !self.path.is_empty() {
+            details.push(self.path.join("/"));
+        }
+        if let Some((id, gen)) = self.object {
+            details.push(format!("object {} {} R", id, gen));
+        }
+        if let Some(offset) = self.offset {
+            details.push(format!("offset {}", offset));
+        }
+        write!(f, "{}", details.join(", "))
+    }
+}
+
 impl fmt::Display for PdfError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PdfError::ParseError(msg) => write!(f, "PDF parse error: {}", msg),
+            PdfError::Parse { location, source } if *location == Location::default() => {
+                write!(f, "PDF parse error: {}", source)
+            }
+            PdfError::Parse { location, source } => {
+                write!(f, "PDF parse error at {}: {}", location, source)
+            }
+            PdfError::Pdf { location, source } if *location == Location::default() => {
+                write!(f, "PDF error: {}", source)
+            }
+            PdfError::Pdf { location, source } => write!(f, "PDF error at {}: {}", location, source),
             PdfError::MissingEntry { typ, field } => {
                 write!(f, "Missing required field '{}' in {} dictionary", field, typ)
             }
@@ -33,7 +90,16 @@ impl fmt::Display for PdfError {
     }
 }
 
-impl std::error::Error for PdfError {}
+impl std::error::Error for PdfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PdfError::Parse { source, .. } => Some(source),
+            PdfError::Pdf { source, .. } => Some(source),
+            PdfError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl From<io::Error> for PdfError {
     fn from(err: io::Error) -> Self {
@@ -41,15 +107,29 @@ impl From<io::Error> for PdfError {
     }
 }
 
-// Convert from oxidize-pdf errors
+// Convert from oxidize-pdf errors, keeping whatever they tell about the location
 impl From<oxidize_pdf::error::PdfError> for PdfError {
     fn from(err: oxidize_pdf::error::PdfError) -> Self {
-        PdfError::ParseError(err.to_string())
+        use oxidize_pdf::error::PdfError as E;
+        let location = match err {
+            E::InvalidObjectReference(id, gen) => Location { object: Some((id, gen)), ..Location::default() },
+            _ => Location::default(),
+        };
+        PdfError::Pdf { location, source: err }
     }
 }
 
 impl From<oxidize_pdf::parser::ParseError> for PdfError {
     fn from(err: oxidize_pdf::parser::ParseError) -> Self {
-        PdfError::ParseError(err.to_string())
+        use oxidize_pdf::parser::ParseError as E;
+        let location = match err {
+            E::SyntaxError { position, .. } | E::CharacterEncodingError { position, .. } => {
+                Location { offset: Some(position), ..Location::default() }
+            }
+            E::InvalidReference(id, gen) => Location { object: Some((id, gen)), ..Location::default() },
+            E::MissingKey(ref key) => Location { path: vec![key.clone()], ..Location::default() },
+            _ => Location::default(),
+        };
+        PdfError::Parse { location, source: err }
     }
 }
@@ -65,6 +65,6 @@ mod value;
 mod api;
 
 pub use api::AcroFormDocument;
-pub use error::PdfError;
+pub use error::{Location, PdfError};
 pub use field::{FormField, FieldType};
 pub use value::FieldValue;
@@ -17,3 +17,13 @@ fn test_list_fields() {
     // We'll implement proper field reading later
     assert!(fields.is_ok());
 }
+
+#[test]
+fn test_parse_error_keeps_source() {
+    use std::error::Error;
+
+    let err = AcroFormDocument::from_bytes(b"not a pdf".to_vec()).err().unwrap();
+    assert!(matches!(err, acroform_oxidize::PdfError::Parse { .. }), "{:?}", err);
+    assert!(err.source().is_some());
+    assert!(err.location().is_some());
+}
@@ -1,10 +1,22 @@
+use pdf::crypt::Permissions;
+use pdf::backend::{Backend, Revision, SeekBackend};
 use pdf::error::PdfError;
-use pdf::file::{CachedFile, FileOptions};
-use pdf::object::{FieldDictionary, FieldType, RcRef, Updater, Annot};
-use pdf::primitive::{Primitive, PdfString, Dictionary};
+use pdf::file::{CachedFile, FileOptions, SaveOptions};
+use pdf::pubsec::Recipient;
+use pdf::object::{FieldDictionary, FieldType, PlainRef, RcRef, Resolve, Updater, Annot};
+use pdf::primitive::{Primitive, PdfString, Dictionary, Name};
 use std::collections::HashMap;
+#[cfg(feature = "threads")]
+use std::collections::HashSet;
+#[cfg(feature = "threads")]
+use pdf::object::Ref;
+use std::io::{Read, Seek, Write};
 use std::path::Path;
 
+use crate::appearance::text_appearance;
+use crate::error::FillError;
+use crate::font::EmbeddedFont;
+use crate::label::{field_labels, FieldLabel};
 use crate::field::{FieldDictionaryExt, InteractiveFormDictionaryExt};
 
 /// High-level representation of a form field
@@ -25,6 +37,9 @@ pub struct FormField {
     pub flags: u32,
     /// The tooltip/alternate name of the field (TU entry in PDF specification)
     pub tooltip: Option<String>,
+    /// The caption guessed from the page text, only set by
+    /// [`AcroFormDocument::fields_with_labels`]
+    pub label: Option<FieldLabel>,
 }
 
 /// Typed representation of field values
@@ -68,19 +83,8 @@ impl FieldValue {
     /// This is primarily an internal method used when writing field values to PDFs.
     pub fn to_primitive(&self) -> Primitive {
         match self {
-            FieldValue::Text(s) => {
-                // Encode the string as UTF-16BE with BOM (0xFE 0xFF) per PDF spec
-                let mut v = Vec::with_capacity(2 + s.len() * 2);
-                // BOM for UTF-16BE
-                v.push(0xFE);
-                v.push(0xFF);
-                // encode_utf16 yields native u16 code units; write them as big-endian bytes
-                for cu in s.encode_utf16() {
-                    v.push((cu >> 8) as u8);
-                    v.push((cu & 0xFF) as u8);
-                }
-                Primitive::String(PdfString::new(v.into()))
-            },
+            // PDFDocEncoding when it has all the characters, UTF-16BE otherwise
+            FieldValue::Text(s) => Primitive::String(PdfString::from_text(s)),
             FieldValue::Integer(i) => Primitive::Integer(*i),
             FieldValue::Choice(s) => Primitive::Name(s.as_str().into()),
             FieldValue::Boolean(b) => Primitive::Boolean(*b),
@@ -88,6 +92,24 @@ impl FieldValue {
     }
 }
 
+/// What `fill` does with text values longer than the `/MaxLen` of their field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaxLenPolicy {
+    /// Keep the first `/MaxLen` characters
+    #[default]
+    Truncate,
+    /// Fail with [`FillError::ValueTooLong`]
+    Reject,
+}
+
+/// Options for filling in form fields
+#[derive(Debug, Clone, Default)]
+pub struct FillOptions {
+    /// Handling of text values longer than the `/MaxLen` of their field,
+    /// such as comb fields for dates or ID numbers
+    pub max_len: MaxLenPolicy,
+}
+
 /// Main API for working with PDF forms
 ///
 /// This struct provides the primary interface for loading PDF files,
@@ -111,8 +133,11 @@ impl FieldValue {
 /// values.insert("name".to_string(), FieldValue::Text("John".to_string()));
 /// doc.fill_and_save(values, "filled.pdf").unwrap();
 /// ```
-pub struct AcroFormDocument {
-    file: CachedFile<Vec<u8>>,
+pub struct AcroFormDocument<B = Vec<u8>> {
+    file: CachedFile<B>,
+    ignore_permissions: bool,
+    fill_options: FillOptions,
+    fonts: Vec<EmbeddedFont>,
 }
 
 impl AcroFormDocument {
@@ -137,7 +162,30 @@ impl AcroFormDocument {
     /// ```
     pub fn from_pdf(path: impl AsRef<Path>) -> Result<Self, PdfError> {
         let file = FileOptions::cached().open(path)?;
-        Ok(AcroFormDocument { file })
+        Ok(AcroFormDocument::new(file))
+    }
+
+    /// Load an encrypted PDF file from the given path using a password
+    ///
+    /// Either the user or the owner password may be given. Opening a document
+    /// with the owner password grants all permissions, regardless of the
+    /// document's permission bits.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PdfError::InvalidPassword` if the password is wrong, or another
+    /// `PdfError` if the file cannot be opened or parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use acroform::AcroFormDocument;
+    ///
+    /// let doc = AcroFormDocument::from_pdf_with_password("form.pdf", b"secret").unwrap();
+    /// ```
+    pub fn from_pdf_with_password(path: impl AsRef<Path>, password: &[u8]) -> Result<Self, PdfError> {
+        let file = FileOptions::cached().password(password).open(path)?;
+        Ok(AcroFormDocument::new(file))
     }
     
     /// Load a PDF from a byte vector
@@ -163,13 +211,159 @@ impl AcroFormDocument {
     /// ```
     pub fn from_bytes(data: Vec<u8>) -> Result<Self, PdfError> {
         let file = FileOptions::cached().load(data)?;
-        Ok(AcroFormDocument { file })
+        Ok(AcroFormDocument::new(file))
+    }
+
+    /// Load an encrypted PDF from a byte vector using a password
+    ///
+    /// See [`AcroFormDocument::from_pdf_with_password`].
+    pub fn from_bytes_with_password(data: Vec<u8>, password: &[u8]) -> Result<Self, PdfError> {
+        let file = FileOptions::cached().password(password).load(data)?;
+        Ok(AcroFormDocument::new(file))
+    }
+
+    /// Load a PDF encrypted to recipients' certificates from a byte vector
+    ///
+    /// Used for documents protected by the public-key security handler
+    /// (`/Adobe.PubSec`). The `recipient` holds the private key and certificate.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PdfError::NotRecipient` if the document is not encrypted for
+    /// the given certificate, or another `PdfError` if the data cannot be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use acroform::{AcroFormDocument, Recipient};
+    /// use std::fs;
+    ///
+    /// let recipient = Recipient::from_pem(
+    ///     &fs::read_to_string("recipient.key").unwrap(),
+    ///     &fs::read_to_string("recipient.crt").unwrap(),
+    /// ).unwrap();
+    /// let doc = AcroFormDocument::from_bytes_with_recipient(fs::read("form.pdf").unwrap(), &recipient).unwrap();
+    /// ```
+    pub fn from_bytes_with_recipient(data: Vec<u8>, recipient: &Recipient) -> Result<Self, PdfError> {
+        let file = FileOptions::cached().recipient(recipient).load(data)?;
+        Ok(AcroFormDocument::new(file))
+    }
+
+}
+
+impl<R: Read + Seek> AcroFormDocument<SeekBackend<R>> {
+    /// Load a PDF from a reader without reading it into memory
+    ///
+    /// Only the parts of the file needed for the form are read, through a
+    /// cache of bounded size. This suits large documents such as scanned
+    /// packets. Use [`AcroFormDocument::fill_to_writer`] to save the filled document.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PdfError` if the data cannot be read or parsed as a valid PDF.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use acroform::{AcroFormDocument, FieldValue};
+    /// use std::collections::HashMap;
+    /// use std::fs::File;
+    /// use std::io::BufWriter;
+    ///
+    /// let mut doc = AcroFormDocument::from_reader(File::open("packet.pdf").unwrap()).unwrap();
+    /// let mut values = HashMap::new();
+    /// values.insert("name".to_string(), FieldValue::Text("John".to_string()));
+    /// let out = BufWriter::new(File::create("filled.pdf").unwrap());
+    /// doc.fill_to_writer(values, out).unwrap();
+    /// ```
+    pub fn from_reader(reader: R) -> Result<Self, PdfError> {
+        let file = FileOptions::cached().load(SeekBackend::new(reader)?)?;
+        Ok(AcroFormDocument::new(file))
+    }
+}
+
+impl<B: Backend> AcroFormDocument<B> {
+    fn new(file: CachedFile<B>) -> Self {
+        AcroFormDocument { file, ignore_permissions: false, fill_options: FillOptions::default(), fonts: Vec::new() }
+    }
+
+    /// The permissions granted by the document
+    ///
+    /// Unencrypted documents and documents opened with the owner password
+    /// grant all permissions.
+    pub fn permissions(&self) -> Permissions {
+        self.file.permissions()
+    }
+
+    /// Skip the permission checks of the modifying operations
+    ///
+    /// By default, `fill` refuses to modify a document whose permission bits
+    /// forbid filling in form fields (bit 6 and bit 9 both clear), like a compliant viewer.
+    /// Only set this if you are entitled to modify the document by other means.
+    pub fn ignore_permissions(&mut self, ignore: bool) {
+        self.ignore_permissions = ignore;
+    }
+
+    /// Set how `fill` and `save_compacted` write the document
+    ///
+    /// For example, `compress_above` compresses new streams such as appearance streams,
+    /// and `object_streams` packs the updated objects into compressed object streams.
+    pub fn set_save_options(&mut self, options: SaveOptions) {
+        self.file.set_save_options(options);
+    }
+
+    /// Set how `fill` treats values that don't suit their field
+    pub fn set_fill_options(&mut self, options: FillOptions) {
+        self.fill_options = options;
+    }
+
+    /// Register a TrueType or OpenType font file for values the fields' fonts can't show
+    ///
+    /// Fonts like `/Helv` only cover Western European text. When a text field's
+    /// font lacks a glyph of its new value, the appearance is set in the first
+    /// registered font that has them all. That font is embedded and added to the
+    /// form's default resources when it is first used.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PdfError` if the file cannot be read or is not a font.
+    pub fn register_font(&mut self, path: impl AsRef<Path>) -> Result<(), PdfError> {
+        self.register_font_data(std::fs::read(path)?)
+    }
+
+    /// Register a TrueType or OpenType font from memory, see [`register_font`](Self::register_font)
+    pub fn register_font_data(&mut self, data: Vec<u8>) -> Result<(), PdfError> {
+        let mut font = EmbeddedFont::new(data)?;
+        // the resource name must not clash with the form's fonts or other registered ones
+        let taken = |name: &Name, fonts: &[EmbeddedFont]| {
+            fonts.iter().any(|f| &f.name == name)
+                || self.file.get_root().forms.as_ref()
+                    .and_then(|forms| forms.dr.as_ref())
+                    .is_some_and(|dr| dr.fonts.contains_key(name))
+        };
+        let mut n = 1;
+        while taken(&font.name, &self.fonts) {
+            font.name = format!("{}_{}", font.base_font(), n).into();
+            n += 1;
+        }
+        self.fonts.push(font);
+        Ok(())
+    }
+
+    fn check_fill_permission(&self) -> Result<(), PdfError> {
+        let permissions = self.permissions();
+        if self.ignore_permissions || permissions.can_fill_forms() {
+            Ok(())
+        } else {
+            Err(PdfError::PermissionDenied { required: Permissions::FILL_FORMS })
+        }
     }
     
     /// Get all form fields in the PDF
     ///
     /// Returns a vector of all fillable form fields in the document.
     /// Each field includes its name, type, current value, and flags.
+    /// With the `threads` feature, `prefetch` loads the fields on all cores first.
     ///
     /// # Errors
     ///
@@ -206,6 +400,7 @@ impl AcroFormDocument {
                         default_value,
                         flags: field.flags,
                         tooltip,
+                        label: None,
                     });
                 }
             }
@@ -213,43 +408,58 @@ impl AcroFormDocument {
         
         Ok(result)
     }
-    
-    /// Fill form fields with provided values and return the PDF as a byte vector
-    ///
-    /// Updates the specified form fields with new values and returns the modified
-    /// PDF as an in-memory byte vector. Fields not specified in the `values` map remain unchanged.
-    ///
-    /// This method performs all operations in-memory without writing to disk,
-    /// making it suitable for web services, stream processing, or other scenarios
-    /// where disk I/O should be avoided.
-    ///
-    /// # Arguments
-    ///
-    /// * `values` - A map from field names to their new values
+
+    /// Get all form fields, each with a [`FormField::label`] guessed from the page text
     ///
-    /// # Errors
+    /// Like [`AcroFormDocument::fields`], but each widget is matched with the closest text
+    /// to its left or above it on its page. The label of a field with several widgets is
+    /// the most confident one. Fields without text nearby have no label, and neither do
+    /// those on pages whose text can't be read.
     ///
-    /// Returns `PdfError` if:
-    /// - The PDF does not contain an AcroForm dictionary
-    /// - Field updates cannot be applied
+    /// This extracts the text of every page with widgets, so it is slower than `fields`.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use acroform::{AcroFormDocument, FieldValue};
-    /// use std::collections::HashMap;
+    /// use acroform::AcroFormDocument;
     ///
-    /// let mut doc = AcroFormDocument::from_pdf("form.pdf").unwrap();
-    /// let mut values = HashMap::new();
-    /// values.insert("firstName".to_string(), FieldValue::Text("John".to_string()));
-    /// values.insert("lastName".to_string(), FieldValue::Text("Doe".to_string()));
-    /// let filled_pdf = doc.fill(values).unwrap();
-    /// // Now you can send `filled_pdf` over HTTP, store it in a database, etc.
+    /// let doc = AcroFormDocument::from_pdf("form.pdf").unwrap();
+    /// for field in doc.fields_with_labels().unwrap() {
+    ///     if let Some(label) = field.label.filter(|l| l.confidence > 0.5) {
+    ///         println!("{}: {}", field.name, label.text);
+    ///     }
+    /// }
     /// ```
-    pub fn fill(
+    pub fn fields_with_labels(&self) -> Result<Vec<FormField>, PdfError> {
+        let mut labels = field_labels(&self.file)?;
+        Ok(self.fields()?.into_iter()
+            .map(|field| FormField { label: labels.remove(&field.name), ..field })
+            .collect())
+    }
+    
+    /// Fill form fields with provided values and write the PDF to `out`
+    ///
+    /// Like [`AcroFormDocument::fill`], but the original document is copied to
+    /// `out` in chunks followed by the update, so it works for documents opened
+    /// with [`AcroFormDocument::from_reader`] without holding them in memory.
+    ///
+    /// # Errors
+    ///
+    /// See [`AcroFormDocument::fill`]. Errors writing to `out` are returned as well.
+    pub fn fill_to_writer(
         &mut self,
         values: HashMap<String, FieldValue>,
-    ) -> Result<Vec<u8>, PdfError> {
+        out: impl Write,
+    ) -> Result<(), FillError> {
+        self.update_fields(&values)?;
+        self.write_fonts()?;
+        Ok(self.file.save_to_writer(out)?)
+    }
+
+    fn update_fields(&mut self, values: &HashMap<String, FieldValue>) -> Result<(), FillError> {
+        self.check_fill_permission()?;
+        let values = &self.limit_lengths(values)?;
+
         // Collect field references and their values to update
         let mut field_updates: Vec<(pdf::object::PlainRef, FieldDictionary)> = Vec::new();
         let mut annotation_updates: Vec<(pdf::object::PlainRef, Annot)> = Vec::new();
@@ -264,7 +474,7 @@ impl AcroFormDocument {
             
             // Find fields to update
             let resolver = self.file.resolver();
-            for (name, value) in &values {
+            for (name, value) in values {
                 if let Some(field) = forms.find_field_by_name(&name, &resolver)? {
                     let field_ref = field.get_ref();
                     let mut updated_field = (*field).clone();
@@ -319,7 +529,249 @@ impl AcroFormDocument {
         for (annot_ref, updated_annot) in annotation_updates {
             self.file.update(annot_ref, updated_annot)?;
         }
-        
+
+        // Viewers show the appearance streams, not the values, so text fields get new ones.
+        // This has to come last, the updates above would bring back the old /AP.
+        Ok(self.update_appearances(values)?)
+    }
+
+    /// Apply the `/MaxLen` of the text fields in `values` according to the fill options
+    fn limit_lengths(&self, values: &HashMap<String, FieldValue>) -> Result<HashMap<String, FieldValue>, FillError> {
+        let forms = self.file.get_root().forms.as_ref()
+            .ok_or_else(|| PdfError::MissingEntry {
+                typ: "Catalog",
+                field: "AcroForm".into()
+            })?;
+        let resolver = self.file.resolver();
+        let mut limited = values.clone();
+        for (name, value) in limited.iter_mut() {
+            let text = match value {
+                FieldValue::Text(text) => text,
+                _ => continue,
+            };
+            let field = match forms.find_field_by_name(name, &resolver)? {
+                Some(field) => field,
+                None => continue,
+            };
+            // /MaxLen is inheritable, the parent chain was checked for cycles by the lookup
+            let mut max_len = field.max_len;
+            let mut parent = field.parent;
+            while let (None, Some(parent_ref)) = (max_len, parent) {
+                let parent_field = resolver.get(parent_ref)?;
+                max_len = parent_field.max_len;
+                parent = parent_field.parent;
+            }
+            match max_len {
+                Some(max_len) if text.chars().count() > max_len as usize => match self.fill_options.max_len {
+                    MaxLenPolicy::Truncate => *text = text.chars().take(max_len as usize).collect(),
+                    MaxLenPolicy::Reject => return Err(FillError::ValueTooLong { field: name.clone(), max_len }),
+                },
+                _ => {}
+            }
+        }
+        Ok(limited)
+    }
+
+    /// Generate the appearance streams of the text fields in `values`
+    ///
+    /// Widgets whose appearance can't be generated keep the old one,
+    /// and `/NeedAppearances` asks viewers to generate it.
+    fn update_appearances(&mut self, values: &HashMap<String, FieldValue>) -> Result<(), PdfError> {
+        let mut appearances = Vec::new();
+        let mut need_appearances = false;
+        {
+            let forms = self.file.get_root().forms.as_ref()
+                .ok_or_else(|| PdfError::MissingEntry {
+                    typ: "Catalog",
+                    field: "AcroForm".into()
+                })?;
+            let resolver = self.file.resolver();
+
+            // sorted, so the output does not depend on the order of the map
+            let mut texts: Vec<_> = values.iter()
+                .filter_map(|(name, value)| match value {
+                    FieldValue::Text(text) => Some((name, text)),
+                    _ => None,
+                })
+                .collect();
+            texts.sort();
+
+            let mut widgets = Vec::new();
+            let mut fields = HashMap::new();
+            for (name, text) in texts {
+                let field = match forms.find_field_by_name(name, &resolver)? {
+                    Some(field) if field.typ == Some(FieldType::Text) => field,
+                    _ => continue,
+                };
+                for widget_ref in widget_refs(&field) {
+                    widgets.push((widget_ref, field.clone(), text));
+                }
+                fields.insert(name.as_str(), (field, text));
+            }
+
+            // some files have page annotations that are copies of the fields, like in update_fields
+            for page in self.file.pages() {
+                let annots = page?.annotations.load(&resolver)?;
+                for annot_ref in annots.data().iter() {
+                    let (annot_ref, name) = match (annot_ref.as_ref(), annot_ref.other.get("T")) {
+                        (Some(r), Some(Primitive::String(name))) => (r.get_inner(), name.to_string_lossy()),
+                        _ => continue,
+                    };
+                    if let Some((field, text)) = fields.get(name.as_str()) {
+                        if !widgets.iter().any(|(r, _, _)| *r == annot_ref) {
+                            widgets.push((annot_ref, field.clone(), text));
+                        }
+                    }
+                }
+            }
+
+            for (widget_ref, field, text) in widgets {
+                let widget = resolver.resolve(widget_ref)?.into_dictionary()?;
+                match text_appearance(forms, &field, &widget, text, &self.fonts, &resolver) {
+                    Ok(Some(appearance)) => appearances.push((widget_ref, widget, appearance)),
+                    Ok(None) => {}
+                    Err(_) => need_appearances = true,
+                }
+            }
+        }
+
+        // standard fonts added for fields whose font is missing from /DR, shared by all appearances
+        let mut fonts = HashMap::new();
+        for (widget_ref, mut widget, appearance) in appearances {
+            let stream = match appearance.write(&mut fonts, &mut self.fonts, &mut self.file) {
+                Ok(stream) => stream,
+                Err(_) => {
+                    need_appearances = true;
+                    continue;
+                }
+            };
+            let mut ap = Dictionary::new();
+            ap.insert("N", Primitive::Reference(stream));
+            widget.insert("AP", ap);
+            self.file.update(widget_ref, Primitive::Dictionary(widget))?;
+        }
+        if need_appearances {
+            set_form_entry(&mut self.file, &["AcroForm"], "NeedAppearances".into(), Primitive::Boolean(true))?;
+        }
+        Ok(())
+    }
+
+    /// Write the registered fonts shown since the last save, subset to the glyphs shown so far
+    fn write_fonts(&mut self) -> Result<(), PdfError> {
+        for font in &mut self.fonts {
+            if let Some(font_ref) = font.write(&mut self.file)? {
+                set_form_entry(&mut self.file, &["AcroForm", "DR", "Font"], font.name.clone(), Primitive::Reference(font_ref))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Set `key` in the dictionary at `path` from the catalog, such as a font in the
+/// default resources of the form at `["AcroForm", "DR", "Font"]`
+///
+/// The dictionaries on the path may each be direct or indirect, the
+/// innermost indirect one is updated. Missing ones are created.
+fn set_form_entry<B: Backend>(file: &mut CachedFile<B>, path: &[&str], key: Name, value: Primitive) -> Result<(), PdfError> {
+    let root = file.trailer.root.get_ref().get_inner();
+    let mut target = root;
+    let mut depth = 0;
+    {
+        let resolver = file.resolver();
+        let mut dict = resolver.resolve(root)?.into_dictionary()?;
+        for (i, step) in path.iter().enumerate() {
+            match dict.get(step) {
+                Some(&Primitive::Reference(r)) => {
+                    target = r;
+                    depth = i + 1;
+                    dict = resolver.resolve(r)?.into_dictionary()?;
+                }
+                Some(Primitive::Dictionary(d)) => dict = d.clone(),
+                _ => break,
+            }
+        }
+    }
+
+    let mut outer = file.resolver().resolve(target)?.into_dictionary()?;
+    // walk down the direct dictionaries, creating the missing ones
+    fn insert(dict: &mut Dictionary, keys: &[&str], key: Name, value: Primitive) -> Result<(), PdfError> {
+        match keys.split_first() {
+            None => {
+                dict.insert(key, value);
+                Ok(())
+            }
+            Some((step, rest)) => {
+                let mut inner = match dict.remove(step) {
+                    Some(p) => p.into_dictionary()?,
+                    None => Dictionary::new(),
+                };
+                insert(&mut inner, rest, key, value)?;
+                dict.insert(*step, inner);
+                Ok(())
+            }
+        }
+    }
+    insert(&mut outer, &path[depth..], key, value)?;
+    file.update(target, Primitive::Dictionary(outer))?;
+    Ok(())
+}
+
+/// The widget annotations of a terminal field
+///
+/// A field with a single widget is usually merged with it, otherwise the widgets are its kids.
+pub(crate) fn widget_refs(field: &RcRef<FieldDictionary>) -> Vec<PlainRef> {
+    if field.rect.is_some() || field.other.get("Subtype") == Some(&Primitive::Name("Widget".into())) {
+        return vec![field.get_ref().get_inner()];
+    }
+    field.kids.iter().map(|kid| kid.get_inner()).collect()
+}
+
+impl AcroFormDocument {
+    /// Fill form fields with provided values and return the PDF as a byte vector
+    ///
+    /// Updates the specified form fields with new values and returns the modified
+    /// PDF as an in-memory byte vector. Fields not specified in the `values` map remain unchanged.
+    ///
+    /// This method performs all operations in-memory without writing to disk,
+    /// making it suitable for web services, stream processing, or other scenarios
+    /// where disk I/O should be avoided.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - A map from field names to their new values
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FillError`] if:
+    /// - The document's permissions do not allow filling in form fields
+    ///   (`PdfError::PermissionDenied`), see [`AcroFormDocument::ignore_permissions`]
+    /// - The PDF does not contain an AcroForm dictionary
+    /// - A value is too long for its field and [`MaxLenPolicy::Reject`] is set
+    /// - Field updates cannot be applied
+    ///
+    /// Fields whose appearance can't be generated keep their old one, and the form
+    /// gets `/NeedAppearances` so viewers generate it instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use acroform::{AcroFormDocument, FieldValue};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut doc = AcroFormDocument::from_pdf("form.pdf").unwrap();
+    /// let mut values = HashMap::new();
+    /// values.insert("firstName".to_string(), FieldValue::Text("John".to_string()));
+    /// values.insert("lastName".to_string(), FieldValue::Text("Doe".to_string()));
+    /// let filled_pdf = doc.fill(values).unwrap();
+    /// // Now you can send `filled_pdf` over HTTP, store it in a database, etc.
+    /// ```
+    pub fn fill(
+        &mut self,
+        values: HashMap<String, FieldValue>,
+    ) -> Result<Vec<u8>, FillError> {
+        self.update_fields(&values)?;
+        self.write_fonts()?;
+
         // Return the file as bytes instead of saving to disk
         Ok(self.file.save()?)
     }
@@ -339,7 +791,8 @@ impl AcroFormDocument {
     ///
     /// # Errors
     ///
-    /// Returns `PdfError` if:
+    /// Returns [`FillError`] if:
+    /// - The document's permissions do not allow filling in form fields
     /// - The PDF does not contain an AcroForm dictionary
     /// - Field updates cannot be applied
     /// - The file cannot be written to the output path
@@ -360,11 +813,106 @@ impl AcroFormDocument {
         &mut self,
         values: HashMap<String, FieldValue>,
         output: impl AsRef<Path>,
-    ) -> Result<(), PdfError> {
+    ) -> Result<(), FillError> {
         let bytes = self.fill(values)?;
         std::fs::write(output, bytes)?;
         Ok(())
     }
+
+    /// Write the document as a new file without stale objects
+    ///
+    /// `fill` appends an incremental update each time it is called, so a document
+    /// that is filled repeatedly keeps growing. This writes a complete new file that
+    /// contains only the objects still reachable from the trailer, renumbered densely.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PdfError` if the document is encrypted or an object cannot be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use acroform::{AcroFormDocument, FieldValue};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut doc = AcroFormDocument::from_pdf("form.pdf").unwrap();
+    /// let mut values = HashMap::new();
+    /// values.insert("firstName".to_string(), FieldValue::Text("John".to_string()));
+    /// doc.fill(values).unwrap();
+    /// let compacted = doc.save_compacted().unwrap();
+    /// ```
+    pub fn save_compacted(&mut self) -> Result<Vec<u8>, PdfError> {
+        self.write_fonts()?;
+        self.file.save_compacted()
+    }
+}
+
+impl<B: Backend> AcroFormDocument<B> {
+
+    /// List the revisions of the document, oldest first
+    ///
+    /// The first revision is the original document. Every `fill` appends
+    /// another one as an incremental update. Each revision lists the objects
+    /// it added or changed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use acroform::AcroFormDocument;
+    ///
+    /// let doc = AcroFormDocument::from_pdf("filled.pdf").unwrap();
+    /// for revision in doc.revisions().unwrap() {
+    ///     let old = doc.open_revision(&revision).unwrap();
+    ///     println!("{:?}: {} changed objects", revision.range, revision.changed.len());
+    ///     for field in old.fields().unwrap() {
+    ///         println!("  {}: {:?}", field.name, field.current_value);
+    ///     }
+    /// }
+    /// ```
+    pub fn revisions(&self) -> Result<Vec<Revision>, PdfError> {
+        self.file.revisions()
+    }
+
+    /// Open the document as it was at an earlier revision
+    ///
+    /// The later updates are ignored, so saving the returned document rolls
+    /// them back. Encrypted documents do not need the password again.
+    pub fn open_revision(&self, revision: &Revision) -> Result<AcroFormDocument, PdfError> {
+        let file = FileOptions::cached().load_revision(&self.file, revision)?;
+        // registered fonts are not carried over, their objects belong to this revision
+        Ok(AcroFormDocument { file, ignore_permissions: self.ignore_permissions, fill_options: self.fill_options.clone(), fonts: Vec::new() })
+    }
+}
+
+#[cfg(feature = "threads")]
+impl<B: Backend + Sync> AcroFormDocument<B> {
+    /// Load the field hierarchy and the page annotations on all cores
+    ///
+    /// The objects are kept in the document's cache, so a following
+    /// [`fields`](Self::fields) or `fill` finds them already parsed.
+    /// Worth it for large documents; kids are loaded one level of the
+    /// hierarchy at a time.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PdfError` if the pages or their annotations cannot be loaded.
+    /// Broken fields are left for [`fields`](Self::fields) to report.
+    pub fn prefetch(&self) -> Result<(), PdfError> {
+        if let Some(ref forms) = self.file.get_root().forms {
+            let mut seen = HashSet::new();
+            let mut level: Vec<Ref<FieldDictionary>> = forms.fields.iter()
+                .flat_map(|field| field.kids.iter().copied())
+                .collect();
+            while !level.is_empty() {
+                level.retain(|r| seen.insert(r.get_inner()));
+                level = self.file.prefetch(&level).into_iter()
+                    .flatten()
+                    .flat_map(|field| field.kids.clone())
+                    .collect();
+            }
+        }
+        self.file.prefetch_annotations()
+    }
 }
 
 #[cfg(test)]
@@ -383,4 +931,17 @@ mod tests {
         let back = FieldValue::from_primitive(&prim).unwrap();
         assert_eq!(int, back);
     }
+
+    #[test]
+    fn test_text_value_encoding() {
+        let text = FieldValue::Text("Smith \u{2013} \u{20ac}12".to_string());
+        let prim = text.to_primitive();
+        assert_eq!(prim.as_string().unwrap().as_bytes(), b"Smith \x85 \xa012");
+        assert_eq!(FieldValue::from_primitive(&prim), Some(text));
+
+        let text = FieldValue::Text("\u{3a3}\u{3bc}\u{3af}\u{3b8}".to_string());
+        let prim = text.to_primitive();
+        assert!(prim.as_string().unwrap().as_bytes().starts_with(&[0xfe, 0xff]));
+        assert_eq!(FieldValue::from_primitive(&prim), Some(text));
+    }
 }
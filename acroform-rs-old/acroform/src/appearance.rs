@@ -0,0 +1,549 @@
+//! Appearance streams for filled text fields
+//!
+//! Viewers show a widget's `/AP` stream rather than its value, so every filled
+//! text field gets a new one. It follows what Acrobat generates: background and
+//! border from `/MK`, then the value clipped to the area inside the border and
+//! set in the font, size and color of the field's `/DA`.
+
+use std::collections::HashMap;
+
+use pdf::content::{serialize_ops, parse_ops, Color, FormXObject, Op, Point, ViewRect, Winding};
+use pdf::encoding::{BaseEncoding, Encoding};
+use pdf::error::PdfError;
+use pdf::font::{Font, FontData, FontType, StandardFont, TFont};
+use pdf::object::{FieldDictionary, FormDict, InteractiveFormDictionary, Lazy, MaybeRef, Object, NoResolve, PlainRef, RcRef, Rectangle, Resolve, Resources, Stream, Updater};
+use pdf::primitive::{Dictionary, Name, PdfString, Primitive};
+
+use crate::font::EmbeddedFont;
+use crate::layout::{self, VerticalMetrics};
+
+/// Field flag of text fields that may hold more than one line
+const MULTILINE: u32 = 1 << 12;
+/// Field flag of text fields whose value is not shown
+const PASSWORD: u32 = 1 << 13;
+/// Field flag of text fields that take no more text than they can show
+const DO_NOT_SCROLL: u32 = 1 << 23;
+/// Field flag of text fields split into `/MaxLen` evenly spaced cells
+const COMB: u32 = 1 << 24;
+
+/// Space between the border and the text, in default user space units
+const PADDING: f32 = 1.0;
+
+/// Used when the `/DA` of a field is missing
+const DEFAULT_APPEARANCE: &[u8] = b"/Helv 0 Tf 0 g";
+
+/// An appearance stream that has not been written yet
+pub(crate) struct Appearance {
+    bbox: Rectangle,
+    content: Vec<u8>,
+    font_name: Name,
+    font: AppearanceFont,
+}
+
+/// Where the font of an appearance comes from
+enum AppearanceFont {
+    /// A font of the form's default resources
+    Resource(Lazy<Font>),
+    /// A standard font the document does not have yet
+    New(Box<Font>),
+    /// A registered font, with the text shown in it
+    Embedded(usize, String),
+}
+
+/// The font the text is measured and encoded with
+enum ShowFont<'a> {
+    Pdf(MaybeRef<Font>),
+    Embedded(&'a EmbeddedFont),
+}
+
+impl ShowFont<'_> {
+    /// Width of `text` for a font size of 1
+    fn text_width(&self, text: &str, resolve: &impl Resolve) -> f32 {
+        match self {
+            // the widths were loaded before, so this can't fail
+            ShowFont::Pdf(font) => font.text_width(text, 1.0, resolve).unwrap_or(0.0),
+            ShowFont::Embedded(font) => font.text_width(text),
+        }
+    }
+
+    fn metrics(&self) -> VerticalMetrics {
+        match self {
+            ShowFont::Pdf(font) => vertical_metrics(font),
+            ShowFont::Embedded(font) => font.metrics(),
+        }
+    }
+
+    fn encode(&self, text: &str, resolve: &impl Resolve) -> Result<Vec<u8>, PdfError> {
+        match self {
+            ShowFont::Pdf(font) => Ok(encode_text(font, text, resolve)?.0),
+            ShowFont::Embedded(font) => Ok(font.encode(text)),
+        }
+    }
+}
+
+impl Appearance {
+    /// Write the appearance stream and return its reference.
+    ///
+    /// Fonts that have to be added are written once and kept in `fonts` for the
+    /// following appearances. Registered fonts only get their reference here,
+    /// they are written after all appearances.
+    pub(crate) fn write(
+        self,
+        fonts: &mut HashMap<Name, Lazy<Font>>,
+        embedded: &mut [EmbeddedFont],
+        update: &mut impl Updater,
+    ) -> Result<PlainRef, PdfError> {
+        let font = match self.font {
+            AppearanceFont::Resource(font) => font,
+            AppearanceFont::New(font) => match fonts.get(&self.font_name) {
+                Some(font) => font.clone(),
+                None => {
+                    let font: Lazy<Font> = update.create(*font)?.into();
+                    fonts.insert(self.font_name.clone(), font.clone());
+                    font
+                }
+            },
+            AppearanceFont::Embedded(i, shown) => {
+                embedded[i].mark_used(&shown);
+                Lazy::from_primitive(Primitive::Reference(embedded[i].reference(update)), &NoResolve)?
+            }
+        };
+        let mut resources = Resources::default();
+        resources.fonts.insert(self.font_name, font);
+        let dict = FormDict {
+            bbox: self.bbox,
+            resources: Some(resources.into()),
+            ..Default::default()
+        };
+        let stream = update.create(FormXObject { stream: Stream::new(dict, self.content) })?;
+        Ok(stream.get_ref().get_inner())
+    }
+}
+
+/// Build the appearance of `widget` showing `text`.
+///
+/// `field` is the terminal field the widget belongs to, which may be the widget itself.
+/// If the field's font lacks glyphs for `text`, the first of the `embedded` fonts that
+/// has them all is used instead.
+/// Returns `None` for widgets without a `/Rect`.
+pub(crate) fn text_appearance(
+    forms: &InteractiveFormDictionary,
+    field: &RcRef<FieldDictionary>,
+    widget: &Dictionary,
+    text: &str,
+    embedded: &[EmbeddedFont],
+    resolve: &impl Resolve,
+) -> Result<Option<Appearance>, PdfError> {
+    let rect = match widget.get("Rect") {
+        Some(p) => Rectangle::from_primitive(p.clone(), resolve)?,
+        None => return Ok(None),
+    };
+    let width = (rect.right - rect.left).abs();
+    let height = (rect.top - rect.bottom).abs();
+
+    let da = match inherited("DA", widget, field, resolve)? {
+        Some(p) => p.as_string()?.as_bytes().to_vec(),
+        None => match forms.da {
+            Some(ref da) => da.as_bytes().to_vec(),
+            None => DEFAULT_APPEARANCE.to_vec(),
+        },
+    };
+    let mut da_ops = parse_ops(&da, resolve)?;
+    let (font_name, da_size) = match da_ops.iter().position(|op| matches!(op, Op::TextFont { .. })) {
+        Some(i) => match da_ops.remove(i) {
+            Op::TextFont { name, size } => (name, size),
+            _ => unreachable!(),
+        },
+        None => (Name::from("Helv"), 0.0),
+    };
+    let quadding = match inherited("Q", widget, field, resolve)? {
+        Some(p) => p.as_integer()?,
+        None => forms.q.unwrap_or(0),
+    };
+
+    let (font, source) = find_font(forms, &font_name, resolve)?;
+    // check the widths once, so measuring below can't fail
+    font.widths(resolve)?;
+
+    let text = if field.flags & PASSWORD != 0 {
+        "*".repeat(text.chars().count())
+    } else {
+        text.to_string()
+    };
+
+    let printable: String = text.chars().filter(|c| !c.is_control()).collect();
+    let fallback = match encode_text(&font, &printable, resolve)?.1 {
+        true => None,
+        false => embedded.iter().position(|f| f.covers(&printable)),
+    };
+    let (font_name, mut source, font) = match fallback {
+        Some(i) => (embedded[i].name.clone(), AppearanceFont::Embedded(i, String::new()), ShowFont::Embedded(&embedded[i])),
+        None => (font_name, source, ShowFont::Pdf(font)),
+    };
+    let measure = |s: &str| font.text_width(s, resolve);
+    let metrics = font.metrics();
+    let multiline = field.flags & MULTILINE != 0;
+    let do_not_scroll = field.flags & DO_NOT_SCROLL != 0;
+    // comb fields must be single line plain text with a /MaxLen
+    let comb = match inherited("MaxLen", widget, field, resolve)? {
+        Some(p) if field.flags & (COMB | MULTILINE | PASSWORD) == COMB => Some(p.as_u32()?).filter(|&n| n > 0),
+        _ => None,
+    };
+
+    let mk = match widget.get("MK") {
+        Some(p) => p.clone().resolve(resolve)?.into_dictionary()?,
+        None => Dictionary::new(),
+    };
+    let border_width = border_width(widget, resolve)?;
+
+    let mut ops = vec![];
+    if let Some(color) = mk_color(&mk, "BG", resolve)? {
+        ops.push(Op::FillColor { color });
+        ops.push(Op::Rect { rect: ViewRect { x: 0.0, y: 0.0, width, height } });
+        ops.push(Op::Fill { winding: Winding::NonZero });
+    }
+    if let Some(color) = mk_color(&mk, "BC", resolve)?.filter(|_| border_width > 0.0) {
+        let half = border_width / 2.0;
+        ops.push(Op::StrokeColor { color });
+        ops.push(Op::LineWidth { width: border_width });
+        ops.push(Op::Rect { rect: ViewRect { x: half, y: half, width: width - border_width, height: height - border_width } });
+        ops.push(Op::Stroke);
+    }
+
+    // the text goes into the area inside the border and the padding
+    let inset = border_width + PADDING;
+    let area = ViewRect {
+        x: 2.0 * inset,
+        y: inset,
+        width: (width - 4.0 * inset).max(0.0),
+        height: (height - 2.0 * inset).max(0.0),
+    };
+    // comb cells span the whole width inside the border
+    let cell_width = comb.map(|n| (width - 2.0 * border_width).max(0.0) / n as f32);
+    let size = match (da_size, cell_width) {
+        (size, _) if size > 0.0 => size,
+        (_, Some(cell_width)) => layout::auto_size_comb(&text, measure, metrics, cell_width, area.height),
+        (_, None) if multiline => layout::auto_size_multiline(&text, measure, metrics, area.width, area.height),
+        (_, None) => layout::auto_size_single_line(&text, measure, metrics, area.width, area.height),
+    };
+    let line_height = metrics.line_height() * size;
+
+    let mut lines = if multiline {
+        layout::wrap(&text, measure, area.width / size)
+    } else {
+        vec![text.replace(['\r', '\n'], " ")]
+    };
+    if do_not_scroll && comb.is_none() {
+        // only what fits is shown, at least the first line
+        if multiline {
+            lines.truncate(((area.height / line_height) as usize).max(1));
+        } else {
+            lines[0] = layout::fit(&lines[0], measure, area.width / size).to_string();
+        }
+    }
+
+    // each line, or character in a comb field, with the position of its start
+    let mut y = if multiline {
+        area.y + area.height - metrics.ascent * size
+    } else {
+        area.y + (area.height - line_height) / 2.0 - metrics.descent * size
+    };
+    let mut runs = vec![];
+    match (comb, cell_width) {
+        (Some(cells), Some(cell_width)) => {
+            let chars: Vec<char> = lines[0].chars().take(cells as usize).collect();
+            // quadding moves the value to the middle or the last cells
+            let skip = cells as usize - chars.len();
+            let first = match quadding {
+                1 => skip / 2,
+                2 => skip,
+                _ => 0,
+            };
+            for (i, c) in chars.into_iter().enumerate() {
+                let c = c.to_string();
+                let x = border_width + (first + i) as f32 * cell_width + (cell_width - measure(&c) * size) / 2.0;
+                runs.push((x, y, c));
+            }
+        }
+        _ => for line in lines {
+            let line_width = measure(&line) * size;
+            let x = match quadding {
+                1 => area.x + (area.width - line_width) / 2.0,
+                2 => area.x + area.width - line_width,
+                _ => area.x,
+            };
+            runs.push((x, y, line));
+            y -= line_height;
+        }
+    }
+
+    ops.push(Op::BeginMarkedContent { tag: "Tx".into(), properties: None });
+    ops.push(Op::Save);
+    ops.push(Op::Rect { rect: ViewRect { x: border_width, y: border_width, width: width - 2.0 * border_width, height: height - 2.0 * border_width } });
+    ops.push(Op::Clip { winding: Winding::NonZero });
+    ops.push(Op::EndPath);
+    ops.push(Op::BeginText);
+    ops.extend(da_ops);
+    ops.push(Op::TextFont { name: font_name.clone(), size });
+
+    if let AppearanceFont::Embedded(_, ref mut shown) = source {
+        *shown = runs.iter().map(|(_, _, run)| run.as_str()).collect();
+    }
+    let mut last = Point { x: 0.0, y: 0.0 };
+    for (x, y, run) in runs {
+        ops.push(Op::MoveTextPosition { translation: Point { x: x - last.x, y: y - last.y } });
+        ops.push(Op::TextDraw { text: PdfString::new(font.encode(&run, resolve)?.into()) });
+        last = Point { x, y };
+    }
+    ops.push(Op::EndText);
+    ops.push(Op::Restore);
+    ops.push(Op::EndMarkedContent);
+
+    Ok(Some(Appearance {
+        bbox: Rectangle { left: 0.0, bottom: 0.0, right: width, top: height },
+        content: serialize_ops(&ops)?,
+        font_name,
+        font: source,
+    }))
+}
+
+/// Look up `key` in the widget, then the field and its parents
+fn inherited(key: &str, widget: &Dictionary, field: &RcRef<FieldDictionary>, resolve: &impl Resolve) -> Result<Option<Primitive>, PdfError> {
+    if let Some(p) = widget.get(key) {
+        return Ok(Some(p.clone().resolve(resolve)?));
+    }
+    let mut field = field.clone();
+    // parent chains were checked for cycles when the field was found
+    loop {
+        if let Some(p) = field.other.get(key) {
+            return Ok(Some(p.clone().resolve(resolve)?));
+        }
+        match field.parent {
+            Some(parent) => field = resolve.get(parent)?,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// The font `name` of the form's default resources, or a standard font standing in for it
+fn find_font(forms: &InteractiveFormDictionary, name: &Name, resolve: &impl Resolve) -> Result<(MaybeRef<Font>, AppearanceFont), PdfError> {
+    if let Some(font) = forms.dr.as_ref().and_then(|dr| dr.fonts.get(name)) {
+        return Ok((font.load(resolve)?, AppearanceFont::Resource(font.clone())));
+    }
+    let standard = StandardFont::by_name(name).unwrap_or(&pdf::font::standard::STANDARD_FONTS[0]);
+    Ok((standard_font(standard).into(), AppearanceFont::New(Box::new(standard_font(standard)))))
+}
+
+/// A font dictionary for one of the standard fonts
+fn standard_font(standard: &StandardFont) -> Font {
+    let encoding = if standard.is_symbolic() {
+        None
+    } else {
+        Some(Encoding { base: BaseEncoding::WinAnsiEncoding, differences: HashMap::new() })
+    };
+    Font {
+        subtype: FontType::Type1,
+        name: Some(standard.name.into()),
+        data: FontData::Type1(TFont {
+            base_font: Some(standard.name.into()),
+            first_char: None,
+            last_char: None,
+            widths: None,
+            font_descriptor: None,
+            other: Dictionary::new(),
+        }),
+        encoding,
+        to_unicode: None,
+        _other: Dictionary::new(),
+    }
+}
+
+/// Ascent and descent of `font`, relative to the font size
+fn vertical_metrics(font: &Font) -> VerticalMetrics {
+    let descriptor = match font.data {
+        FontData::Type1(ref t) | FontData::TrueType(ref t) => t.font_descriptor.as_ref(),
+        FontData::CIDFontType0(ref c) | FontData::CIDFontType2(ref c) => Some(&c.font_descriptor),
+        FontData::Type0(ref t) => t.descendant_fonts.first().and_then(|f| match f.data {
+            FontData::CIDFontType0(ref c) | FontData::CIDFontType2(ref c) => Some(&c.font_descriptor),
+            _ => None,
+        }),
+        _ => None,
+    };
+    let (ascent, descent) = match (descriptor.and_then(|d| d.ascent), descriptor.and_then(|d| d.descent)) {
+        (Some(ascent), Some(descent)) if ascent > descent => (ascent, descent),
+        _ => {
+            let standard = font.standard_font().unwrap_or(&pdf::font::standard::STANDARD_FONTS[0]);
+            (standard.ascent, standard.descent)
+        }
+    };
+    VerticalMetrics { ascent: ascent / 1000.0, descent: descent / 1000.0 }
+}
+
+/// Width of the border, from `/BS` or `/Border`
+fn border_width(widget: &Dictionary, resolve: &impl Resolve) -> Result<f32, PdfError> {
+    if let Some(bs) = widget.get("BS") {
+        if let Some(w) = bs.clone().resolve(resolve)?.into_dictionary()?.get("W") {
+            return w.as_number();
+        }
+    } else if let Some(border) = widget.get("Border") {
+        if let Some(w) = border.clone().resolve(resolve)?.as_array()?.get(2) {
+            return w.as_number();
+        }
+    }
+    Ok(1.0)
+}
+
+/// A color of the `/MK` dictionary, `None` if it is missing or transparent
+fn mk_color(mk: &Dictionary, key: &str, resolve: &impl Resolve) -> Result<Option<Color>, PdfError> {
+    let components = match mk.get(key) {
+        Some(p) => p.clone().resolve(resolve)?.into_array()?,
+        None => return Ok(None),
+    };
+    let c = components.iter().map(|p| p.as_number()).collect::<Result<Vec<f32>, _>>()?;
+    Ok(match c[..] {
+        [gray] => Some(Color::Gray(gray)),
+        [red, green, blue] => Some(Color::Rgb(pdf::content::Rgb { red, green, blue })),
+        [cyan, magenta, yellow, key] => Some(Color::Cmyk(pdf::content::Cmyk { cyan, magenta, yellow, key })),
+        _ => None,
+    })
+}
+
+/// The codes showing `text` in `font`, and whether it has codes for all of it
+///
+/// Characters the font can't show are left out.
+fn encode_text(font: &Font, text: &str, resolve: &impl Resolve) -> Result<(Vec<u8>, bool), PdfError> {
+    if !font.is_cid() {
+        let codes: Vec<u8> = text.chars().filter_map(|c| font.encode_char(c)).collect();
+        let complete = codes.len() == text.chars().count();
+        return Ok((codes, complete));
+    }
+    // composite fonts: codes of the CMap, found through its character set or the ToUnicode map
+    let codes = font.encode_composite(text, resolve)?;
+    let complete = codes.iter().all(Option::is_some);
+    Ok((codes.into_iter().flatten().flat_map(|code| code.bytes()).collect(), complete))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn field(da: &str, flags: i32) -> (RcRef<FieldDictionary>, Dictionary) {
+        field_with(da, flags, Dictionary::new())
+    }
+
+    fn field_with(da: &str, flags: i32, mut dict: Dictionary) -> (RcRef<FieldDictionary>, Dictionary) {
+        dict.insert("FT", Name::from("Tx"));
+        dict.insert("Ff", flags);
+        dict.insert("DA", PdfString::new(da.as_bytes().into()));
+        dict.insert("Rect", Primitive::Array(vec![0.into(), 0.into(), 100.into(), 20.into()]));
+        let field = FieldDictionary::from_primitive(Primitive::Dictionary(dict.clone()), &NoResolve).unwrap();
+        (RcRef::new(PlainRef { id: 1, gen: 0 }, Arc::new(field)), dict)
+    }
+
+    fn forms() -> InteractiveFormDictionary {
+        let mut dict = Dictionary::new();
+        dict.insert("Fields", Primitive::Array(vec![]));
+        InteractiveFormDictionary::from_primitive(Primitive::Dictionary(dict), &NoResolve).unwrap()
+    }
+
+    fn font_sizes(appearance: &Appearance) -> Vec<f32> {
+        parse_ops(&appearance.content, &NoResolve).unwrap().into_iter()
+            .filter_map(|op| match op {
+                Op::TextFont { size, .. } => Some(size),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fixed_size_is_kept() {
+        let (field, widget) = field("/Helv 9 Tf 0 g", 0);
+        let appearance = text_appearance(&forms(), &field, &widget, "text", &[], &NoResolve).unwrap().unwrap();
+        assert_eq!(font_sizes(&appearance), [9.0]);
+    }
+
+    #[test]
+    fn auto_size_shrinks_long_values() {
+        let (field, widget) = field("/Helv 0 Tf 0 g", 0);
+        let short = text_appearance(&forms(), &field, &widget, "ab", &[], &NoResolve).unwrap().unwrap();
+        let long = text_appearance(&forms(), &field, &widget, &"ab".repeat(20), &[], &NoResolve).unwrap().unwrap();
+        let (short, long) = (font_sizes(&short)[0], font_sizes(&long)[0]);
+        // the height would allow 16 / 0.925 for short values: 20 minus border and padding,
+        // over the line height of Helvetica, but auto sizes stop at the maximum
+        assert_eq!(short, layout::MAX_AUTO_FONT_SIZE);
+        assert!(long < short && long >= layout::MIN_AUTO_FONT_SIZE, "{}", long);
+    }
+
+    #[test]
+    fn auto_size_multiline_wraps() {
+        let (field, widget) = field("/Helv 0 Tf 0 g", MULTILINE as i32);
+        let appearance = text_appearance(&forms(), &field, &widget, "one two three four five six", &[], &NoResolve).unwrap().unwrap();
+        let ops = parse_ops(&appearance.content, &NoResolve).unwrap();
+        assert!(font_sizes(&appearance)[0] <= layout::MAX_AUTO_FONT_SIZE);
+        assert!(ops.iter().filter(|op| matches!(op, Op::TextDraw { .. })).count() > 1);
+    }
+
+    fn text_runs(appearance: &Appearance) -> Vec<(f32, Vec<u8>)> {
+        let mut x = 0.0;
+        let mut runs = vec![];
+        for op in parse_ops(&appearance.content, &NoResolve).unwrap() {
+            match op {
+                Op::MoveTextPosition { translation } => x += translation.x,
+                Op::TextDraw { text } => runs.push((x, text.as_bytes().to_vec())),
+                _ => {}
+            }
+        }
+        runs
+    }
+
+    #[test]
+    fn comb_places_one_character_per_cell() {
+        let mut dict = Dictionary::new();
+        dict.insert("MaxLen", 5);
+        dict.insert("Border", Primitive::Array(vec![0.into(), 0.into(), 0.into()]));
+        let (field, widget) = field_with("/Helv 10 Tf 0 g", COMB as i32, dict);
+        let appearance = text_appearance(&forms(), &field, &widget, "1234567", &[], &NoResolve).unwrap().unwrap();
+        let runs = text_runs(&appearance);
+        // five cells of 20, the rest of the value is dropped
+        assert_eq!(runs.len(), 5);
+        for (i, (x, text)) in runs.iter().enumerate() {
+            assert_eq!(text, &[b'1' + i as u8]);
+            // digits are 556 wide in Helvetica
+            assert!((x - (i as f32 * 20.0 + (20.0 - 5.56) / 2.0)).abs() < 0.01, "{}", x);
+        }
+    }
+
+    #[test]
+    fn cjk_font_with_predefined_cmap() {
+        let dr = "<< /Fields [] /DR << /Font << /F1 << /Type /Font /Subtype /Type0 /BaseFont /STSong-Light
+            /Encoding /UniGB-UCS2-H /DescendantFonts [<< /Type /Font /Subtype /CIDFontType0 /BaseFont /STSong-Light
+                /CIDSystemInfo << /Registry (Adobe) /Ordering (GB1) /Supplement 4 >>
+                /FontDescriptor << /Type /FontDescriptor /FontName /STSong-Light /Flags 6 /FontBBox [0 -200 1000 900]
+                    /ItalicAngle 0 /Ascent 880 /Descent -120 >> >>] >> >> >> >>";
+        let dr = pdf::parser::parse(dr.as_bytes(), &NoResolve, pdf::parser::ParseFlags::DICT).unwrap();
+        let forms = InteractiveFormDictionary::from_primitive(dr, &NoResolve).unwrap();
+        let (field, widget) = field("/F1 10 Tf 0 g", 0);
+        let appearance = text_appearance(&forms, &field, &widget, "\u{4E2D}\u{6587}", &[], &NoResolve).unwrap().unwrap();
+        assert_eq!(text_runs(&appearance)[0].1, [0x4E, 0x2D, 0x65, 0x87]);
+        assert!(matches!(appearance.font, AppearanceFont::Resource(_)));
+    }
+
+    #[test]
+    fn do_not_scroll_drops_what_does_not_fit() {
+        let long = "x".repeat(100);
+        let (scrolls, widget) = field("/Helv 10 Tf 0 g", 0);
+        let scrolling = text_appearance(&forms(), &scrolls, &widget, &long, &[], &NoResolve).unwrap().unwrap();
+        assert_eq!(text_runs(&scrolling)[0].1.len(), 100);
+
+        let (single, widget) = field("/Helv 10 Tf 0 g", DO_NOT_SCROLL as i32);
+        let fixed = text_appearance(&forms(), &single, &widget, &long, &[], &NoResolve).unwrap().unwrap();
+        // 92 wide inside border and padding, x is 500 wide in Helvetica
+        assert_eq!(text_runs(&fixed)[0].1.len(), 18);
+
+        let (multiline, widget) = field("/Helv 10 Tf 0 g", (DO_NOT_SCROLL | MULTILINE) as i32);
+        let lines = text_appearance(&forms(), &multiline, &widget, "a\nb\nc", &[], &NoResolve).unwrap().unwrap();
+        // 16 high, one line of 9.25
+        assert_eq!(text_runs(&lines).len(), 1);
+    }
+}
@@ -0,0 +1,46 @@
+//! Errors of filling in forms
+
+use std::fmt;
+
+use pdf::error::PdfError;
+
+/// Error returned by [`AcroFormDocument::fill`](crate::AcroFormDocument::fill) and its variants
+#[derive(Debug)]
+pub enum FillError {
+    /// The value of a text field is longer than its `/MaxLen`,
+    /// see [`MaxLenPolicy::Reject`](crate::MaxLenPolicy::Reject)
+    ValueTooLong { field: String, max_len: u32 },
+
+    /// The document could not be read, updated or written
+    Pdf(PdfError),
+}
+
+impl fmt::Display for FillError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FillError::ValueTooLong { field, max_len } => write!(f, "Value of field {} is longer than its /MaxLen {}", field, max_len),
+            FillError::Pdf(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FillError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FillError::ValueTooLong { .. } => None,
+            FillError::Pdf(e) => Some(e),
+        }
+    }
+}
+
+impl From<PdfError> for FillError {
+    fn from(e: PdfError) -> Self {
+        FillError::Pdf(e)
+    }
+}
+
+impl From<std::io::Error> for FillError {
+    fn from(e: std::io::Error) -> Self {
+        FillError::Pdf(e.into())
+    }
+}
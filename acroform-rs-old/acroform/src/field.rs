@@ -1,5 +1,6 @@
 use pdf::error::PdfError;
 use pdf::object::{FieldDictionary, InteractiveFormDictionary, Resolve, RcRef};
+use std::collections::HashSet;
 
 /// Extension trait to add traversal functionality to FieldDictionary
 ///
@@ -17,12 +18,12 @@ pub trait FieldDictionaryExt {
     ///
     /// # Errors
     ///
-    /// Returns `PdfError` if parent references cannot be resolved.
+    /// Returns `PdfError` if parent references cannot be resolved or form a cycle.
     fn get_full_name(&self, resolver: &impl Resolve) -> Result<String, PdfError>;
     
     /// Recursively traverse all child fields and return their references
     ///
-    /// This method recursively walks through all children of this field dictionary,
+    /// This method walks through all descendants of this field dictionary,
     /// collecting references to all terminal (leaf) fields that have a type.
     ///
     /// # Arguments
@@ -46,8 +47,12 @@ impl FieldDictionaryExt for FieldDictionary {
         
         // Walk up the parent chain by collecting all parent refs first
         let mut parent_refs = Vec::new();
+        let mut seen = HashSet::new();
         let mut current_parent = self.parent;
         while let Some(parent_ref) = current_parent {
+            if !seen.insert(parent_ref.get_inner()) {
+                return Err(PdfError::Other { msg: format!("parent chain of field {:?} contains a cycle", self.name) });
+            }
             parent_refs.push(parent_ref);
             let parent: RcRef<FieldDictionary> = resolver.get(parent_ref)?;
             current_parent = parent.parent;
@@ -66,18 +71,24 @@ impl FieldDictionaryExt for FieldDictionary {
     
     fn traverse_field_refs(&self, resolver: &impl Resolve) -> Result<Vec<RcRef<FieldDictionary>>, PdfError> {
         let mut result = Vec::new();
+        let mut seen = HashSet::new();
         
-        // Recursively traverse children
-        for kid_ref in &self.kids {
-            let kid: RcRef<FieldDictionary> = resolver.get(*kid_ref)?;
+        // Depth-first with an explicit stack, so deep or cyclic hierarchies
+        // can't overflow the call stack. Kids that were already visited are skipped.
+        let mut stack: Vec<_> = self.kids.iter().rev().copied().collect();
+        while let Some(kid_ref) = stack.pop() {
+            if !seen.insert(kid_ref.get_inner()) {
+                continue;
+            }
+            let kid: RcRef<FieldDictionary> = resolver.get(kid_ref)?;
             
             // If this kid has a type, it's a terminal field
             if kid.typ.is_some() {
                 result.push(kid.clone());
             }
             
-            // Recursively process grandchildren
-            result.extend(kid.traverse_field_refs(resolver)?);
+            // Process grandchildren next
+            stack.extend(kid.kids.iter().rev().copied());
         }
         
         Ok(result)
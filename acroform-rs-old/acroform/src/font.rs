@@ -0,0 +1,275 @@
+//! TrueType and OpenType fonts embedded for field values
+//!
+//! Registered fonts are embedded as composite fonts with the `Identity-H` encoding,
+//! so any glyph can be shown with a two byte code equal to its glyph id in the
+//! registered font. The font objects are written when the document is saved, with
+//! TrueType fonts subset to the glyphs shown so far and a `/CIDToGIDMap` from the
+//! original glyph ids to those of the subset. Appearances written before stay valid
+//! when later fills add glyphs.
+
+mod subset;
+
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::sync::Arc;
+
+use pdf::error::PdfError;
+use pdf::font::{write_cmap, CidToGidMap, Font, ToUnicodeMap};
+use pdf::object::{ObjectWrite, PlainRef, Stream, Updater};
+use pdf::primitive::{Dictionary, Name, PdfString, Primitive};
+use ttf_parser::Face;
+
+use crate::layout::VerticalMetrics;
+
+/// A font file registered with the document
+pub(crate) struct EmbeddedFont {
+    data: Arc<[u8]>,
+    /// PostScript name of the font
+    base_font: String,
+    /// Name of the font in the form's default resources
+    pub(crate) name: Name,
+    /// The Type0 font, reserved when the font is first shown
+    font_ref: Option<PlainRef>,
+    /// The font descriptor, rewritten with each subset
+    descriptor_ref: Option<PlainRef>,
+    /// The font file stream, rewritten with each subset
+    file_ref: Option<PlainRef>,
+    /// The `/CIDToGIDMap` stream of the subset, rewritten with it
+    cid_to_gid_ref: Option<PlainRef>,
+    /// The ToUnicode CMap, rewritten with each subset
+    to_unicode_ref: Option<PlainRef>,
+    /// Glyphs shown so far, with the text they stand for
+    used: BTreeMap<u16, String>,
+    /// Glyphs were shown since the font objects were last written
+    changed: bool,
+}
+
+impl EmbeddedFont {
+    /// Check `data` and take its PostScript name, which is also the resource name at first
+    pub(crate) fn new(data: Vec<u8>) -> Result<Self, PdfError> {
+        let face = parse(&data)?;
+        let base_font = face.names().into_iter()
+            .filter(|n| n.name_id == ttf_parser::name_id::POST_SCRIPT_NAME)
+            .find_map(|n| n.to_string())
+            .map(|name| name.chars().filter(|c| c.is_ascii_graphic() && !"()<>[]{}/%#".contains(*c)).collect::<String>())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "EmbeddedFont".to_string());
+        Ok(EmbeddedFont {
+            data: data.into(),
+            name: base_font.as_str().into(),
+            base_font,
+            font_ref: None,
+            descriptor_ref: None,
+            file_ref: None,
+            cid_to_gid_ref: None,
+            to_unicode_ref: None,
+            used: BTreeMap::new(),
+            changed: false,
+        })
+    }
+
+    /// PostScript name of the font
+    pub(crate) fn base_font(&self) -> &str {
+        &self.base_font
+    }
+
+    fn face(&self) -> Face<'_> {
+        // checked in `new`
+        parse(&self.data).expect("font was parsed before")
+    }
+
+    /// Whether the font has glyphs for all of `text`, line breaks aside
+    pub(crate) fn covers(&self, text: &str) -> bool {
+        let face = self.face();
+        text.chars().filter(|c| !c.is_control()).all(|c| face.glyph_index(c).is_some())
+    }
+
+    /// Width of `text` for a font size of 1
+    pub(crate) fn text_width(&self, text: &str) -> f32 {
+        let face = self.face();
+        let units: u32 = text.chars()
+            .filter_map(|c| face.glyph_index(c))
+            .filter_map(|gid| face.glyph_hor_advance(gid))
+            .map(u32::from)
+            .sum();
+        units as f32 / face.units_per_em() as f32
+    }
+
+    /// Ascent and descent, relative to the font size
+    pub(crate) fn metrics(&self) -> VerticalMetrics {
+        let face = self.face();
+        let em = face.units_per_em() as f32;
+        VerticalMetrics { ascent: face.ascender() as f32 / em, descent: face.descender() as f32 / em }
+    }
+
+    /// The codes showing `text`, the glyph ids; characters without a glyph are left out
+    pub(crate) fn encode(&self, text: &str) -> Vec<u8> {
+        let face = self.face();
+        text.chars().filter_map(|c| face.glyph_index(c)).flat_map(|gid| gid.0.to_be_bytes()).collect()
+    }
+
+    /// Record the glyphs of `text` for the widths and the ToUnicode map
+    pub(crate) fn mark_used(&mut self, text: &str) {
+        let face = self.face();
+        let glyphs: Vec<_> = text.chars().filter_map(|c| Some((face.glyph_index(c)?.0, c))).collect();
+        for (gid, c) in glyphs {
+            if let Entry::Vacant(entry) = self.used.entry(gid) {
+                entry.insert(c.to_string());
+                self.changed = true;
+            }
+        }
+    }
+
+    /// The reference of the Type0 font, reserved on the first call
+    pub(crate) fn reference(&mut self, update: &mut impl Updater) -> PlainRef {
+        *self.font_ref.get_or_insert_with(|| update.promise::<Font>().get_inner())
+    }
+
+    /// Write the font objects if glyphs were shown since the last call
+    ///
+    /// A reserved font is always written the first time, even if no glyphs were shown,
+    /// since appearances already refer to it.
+    /// Returns the reference of the font when it is written for the first time.
+    pub(crate) fn write(&mut self, update: &mut impl Updater) -> Result<Option<PlainRef>, PdfError> {
+        let first = self.descriptor_ref.is_none();
+        let font_ref = match self.font_ref {
+            Some(font_ref) if self.changed || first => font_ref,
+            _ => return Ok(None),
+        };
+        let data = self.data.clone();
+        let face = parse(&data)?;
+        let cff = face.tables().glyf.is_none();
+        let scale = 1000.0 / face.units_per_em() as f32;
+
+        // OpenType fonts with CFF outlines are embedded whole
+        let (file, cid_to_gid, base_font) = if cff {
+            (data.to_vec(), None, self.base_font.clone())
+        } else {
+            let chars = self.used.iter().filter_map(|(&gid, text)| Some((text.chars().next()?, gid))).collect();
+            let subset = subset::subset(&data, &chars)?;
+            // the codes are the glyph ids of the registered font
+            let max_cid = self.used.keys().next_back().copied().unwrap_or(0);
+            let table = (0..=max_cid).map(|cid| subset.glyphs.get(&cid).copied().unwrap_or(0)).collect();
+            (subset.data, Some(CidToGidMap::Table(table)), format!("{}+{}", subset_tag(self.used.keys()), self.base_font))
+        };
+
+        let mut info = Dictionary::new();
+        if cff {
+            info.insert("Subtype", Name::from("OpenType"));
+        } else {
+            info.insert("Length1", file.len() as i32);
+        }
+        let file_ref = create_or_update(update, self.file_ref, Stream::new(info, file))?;
+        let descriptor = self.descriptor(&face, scale, cff, &base_font, file_ref);
+        let descriptor_ref = create_or_update(update, self.descriptor_ref, Primitive::Dictionary(descriptor))?;
+
+        let mut system_info = Dictionary::new();
+        system_info.insert("Registry", PdfString::new("Adobe".as_bytes().into()));
+        system_info.insert("Ordering", PdfString::new("Identity".as_bytes().into()));
+        system_info.insert("Supplement", 0);
+
+        let mut cid_font = Dictionary::new();
+        cid_font.insert("Type", Name::from("Font"));
+        cid_font.insert("Subtype", Name::from(if cff { "CIDFontType0" } else { "CIDFontType2" }));
+        cid_font.insert("BaseFont", Name::from(base_font.as_str()));
+        cid_font.insert("CIDSystemInfo", system_info);
+        cid_font.insert("FontDescriptor", Primitive::Reference(descriptor_ref));
+        cid_font.insert("W", self.widths(&face, scale));
+        if let Some(map) = cid_to_gid {
+            let cid_to_gid_ref = create_or_update(update, self.cid_to_gid_ref, map)?;
+            cid_font.insert("CIDToGIDMap", Primitive::Reference(cid_to_gid_ref));
+            self.cid_to_gid_ref = Some(cid_to_gid_ref);
+        }
+
+        let to_unicode = ToUnicodeMap::create(self.used.iter().map(|(&gid, text)| (gid, text.as_str().into())));
+        let to_unicode_ref = create_or_update(update, self.to_unicode_ref, Stream::new((), write_cmap(&to_unicode).into_bytes()))?;
+
+        let mut font = Dictionary::new();
+        font.insert("Type", Name::from("Font"));
+        font.insert("Subtype", Name::from("Type0"));
+        font.insert("BaseFont", Name::from(base_font.as_str()));
+        font.insert("Encoding", Name::from("Identity-H"));
+        font.insert("DescendantFonts", Primitive::Array(vec![Primitive::Dictionary(cid_font)]));
+        font.insert("ToUnicode", Primitive::Reference(to_unicode_ref));
+        update.update(font_ref, Primitive::Dictionary(font))?;
+
+        self.to_unicode_ref = Some(to_unicode_ref);
+        self.file_ref = Some(file_ref);
+        self.descriptor_ref = Some(descriptor_ref);
+        self.changed = false;
+        Ok(first.then_some(font_ref))
+    }
+
+    fn descriptor(&self, face: &Face, scale: f32, cff: bool, base_font: &str, file_ref: PlainRef) -> Dictionary {
+        let bbox = face.global_bounding_box();
+        let mut flags = 4; // symbolic, the glyphs are not from the standard Latin set
+        if face.is_monospaced() {
+            flags |= 1;
+        }
+        if face.is_italic() {
+            flags |= 1 << 6;
+        }
+        let mut descriptor = Dictionary::new();
+        descriptor.insert("Type", Name::from("FontDescriptor"));
+        descriptor.insert("FontName", Name::from(base_font));
+        descriptor.insert("Flags", flags);
+        descriptor.insert("FontBBox", Primitive::Array(
+            [bbox.x_min, bbox.y_min, bbox.x_max, bbox.y_max].iter().map(|&v| Primitive::Number(v as f32 * scale)).collect()
+        ));
+        descriptor.insert("ItalicAngle", face.italic_angle());
+        descriptor.insert("Ascent", face.ascender() as f32 * scale);
+        descriptor.insert("Descent", face.descender() as f32 * scale);
+        descriptor.insert("CapHeight", face.capital_height().unwrap_or(face.ascender()) as f32 * scale);
+        descriptor.insert("StemV", 80);
+        descriptor.insert(if cff { "FontFile3" } else { "FontFile2" }, Primitive::Reference(file_ref));
+        descriptor
+    }
+
+    /// The `/W` array of the glyphs shown, runs of consecutive glyph ids share an entry
+    fn widths(&self, face: &Face, scale: f32) -> Primitive {
+        let mut w = vec![];
+        let mut run: Vec<Primitive> = vec![];
+        let mut next = None;
+        for &gid in self.used.keys() {
+            if next != Some(gid) && !run.is_empty() {
+                w.push(Primitive::Array(std::mem::take(&mut run)));
+            }
+            if run.is_empty() {
+                w.push(Primitive::Integer(gid as i32));
+            }
+            let advance = face.glyph_hor_advance(ttf_parser::GlyphId(gid)).unwrap_or(0);
+            run.push(Primitive::Integer((advance as f32 * scale).round() as i32));
+            next = gid.checked_add(1);
+        }
+        if !run.is_empty() {
+            w.push(Primitive::Array(run));
+        }
+        Primitive::Array(w)
+    }
+}
+
+/// Create the object the first time, replace it later
+fn create_or_update<T: ObjectWrite>(update: &mut impl Updater, old: Option<PlainRef>, obj: T) -> Result<PlainRef, PdfError> {
+    let new = match old {
+        Some(old) => update.update(old, obj)?,
+        None => update.create(obj)?,
+    };
+    Ok(new.get_ref().get_inner())
+}
+
+/// Six capital letters that tell subsets of a font apart, from a hash of its glyphs
+fn subset_tag<'a>(glyphs: impl Iterator<Item = &'a u16>) -> String {
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in glyphs.flat_map(|gid| gid.to_be_bytes()) {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    (0..6).map(|_| {
+        let letter = (b'A' + (hash % 26) as u8) as char;
+        hash /= 26;
+        letter
+    }).collect()
+}
+
+fn parse(data: &[u8]) -> Result<Face<'_>, PdfError> {
+    Face::parse(data, 0).map_err(|e| PdfError::Other { msg: format!("can't read font: {}", e) })
+}
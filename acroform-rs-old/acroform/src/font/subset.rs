@@ -0,0 +1,367 @@
+//! TrueType subsetting
+//!
+//! Keeps the outlines of the glyphs shown and the glyphs they are composed of,
+//! numbered anew in the order of their old glyph ids with `.notdef` first.
+//! Hinting programs and the `OS/2` and `name` tables are kept as they are,
+//! layout tables like `GSUB` and `kern` are dropped since PDF viewers don't use them.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use pdf::error::PdfError;
+use ttf_parser::{RawFace, Tag};
+
+/// Tables copied unchanged
+const COPIED: [&[u8; 4]; 6] = [b"OS/2", b"cvt ", b"fpgm", b"gasp", b"name", b"prep"];
+
+// flags of composite glyph components
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+/// A subset font file
+pub(crate) struct Subset {
+    pub data: Vec<u8>,
+    /// new glyph id of each old one that was kept
+    pub glyphs: BTreeMap<u16, u16>,
+}
+
+/// Subset the TrueType font `data` to the glyphs of `chars`, a map from
+/// characters to old glyph ids, which also becomes the subset's `cmap`
+pub(crate) fn subset(data: &[u8], chars: &BTreeMap<char, u16>) -> Result<Subset, PdfError> {
+    let face = RawFace::parse(data, 0).map_err(|e| PdfError::Other { msg: format!("can't read font: {}", e) })?;
+    let table = |tag: &[u8; 4]| face.table(Tag::from_bytes(tag));
+    let require = |tag: &[u8; 4]| table(tag).ok_or_else(|| PdfError::Other {
+        msg: format!("font has no {} table", String::from_utf8_lossy(tag))
+    });
+
+    let head = require(b"head")?;
+    let hhea = require(b"hhea")?;
+    let maxp = require(b"maxp")?;
+    let hmtx = require(b"hmtx")?;
+    let loca = require(b"loca")?;
+    let glyf = require(b"glyf")?;
+    let num_glyphs = read_u16(maxp, 4)?;
+    let long_loca = read_u16(head, 50)? != 0;
+    let num_metrics = read_u16(hhea, 34)?;
+
+    let glyph = |gid: u16| -> Result<&[u8], PdfError> {
+        if gid >= num_glyphs {
+            return Err(PdfError::Other { msg: format!("glyph {} out of range", gid) });
+        }
+        let (start, end) = if long_loca {
+            (read_u32(loca, 4 * gid as usize)?, read_u32(loca, 4 * gid as usize + 4)?)
+        } else {
+            (2 * read_u16(loca, 2 * gid as usize)? as u32, 2 * read_u16(loca, 2 * gid as usize + 2)? as u32)
+        };
+        glyf.get(start as usize..end as usize).ok_or_else(|| PdfError::Other { msg: format!("glyph {} out of bounds", gid) })
+    };
+
+    // the glyphs shown, and the glyphs composite glyphs are made of
+    let mut keep = BTreeSet::from([0]);
+    let mut todo: Vec<u16> = chars.values().copied().collect();
+    while let Some(gid) = todo.pop() {
+        if keep.insert(gid) {
+            for (_, component) in components(glyph(gid)?)? {
+                if !keep.contains(&component) {
+                    todo.push(component);
+                }
+            }
+        }
+    }
+    let glyphs: BTreeMap<u16, u16> = keep.iter().enumerate().map(|(new, &old)| (old, new as u16)).collect();
+
+    let mut new_glyf = vec![];
+    let mut new_loca = vec![];
+    let mut new_hmtx = vec![];
+    for &old in glyphs.keys() {
+        new_loca.extend((new_glyf.len() as u32).to_be_bytes());
+        let mut outline = glyph(old)?.to_vec();
+        for (offset, component) in components(&outline)? {
+            outline[offset..offset + 2].copy_from_slice(&glyphs[&component].to_be_bytes());
+        }
+        new_glyf.extend(outline);
+        while new_glyf.len() % 4 != 0 {
+            new_glyf.push(0);
+        }
+
+        // glyphs past the last long metric share its advance
+        let (advance, lsb) = if old < num_metrics {
+            (read_u16(hmtx, 4 * old as usize)?, read_u16(hmtx, 4 * old as usize + 2)?)
+        } else {
+            let last = num_metrics.saturating_sub(1) as usize;
+            (read_u16(hmtx, 4 * last)?, read_u16(hmtx, 4 * num_metrics as usize + 2 * (old - num_metrics) as usize)?)
+        };
+        new_hmtx.extend(advance.to_be_bytes());
+        new_hmtx.extend(lsb.to_be_bytes());
+    }
+    new_loca.extend((new_glyf.len() as u32).to_be_bytes());
+
+    let count = glyphs.len() as u16;
+    let mut new_head = head.to_vec();
+    set_u32(&mut new_head, 8, 0)?; // checkSumAdjustment, set when the file is complete
+    set_u16(&mut new_head, 50, 1)?; // long offsets in loca
+    let mut new_hhea = hhea.to_vec();
+    set_u16(&mut new_hhea, 34, count)?;
+    let mut new_maxp = maxp.to_vec();
+    set_u16(&mut new_maxp, 4, count)?;
+    // version 3, no glyph names
+    let mut new_post = table(b"post").and_then(|post| post.get(..32)).map(|post| post.to_vec()).unwrap_or_else(|| vec![0; 32]);
+    new_post[..4].copy_from_slice(&0x0003_0000u32.to_be_bytes());
+
+    let new_chars: BTreeMap<char, u16> = chars.iter().map(|(&c, old)| (c, glyphs[old])).collect();
+    let mut tables: BTreeMap<[u8; 4], Vec<u8>> = BTreeMap::from([
+        (*b"cmap", cmap(&new_chars)),
+        (*b"glyf", new_glyf),
+        (*b"head", new_head),
+        (*b"hhea", new_hhea),
+        (*b"hmtx", new_hmtx),
+        (*b"loca", new_loca),
+        (*b"maxp", new_maxp),
+        (*b"post", new_post),
+    ]);
+    for tag in COPIED {
+        if let Some(data) = table(tag) {
+            tables.insert(*tag, data.to_vec());
+        }
+    }
+
+    let mut data = write_font(&tables);
+    let head_offset = table_offset(&data, b"head");
+    let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&data));
+    data[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    Ok(Subset { data, glyphs })
+}
+
+/// The components of a composite glyph, with the offset of their glyph id
+fn components(glyph: &[u8]) -> Result<Vec<(usize, u16)>, PdfError> {
+    let mut components = vec![];
+    // simple glyphs have a positive number of contours, empty glyphs no data
+    if glyph.len() < 10 || read_u16(glyph, 0)? as i16 >= 0 {
+        return Ok(components);
+    }
+    let mut pos = 10;
+    loop {
+        let flags = read_u16(glyph, pos)?;
+        components.push((pos + 2, read_u16(glyph, pos + 2)?));
+        pos += 4;
+        pos += if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & WE_HAVE_A_SCALE != 0 {
+            pos += 2;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            pos += 4;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            pos += 8;
+        }
+        if flags & MORE_COMPONENTS == 0 {
+            return Ok(components);
+        }
+    }
+}
+
+/// A `cmap` with a format 4 table for the Basic Multilingual Plane and a
+/// format 12 table for all characters
+///
+/// Characters whose glyphs follow each other are written as one range. The format 4
+/// table is left out if it has too many ranges for its 16 bit length field.
+fn cmap(chars: &BTreeMap<char, u16>) -> Vec<u8> {
+    let ranges = ranges(chars);
+
+    // format 4, a segment for each range in the BMP and the final one
+    let bmp: Vec<(u32, u32, u16)> = ranges.iter()
+        .filter(|&&(start, ..)| start < 0xFFFF)
+        .map(|&(start, end, gid)| (start, end.min(0xFFFE), gid))
+        .collect();
+    let segments = bmp.len() as u32 + 1;
+    let format4_len = 16 + 8 * segments;
+    let format4 = (format4_len <= u16::MAX as u32).then(|| {
+        let entry_selector = 31 - segments.leading_zeros();
+        let search_range = 2 << entry_selector;
+        let mut format4 = vec![];
+        for v in [4, format4_len, 0, 2 * segments, search_range, entry_selector, 2 * segments - search_range] {
+            format4.extend((v as u16).to_be_bytes());
+        }
+        let end_codes = bmp.iter().map(|&(_, end, _)| end as u16).chain([0xFFFF]);
+        let start_codes = bmp.iter().map(|&(start, ..)| start as u16).chain([0xFFFF]);
+        let deltas = bmp.iter().map(|&(start, _, gid)| gid.wrapping_sub(start as u16)).chain([1]);
+        format4.extend(end_codes.flat_map(u16::to_be_bytes));
+        format4.extend([0, 0]); // reservedPad
+        format4.extend(start_codes.flat_map(u16::to_be_bytes));
+        format4.extend(deltas.flat_map(u16::to_be_bytes));
+        format4.extend((0..segments).flat_map(|_| [0, 0])); // idRangeOffset
+        format4
+    });
+
+    let mut format12 = vec![];
+    format12.extend(12u16.to_be_bytes());
+    format12.extend(0u16.to_be_bytes());
+    format12.extend((16 + 12 * ranges.len() as u32).to_be_bytes());
+    format12.extend(0u32.to_be_bytes());
+    format12.extend((ranges.len() as u32).to_be_bytes());
+    for &(start, end, gid) in &ranges {
+        format12.extend(start.to_be_bytes());
+        format12.extend(end.to_be_bytes());
+        format12.extend((gid as u32).to_be_bytes());
+    }
+
+    // header and encoding records: Windows Unicode BMP, then Windows Unicode full
+    let mut records = vec![];
+    let mut offset = 4 + 8 * (format4.is_some() as u32 + 1);
+    if let Some(ref format4) = format4 {
+        records.push((1u16, offset));
+        offset += format4.len() as u32;
+    }
+    records.push((10, offset));
+
+    let mut out = vec![];
+    out.extend(0u16.to_be_bytes());
+    out.extend((records.len() as u16).to_be_bytes());
+    for (encoding, offset) in records {
+        out.extend(3u16.to_be_bytes());
+        out.extend(encoding.to_be_bytes());
+        out.extend(offset.to_be_bytes());
+    }
+    out.extend(format4.unwrap_or_default());
+    out.extend(format12);
+    out
+}
+
+/// Runs of characters mapped to consecutive glyphs: first and last character and the first glyph
+fn ranges(chars: &BTreeMap<char, u16>) -> Vec<(u32, u32, u16)> {
+    let mut ranges: Vec<(u32, u32, u16)> = vec![];
+    for (&c, &gid) in chars {
+        let c = c as u32;
+        match ranges.last_mut() {
+            // the BMP ends a range, so format 4 can take the ranges as they are
+            Some((start, end, first)) if *end + 1 == c && c != 0x10000
+                && u32::from(*first) + (c - *start) == u32::from(gid) => *end = c,
+            _ => ranges.push((c, c, gid)),
+        }
+    }
+    ranges
+}
+
+/// Write the table directory and the tables, sorted by tag
+fn write_font(tables: &BTreeMap<[u8; 4], Vec<u8>>) -> Vec<u8> {
+    let num_tables = tables.len() as u16;
+    let entry_selector = 15 - num_tables.leading_zeros() as u16;
+    let search_range = 16 << entry_selector;
+    let mut out = vec![];
+    out.extend(0x0001_0000u32.to_be_bytes());
+    for v in [num_tables, search_range, entry_selector, 16 * num_tables - search_range] {
+        out.extend(v.to_be_bytes());
+    }
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in tables {
+        out.extend(tag);
+        out.extend(checksum(data).to_be_bytes());
+        out.extend((offset as u32).to_be_bytes());
+        out.extend((data.len() as u32).to_be_bytes());
+        offset += (data.len() + 3) & !3;
+    }
+    for data in tables.values() {
+        out.extend(data);
+        out.resize((out.len() + 3) & !3, 0);
+    }
+    out
+}
+
+fn table_offset(font: &[u8], tag: &[u8; 4]) -> usize {
+    let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+    (0..num_tables)
+        .map(|i| &font[12 + 16 * i..28 + 16 * i])
+        .find(|record| &record[..4] == tag)
+        .map(|record| u32::from_be_bytes([record[8], record[9], record[10], record[11]]) as usize)
+        .expect("table was written")
+}
+
+/// Sum of the big endian 32 bit words of `data`, padded with zeros
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_be_bytes(word)
+        })
+        .fold(0, u32::wrapping_add)
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16, PdfError> {
+    match data.get(pos..pos + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+        None => Err(PdfError::EOF),
+    }
+}
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, PdfError> {
+    match data.get(pos..pos + 4) {
+        Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(PdfError::EOF),
+    }
+}
+fn set_u16(data: &mut [u8], pos: usize, value: u16) -> Result<(), PdfError> {
+    data.get_mut(pos..pos + 2).ok_or(PdfError::EOF)?.copy_from_slice(&value.to_be_bytes());
+    Ok(())
+}
+fn set_u32(data: &mut [u8], pos: usize, value: u32) -> Result<(), PdfError> {
+    data.get_mut(pos..pos + 4).ok_or(PdfError::EOF)?.copy_from_slice(&value.to_be_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ttf_parser::Face;
+
+    #[test]
+    fn subset_keeps_shown_glyphs() {
+        let data = std::fs::read("../acroform_files/DejaVuSans-ExtraLight.ttf").unwrap();
+        let face = Face::parse(&data, 0).unwrap();
+        let chars: BTreeMap<char, u16> = "Kαλé".chars().map(|c| (c, face.glyph_index(c).unwrap().0)).collect();
+
+        let subset = subset(&data, &chars).unwrap();
+        assert!(subset.data.len() < data.len() / 10);
+        let small = Face::parse(&subset.data, 0).unwrap();
+        assert_eq!(small.number_of_glyphs() as usize, subset.glyphs.len());
+        for (c, old) in chars {
+            let new = small.glyph_index(c).expect("character lost");
+            assert_eq!(subset.glyphs[&old], new.0);
+            assert_eq!(small.glyph_hor_advance(new), face.glyph_hor_advance(ttf_parser::GlyphId(old)));
+            assert_eq!(small.glyph_bounding_box(new), face.glyph_bounding_box(ttf_parser::GlyphId(old)));
+        }
+        assert_eq!(checksum(&subset.data), 0xB1B0_AFBA);
+    }
+
+    #[test]
+    fn cmap_ranges() {
+        use ttf_parser::cmap::{Format, Table};
+
+        let lookup = |data: &[u8], c: char| -> Vec<Option<u16>> {
+            let table = Table::parse(data).unwrap();
+            table.subtables.into_iter().map(|t| t.glyph_index(c as u32).map(|g| g.0)).collect()
+        };
+        let formats = |data: &[u8]| -> Vec<u8> {
+            let table = Table::parse(data).unwrap();
+            table.subtables.into_iter().map(|t| match t.format {
+                Format::SegmentMappingToDeltaValues(_) => 4,
+                Format::SegmentedCoverage(_) => 12,
+                _ => 0,
+            }).collect()
+        };
+
+        // consecutive glyphs share a range, one in each table
+        let chars: BTreeMap<char, u16> = ('a'..='z').zip(1..).chain([('\u{1F600}', 30)]).collect();
+        let data = cmap(&chars);
+        assert_eq!(formats(&data), [4, 12]);
+        assert_eq!(lookup(&data, 'm'), [Some(13), Some(13)]);
+        assert_eq!(lookup(&data, '\u{1F600}'), [None, Some(30)]);
+        assert_eq!(data.len(), 4 + 2 * 8 + (16 + 8 * 2) + (16 + 12 * 2));
+
+        // too many ranges for format 4: every other glyph, for more than 8190 characters
+        let chars: BTreeMap<char, u16> = ('\u{4E00}'..='\u{6DFF}').zip((1..).step_by(2)).collect();
+        let data = cmap(&chars);
+        assert_eq!(formats(&data), [12]);
+        assert_eq!(lookup(&data, '\u{4E01}'), [Some(3)]);
+        assert_eq!(lookup(&data, '\u{6DFF}'), [Some(2 * 0x2000 - 1)]);
+    }
+}
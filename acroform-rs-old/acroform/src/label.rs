@@ -0,0 +1,274 @@
+//! Labels for fields, guessed from the text printed next to their widgets
+//!
+//! Printed forms put the caption of a box to its left or above it. For each widget
+//! the closest piece of page text in one of those places is taken, with a confidence
+//! that falls off with the distance and when another widget sits in between.
+
+use std::collections::{HashMap, HashSet};
+
+use pdf::backend::Backend;
+use pdf::error::PdfError;
+use pdf::file::CachedFile;
+use pdf::object::Rectangle;
+use pdf::primitive::Primitive;
+use pdf::text::{page_text, TextRun};
+
+use crate::api::widget_refs;
+use crate::field::{FieldDictionaryExt, InteractiveFormDictionaryExt};
+
+/// Text left of a widget is not taken when it ends further away than this, in points
+const MAX_LEFT_GAP: f32 = 144.0;
+
+/// Text above a widget is not taken when it is further away than this, in points
+const MAX_ABOVE_GAP: f32 = 36.0;
+
+/// Confidence kept when another widget lies between the text and the widget
+const BLOCKED_FACTOR: f32 = 0.5;
+
+/// Confidence kept for text above a widget that does not start at its left edge
+const UNALIGNED_FACTOR: f32 = 0.8;
+
+/// A caption for a field, guessed from the page text
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldLabel {
+    /// The text, without trailing colons and fill-in underscores
+    pub text: String,
+    /// How likely the text is the caption of the field, from 0 to 1
+    pub confidence: f32,
+}
+
+/// A piece of page text that may be a label
+struct Phrase {
+    text: String,
+    bbox: Rectangle,
+}
+
+/// The best label of each field by its full name
+///
+/// Pages whose text can't be extracted are skipped, their fields get no label from them.
+pub(crate) fn field_labels<B: Backend>(file: &CachedFile<B>) -> Result<HashMap<String, FieldLabel>, PdfError> {
+    let mut labels: HashMap<String, FieldLabel> = HashMap::new();
+    let forms = match file.get_root().forms {
+        Some(ref forms) => forms,
+        None => return Ok(labels),
+    };
+    let resolver = file.resolver();
+
+    let mut names = HashMap::new();
+    for field in forms.all_fields(&resolver)? {
+        if field.typ.is_some() {
+            let name = field.get_full_name(&resolver)?;
+            for widget_ref in widget_refs(&field) {
+                names.insert(widget_ref, name.clone());
+            }
+        }
+    }
+    let full_names: HashSet<&String> = names.values().collect();
+
+    for page in file.pages() {
+        let page = page?;
+        let annots = page.annotations.load(&resolver)?;
+        // some files have page annotations that are copies of the fields, found by their name,
+        // which is the full name for annotations without a parent
+        let widgets: Vec<(&String, Rectangle)> = annots.data().iter()
+            .filter_map(|annot| {
+                let name = match (annot.as_ref(), annot.other.get("T")) {
+                    (Some(r), _) if names.contains_key(&r.get_inner()) => &names[&r.get_inner()],
+                    (_, Some(Primitive::String(t))) if annot.other.get("Parent").is_none() => {
+                        *full_names.get(&t.to_string_lossy())?
+                    }
+                    _ => return None,
+                };
+                Some((name, normalize(annot.rect?)))
+            })
+            .collect();
+        if widgets.is_empty() {
+            continue;
+        }
+
+        let phrases = match page_text(&page, &resolver) {
+            Ok(runs) => phrases(&runs),
+            Err(_) => continue,
+        };
+        let rects: Vec<Rectangle> = widgets.iter().map(|&(_, rect)| rect).collect();
+        for &(name, rect) in &widgets {
+            if let Some(label) = best_label(rect, &phrases, &rects) {
+                if labels.get(name).is_none_or(|old| old.confidence < label.confidence) {
+                    labels.insert(name.clone(), label);
+                }
+            }
+        }
+    }
+    Ok(labels)
+}
+
+/// The phrase most likely to be the caption of the widget at `rect`
+fn best_label(rect: Rectangle, phrases: &[Phrase], widgets: &[Rectangle]) -> Option<FieldLabel> {
+    phrases.iter()
+        .filter_map(|phrase| {
+            let confidence = left_confidence(rect, phrase.bbox, widgets).max(above_confidence(rect, phrase.bbox, widgets));
+            (confidence > 0.0).then(|| FieldLabel { text: phrase.text.clone(), confidence })
+        })
+        .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+}
+
+/// Confidence for text on the same line as the widget, ending left of it
+fn left_confidence(rect: Rectangle, text: Rectangle, widgets: &[Rectangle]) -> f32 {
+    let overlap = text.top.min(rect.top) - text.bottom.max(rect.bottom);
+    let height = (text.top - text.bottom).min(rect.top - rect.bottom);
+    // a little overlap is allowed, text often reaches into the box
+    let gap = rect.left - text.right;
+    if overlap < height / 2.0 || !(-2.0 ..= MAX_LEFT_GAP).contains(&gap) {
+        return 0.0;
+    }
+    let between = Rectangle { left: text.right, right: rect.left, bottom: rect.bottom, top: rect.top };
+    let blocked = widgets.iter().any(|w| inside(w, between));
+    (1.0 - gap.max(0.0) / MAX_LEFT_GAP) * if blocked { BLOCKED_FACTOR } else { 1.0 }
+}
+
+/// Confidence for text above the widget, overlapping it horizontally
+fn above_confidence(rect: Rectangle, text: Rectangle, widgets: &[Rectangle]) -> f32 {
+    let overlap = text.right.min(rect.right) - text.left.max(rect.left);
+    // captions printed inside the top of a tall box count as well
+    let gap = text.bottom - rect.top;
+    if overlap <= 0.0 || gap < -(text.top - text.bottom) / 2.0 || gap > MAX_ABOVE_GAP {
+        return 0.0;
+    }
+    let aligned = (text.left - rect.left).abs() <= 10.0;
+    let between = Rectangle { left: rect.left, right: rect.right, bottom: rect.top, top: text.bottom };
+    let blocked = widgets.iter().any(|w| inside(w, between));
+    (1.0 - gap.max(0.0) / MAX_ABOVE_GAP)
+        * if aligned { 1.0 } else { UNALIGNED_FACTOR }
+        * if blocked { BLOCKED_FACTOR } else { 1.0 }
+}
+
+/// Whether the center of `widget` lies within `area`
+fn inside(widget: &Rectangle, area: Rectangle) -> bool {
+    let x = (widget.left + widget.right) / 2.0;
+    let y = (widget.bottom + widget.top) / 2.0;
+    area.left < x && x < area.right && area.bottom < y && y < area.top
+}
+
+/// Split the runs at fill-in lines, join words on a line and drop what can't be a label
+fn phrases(runs: &[TextRun]) -> Vec<Phrase> {
+    let mut pieces: Vec<Phrase> = vec![];
+    for run in runs {
+        for piece in split_run(run) {
+            let height = piece.bbox.top - piece.bbox.bottom;
+            match pieces.last_mut() {
+                Some(last) if same_line(last.bbox, piece.bbox) && (piece.bbox.left - last.bbox.right).abs() < height / 2.0 => {
+                    if !last.text.ends_with(' ') && !piece.text.starts_with(' ') {
+                        last.text.push(' ');
+                    }
+                    last.text.push_str(&piece.text);
+                    last.bbox.right = piece.bbox.right;
+                    last.bbox.bottom = last.bbox.bottom.min(piece.bbox.bottom);
+                    last.bbox.top = last.bbox.top.max(piece.bbox.top);
+                }
+                _ => pieces.push(piece),
+            }
+        }
+    }
+    pieces.into_iter()
+        .filter_map(|mut phrase| {
+            phrase.text = clean(&phrase.text)?;
+            Some(phrase)
+        })
+        .collect()
+}
+
+/// The parts of `run` between fill-in lines of underscores, placed in proportion to their characters
+fn split_run(run: &TextRun) -> Vec<Phrase> {
+    let chars: Vec<char> = run.text.chars().collect();
+    let bbox = normalize(run.bbox);
+    let width = (bbox.right - bbox.left) / chars.len().max(1) as f32;
+    let mut parts = vec![];
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start ..= chars.len()).find(|&i| i == chars.len() || chars[i ..].starts_with(&['_', '_', '_'])).unwrap_or(chars.len());
+        if end > start {
+            parts.push(Phrase {
+                text: chars[start .. end].iter().collect(),
+                bbox: Rectangle { left: bbox.left + start as f32 * width, right: bbox.left + end as f32 * width, ..bbox },
+            });
+        }
+        start = (end ..= chars.len()).find(|&i| i == chars.len() || chars[i] != '_').unwrap_or(chars.len());
+    }
+    parts
+}
+
+fn same_line(a: Rectangle, b: Rectangle) -> bool {
+    let overlap = a.top.min(b.top) - a.bottom.max(b.bottom);
+    overlap > (a.top - a.bottom).min(b.top - b.bottom) / 2.0
+}
+
+/// `text` without surrounding space, trailing colons, dots and underscores
+///
+/// Section numbers like `2` or `IV`, single characters and notes in parentheses are no labels.
+fn clean(text: &str) -> Option<String> {
+    let text = text.trim().trim_end_matches(|c: char| c == ':' || c == '.' || c == '_' || c.is_whitespace());
+    let numbering = text.chars().all(|c| c.is_ascii_digit()) || text.chars().all(|c| "IVXLC".contains(c));
+    let note = text.starts_with('(') && text.ends_with(')');
+    let short = text.chars().filter(|c| c.is_alphanumeric()).count() < 2;
+    (!numbering && !note && !short).then(|| text.to_string())
+}
+
+fn normalize(r: Rectangle) -> Rectangle {
+    Rectangle {
+        left: r.left.min(r.right),
+        right: r.left.max(r.right),
+        bottom: r.bottom.min(r.top),
+        top: r.bottom.max(r.top),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: f32, bottom: f32, right: f32, top: f32) -> Rectangle {
+        Rectangle { left, bottom, right, top }
+    }
+
+    fn phrase(text: &str, bbox: Rectangle) -> Phrase {
+        Phrase { text: text.into(), bbox }
+    }
+
+    #[test]
+    fn left_beats_far_above() {
+        let widget = rect(100.0, 100.0, 200.0, 112.0);
+        let phrases = [phrase("Name", rect(60.0, 101.0, 95.0, 110.0)), phrase("Section 1", rect(100.0, 140.0, 160.0, 148.0))];
+        let label = best_label(widget, &phrases, &[widget]).unwrap();
+        assert_eq!(label.text, "Name");
+        assert!(label.confidence > 0.9);
+    }
+
+    #[test]
+    fn blocked_by_other_widget() {
+        let first = rect(40.0, 600.0, 200.0, 612.0);
+        let second = rect(40.0, 580.0, 200.0, 592.0);
+        let phrases = [phrase("REQUISITES", rect(100.0, 616.0, 140.0, 624.0))];
+        let widgets = [first, second];
+        let a = best_label(first, &phrases, &widgets).unwrap();
+        let b = best_label(second, &phrases, &widgets).unwrap();
+        assert_eq!(b.text, "REQUISITES");
+        assert!(b.confidence < a.confidence / 2.0);
+    }
+
+    #[test]
+    fn nothing_near() {
+        let widget = rect(100.0, 100.0, 200.0, 112.0);
+        let phrases = [phrase("Footer", rect(100.0, 20.0, 160.0, 28.0)), phrase("Right", rect(210.0, 101.0, 240.0, 110.0))];
+        assert_eq!(best_label(widget, &phrases, &[widget]), None);
+    }
+
+    #[test]
+    fn clean_text() {
+        assert_eq!(clean("  Date of birth: ").as_deref(), Some("Date of birth"));
+        assert_eq!(clean("Name ....").as_deref(), Some("Name"));
+        assert_eq!(clean(" ____ "), None);
+        assert_eq!(clean("IV."), None);
+        assert_eq!(clean("2"), None);
+        assert_eq!(clean("(Explain on back)"), None);
+    }
+}
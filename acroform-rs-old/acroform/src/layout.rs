@@ -0,0 +1,194 @@
+//! Layout of field values inside their widgets
+//!
+//! Everything here works in text space units for a font size of 1, so the
+//! measurements can be scaled to any candidate size while searching for the
+//! size that fits.
+
+/// Smallest font size chosen for auto-sized fields (`0 Tf` in `/DA`)
+pub const MIN_AUTO_FONT_SIZE: f32 = 4.0;
+
+/// Largest font size chosen for auto-sized fields, as in Acrobat
+pub const MAX_AUTO_FONT_SIZE: f32 = 12.0;
+
+/// Step used when shrinking multiline text until it fits
+const AUTO_FONT_SIZE_STEP: f32 = 0.5;
+
+/// Vertical extent of a font, relative to the font size
+#[derive(Debug, Clone, Copy)]
+pub struct VerticalMetrics {
+    /// Height above the baseline, positive
+    pub ascent: f32,
+    /// Depth below the baseline, negative
+    pub descent: f32,
+}
+
+impl VerticalMetrics {
+    /// Distance between the baselines of two lines
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent
+    }
+}
+
+/// Largest font size at which `text` fits on a single line of `width` x `height`
+///
+/// The height allows one line of text, the width all of `text`.
+/// The result is at most [`MAX_AUTO_FONT_SIZE`], so tall fields don't get huge text,
+/// and at least [`MIN_AUTO_FONT_SIZE`], even if the text does not fit then.
+pub fn auto_size_single_line(
+    text: &str,
+    measure: impl Fn(&str) -> f32,
+    metrics: VerticalMetrics,
+    width: f32,
+    height: f32,
+) -> f32 {
+    let mut size = height / metrics.line_height();
+    let text_width = measure(text);
+    if text_width > 0.0 {
+        size = size.min(width / text_width);
+    }
+    size.clamp(MIN_AUTO_FONT_SIZE, MAX_AUTO_FONT_SIZE)
+}
+
+/// Largest font size at which `text`, wrapped to `width`, fits into `height`
+///
+/// Starts at [`MAX_AUTO_FONT_SIZE`] and shrinks in half point steps down to
+/// [`MIN_AUTO_FONT_SIZE`].
+pub fn auto_size_multiline(
+    text: &str,
+    measure: impl Fn(&str) -> f32,
+    metrics: VerticalMetrics,
+    width: f32,
+    height: f32,
+) -> f32 {
+    let mut size = MAX_AUTO_FONT_SIZE;
+    while size > MIN_AUTO_FONT_SIZE {
+        let lines = wrap(text, &measure, width / size);
+        if lines.len() as f32 * metrics.line_height() * size <= height {
+            break;
+        }
+        size -= AUTO_FONT_SIZE_STEP;
+    }
+    size.max(MIN_AUTO_FONT_SIZE)
+}
+
+/// Largest font size at which each character of `text` fits into a comb cell of `cell_width` x `height`
+///
+/// The result is limited like for single lines.
+pub fn auto_size_comb(
+    text: &str,
+    measure: impl Fn(&str) -> f32,
+    metrics: VerticalMetrics,
+    cell_width: f32,
+    height: f32,
+) -> f32 {
+    let mut size = height / metrics.line_height();
+    let widest = text.chars().map(|c| measure(c.encode_utf8(&mut [0; 4]))).fold(0.0, f32::max);
+    if widest > 0.0 {
+        size = size.min(cell_width / widest);
+    }
+    size.clamp(MIN_AUTO_FONT_SIZE, MAX_AUTO_FONT_SIZE)
+}
+
+/// The longest start of `text` that is no wider than `width`
+///
+/// Used for fields that don't scroll, which only take what they can show.
+pub fn fit(text: &str, measure: impl Fn(&str) -> f32, width: f32) -> &str {
+    if measure(text) <= width {
+        return text;
+    }
+    let mut end = 0;
+    for (i, c) in text.char_indices() {
+        if measure(&text[..i + c.len_utf8()]) > width {
+            break;
+        }
+        end = i + c.len_utf8();
+    }
+    &text[..end]
+}
+
+/// Break `text` into lines no wider than `width`
+///
+/// Lines are broken at explicit line breaks and between words. A word that is
+/// wider than `width` on its own is broken between characters.
+pub fn wrap(text: &str, measure: impl Fn(&str) -> f32, width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split("\r\n").flat_map(|p| p.split(['\r', '\n'])) {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if measure(&candidate) <= width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            // the word alone may still be too wide
+            for c in word.chars() {
+                line.push(c);
+                if measure(&line) > width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every character is half as wide as the font size
+    fn measure(s: &str) -> f32 {
+        s.chars().count() as f32 * 0.5
+    }
+
+    const METRICS: VerticalMetrics = VerticalMetrics { ascent: 0.8, descent: -0.2 };
+
+    #[test]
+    fn single_line_shrinks_to_fit() {
+        // limited by the height
+        assert_eq!(auto_size_single_line("ab", measure, METRICS, 100.0, 10.0), 10.0);
+        // limited by the width: 10 characters at size 8 are 40 wide
+        assert_eq!(auto_size_single_line("0123456789", measure, METRICS, 40.0, 20.0), 8.0);
+        // tall fields get no more than the maximum
+        assert_eq!(auto_size_single_line("ab", measure, METRICS, 100.0, 50.0), MAX_AUTO_FONT_SIZE);
+        // but never below the minimum
+        assert_eq!(auto_size_single_line(&"x".repeat(100), measure, METRICS, 40.0, 20.0), MIN_AUTO_FONT_SIZE);
+    }
+
+    #[test]
+    fn multiline_wraps_and_shrinks() {
+        assert_eq!(auto_size_multiline("short", measure, METRICS, 100.0, 100.0), MAX_AUTO_FONT_SIZE);
+        let text = "the quick brown fox jumps over the lazy dog";
+        let size = auto_size_multiline(text, measure, METRICS, 60.0, 30.0);
+        assert!(size < MAX_AUTO_FONT_SIZE);
+        let lines = wrap(text, measure, 60.0 / size);
+        assert!(lines.len() as f32 * size <= 30.0);
+    }
+
+    #[test]
+    fn comb_fits_widest_character() {
+        // limited by the height
+        assert_eq!(auto_size_comb("123", measure, METRICS, 20.0, 10.0), 10.0);
+        // limited by the cell: 5 wide at size 10
+        assert_eq!(auto_size_comb("123", measure, METRICS, 4.0, 20.0), 8.0);
+    }
+
+    #[test]
+    fn fit_cuts_at_width() {
+        assert_eq!(fit("abcdef", measure, 10.0), "abcdef");
+        assert_eq!(fit("abcdef", measure, 1.7), "abc");
+        assert_eq!(fit("abcdef", measure, 0.2), "");
+    }
+
+    #[test]
+    fn wrap_words_and_newlines() {
+        assert_eq!(wrap("aa bb cc", measure, 2.5), vec!["aa bb", "cc"]);
+        assert_eq!(wrap("aa\nbb\r\n\ncc", measure, 10.0), vec!["aa", "bb", "", "cc"]);
+        assert_eq!(wrap("abcdefg", measure, 1.5), vec!["abc", "def", "g"]);
+    }
+}
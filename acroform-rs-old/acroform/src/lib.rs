@@ -57,14 +57,41 @@ This library supports the following field types:
 
 Field names are fully qualified (e.g., `"parent.child.field"`) and automatically
 resolved for you, even in forms with nested field hierarchies.
+
+Filled text fields get new appearance streams, set in the field's font. For values
+that font can't show, such as Greek or Chinese text with `/Helv`, register a
+TrueType or OpenType font with `AcroFormDocument::register_font`; it is embedded
+when a value needs it.
+
+## Errors
+
+[`AcroFormDocument::fill`], [`AcroFormDocument::fill_and_save`] and
+[`AcroFormDocument::fill_to_writer`] return [`FillError`]. Besides the errors of the
+underlying [`PdfError`], it reports form-level failures such as a value longer than
+its field's `/MaxLen` when [`MaxLenPolicy::Reject`] is set.
+
+This is a breaking change: these methods used to return `PdfError`, and matches on
+their errors now have to look inside [`FillError::Pdf`]. Functions propagating them
+with `?` have to return `FillError`, or a type such as `Box<dyn std::error::Error>`.
 */
 
 mod field;
+mod font;
 mod api;
-
-pub use api::{AcroFormDocument, FormField, FieldValue};
+mod appearance;
+mod layout;
+mod label;
+mod error;
+
+pub use api::{AcroFormDocument, FormField, FieldValue, FillOptions, MaxLenPolicy};
+pub use label::FieldLabel;
+pub use error::FillError;
 pub use field::{FieldDictionaryExt, InteractiveFormDictionaryExt};
 
 // Re-export commonly used types from pdf crate
+pub use pdf::backend::{Revision, SeekBackend};
 pub use pdf::error::PdfError;
+pub use pdf::crypt::Permissions;
+pub use pdf::pubsec::Recipient;
+pub use pdf::file::{SaveOptions, XRefFormat};
 pub use pdf::object::FieldType;
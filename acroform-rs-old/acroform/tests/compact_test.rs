@@ -0,0 +1,108 @@
+//! Tests for writing a compacted copy of a repeatedly filled form
+
+use acroform::{AcroFormDocument, FieldValue, SaveOptions};
+use pdf::file::FileOptions;
+use pdf::object::{NoResolve, PlainRef, Resolve, Trace};
+use pdf::primitive::Primitive;
+use pdf::parser::{parse, ParseFlags};
+use std::collections::{HashMap, HashSet};
+
+/// Assert that every object of a file is referenced by the trailer or another object.
+/// Cross-reference and object streams are found through the cross-reference data instead.
+fn assert_all_referenced(data: &[u8]) {
+    let file = FileOptions::uncached().load(data.to_vec()).unwrap();
+    let resolver = file.resolver();
+
+    let mut referenced = HashSet::new();
+    if let Some(pos) = data.windows(7).rposition(|w| w == b"trailer") {
+        let trailer = parse(&data[pos + 7..], &NoResolve, ParseFlags::DICT).unwrap();
+        trailer.trace(&mut |r| { referenced.insert(r.id); });
+    }
+    let size = file.trailer.size as u64;
+    for id in 1..size {
+        let object = resolver.resolve(PlainRef { id, gen: 0 }).unwrap();
+        if let Primitive::Stream(ref s) = object {
+            if matches!(s.info.get("Type").and_then(|t| t.as_name().ok()), Some("XRef" | "ObjStm")) {
+                referenced.insert(id);
+            }
+        }
+        object.trace(&mut |r| { referenced.insert(r.id); });
+    }
+    let unreferenced: Vec<_> = (1..size).filter(|id| !referenced.contains(id)).collect();
+    assert!(unreferenced.is_empty(), "unreferenced objects {:?}", unreferenced);
+}
+
+#[test]
+fn test_compacted_after_repeated_fills() {
+    let original = std::fs::read("../acroform_files/af8_clean.pdf").unwrap();
+    let mut doc = AcroFormDocument::from_bytes(original.clone())
+        .expect("Failed to load PDF");
+
+    let field_name = doc.fields().unwrap().iter()
+        .find(|f| f.name.contains("MbrName[1]"))
+        .expect("Test field not found")
+        .name.clone();
+
+    let mut filled = Vec::new();
+    for i in 0..3 {
+        let mut values = HashMap::new();
+        values.insert(field_name.clone(), FieldValue::Text(format!("Fill {}", i)));
+        filled = doc.fill(values).expect("Failed to fill form");
+    }
+
+    let compacted = doc.save_compacted().expect("Failed to compact");
+    // Every fill leaves stale copies of the updated objects behind
+    let size = |data: &[u8]| FileOptions::uncached().load(data.to_vec()).unwrap().trailer.size;
+    assert!(size(&compacted) < size(&filled), "stale objects were kept");
+    assert_eq!(&compacted[..8], &original[..8]);
+    assert_all_referenced(&compacted);
+
+    let doc2 = AcroFormDocument::from_bytes(compacted)
+        .expect("Failed to reopen compacted PDF");
+    let fields = doc.fields().unwrap();
+    let fields2 = doc2.fields().unwrap();
+    assert_eq!(fields.len(), fields2.len());
+    for (a, b) in fields.iter().zip(&fields2) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.current_value, b.current_value);
+    }
+    let updated = fields2.iter().find(|f| f.name == field_name).unwrap();
+    assert_eq!(updated.current_value, Some(FieldValue::Text("Fill 2".to_string())));
+}
+
+#[test]
+fn test_compacted_with_object_streams() {
+    let original = std::fs::read("../acroform_files/af8_clean.pdf").unwrap();
+    let mut doc = AcroFormDocument::from_bytes(original.clone())
+        .expect("Failed to load PDF");
+    doc.set_save_options(SaveOptions {
+        object_streams: true,
+        compress_above: Some(256),
+        ..Default::default()
+    });
+
+    let field_name = doc.fields().unwrap()[0].name.clone();
+    let mut values = HashMap::new();
+    values.insert(field_name.clone(), FieldValue::Text("Packed".to_string()));
+    doc.fill(values).expect("Failed to fill form");
+
+    let compacted = doc.save_compacted().expect("Failed to compact");
+    assert!(compacted.len() < original.len(), "compacted file is not smaller than the original");
+    assert_all_referenced(&compacted);
+
+    let doc2 = AcroFormDocument::from_bytes(compacted)
+        .expect("Failed to reopen compacted PDF");
+    let field = doc2.fields().unwrap().into_iter()
+        .find(|f| f.name == field_name)
+        .expect("Field not found in compacted PDF");
+    assert_eq!(field.current_value, Some(FieldValue::Text("Packed".to_string())));
+}
+
+#[test]
+fn test_compacted_without_indirect_lengths() {
+    // the content stream keeps its /Length in an object of its own
+    let original = std::fs::read("../files/example.pdf").unwrap();
+    let mut file = FileOptions::uncached().load(original).unwrap();
+    let compacted = file.save_compacted().expect("Failed to compact");
+    assert_all_referenced(&compacted);
+}
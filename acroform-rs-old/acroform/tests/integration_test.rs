@@ -214,3 +214,316 @@ fn test_field_default_value_extraction() {
         let _default = &field.default_value;
     }
 }
+
+#[cfg(feature = "threads")]
+#[test]
+fn test_prefetch() {
+    let doc = AcroFormDocument::from_pdf("../acroform_files/af8.pdf")
+        .expect("Failed to load PDF");
+    let expected = doc.fields().expect("Failed to get fields");
+
+    let mut doc = AcroFormDocument::from_pdf("../acroform_files/af8.pdf")
+        .expect("Failed to load PDF");
+    doc.prefetch().expect("Failed to prefetch");
+    let fields = doc.fields().unwrap();
+    assert_eq!(fields.len(), expected.len());
+    for (field, expected) in fields.iter().zip(&expected) {
+        assert_eq!(field.name, expected.name);
+        assert_eq!(field.current_value, expected.current_value);
+    }
+
+    let mut values = HashMap::new();
+    values.insert(expected[0].name.clone(), FieldValue::Text("Prefetched".to_string()));
+    doc.fill(values).expect("Failed to fill form");
+}
+
+#[test]
+fn test_text_appearance_generated() {
+    use pdf::content::{parse_ops, Op};
+    use pdf::file::FileOptions;
+    use pdf::object::AppearanceStreamEntry;
+
+    let name = "topmostSubform[0].Page1[0].P[0].MbrName[1]";
+    let mut doc = AcroFormDocument::from_pdf("../acroform_files/af8.pdf")
+        .expect("Failed to load PDF");
+    let mut values = HashMap::new();
+    values.insert(name.to_string(), FieldValue::Text("A value long enough to need a smaller font".to_string()));
+    let filled = doc.fill(values).expect("Failed to fill form");
+
+    let file = FileOptions::cached().load(filled).expect("Failed to reload PDF");
+    let resolver = file.resolver();
+    let page = file.get_page(0).unwrap();
+    let annots = page.annotations.load(&resolver).unwrap();
+    let annot = annots.data().iter()
+        .find(|a| a.other.get("T").and_then(|t| t.as_string().ok()).map(|t| t.to_string_lossy()) == Some(name.into()))
+        .expect("widget not found");
+    let appearance = match *annot.appearance_streams.as_ref().expect("no /AP").normal {
+        AppearanceStreamEntry::Single(ref xobject) => xobject.clone(),
+        _ => panic!("expected a single normal appearance"),
+    };
+    let ops = parse_ops(&appearance.stream.data(&resolver).unwrap(), &resolver).unwrap();
+
+    // the value is set in one line, in the font size of the field's /DA
+    let size = ops.iter().find_map(|op| match op {
+        Op::TextFont { size, .. } => Some(*size),
+        _ => None,
+    }).expect("no font set");
+    assert_eq!(size, 10.0);
+    assert_eq!(ops.iter().filter(|op| matches!(op, Op::TextDraw { .. })).count(), 1);
+}
+
+#[test]
+fn test_max_len() {
+    use acroform::{FillError, FillOptions, MaxLenPolicy};
+
+    // give MbrName a /MaxLen of 3, in place of its tooltip so the xref offsets stay valid
+    let data = std::fs::read("../acroform_files/af8.pdf").unwrap();
+    let (from, to) = (b"/TU (MbrName)", b"/MaxLen 3    ");
+    let mut patched = data.clone();
+    for i in 0..data.len() - from.len() {
+        if &data[i..i + from.len()] == from {
+            patched[i..i + to.len()].copy_from_slice(to);
+        }
+    }
+    let name = "topmostSubform[0].Page1[0].P[0].MbrName[1]";
+    let mut values = HashMap::new();
+    values.insert(name.to_string(), FieldValue::Text("ABCDEF".to_string()));
+
+    let mut doc = AcroFormDocument::from_bytes(patched.clone()).unwrap();
+    let filled = doc.fill(values.clone()).expect("Failed to fill form");
+    let doc = AcroFormDocument::from_bytes(filled).unwrap();
+    let field = doc.fields().unwrap().into_iter().find(|f| f.name == name).unwrap();
+    assert_eq!(field.current_value, Some(FieldValue::Text("ABC".to_string())));
+
+    let mut doc = AcroFormDocument::from_bytes(patched).unwrap();
+    doc.set_fill_options(FillOptions { max_len: MaxLenPolicy::Reject });
+    let err = doc.fill(values).unwrap_err();
+    assert!(matches!(err, FillError::ValueTooLong { max_len: 3, .. }), "{:?}", err);
+}
+
+#[test]
+fn test_appearance_error_sets_need_appearances() {
+    use acroform::InteractiveFormDictionaryExt;
+    use pdf::file::FileOptions;
+    use pdf::object::Resolve;
+
+    // a /Q that is not a number, so no appearance can be generated,
+    // in a form that does not have /NeedAppearances yet
+    let data = std::fs::read("../acroform_files/af8.pdf").unwrap();
+    let mut patched = data.clone();
+    let patches: [(&[u8], &[u8]); 2] = [(b"/TU (MbrName)", b"/Q (right)   "), (b"/NeedAppearances", b"/XeedAppearances")];
+    for (from, to) in patches {
+        for i in 0..data.len() - from.len() {
+            if &data[i..i + from.len()] == from {
+                patched[i..i + to.len()].copy_from_slice(to);
+            }
+        }
+    }
+    let name = "topmostSubform[0].Page1[0].P[0].MbrName[1]";
+    let mut values = HashMap::new();
+    values.insert(name.to_string(), FieldValue::Text("NEW_VALUE".to_string()));
+
+    let mut doc = AcroFormDocument::from_bytes(patched.clone()).unwrap();
+    let filled = doc.fill(values).expect("Failed to fill form");
+
+    let doc = AcroFormDocument::from_bytes(filled.clone()).unwrap();
+    let field = doc.fields().unwrap().into_iter().find(|f| f.name == name).unwrap();
+    assert_eq!(field.current_value, Some(FieldValue::Text("NEW_VALUE".to_string())));
+
+    let appearance = |data: Vec<u8>| {
+        let file = FileOptions::cached().load(data).unwrap();
+        let resolver = file.resolver();
+        let forms = file.get_root().forms.as_ref().unwrap();
+        let field = forms.find_field_by_name(name, &resolver).unwrap().unwrap();
+        let widget = resolver.resolve(field.get_ref().get_inner()).unwrap().into_dictionary().unwrap();
+        (forms.need_appearences, widget.get("AP").cloned())
+    };
+    let (before, old_ap) = appearance(patched);
+    let (after, new_ap) = appearance(filled);
+    assert!(!before);
+    assert!(after);
+    // the old appearance is kept
+    assert!(old_ap.is_some());
+    assert_eq!(new_ap, old_ap);
+}
+
+#[test]
+fn test_registered_font_for_greek() {
+    use pdf::file::FileOptions;
+    use pdf::font::{CidToGidMap, FontData};
+    use pdf::object::AppearanceStreamEntry;
+
+    let name = "topmostSubform[0].Page1[0].P[0].MbrName[1]";
+    let mut doc = AcroFormDocument::from_pdf("../acroform_files/af8.pdf")
+        .expect("Failed to load PDF");
+    doc.register_font("../acroform_files/DejaVuSans-ExtraLight.ttf").expect("Failed to register font");
+    let mut values = HashMap::new();
+    values.insert(name.to_string(), FieldValue::Text("Καλημέρα".to_string()));
+    let filled = doc.fill(values).expect("Failed to fill form");
+
+    let file = FileOptions::cached().load(filled).expect("Failed to reload PDF");
+    let resolver = file.resolver();
+
+    // the font was added to the form's default resources
+    let forms = file.get_root().forms.as_ref().unwrap();
+    let dr = forms.dr.as_ref().expect("no /DR");
+    let font = dr.fonts.get("DejaVuSans-ExtraLight").expect("font not in /DR").load(&resolver).unwrap();
+    let descendant = match font.data {
+        FontData::Type0(ref t) => t.descendant_fonts[0].clone(),
+        _ => panic!("expected a Type0 font"),
+    };
+    assert!(matches!(descendant.data, FontData::CIDFontType2(_)));
+    // subset to the glyphs shown
+    let base_font = descendant.name.as_ref().unwrap().as_str();
+    assert_eq!(&base_font[6..], "+DejaVuSans-ExtraLight");
+    assert!(base_font[..6].bytes().all(|b| b.is_ascii_uppercase()), "{}", base_font);
+    assert!(matches!(descendant.cid_to_gid_map(), Some(CidToGidMap::Table(_))));
+    let embedded = descendant.embedded_data(&resolver).unwrap().unwrap();
+    assert!(embedded.len() < 20_000, "{} bytes embedded", embedded.len());
+
+    // and the appearance shows the value in it
+    let page = file.get_page(0).unwrap();
+    let annots = page.annotations.load(&resolver).unwrap();
+    let annot = annots.data().iter()
+        .find(|a| a.other.get("T").and_then(|t| t.as_string().ok()).map(|t| t.to_string_lossy()) == Some(name.into()))
+        .expect("widget not found");
+    let appearance = match *annot.appearance_streams.as_ref().expect("no /AP").normal {
+        AppearanceStreamEntry::Single(ref xobject) => xobject.clone(),
+        _ => panic!("expected a single normal appearance"),
+    };
+    let resources = appearance.stream.info.resources.as_ref().unwrap();
+    let font = resources.fonts.get("DejaVuSans-ExtraLight").expect("font not in the appearance").load(&resolver).unwrap();
+    let to_unicode = font.to_unicode(&resolver).unwrap().unwrap();
+    let mut shown: Vec<_> = to_unicode.iter().map(|(_, s)| s.to_string()).collect();
+    shown.sort();
+    let mut expected: Vec<_> = "Καλημέρα".chars().map(String::from).collect();
+    expected.sort();
+    expected.dedup();
+    assert_eq!(shown, expected);
+}
+
+#[test]
+fn test_registered_font_grows_with_later_fills() {
+    use pdf::file::FileOptions;
+
+    let name = "topmostSubform[0].Page1[0].P[0].MbrName[1]";
+    let mut doc = AcroFormDocument::from_pdf("../acroform_files/af8.pdf")
+        .expect("Failed to load PDF");
+    doc.register_font("../acroform_files/DejaVuSans-ExtraLight.ttf").expect("Failed to register font");
+    let mut filled = vec![];
+    for value in ["Κα", "λμ"] {
+        let mut values = HashMap::new();
+        values.insert(name.to_string(), FieldValue::Text(value.to_string()));
+        filled = doc.fill(values).expect("Failed to fill form");
+    }
+
+    // the subset written by the second fill has the glyphs of both
+    let file = FileOptions::cached().load(filled).expect("Failed to reload PDF");
+    let resolver = file.resolver();
+    let dr = file.get_root().forms.as_ref().unwrap().dr.as_ref().unwrap();
+    let font = dr.fonts.get("DejaVuSans-ExtraLight").unwrap().load(&resolver).unwrap();
+    let to_unicode = font.to_unicode(&resolver).unwrap().unwrap();
+    let mut shown: Vec<_> = to_unicode.iter().map(|(_, s)| s.to_string()).collect();
+    shown.sort();
+    assert_eq!(shown, ["Κ", "α", "λ", "μ"]);
+}
+
+#[test]
+fn test_registered_font_written_without_glyphs() {
+    use pdf::file::FileOptions;
+    use pdf::font::FontData;
+
+    // make the one line high DoNotScroll field multiline, so only the empty first line of the value is shown
+    let data = std::fs::read("../acroform_files/af8.pdf").unwrap();
+    let (from, to) = (b"/Ff 8388608", b"/Ff 8392704");
+    let mut patched = data.clone();
+    for i in 0..data.len() - from.len() {
+        if &data[i..i + from.len()] == from {
+            patched[i..i + to.len()].copy_from_slice(to);
+        }
+    }
+
+    // the value needs the registered font, but no glyph of it is shown
+    let name = "topmostSubform[0].Page1[0].P[0].MbrName[1]";
+    let mut doc = AcroFormDocument::from_bytes(patched).unwrap();
+    doc.register_font("../acroform_files/DejaVuSans-ExtraLight.ttf").expect("Failed to register font");
+    let mut values = HashMap::new();
+    values.insert(name.to_string(), FieldValue::Text("\nΚα".to_string()));
+    let filled = doc.fill(values).expect("Failed to fill form");
+
+    let file = FileOptions::cached().load(filled).expect("Failed to reload PDF");
+    let resolver = file.resolver();
+    let dr = file.get_root().forms.as_ref().unwrap().dr.as_ref().unwrap();
+    let font = dr.fonts.get("DejaVuSans-ExtraLight").expect("font not in /DR").load(&resolver).unwrap();
+    assert!(matches!(font.data, FontData::Type0(_)));
+}
+
+#[test]
+fn test_registered_font_streams_rewritten_in_place() {
+    use pdf::file::FileOptions;
+    use pdf::object::Resolve;
+
+    let name = "topmostSubform[0].Page1[0].P[0].MbrName[1]";
+    let mut doc = AcroFormDocument::from_pdf("../acroform_files/af8.pdf")
+        .expect("Failed to load PDF");
+    doc.register_font("../acroform_files/DejaVuSans-ExtraLight.ttf").expect("Failed to register font");
+    let mut refs = vec![];
+    for value in ["Κα", "λμ"] {
+        let mut values = HashMap::new();
+        values.insert(name.to_string(), FieldValue::Text(value.to_string()));
+        let filled = doc.fill(values).expect("Failed to fill form");
+
+        let file = FileOptions::cached().load(filled).expect("Failed to reload PDF");
+        let resolver = file.resolver();
+        let dr = file.get_root().forms.as_ref().unwrap().dr.as_ref().unwrap();
+        let font = dr.fonts.get("DejaVuSans-ExtraLight").unwrap().load(&resolver).unwrap();
+        let to_unicode = font.to_unicode.as_ref().unwrap().get_ref().get_inner();
+        let dict = resolver.resolve(font.as_ref().unwrap().get_inner()).unwrap().into_dictionary().unwrap();
+        let descendants = dict.get("DescendantFonts").unwrap().clone().into_array().unwrap();
+        let cid_font = descendants[0].clone().into_dictionary().unwrap();
+        let cid_to_gid = cid_font.get("CIDToGIDMap").unwrap().clone().into_reference().unwrap();
+        refs.push((to_unicode, cid_to_gid));
+    }
+    // the second save updates the objects of the first instead of adding new ones
+    assert_eq!(refs[0], refs[1]);
+}
+
+#[test]
+fn test_field_labels_from_page_text() {
+    let doc = AcroFormDocument::from_pdf("../acroform_files/af8_clean.pdf").unwrap();
+    let fields = doc.fields_with_labels().unwrap();
+    let label = |name: &str| {
+        fields.iter().find(|f| f.name.ends_with(name)).unwrap().label.clone()
+    };
+
+    // captions printed above the boxes
+    let name = label(".MbrName[1]").unwrap();
+    assert_eq!(name.text, "NAME (Last, First, Middle Initial)");
+    assert!(name.confidence > 0.8);
+    assert_eq!(label(".MbrGrade[1]").unwrap().text, "GRADE");
+    assert_eq!(label(".RvwOff[1]").unwrap().text, "REVIEWING OFFICER");
+
+    // a column header is less certain for the rows below the first
+    let first = label(".ReqFld1[0]").unwrap();
+    let second = label(".ReqFld2[0]").unwrap();
+    assert_eq!(first.text, "REQUISITES");
+    assert_eq!(second.text, "REQUISITES");
+    assert!(second.confidence < first.confidence);
+    assert_eq!(label(".ReqFld5[0]"), None);
+}
+
+#[test]
+fn test_field_labels_skip_unreadable_pages() {
+    // the content of the second page can't be decoded as hex
+    let data = std::fs::read("../acroform_files/af8_clean.pdf").unwrap();
+    let (from, to) = (b"/Filter /FlateDecode /Length 876 >>", b"/Filter/ASCIIHexDecode/Length 876>>");
+    let i = data.windows(from.len()).position(|w| w == from).unwrap();
+    let mut patched = data;
+    patched[i..i + to.len()].copy_from_slice(to);
+
+    // the first page still has its labels
+    let doc = AcroFormDocument::from_bytes(patched).unwrap();
+    let fields = doc.fields_with_labels().unwrap();
+    let field = fields.iter().find(|f| f.name.ends_with(".MbrName[1]")).unwrap();
+    assert_eq!(field.label.as_ref().unwrap().text, "NAME (Last, First, Middle Initial)");
+}
@@ -0,0 +1,28 @@
+//! Tests that malformed files found by fuzzing give errors instead of panics
+
+use acroform::{AcroFormDocument, FieldValue};
+use std::collections::HashMap;
+
+#[test]
+fn test_invalid_files_do_not_panic() {
+    let mut paths: Vec<_> = std::fs::read_dir("../files/invalid")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.push("../acroform_files/af8_error.pdf".into());
+
+    for path in paths {
+        println!("{}", path.display());
+        let Ok(mut doc) = AcroFormDocument::from_bytes(std::fs::read(&path).unwrap()) else {
+            continue;
+        };
+        let _ = doc.revisions();
+        let Ok(fields) = doc.fields() else { continue };
+        let values: HashMap<_, _> = fields
+            .into_iter()
+            .map(|field| (field.name, FieldValue::Text("value".to_string())))
+            .collect();
+        let _ = doc.fill(values);
+        let _ = doc.save_compacted();
+    }
+}
@@ -0,0 +1,68 @@
+use acroform::{AcroFormDocument, FieldValue, FillError, PdfError, Permissions};
+use std::collections::HashMap;
+
+/// `passwords_aes_256.pdf` grants everything (`/P -1`). Patch it in place to
+/// clear bit 6 and bit 9, keeping all byte offsets intact. For revision 5 the
+/// permissions do not take part in the key derivation.
+fn restricted_pdf() -> Vec<u8> {
+    let mut data = std::fs::read("../files/password_protected/passwords_aes_256.pdf")
+        .expect("Failed to read PDF");
+    let needle = b"/P -1/EncryptMetadata true";
+    let pos = data.windows(needle.len()).position(|w| w == needle)
+        .expect("missing /P entry");
+    let replacement = format!("{:<width$}", "/P -292", width = needle.len());
+    data[pos..pos + needle.len()].copy_from_slice(replacement.as_bytes());
+    data
+}
+
+fn values() -> HashMap<String, FieldValue> {
+    let mut values = HashMap::new();
+    values.insert("name".to_string(), FieldValue::Text("John".to_string()));
+    values
+}
+
+#[test]
+fn test_unencrypted_grants_all() {
+    let doc = AcroFormDocument::from_pdf("../acroform_files/af8.pdf")
+        .expect("Failed to load PDF");
+    assert_eq!(doc.permissions(), Permissions::all());
+}
+
+#[test]
+fn test_fill_denied_by_permissions() {
+    let mut doc = AcroFormDocument::from_bytes_with_password(restricted_pdf(), b"userpassword")
+        .expect("Failed to load PDF");
+    let permissions = doc.permissions();
+    assert!(!permissions.can_fill_forms());
+    assert!(permissions.contains(Permissions::PRINT));
+
+    match doc.fill(values()) {
+        Err(FillError::Pdf(PdfError::PermissionDenied { required })) => assert_eq!(required, Permissions::FILL_FORMS),
+        r => panic!("expected PermissionDenied, got {:?}", r.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_fill_with_owner_password() {
+    let mut doc = AcroFormDocument::from_bytes_with_password(restricted_pdf(), b"ownerpassword")
+        .expect("Failed to load PDF");
+    assert_eq!(doc.permissions(), Permissions::all());
+
+    // the document has no form, so filling gets past the permission check and fails later
+    match doc.fill(values()) {
+        Err(FillError::Pdf(PdfError::MissingEntry { field, .. })) => assert_eq!(field, "AcroForm"),
+        r => panic!("expected MissingEntry, got {:?}", r.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_fill_ignoring_permissions() {
+    let mut doc = AcroFormDocument::from_bytes_with_password(restricted_pdf(), b"userpassword")
+        .expect("Failed to load PDF");
+    doc.ignore_permissions(true);
+
+    match doc.fill(values()) {
+        Err(FillError::Pdf(PdfError::MissingEntry { field, .. })) => assert_eq!(field, "AcroForm"),
+        r => panic!("expected MissingEntry, got {:?}", r.map(|_| ())),
+    }
+}
@@ -0,0 +1,41 @@
+//! Tests for listing and opening the revisions written by repeated fills
+
+use acroform::{AcroFormDocument, FieldValue};
+use std::collections::HashMap;
+
+#[test]
+fn test_revisions_after_fills() {
+    let original = std::fs::read("../acroform_files/af8_clean.pdf").unwrap();
+    let mut doc = AcroFormDocument::from_bytes(original.clone())
+        .expect("Failed to load PDF");
+    let base = doc.revisions().expect("Failed to list revisions").len();
+
+    let field_name = doc.fields().unwrap()[0].name.clone();
+    let mut filled = Vec::new();
+    for i in 0..2 {
+        let mut values = HashMap::new();
+        values.insert(field_name.clone(), FieldValue::Text(format!("Fill {}", i)));
+        filled = doc.fill(values).expect("Failed to fill form");
+    }
+
+    let doc = AcroFormDocument::from_bytes(filled.clone()).expect("Failed to reopen PDF");
+    let revisions = doc.revisions().expect("Failed to list revisions");
+    assert_eq!(revisions.len(), base + 2);
+    assert_eq!(revisions[base - 1].range.end, original.len());
+    assert_eq!(revisions[base + 1].range.end, filled.len());
+    // both fills update the field dictionary
+    let first = &revisions[base];
+    let second = &revisions[base + 1];
+    assert!(first.changed.iter().any(|r| second.changed.contains(r)));
+
+    let value = |doc: &AcroFormDocument| {
+        doc.fields().unwrap().into_iter()
+            .find(|f| f.name == field_name)
+            .unwrap()
+            .current_value
+    };
+    let original_value = value(&AcroFormDocument::from_bytes(original).unwrap());
+    assert_eq!(value(&doc.open_revision(&revisions[base - 1]).unwrap()), original_value);
+    assert_eq!(value(&doc.open_revision(first).unwrap()), Some(FieldValue::Text("Fill 0".to_string())));
+    assert_eq!(value(&doc.open_revision(second).unwrap()), Some(FieldValue::Text("Fill 1".to_string())));
+}
@@ -0,0 +1,28 @@
+//! Tests for filling forms read from a `Read + Seek` source
+
+use acroform::{AcroFormDocument, FieldValue};
+use std::collections::HashMap;
+use std::fs::File;
+
+#[test]
+fn test_fill_from_reader() {
+    let path = "../acroform_files/af8_clean.pdf";
+    let mut streamed_doc = AcroFormDocument::from_reader(File::open(path).unwrap())
+        .expect("Failed to open PDF");
+    let mut doc = AcroFormDocument::from_pdf(path).expect("Failed to load PDF");
+
+    let fields = streamed_doc.fields().expect("Failed to read fields");
+    assert_eq!(fields.len(), doc.fields().unwrap().len());
+
+    let field_name = fields[0].name.clone();
+    let mut values = HashMap::new();
+    values.insert(field_name.clone(), FieldValue::Text("Streamed".to_string()));
+
+    let mut streamed = Vec::new();
+    streamed_doc.fill_to_writer(values.clone(), &mut streamed).expect("Failed to fill form");
+    assert_eq!(streamed, doc.fill(values).unwrap());
+
+    let filled = AcroFormDocument::from_bytes(streamed).expect("Failed to reopen PDF");
+    let field = filled.fields().unwrap().into_iter().find(|f| f.name == field_name).unwrap();
+    assert_eq!(field.current_value, Some(FieldValue::Text("Streamed".to_string())));
+}
@@ -52,7 +52,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         bits_per_component: Some(8),
         .. Default::default()
     };
-    let image = Stream::new_with_filters(image_dict, img_data, vec![StreamFilter::DCTDecode(DCTDecodeParams { color_transform: None})]);
+    let image = Stream::new_with_filters(image_dict, img_data, vec![StreamFilter::DCTDecode(DCTDecodeParams { color_transform: None, other: Default::default() })]);
 
     let mut file = FileOptions::cached().open(&args.input).unwrap();
     let page = file.get_page(args.page).expect("no such page");
@@ -47,6 +47,7 @@ fn main() -> Result<(), PdfError> {
             font_descriptor: None,
             last_char: None,
             widths: None,
+            other: Default::default(),
         }),
         encoding: Some(pdf::encoding::Encoding::standard()),
         name: None,
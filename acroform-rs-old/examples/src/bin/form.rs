@@ -1,11 +1,10 @@
 extern crate pdf;
 
-use std::collections::HashMap;
 use std::env::args;
 
-use pdf::content::{FormXObject, Op, serialize_ops};
+use pdf::content::{ContentBuilder, FormXObject, serialize_ops};
 use pdf::error::PdfError;
-use pdf::file::{FileOptions, Log};
+use pdf::file::FileOptions;
 use pdf::font::{Font, FontData, TFont};
 use pdf::object::*;
 use pdf::primitive::{PdfString, Primitive, Name};
@@ -25,6 +24,7 @@ fn run() -> Result<(), PdfError> {
             font_descriptor: None,
             last_char: None,
             widths: None,
+            other: Default::default(),
         }),
         encoding: Some(pdf::encoding::Encoding::standard()),
         name: None,
@@ -34,43 +34,29 @@ fn run() -> Result<(), PdfError> {
     };
     let font_name = Name::from("Helvetica");
     let font = file.create(font)?;
-    let mut fonts = HashMap::new();
-    fonts.insert("Helvetica".into(), font.into());
-    let resources = Resources {
-        fonts,
-        .. Default::default()
-    };
-    let resources = file.create(resources)?;
 
     let page0 = file.get_page(0).unwrap();
     let annots = page0.annotations.load(&file.resolver()).expect("can't load annotations");
     for annot in &*annots {
         if let Some(ref a) = annot.appearance_streams {
-            match **a.normal {
-                AppearanceStreamEntry::Single(ref s) => {
-                    //dbg!(&s.stream.resources);
-                    
-                    let form_dict = FormDict {
-                        resources: Some(resources.clone().into()),
-                        .. (**s.stream).clone()
-                    };
-
-                    let ops = vec![
-                        Op::Save,
-                        Op::TextFont { name: font_name.clone(), size: 14.0 },
-                        Op::TextDraw { text: PdfString::from("Hello World!") },
-                        Op::EndText,
-                        Op::Restore
-                    ];
-                    let stream = Stream::new(form_dict, serialize_ops(&ops)?);
-
-                    let normal2 = AppearanceStreamEntry::Single(FormXObject { stream });
-
-                    if let Some(normal_ref) = a.normal.as_ref() {
-                        file.update(normal_ref.get_inner(), normal2)?;
-                    }
+            if let AppearanceStreamEntry::Single(ref s) = *a.normal {
+                let mut content = ContentBuilder::new();
+                content.save().text(|text| {
+                    text.set_font(font_name.clone(), font.clone(), 14.0).show("Hello World!");
+                }).restore();
+
+                // keep the /Matrix and other entries of the old appearance
+                let (ops, resources) = content.finish();
+                let form_dict = FormDict {
+                    resources: Some(resources.into()),
+                    .. (**s.stream).clone()
+                };
+                let stream = Stream::new(form_dict, serialize_ops(&ops)?);
+                let normal2 = AppearanceStreamEntry::Single(FormXObject { stream });
+
+                if let Some(normal_ref) = a.normal.as_ref() {
+                    file.update(normal_ref.get_inner(), normal2)?;
                 }
-                _ => {}
             }
         }
     }
@@ -103,7 +89,7 @@ fn run() -> Result<(), PdfError> {
 
         //dbg!(&updated_field);
         
-        let reference = file.update(
+        file.update(
             to_update_field.get_ref().get_inner(),
             updated_field,
         )?;
@@ -111,7 +97,6 @@ fn run() -> Result<(), PdfError> {
         file.save_to("output/out.pdf")?;
 
         println!("\nUpdated field:");
-        //println!("{:?}\n", reference);
     }
 
     Ok(())
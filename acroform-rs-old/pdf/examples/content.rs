@@ -22,14 +22,16 @@ fn main() -> Result<(), PdfError> {
 
     let mut pages = Vec::new();
 
-    let content = Content::from_ops(vec![
-        Op::MoveTo { p: Point { x: 100., y: 100. } },
-        Op::LineTo { p: Point { x: 100., y: 200. } },
-        Op::LineTo { p: Point { x: 200., y: 200. } },
-        Op::LineTo { p: Point { x: 200., y: 100. } },
-        Op::Close,
-        Op::Stroke,
-    ]);
+    let mut square = ContentBuilder::new();
+    square
+        .move_to((100., 100.))
+        .line_to((100., 200.))
+        .line_to((200., 200.))
+        .line_to((200., 100.))
+        .close()
+        .stroke();
+    let (ops, resources) = square.finish();
+    let content = Content::from_ops(ops);
     let mut new_page = PageBuilder::from_content(content, &NoResolve)?;
     new_page.media_box = Some(pdf::object::Rectangle {
         left: 0.0,
@@ -37,8 +39,6 @@ fn main() -> Result<(), PdfError> {
         bottom: 400.0,
         right: 400.0
     });
-    let resources = Resources::default();
-
     /*
     let font = Font {
         name: Some("Test".into()),
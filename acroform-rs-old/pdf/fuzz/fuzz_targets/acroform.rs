@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use acroform::{AcroFormDocument, FieldValue};
+use std::collections::HashMap;
+
+fn harness(data: &[u8]) {
+    let Ok(mut doc) = AcroFormDocument::from_bytes(data.to_vec()) else { return };
+    let Ok(fields) = doc.fields() else { return };
+    let values: HashMap<_, _> = fields.into_iter()
+        .map(|field| (field.name, FieldValue::Text("fuzz".into())))
+        .collect();
+    if let Ok(filled) = doc.fill(values) {
+        if let Ok(doc) = AcroFormDocument::from_bytes(filled) {
+            let _ = doc.fields();
+        }
+    }
+    let _ = doc.save_compacted();
+}
+
+fuzz_target!(|data: &[u8]| {
+    harness(data);
+});
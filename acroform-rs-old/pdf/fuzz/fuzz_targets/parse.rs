@@ -1,14 +1,38 @@
 #![no_main]
 use libfuzzer_sys::fuzz_target;
+use pdf::file::FileOptions;
+use pdf::object::*;
 
-fn harness(data: &[u8]) {
-    if let Ok(file) = pdf::file::FileOptions::cached().load(data) {
-        for idx in 0..file.num_pages() {
-            let _ = file.get_page(idx);
+fn harness(data: &[u8], options: ParseOptions) {
+    let Ok(file) = FileOptions::cached().parse_options(options).load(data) else { return };
+    let resolver = file.resolver();
+    for page in file.pages() {
+        let Ok(page) = page else { continue };
+        if let Some(ref contents) = page.contents {
+            let _ = contents.operations(&resolver);
+        }
+        let Ok(resources) = page.resources() else { continue };
+        for font in resources.fonts.values() {
+            if let Ok(font) = font.load(&resolver) {
+                let _ = font.widths(&resolver);
+                let _ = font.to_unicode(&resolver);
+            }
+        }
+        for &xobject in resources.xobjects.values() {
+            match resolver.get(xobject).as_deref() {
+                Ok(XObject::Image(image)) => {
+                    let _ = image.image_data(&resolver);
+                }
+                Ok(XObject::Form(form)) => {
+                    let _ = form.operations(&resolver);
+                }
+                _ => {}
+            }
         }
     }
 }
 
 fuzz_target!(|data: &[u8]| {
-    let _ = harness(data);
-});
\ No newline at end of file
+    harness(data, ParseOptions::strict());
+    harness(data, ParseOptions::tolerant());
+});
@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pdf::file::FileOptions;
+use pdf::object::*;
+
+fn harness(data: &[u8]) {
+    let Ok(mut file) = FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(data.to_vec()) else { return };
+    let _ = file.revisions();
+    let pages: Vec<_> = file.pages().filter_map(|page| page.ok()).collect();
+    for page in pages {
+        let _ = file.update(page.get_ref().get_inner(), PagesNode::Leaf((*page).clone()));
+    }
+    if let Ok(saved) = file.save() {
+        let _ = FileOptions::uncached().load(saved);
+    }
+    let mut out = Vec::new();
+    let _ = file.save_to_writer(&mut out);
+}
+
+fuzz_target!(|data: &[u8]| {
+    harness(data);
+});
@@ -1,10 +1,14 @@
 use crate::error::*;
-use crate::parser::Lexer;
-use crate::parser::read_xref_and_trailer_at;
-use crate::xref::XRefTable;
+use crate::parser::{Lexer, is_whitespace};
+use crate::parser::{read_xref_and_trailer_at, parse_xref_stream, merge_hybrid_sections};
+use crate::xref::{XRef, XRefSection, XRefTable};
 use crate::primitive::Dictionary;
 use crate::object::*;
 use std::ops::Deref;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
 
 use std::ops::{
     RangeFull,
@@ -16,13 +20,30 @@ use std::ops::{
 pub const MAX_ID: u32 = 1_000_000;
 
 pub trait Backend: Sized {
-    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]>;
+    /// Read the given range. In-memory backends borrow the data, others copy it.
+    fn read<T: IndexRange>(&self, range: T) -> Result<Cow<'_, [u8]>>;
     //fn write<T: IndexRange>(&mut self, range: T) -> Result<&mut [u8]>;
     fn len(&self) -> usize;
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Call `f` with the data from `pos` to the end of the file.
+    ///
+    /// Backends that do not hold the whole file in memory pass a shorter window first
+    /// and retry with larger ones as long as `f` fails with an error that
+    /// [needs more data](PdfError::needs_more_data).
+    fn read_from<R>(&self, pos: usize, mut f: impl FnMut(&[u8]) -> Result<R>) -> Result<R> {
+        f(&t!(self.read(pos ..)))
+    }
+
+    /// Call `f` with the data from some position to the end of the file and that position.
+    ///
+    /// Like [`Backend::read_from`], the window may grow towards the start of the file.
+    fn read_tail<R>(&self, mut f: impl FnMut(&[u8], usize) -> Result<R>) -> Result<R> {
+        f(&t!(self.read(..)), 0)
+    }
+
     /// Returns the offset of the beginning of the file, i.e., where the `%PDF-1.5` header is.
     /// (currently only used internally!)
     fn locate_start_offset(&self) -> Result<usize> {
@@ -37,83 +58,254 @@ pub trait Backend: Sized {
             .ok_or_else(|| PdfError::Other{ msg: "file header is missing".to_string() })
     }
 
+    /// Like [`Backend::locate_start_offset`], but searches the whole file,
+    /// for files with junk (like mail headers) in front of the header.
+    fn search_start_offset(&self) -> Result<usize> {
+        const HEADER: &[u8] = b"%PDF-";
+        self.read_from(0, |data| {
+            data
+                .windows(HEADER.len())
+                .position(|window| window == HEADER)
+                .ok_or_else(|| PdfError::NotFound { word: "%PDF-".into() })
+        })
+    }
+
     /// Returns the value of startxref (currently only used internally!)
     fn locate_xref_offset(&self) -> Result<usize> {
         // locate the xref offset at the end of the file
         // `\nPOS\n%%EOF` where POS is the position encoded as base 10 integer.
         // u64::MAX has 20 digits + \n\n(2) + %%EOF(5) = 27 bytes max.
 
-        let mut lexer = Lexer::new(t!(self.read(..)));
-        lexer.set_pos_from_end(0);
-        t!(lexer.seek_substr_back(b"startxref"));
-        t!(lexer.next()).to::<usize>()
+        self.read_tail(|data, _| {
+            let mut lexer = Lexer::new(data);
+            lexer.set_pos_from_end(0);
+            t!(lexer.seek_substr_back(b"startxref"));
+            t!(lexer.next()).to::<usize>()
+        })
+    }
+
+    /// Returns the value of the last `startxref` that points at an xref section.
+    ///
+    /// Unlike [`Backend::locate_xref_offset`], this skips garbage appended after `%%EOF`,
+    /// even if it contains the word `startxref`.
+    /// A broken `startxref` after objects is damage rather than garbage, and is not skipped.
+    fn search_xref_offset(&self, start_offset: usize) -> Result<usize> {
+        self.read_tail(|data, _| {
+            let mut lexer = Lexer::new(data);
+            lexer.set_pos(data.len());
+            loop {
+                t!(lexer.seek_substr_back(b"startxref"));
+                let keyword_pos = lexer.get_pos() - b"startxref".len();
+                if let Ok(offset) = lexer.next().and_then(|word| word.to::<usize>()) {
+                    if self.points_at_xref(start_offset, offset) {
+                        return Ok(offset);
+                    }
+                }
+                lexer.set_pos(keyword_pos);
+                let mut previous = lexer;
+                let eof = previous.seek_substr_back(b"%%EOF").map_err(|_| PdfError::NotFound { word: "startxref".into() })?;
+                let garbage = eof.as_slice();
+                if [&b"endobj"[..], b"trailer"].iter().any(|word| garbage.windows(word.len()).any(|w| w == *word)) {
+                    bail!("startxref does not point at an xref section");
+                }
+            }
+        })
+    }
+
+    /// Whether an xref table (`xref`) or an xref stream (`N G obj`) starts at `offset`.
+    fn points_at_xref(&self, start_offset: usize, offset: usize) -> bool {
+        let pos = match start_offset.checked_add(offset) {
+            Some(pos) if pos < self.len() => pos,
+            _ => return false
+        };
+        let data = match self.read(pos .. self.len().min(pos + 64)) {
+            Ok(data) => data,
+            Err(_) => return false
+        };
+        let mut lexer = Lexer::new(&data);
+        match lexer.next() {
+            Ok(word) if word.equals(b"xref") => true,
+            Ok(word) if word.is_integer() => {
+                lexer.next().is_ok_and(|gen| gen.is_integer())
+                    && lexer.next().is_ok_and(|word| word.equals(b"obj"))
+            }
+            _ => false
+        }
+    }
+
+    /// The xref offset, using [`Backend::search_xref_offset`] if `options` allow garbage.
+    fn xref_offset(&self, start_offset: usize, options: &ParseOptions) -> Result<usize> {
+        if options.allow_garbage {
+            self.search_xref_offset(start_offset)
+        } else {
+            self.locate_xref_offset()
+        }
     }
 
     /// Used internally by File, but could also be useful for applications that want to look at the raw PDF objects.
     fn read_xref_table_and_trailer(&self, start_offset: usize, resolve: &impl Resolve) -> Result<(XRefTable, Dictionary)> {
-        let xref_offset = t!(self.locate_xref_offset());
-        let pos = t!(start_offset.checked_add(xref_offset).ok_or(PdfError::Invalid));
-        if pos >= self.len() {
-            bail!("XRef offset outside file bounds");
-        }
+        let xref_offset = t!(self.xref_offset(start_offset, resolve.options()));
+        let mut chain = t!(self.read_xref_chain(start_offset, xref_offset, resolve));
+        let (_, sections, trailer) = chain.remove(0);
 
-        let mut lexer = Lexer::with_offset(t!(self.read(pos ..)), pos);
-        
-        let (xref_sections, trailer) = t!(read_xref_and_trailer_at(&mut lexer, resolve));
-        
         let highest_id = t!(trailer.get("Size")
             .ok_or_else(|| PdfError::MissingEntry {field: "Size".into(), typ: "XRefTable"})?
             .as_u32());
 
-        if highest_id > MAX_ID {
-            bail!("too many objects");
+        let limit = resolve.options().max_objects;
+        if highest_id as usize > limit {
+            return Err(PdfError::TooManyObjects { count: highest_id as usize, limit });
         }
         let mut refs = XRefTable::new(highest_id as ObjNr);
-        for section in xref_sections {
+        // newest first, see `add_entries_from`
+        for section in sections.into_iter().chain(chain.into_iter().flat_map(|(_, sections, _)| sections)) {
             refs.add_entries_from(section)?;
         }
-        
-        let mut prev_trailer = {
-            match trailer.get("Prev") {
-                Some(p) => Some(t!(p.as_usize())),
-                None => None
-            }
-        };
         trace!("READ XREF AND TABLE");
-        let mut seen = vec![];
-        while let Some(prev_xref_offset) = prev_trailer {
-            if seen.contains(&prev_xref_offset) {
+        Ok((refs, trailer))
+    }
+
+    /// Read the xref section at `xref_offset` and all earlier ones linked by `/Prev`, newest first.
+    /// Returns the offset, the sections and the trailer of each.
+    fn read_xref_chain(&self, start_offset: usize, xref_offset: usize, resolve: &impl Resolve) -> Result<Vec<(usize, Vec<XRefSection>, Dictionary)>> {
+        let mut chain: Vec<(usize, Vec<XRefSection>, Dictionary)> = vec![];
+        let mut next = Some(xref_offset);
+        while let Some(offset) = next {
+            if chain.iter().any(|&(o, _, _)| o == offset) {
                 bail!("xref offsets loop");
             }
-            seen.push(prev_xref_offset);
-
-            let pos = t!(start_offset.checked_add(prev_xref_offset).ok_or(PdfError::Invalid));
-            let mut lexer = Lexer::with_offset(t!(self.read(pos..)), pos);
-            let (xref_sections, trailer) = t!(read_xref_and_trailer_at(&mut lexer, resolve));
-            
-            for section in xref_sections {
-                refs.add_entries_from(section)?;
+            let pos = t!(start_offset.checked_add(offset).ok_or(PdfError::Invalid));
+            if pos >= self.len() {
+                bail!("XRef offset outside file bounds");
+            }
+            let (mut sections, trailer) = t!(self.read_from(pos, |data| {
+                read_xref_and_trailer_at(&mut Lexer::with_offset(data, pos), resolve)
+            }));
+
+            // hybrid-reference file: the objects in object streams are listed in a separate xref stream
+            if let Some(stream_offset) = trailer.get("XRefStm") {
+                let pos = t!(start_offset.checked_add(t!(stream_offset.as_usize())).ok_or(PdfError::Invalid));
+                let stream_sections = t!(self.read_from(pos, |data| {
+                    parse_xref_stream(&mut Lexer::with_offset(data, pos), resolve)
+                }));
+                sections = merge_hybrid_sections(sections, stream_sections);
+            }
+
+            next = match trailer.get("Prev") {
+                Some(p) => Some(t!(p.as_usize())),
+                None => None
+            };
+            chain.push((offset, sections, trailer));
+        }
+        Ok(chain)
+    }
+
+    /// List the revisions of the file, oldest first.
+    ///
+    /// Each incremental update appends a new revision with its own xref section and trailer.
+    /// The xref sections of a linearized file's first page belong to the revision that contains them.
+    fn read_revisions(&self, start_offset: usize, resolve: &impl Resolve) -> Result<Vec<Revision>> {
+        let xref_offset = t!(self.xref_offset(start_offset, resolve.options()));
+        let chain = t!(self.read_xref_chain(start_offset, xref_offset, resolve));
+
+        let mut revisions: Vec<Revision> = vec![];
+        let mut entries: Vec<Vec<XRefSection>> = vec![];
+        for (offset, sections, trailer) in chain.into_iter().rev() {
+            let pos = start_offset + offset;
+            match revisions.last_mut() {
+                Some(last) if pos < last.range.end => {
+                    last.trailer = trailer;
+                    entries.last_mut().unwrap().extend(sections);
+                }
+                _ => {
+                    let start = revisions.last().map_or(0, |r| r.range.end);
+                    let end = t!(self.revision_end(pos));
+                    revisions.push(Revision {
+                        range: start .. end,
+                        xref_offset: offset,
+                        trailer,
+                        added: vec![],
+                        changed: vec![],
+                        freed: vec![],
+                    });
+                    entries.push(sections);
+                }
+            }
+        }
+
+        // generation numbers of the objects in use so far
+        let mut in_use: HashMap<ObjNr, GenNr> = HashMap::new();
+        for (revision, sections) in revisions.iter_mut().zip(entries) {
+            // sections are in order oldest first
+            let mut defined = BTreeMap::new();
+            for section in &sections {
+                for (id, &entry) in section.entries() {
+                    defined.insert(id as ObjNr, entry);
+                }
             }
-            
-            prev_trailer = {
-                match trailer.get("Prev") {
-                    Some(p) => {
-                        let prev = t!(p.as_usize());
-                        Some(prev)
+            for (id, entry) in defined {
+                match entry {
+                    XRef::Raw { .. } | XRef::Stream { .. } => {
+                        let gen = entry.get_gen_nr();
+                        let r = PlainRef { id, gen };
+                        match in_use.insert(id, gen) {
+                            Some(_) => revision.changed.push(r),
+                            None => revision.added.push(r),
+                        }
                     }
-                    None => None
+                    XRef::Free { .. } if in_use.remove(&id).is_some() => revision.freed.push(id),
+                    _ => {}
                 }
-            };
+            }
         }
-        Ok((refs, trailer))
+        Ok(revisions)
+    }
+
+    /// Position after the `%%EOF` marker following `pos` and its end of line
+    fn revision_end(&self, pos: usize) -> Result<usize> {
+        const EOF: &[u8] = b"%%EOF";
+        let end = self.read_from(pos, |data| {
+            let i = data.windows(EOF.len()).position(|w| w == EOF).ok_or(PdfError::EOF)?;
+            let mut end = i + EOF.len();
+            // the end of line, unless the window ends right after the marker
+            if end + 2 > data.len() && pos + data.len() < self.len() {
+                return Err(PdfError::EOF);
+            }
+            if data.get(end) == Some(&b'\r') {
+                end += 1;
+            }
+            if data.get(end) == Some(&b'\n') {
+                end += 1;
+            }
+            Ok(pos + end)
+        });
+        Ok(end.unwrap_or(self.len()))
     }
 }
 
+/// One revision of a file, i.e. the original document or one incremental update of it.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    /// The bytes of this revision in the backend, ending after its `%%EOF` marker.
+    /// The file up to `range.end` is the document as of this revision.
+    pub range: Range<usize>,
+    /// Position of the xref section, relative to the `%PDF` header like `startxref`
+    pub xref_offset: usize,
+    /// The trailer of this revision
+    pub trailer: Dictionary,
+    /// Objects defined for the first time
+    pub added: Vec<PlainRef>,
+    /// Objects that replace an earlier definition
+    pub changed: Vec<PlainRef>,
+    /// Objects that were deleted
+    pub freed: Vec<ObjNr>,
+}
+
 
 impl<T> Backend for T where T: Deref<Target=[u8]> { //+ DerefMut<Target=[u8]> {
-    fn read<R: IndexRange>(&self, range: R) -> Result<&[u8]> {
+    fn read<R: IndexRange>(&self, range: R) -> Result<Cow<'_, [u8]>> {
         let r = t!(range.to_range(self.len()));
-        Ok(&self[r])
+        Ok(Cow::Borrowed(&self[r]))
     }
     /*
     fn write<R: IndexRange>(&mut self, range: R) -> Result<&mut [u8]> {
@@ -126,6 +318,154 @@ impl<T> Backend for T where T: Deref<Target=[u8]> { //+ DerefMut<Target=[u8]> {
     }
 }
 
+/// A backend reading from any `Read + Seek` source, like a `std::fs::File`,
+/// without loading it into memory.
+///
+/// The file is read in pages of `PAGE_SIZE` bytes. The most recently used pages are
+/// kept in a cache of bounded size, so only the parts of the file that are actually
+/// used are read, which makes it suitable for large documents.
+pub struct SeekBackend<R> {
+    inner: Mutex<PageCache<R>>,
+    len: usize,
+    max_window: usize,
+}
+
+struct PageCache<R> {
+    reader: R,
+    // each page with the time it was last used
+    pages: HashMap<usize, (Arc<[u8]>, u64)>,
+    // page numbers by the time they were last used, least recently used first
+    lru: BTreeMap<u64, usize>,
+    time: u64,
+    max_pages: usize,
+}
+
+impl<R: Read + Seek> SeekBackend<R> {
+    pub const PAGE_SIZE: usize = 64 * 1024;
+
+    /// Largest window passed to [`Backend::read_from`] and [`Backend::read_tail`] by default.
+    pub const MAX_WINDOW: usize = 64 << 20;
+
+    /// Read from `reader`, caching up to 16 MiB.
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_cache_size(reader, 16 << 20)
+    }
+
+    /// Read from `reader`, caching up to `cache_size` bytes, but at least one page.
+    pub fn with_cache_size(mut reader: R, cache_size: usize) -> Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        let len = usize::try_from(len).map_err(|_| other!("file too large"))?;
+        Ok(SeekBackend {
+            inner: Mutex::new(PageCache {
+                reader,
+                pages: HashMap::new(),
+                lru: BTreeMap::new(),
+                time: 0,
+                max_pages: (cache_size / Self::PAGE_SIZE).max(1),
+            }),
+            len,
+            max_window: Self::MAX_WINDOW,
+        })
+    }
+
+    /// Read at most `max_window` bytes when looking for the end of an object or the
+    /// last xref section, but at least one page.
+    ///
+    /// Objects that don't fit, like streams longer than this, can't be read.
+    pub fn max_window(mut self, max_window: usize) -> Self {
+        self.max_window = max_window.max(Self::PAGE_SIZE);
+        self
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner().unwrap().reader
+    }
+
+    fn page(&self, cache: &mut PageCache<R>, n: usize) -> Result<Arc<[u8]>> {
+        cache.time += 1;
+        let time = cache.time;
+        if let Some((page, used)) = cache.pages.get_mut(&n) {
+            cache.lru.remove(used);
+            cache.lru.insert(time, n);
+            *used = time;
+            return Ok(page.clone());
+        }
+
+        let start = n * Self::PAGE_SIZE;
+        let mut page = vec![0; Self::PAGE_SIZE.min(self.len - start)];
+        cache.reader.seek(SeekFrom::Start(start as u64))?;
+        cache.reader.read_exact(&mut page)?;
+        let page: Arc<[u8]> = page.into();
+
+        if cache.lru.len() >= cache.max_pages {
+            if let Some((_, old)) = cache.lru.pop_first() {
+                cache.pages.remove(&old);
+            }
+        }
+        cache.pages.insert(n, (page.clone(), time));
+        cache.lru.insert(time, n);
+        Ok(page)
+    }
+}
+
+impl<R: Read + Seek> Backend for SeekBackend<R> {
+    fn read<T: IndexRange>(&self, range: T) -> Result<Cow<'_, [u8]>> {
+        let r = t!(range.to_range(self.len));
+        let mut data = Vec::with_capacity(r.len());
+        let mut cache = self.inner.lock().unwrap();
+        let mut pos = r.start;
+        while pos < r.end {
+            let n = pos / Self::PAGE_SIZE;
+            let page = t!(self.page(&mut cache, n));
+            let offset = pos - n * Self::PAGE_SIZE;
+            let end = page.len().min(offset + r.end - pos);
+            data.extend_from_slice(&page[offset .. end]);
+            pos += end - offset;
+        }
+        Ok(Cow::Owned(data))
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn read_from<T>(&self, pos: usize, mut f: impl FnMut(&[u8]) -> Result<T>) -> Result<T> {
+        let mut window = Self::PAGE_SIZE.min(self.max_window);
+        loop {
+            let end = pos.saturating_add(window).min(self.len);
+            let data = t!(self.read(pos .. end));
+            // end a partial window at whitespace, so its last token is not cut short
+            let data = match end < self.len {
+                true => data.iter().rposition(|&b| is_whitespace(b)).map_or(&data[..], |i| &data[.. i + 1]),
+                false => &data[..],
+            };
+            match f(data) {
+                Err(e) if e.needs_more_data() && end < self.len && window < self.max_window => {
+                    window = window.saturating_mul(4).min(self.max_window);
+                }
+                result => return result,
+            }
+        }
+    }
+    fn read_tail<T>(&self, mut f: impl FnMut(&[u8], usize) -> Result<T>) -> Result<T> {
+        let mut window = Self::PAGE_SIZE.min(self.max_window);
+        loop {
+            let start = self.len.saturating_sub(window);
+            let data = t!(self.read(start ..));
+            // and start it after whitespace
+            let skip = match start > 0 {
+                true => data.iter().position(|&b| is_whitespace(b)).unwrap_or(0),
+                false => 0,
+            };
+            match f(&data[skip ..], start + skip) {
+                Err(e) if e.needs_more_data() && start > 0 && window < self.max_window => {
+                    window = window.saturating_mul(4).min(self.max_window);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
 /// `IndexRange` is implemented by Rust's built-in range types, produced
 /// by range syntax like `..`, `a..`, `..b` or `c..d`.
 pub trait IndexRange
@@ -114,7 +114,8 @@ impl CatalogBuilder {
             kids,
             resources: None,
             media_box: None,
-            crop_box: None
+            crop_box: None,
+            other: Dictionary::new(),
         }, update)?;
 
         for (page, promise) in self.pages.into_iter().zip(kids_promise) {
@@ -147,6 +148,7 @@ impl CatalogBuilder {
             struct_tree_root: None,
             forms: None,
             page_labels: None,
+            other: Dictionary::new(),
         })
     }
 }
@@ -189,6 +191,7 @@ where
             id: vec!["foo".into(), "bar".into()],
             info_dict: self.info,
             prev_trailer_pos: None,
+            other: Dictionary::new(),
         };
         self.storage.save(&mut trailer)?;
         Ok(self.storage.into_inner())
@@ -249,7 +252,6 @@ impl<R: Resolve> ImporterMap<R> {
         match (a, b) {
             (Primitive::Array(a_parts), Primitive::Array(b_parts)) => {
                 if a_parts.len() != b_parts.len() {
-                    dbg!(a_parts, b_parts);
                     println!("different length {} vs. {}", a_parts.len(), b_parts.len());
                     println!("a = {a_parts:?}");
                     println!("b = {b_parts:?}");
@@ -13,6 +13,9 @@ use crate::primitive::*;
 use crate::enc::StreamFilter;
 use crate as pdf;
 
+pub mod builder;
+pub use builder::{ContentBuilder, TextBuilder};
+
 /// Represents a PDF content stream - a `Vec` of `Operator`s
 #[derive(Debug, Clone, DataSize)]
 pub struct Content {
@@ -730,7 +733,7 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
                 }
                 writeln!(f, "] TJ")?;
             },
-            Op::InlineImage { image: _ } => unimplemented!(),
+            Op::InlineImage { .. } => bail!("serializing inline images is not supported"),
             Op::XObject { ref name } => {
                 serialize_name(name, f)?;
                 writeln!(f, " Do")?;
@@ -795,6 +798,11 @@ impl Display for Point {
         write!(f, "{} {}", self.x, self.y)
     }
 }
+impl From<(f32, f32)> for Point {
+    fn from((x, y): (f32, f32)) -> Self {
+        Point { x, y }
+    }
+}
 #[cfg(feature = "euclid")]
 impl Into<euclid::Point2D<f32, PdfSpace>> for Point {
     fn into(self) -> euclid::Point2D<f32, PdfSpace> {
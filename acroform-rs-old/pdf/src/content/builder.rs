@@ -0,0 +1,439 @@
+//! Building content streams
+//!
+//! [`ContentBuilder`] appends [`Op`]s through methods named after what they draw, and adds
+//! the fonts and XObjects they use to its [`Resources`], so the result can be written as a
+//! page or a form XObject without keeping names and resources in step by hand.
+//!
+//! ```ignore
+//! let mut content = ContentBuilder::new();
+//! content
+//!     .fill_color(Color::Gray(0.9))
+//!     .rounded_rect(0.0, 0.0, 200.0, 20.0, 4.0)
+//!     .fill(Winding::NonZero)
+//!     .text(|text| {
+//!         text.set_font("Helv", font, 12.0).move_by(4.0, 6.0).show("Hello World!");
+//!     });
+//! let form = content.form_xobject(Rectangle { left: 0.0, bottom: 0.0, right: 200.0, top: 20.0 })?;
+//! ```
+
+use crate::content::{serialize_ops, Color, FormXObject, LineCap, LineJoin, Matrix, Op, Point, TextDrawAdjusted, TextMode, ViewRect, Winding};
+use crate::error::Result;
+use crate::font::Font;
+use crate::object::{FormDict, Lazy, Ref, Rectangle, Resources, Stream, XObject};
+use crate::primitive::{Name, PdfString};
+
+/// How far the control points of a quarter circle are from its ends, relative to the radius
+const KAPPA: f32 = 0.552_284_8;
+
+/// Builds a content stream and the resources it needs
+#[derive(Debug, Default)]
+pub struct ContentBuilder {
+    ops: Vec<Op>,
+    resources: Resources,
+    /// The current point, where the next path segment starts
+    current: Option<Point>,
+    /// Start of the current subpath, the current point after closing it
+    start: Option<Point>,
+}
+
+impl ContentBuilder {
+    pub fn new() -> ContentBuilder {
+        ContentBuilder::default()
+    }
+
+    /// Continue content that uses `resources`, like a page that is drawn on
+    pub fn with_resources(resources: Resources) -> ContentBuilder {
+        ContentBuilder { resources, ..ContentBuilder::default() }
+    }
+
+    /// Append any operator
+    pub fn op(&mut self, op: Op) -> &mut Self {
+        self.ops.push(op);
+        self
+    }
+
+    /// Add `font` to the resources as `name`, replacing a font of the same name
+    pub fn font(&mut self, name: impl Into<Name>, font: impl Into<Lazy<Font>>) -> &mut Self {
+        self.resources.fonts.insert(name.into(), font.into());
+        self
+    }
+
+    /// Add `xobject` to the resources as `name`, replacing an XObject of the same name
+    pub fn xobject(&mut self, name: impl Into<Name>, xobject: Ref<XObject>) -> &mut Self {
+        self.resources.xobjects.insert(name.into(), xobject);
+        self
+    }
+
+    /// Paint `xobject` with `matrix` mapping its unit square, or the bounding box of a form,
+    /// onto the page. The XObject is added to the resources as `name`.
+    pub fn place_xobject(&mut self, name: impl Into<Name>, xobject: Ref<XObject>, matrix: Matrix) -> &mut Self {
+        let name = name.into();
+        self.xobject(name.clone(), xobject);
+        self.ops.extend([Op::Save, Op::Transform { matrix }, Op::XObject { name }, Op::Restore]);
+        self
+    }
+
+    /// Begin a new subpath at `p`
+    pub fn move_to(&mut self, p: impl Into<Point>) -> &mut Self {
+        let p = p.into();
+        self.ops.push(Op::MoveTo { p });
+        self.current = Some(p);
+        self.start = Some(p);
+        self
+    }
+
+    /// A straight line from the current point to `p`
+    pub fn line_to(&mut self, p: impl Into<Point>) -> &mut Self {
+        let p = p.into();
+        self.ops.push(Op::LineTo { p });
+        self.current = Some(p);
+        self
+    }
+
+    /// A cubic Bézier curve from the current point to `p`, with the control points `c1` and `c2`
+    pub fn curve_to(&mut self, c1: impl Into<Point>, c2: impl Into<Point>, p: impl Into<Point>) -> &mut Self {
+        let p = p.into();
+        self.ops.push(Op::CurveTo { c1: c1.into(), c2: c2.into(), p });
+        self.current = Some(p);
+        self
+    }
+
+    /// A quadratic Bézier curve from the current point to `p`, with the control point `c`
+    ///
+    /// PDF only has cubic curves, so it is written as the cubic curve of the same shape.
+    /// Without a current point, a new subpath is started at `c`.
+    pub fn quad_to(&mut self, c: impl Into<Point>, p: impl Into<Point>) -> &mut Self {
+        let (c, p) = (c.into(), p.into());
+        let start = match self.current {
+            Some(start) => start,
+            None => {
+                self.move_to(c);
+                c
+            }
+        };
+        let c1 = Point { x: start.x + 2.0 / 3.0 * (c.x - start.x), y: start.y + 2.0 / 3.0 * (c.y - start.y) };
+        let c2 = Point { x: p.x + 2.0 / 3.0 * (c.x - p.x), y: p.y + 2.0 / 3.0 * (c.y - p.y) };
+        self.curve_to(c1, c2, p)
+    }
+
+    /// Close the current subpath with a line back to its start
+    pub fn close(&mut self) -> &mut Self {
+        self.ops.push(Op::Close);
+        self.current = self.start;
+        self
+    }
+
+    /// A rectangle with the lower left corner at `x`, `y` as a closed subpath
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) -> &mut Self {
+        self.ops.push(Op::Rect { rect: ViewRect { x, y, width, height } });
+        self.current = Some(Point { x, y });
+        self.start = self.current;
+        self
+    }
+
+    /// A rectangle with corners rounded to quarter circles of `radius`
+    ///
+    /// The radius is limited to half the width and height, without it this is [`rect`](Self::rect).
+    pub fn rounded_rect(&mut self, x: f32, y: f32, width: f32, height: f32, radius: f32) -> &mut Self {
+        let r = radius.min(width.abs() / 2.0).min(height.abs() / 2.0);
+        if r <= 0.0 {
+            return self.rect(x, y, width, height);
+        }
+        let (left, right) = (x.min(x + width), x.max(x + width));
+        let (bottom, top) = (y.min(y + height), y.max(y + height));
+        let k = r * KAPPA;
+        self.move_to((left + r, bottom))
+            .line_to((right - r, bottom))
+            .curve_to((right - r + k, bottom), (right, bottom + r - k), (right, bottom + r))
+            .line_to((right, top - r))
+            .curve_to((right, top - r + k), (right - r + k, top), (right - r, top))
+            .line_to((left + r, top))
+            .curve_to((left + r - k, top), (left, top - r + k), (left, top - r))
+            .line_to((left, bottom + r))
+            .curve_to((left, bottom + r - k), (left + r - k, bottom), (left + r, bottom))
+            .close()
+    }
+
+    /// Stroke the path
+    pub fn stroke(&mut self) -> &mut Self {
+        self.paint(Op::Stroke)
+    }
+
+    /// Fill the path
+    pub fn fill(&mut self, winding: Winding) -> &mut Self {
+        self.paint(Op::Fill { winding })
+    }
+
+    /// Fill, then stroke the path
+    pub fn fill_and_stroke(&mut self, winding: Winding) -> &mut Self {
+        self.paint(Op::FillAndStroke { winding })
+    }
+
+    /// Intersect the clipping path with the path, which is not painted
+    ///
+    /// The clipping path is part of the graphics state, so it is undone by [`restore`](Self::restore).
+    pub fn clip(&mut self, winding: Winding) -> &mut Self {
+        self.ops.push(Op::Clip { winding });
+        self.paint(Op::EndPath)
+    }
+
+    /// End the path without painting it
+    pub fn end_path(&mut self) -> &mut Self {
+        self.paint(Op::EndPath)
+    }
+
+    fn paint(&mut self, op: Op) -> &mut Self {
+        self.ops.push(op);
+        self.current = None;
+        self.start = None;
+        self
+    }
+
+    /// Push the graphics state
+    pub fn save(&mut self) -> &mut Self {
+        self.op(Op::Save)
+    }
+
+    /// Pop the graphics state pushed by the last [`save`](Self::save)
+    pub fn restore(&mut self) -> &mut Self {
+        self.op(Op::Restore)
+    }
+
+    /// Concatenate `matrix` to the current transformation matrix
+    pub fn transform(&mut self, matrix: Matrix) -> &mut Self {
+        self.op(Op::Transform { matrix })
+    }
+
+    pub fn fill_color(&mut self, color: Color) -> &mut Self {
+        self.op(Op::FillColor { color })
+    }
+
+    pub fn stroke_color(&mut self, color: Color) -> &mut Self {
+        self.op(Op::StrokeColor { color })
+    }
+
+    pub fn line_width(&mut self, width: f32) -> &mut Self {
+        self.op(Op::LineWidth { width })
+    }
+
+    /// Dashes of the lengths in `pattern`, alternating on and off, starting `phase` into it
+    pub fn dash(&mut self, pattern: Vec<f32>, phase: f32) -> &mut Self {
+        self.op(Op::Dash { pattern, phase })
+    }
+
+    pub fn line_cap(&mut self, cap: LineCap) -> &mut Self {
+        self.op(Op::LineCap { cap })
+    }
+
+    pub fn line_join(&mut self, join: LineJoin) -> &mut Self {
+        self.op(Op::LineJoin { join })
+    }
+
+    /// A marked content sequence tagged `tag` around what `content` adds, like `/Tx BMC` in field appearances
+    pub fn marked_content(&mut self, tag: impl Into<Name>, content: impl FnOnce(&mut ContentBuilder)) -> &mut Self {
+        self.ops.push(Op::BeginMarkedContent { tag: tag.into(), properties: None });
+        content(self);
+        self.op(Op::EndMarkedContent)
+    }
+
+    /// A text object with what `text` adds
+    pub fn text(&mut self, text: impl FnOnce(&mut TextBuilder)) -> &mut Self {
+        self.ops.push(Op::BeginText);
+        text(&mut TextBuilder { content: self });
+        self.op(Op::EndText)
+    }
+
+    /// The operators so far
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// The resources used so far
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    /// The content stream data
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serialize_ops(&self.ops)
+    }
+
+    /// The operators and resources, e.g. for a [`PageBuilder`](crate::build::PageBuilder)
+    pub fn finish(self) -> (Vec<Op>, Resources) {
+        (self.ops, self.resources)
+    }
+
+    /// A form XObject showing the content within `bbox`, with its resources
+    pub fn form_xobject(self, bbox: Rectangle) -> Result<FormXObject> {
+        let data = self.to_bytes()?;
+        let dict = FormDict {
+            form_type: 1,
+            bbox,
+            resources: Some(self.resources.into()),
+            ..FormDict::default()
+        };
+        Ok(FormXObject { stream: Stream::new(dict, data) })
+    }
+}
+
+/// Adds to a text object of a [`ContentBuilder`], see [`ContentBuilder::text`]
+pub struct TextBuilder<'a> {
+    content: &'a mut ContentBuilder,
+}
+
+impl TextBuilder<'_> {
+    fn op(&mut self, op: Op) -> &mut Self {
+        self.content.ops.push(op);
+        self
+    }
+
+    /// Set the font to the one called `name` in the resources, at `size`
+    pub fn font(&mut self, name: impl Into<Name>, size: f32) -> &mut Self {
+        self.op(Op::TextFont { name: name.into(), size })
+    }
+
+    /// Add `font` to the resources as `name` and set it at `size`
+    pub fn set_font(&mut self, name: impl Into<Name>, font: impl Into<Lazy<Font>>, size: f32) -> &mut Self {
+        let name = name.into();
+        self.content.font(name.clone(), font);
+        self.font(name, size)
+    }
+
+    /// Move to the start of the next line, offset by `x`, `y` from the start of the current one
+    pub fn move_by(&mut self, x: f32, y: f32) -> &mut Self {
+        self.op(Op::MoveTextPosition { translation: Point { x, y } })
+    }
+
+    /// Set the text matrix and the start of the line
+    pub fn matrix(&mut self, matrix: Matrix) -> &mut Self {
+        self.op(Op::SetTextMatrix { matrix })
+    }
+
+    /// Move down to the next line by the leading
+    pub fn next_line(&mut self) -> &mut Self {
+        self.op(Op::TextNewline)
+    }
+
+    pub fn leading(&mut self, leading: f32) -> &mut Self {
+        self.op(Op::Leading { leading })
+    }
+
+    pub fn char_spacing(&mut self, char_space: f32) -> &mut Self {
+        self.op(Op::CharSpacing { char_space })
+    }
+
+    pub fn word_spacing(&mut self, word_space: f32) -> &mut Self {
+        self.op(Op::WordSpacing { word_space })
+    }
+
+    /// Horizontal scaling in percent
+    pub fn scaling(&mut self, horiz_scale: f32) -> &mut Self {
+        self.op(Op::TextScaling { horiz_scale })
+    }
+
+    pub fn rise(&mut self, rise: f32) -> &mut Self {
+        self.op(Op::TextRise { rise })
+    }
+
+    pub fn render_mode(&mut self, mode: TextMode) -> &mut Self {
+        self.op(Op::TextRenderMode { mode })
+    }
+
+    /// Show `text`, which has to be encoded for the font already
+    pub fn show(&mut self, text: impl Into<PdfString>) -> &mut Self {
+        self.op(Op::TextDraw { text: text.into() })
+    }
+
+    /// Show strings with adjustments between them, in thousandths of the font size
+    pub fn show_adjusted(&mut self, array: Vec<TextDrawAdjusted>) -> &mut Self {
+        self.op(Op::TextDrawAdjusted { array })
+    }
+
+    /// Set the fill color, which paints the glyphs in the default render mode
+    pub fn fill_color(&mut self, color: Color) -> &mut Self {
+        self.op(Op::FillColor { color })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::parse_ops;
+    use crate::object::{NoResolve, PlainRef};
+
+    #[test]
+    fn paths_round_trip() {
+        let mut content = ContentBuilder::new();
+        content
+            .save()
+            .stroke_color(Color::Gray(0.5))
+            .line_width(2.0)
+            .move_to((10.0, 10.0))
+            .line_to((20.0, 10.0))
+            .quad_to((30.0, 10.0), (30.0, 40.0))
+            .close()
+            .stroke()
+            .rect(0.0, 0.0, 50.0, 50.0)
+            .clip(Winding::EvenOdd)
+            .restore();
+        let data = content.to_bytes().unwrap();
+        let ops = parse_ops(&data, &NoResolve).unwrap();
+        assert_eq!(ops.len(), content.ops().len());
+        let curve = content.ops().iter().find_map(|op| match *op {
+            Op::CurveTo { c1, c2, p } => Some((c1, c2, p)),
+            _ => None,
+        });
+        let (c1, c2, p) = curve.unwrap();
+        assert!((c1.x - 80.0 / 3.0).abs() < 1e-4 && c1.y == 10.0);
+        assert!(c2.x == 30.0 && (c2.y - 20.0).abs() < 1e-4);
+        assert_eq!(p, Point { x: 30.0, y: 40.0 });
+        assert!(matches!(ops[ops.len() - 3], Op::Clip { winding: Winding::EvenOdd }));
+        assert!(matches!(ops[ops.len() - 2], Op::EndPath));
+    }
+
+    #[test]
+    fn quad_to_without_current_point() {
+        let mut content = ContentBuilder::new();
+        content.quad_to((10.0, 0.0), (10.0, 10.0)).stroke();
+        let ops = parse_ops(&content.to_bytes().unwrap(), &NoResolve).unwrap();
+        assert!(matches!(ops[..], [Op::MoveTo { p: Point { x, y } }, Op::CurveTo { .. }, Op::Stroke] if x == 10.0 && y == 0.0));
+    }
+
+    #[test]
+    fn rounded_rect() {
+        let mut content = ContentBuilder::new();
+        content.rounded_rect(0.0, 0.0, 100.0, 20.0, 50.0);
+        let ops = content.ops();
+        // four sides and four corners, the radius limited to half the height
+        assert_eq!(ops.len(), 10);
+        assert!(matches!(ops[0], Op::MoveTo { p: Point { x, y } } if x == 10.0 && y == 0.0));
+        assert!(matches!(ops[2], Op::CurveTo { p: Point { x, y }, .. } if x == 100.0 && y == 10.0));
+        assert!(matches!(ops[9], Op::Close));
+
+        let mut square = ContentBuilder::new();
+        square.rounded_rect(0.0, 0.0, 10.0, 10.0, 0.0);
+        assert!(matches!(square.ops(), [Op::Rect { .. }]));
+    }
+
+    #[test]
+    fn resources_are_added() {
+        let font = Lazy::<Font>::default();
+        let image: Ref<XObject> = Ref::new(PlainRef { id: 7, gen: 0 });
+        let mut content = ContentBuilder::new();
+        content
+            .text(|text| {
+                text.set_font("F1", font, 12.0).move_by(10.0, 20.0).show("Hi");
+            })
+            .place_xobject("Im1", image, Matrix { a: 20.0, d: 10.0, ..Matrix::default() });
+        let ops = parse_ops(&content.to_bytes().unwrap(), &NoResolve).unwrap();
+        assert!(matches!(ops[..], [
+            Op::BeginText, Op::TextFont { .. }, Op::MoveTextPosition { .. }, Op::TextDraw { .. }, Op::EndText,
+            Op::Save, Op::Transform { .. }, Op::XObject { .. }, Op::Restore,
+        ]));
+
+        let form = content.form_xobject(Rectangle { left: 0.0, bottom: 0.0, right: 100.0, top: 50.0 }).unwrap();
+        let resources = form.dict().resources.as_ref().unwrap();
+        assert!(resources.fonts.contains_key("F1"));
+        assert_eq!(resources.xobjects["Im1"].get_inner().id, 7);
+    }
+}
@@ -11,6 +11,8 @@ use datasize::DataSize;
 use crate::object::PlainRef;
 use crate::primitive::{Dictionary, PdfString, Name};
 use crate::error::{PdfError, Result};
+use crate::pubsec::Recipient;
+use bitflags::bitflags;
 
 type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
@@ -60,19 +62,26 @@ impl Rc4 {
     }
 }
 
-/// 7.6.1 Table 20 + 7.6.3.2 Table 21
+/// 7.6.1 Table 20 + 7.6.3.2 Table 21 + 7.6.5.2 Table 23
 #[derive(Object, Debug, Clone, DataSize)]
 pub struct CryptDict {
-    #[pdf(key="O")]
+    #[pdf(key="Filter", default="Name::from(\"Standard\")")]
+    filter: Name,
+
+    #[pdf(key="SubFilter")]
+    sub_filter: Option<Name>,
+
+    // O, U, R and P are required by the standard security handler only
+    #[pdf(key="O", default="PdfString::from(\"\")")]
     o: PdfString,
 
-    #[pdf(key="U")]
+    #[pdf(key="U", default="PdfString::from(\"\")")]
     u: PdfString,
 
-    #[pdf(key="R")]
+    #[pdf(key="R", default="0")]
     r: u32,
 
-    #[pdf(key="P")]
+    #[pdf(key="P", default="-1")]
     p: i32,
 
     #[pdf(key="V")]
@@ -96,10 +105,75 @@ pub struct CryptDict {
     #[pdf(key = "UE")]
     ue: Option<PdfString>,
 
+    /// public-key security handler, `adbe.pkcs7.s3` and `adbe.pkcs7.s4`
+    #[pdf(key = "Recipients", default="vec![]")]
+    recipients: Vec<PdfString>,
+
     #[pdf(other)]
     _other: Dictionary
 }
 
+impl CryptDict {
+    /// The name of the security handler (`/Filter`), `Standard` or `Adobe.PubSec`.
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+    /// The user access permissions granted by `/P`.
+    pub fn permissions(&self) -> Permissions {
+        Permissions::from_p(self.p, self.r)
+    }
+    /// The revision of the standard security handler (`/R`).
+    pub fn revision(&self) -> u32 {
+        self.r
+    }
+}
+
+bitflags! {
+    /// 7.6.3.2 Table 22 – User access permissions.
+    ///
+    /// The flag values match the bit positions of the `/P` entry.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Permissions: u32 {
+        /// bit 3: print the document
+        const PRINT = 1 << 2;
+        /// bit 4: modify the contents of the document
+        const MODIFY = 1 << 3;
+        /// bit 5: copy or extract text and graphics
+        const EXTRACT = 1 << 4;
+        /// bit 6: add or modify annotations, fill in form fields
+        const MODIFY_ANNOTATIONS = 1 << 5;
+        /// bit 9: fill in existing form fields, even if bit 6 is clear
+        const FILL_FORMS = 1 << 8;
+        /// bit 10: extract text and graphics for accessibility
+        const EXTRACT_FOR_ACCESSIBILITY = 1 << 9;
+        /// bit 11: assemble the document (insert, rotate or delete pages)
+        const ASSEMBLE = 1 << 10;
+        /// bit 12: print at full resolution
+        const PRINT_HIGH_QUALITY = 1 << 11;
+    }
+}
+impl Permissions {
+    /// Decode the `/P` value of an encryption dictionary with revision `r`.
+    ///
+    /// Revision 2 handlers have no bits 9–12; the corresponding
+    /// permissions follow their coarser revision 2 counterparts.
+    pub fn from_p(p: i32, r: u32) -> Permissions {
+        let mut perms = Permissions::from_bits_truncate(p as u32);
+        if r < 3 {
+            perms.set(Permissions::FILL_FORMS, perms.contains(Permissions::MODIFY_ANNOTATIONS));
+            perms.set(Permissions::EXTRACT_FOR_ACCESSIBILITY, perms.contains(Permissions::EXTRACT));
+            perms.set(Permissions::ASSEMBLE, perms.contains(Permissions::MODIFY));
+            perms.set(Permissions::PRINT_HIGH_QUALITY, perms.contains(Permissions::PRINT));
+        }
+        perms
+    }
+    /// Whether existing form fields may be filled in (bit 6 or bit 9).
+    pub fn can_fill_forms(&self) -> bool {
+        self.intersects(Permissions::FILL_FORMS | Permissions::MODIFY_ANNOTATIONS)
+    }
+}
+
 #[derive(Object, Debug, Clone, Copy, DataSize)]
 pub enum CryptMethod {
     None,
@@ -126,10 +200,18 @@ pub struct CryptFilter {
     #[pdf(key="Length")]
     pub length: Option<u32>,
 
+    /// public-key security handler, `adbe.pkcs7.s5`
+    #[pdf(key="Recipients", default="vec![]")]
+    pub recipients: Vec<PdfString>,
+
+    #[pdf(key="EncryptMetadata", default="true")]
+    pub encrypt_metadata: bool,
+
     #[pdf(other)]
     _other: Dictionary
 }
 
+#[derive(Clone)]
 pub struct Decoder {
     key_size: usize,
     key: Vec<u8>, // maximum length
@@ -146,6 +228,10 @@ pub struct Decoder {
     /// Whether the metadata is encrypted, as indicated by /EncryptMetadata
     /// in the /Encrypt dictionary.
     encrypt_metadata: bool,
+    /// Whether the owner password was supplied. The owner has all permissions,
+    /// regardless of `/P`.
+    owner: bool,
+    permissions: Permissions,
 }
 impl Decoder {
     pub fn default(dict: &CryptDict, id: &[u8]) -> Result<Decoder> {
@@ -164,9 +250,21 @@ impl Decoder {
             encrypt_indirect_object: None,
             metadata_indirect_object: None,
             encrypt_metadata,
+            owner: false,
+            permissions: Permissions::all(),
         }
     }
 
+    /// true if the document was opened with the owner password
+    pub fn is_owner(&self) -> bool {
+        self.owner
+    }
+
+    /// The permissions granted to the user that opened the document.
+    pub fn permissions(&self) -> Permissions {
+        self.permissions
+    }
+
     pub fn from_password(dict: &CryptDict, id: &[u8], pass: &[u8]) -> Result<Decoder> {
         fn compute_u_rev_2(key: &[u8]) -> Vec<u8> {
             // algorithm 4
@@ -329,11 +427,15 @@ impl Decoder {
             err!(other!("unsupported standard security handler revision {}", level))
         };
         if level <= 4 {
+            if key_bits % 8 != 0 || !(40..=128).contains(&key_bits) {
+                bail!("invalid key length {}", key_bits);
+            }
             let key_size = key_bits as usize / 8;
             let key = key_derivation_user_password_rc4(level, key_size, dict, id, pass);
 
             if check_password_rc4(level, dict.u.as_bytes(), id, &key[..std::cmp::min(key_size, 16)]) {
-                let decoder = Decoder::new(key, key_size, method, dict.encrypt_metadata);
+                let mut decoder = Decoder::new(key, key_size, method, dict.encrypt_metadata);
+                decoder.permissions = dict.permissions();
                 Ok(decoder)
             } else {
                 let password_wrap_key = key_derivation_owner_password_rc4(level, key_size, pass)?;
@@ -357,7 +459,8 @@ impl Decoder {
                 );
 
                 if check_password_rc4(level, dict.u.as_bytes(), id, &key[..key_size]) {
-                    let decoder = Decoder::new(key, key_size, method, dict.encrypt_metadata);
+                    let mut decoder = Decoder::new(key, key_size, method, dict.encrypt_metadata);
+                    decoder.owner = true;
                     Ok(decoder)
                 } else {
                     Err(PdfError::InvalidPassword)
@@ -411,13 +514,14 @@ impl Decoder {
             .as_bytes()
             .to_vec();
 
-            let (intermediate_key, mut wrapped_key) = if level == 6 {
+            let (intermediate_key, mut wrapped_key, owner) = if level == 6 {
                 let user_hash_computed =
                     Self::revision_6_kdf(password_encoded, user_validation_salt, b"");
                 if user_hash_computed == user_hash {
                     (
                         Self::revision_6_kdf(password_encoded, user_key_salt, b"").into(),
                         ue,
+                        false,
                     )
                 } else {
                     let owner_hash_computed =
@@ -426,6 +530,7 @@ impl Decoder {
                         (
                             Self::revision_6_kdf(password_encoded, owner_key_salt, u).into(),
                             oe,
+                            true,
                         )
                     } else {
                         err!(PdfError::InvalidPassword);
@@ -443,7 +548,7 @@ impl Decoder {
                     let mut intermediate_kdf_hash = Sha256::new();
                     intermediate_kdf_hash.update(password_encoded);
                     intermediate_kdf_hash.update(user_key_salt);
-                    (intermediate_kdf_hash.finalize(), ue)
+                    (intermediate_kdf_hash.finalize(), ue, false)
                 } else {
                     let mut owner_check_hash = Sha256::new();
                     owner_check_hash.update(password_encoded);
@@ -455,25 +560,103 @@ impl Decoder {
                         intermediate_kdf_hash.update(password_encoded);
                         intermediate_kdf_hash.update(owner_key_salt);
                         intermediate_kdf_hash.update(u);
-                        (intermediate_kdf_hash.finalize(), oe)
+                        (intermediate_kdf_hash.finalize(), oe, true)
                     } else {
                         err!(PdfError::InvalidPassword);
                     }
                 }
             };
 
+            if wrapped_key.len() != 32 {
+                bail!("UE and OE in Encrypt dictionary should have a length of 32 bytes, not {}", wrapped_key.len());
+            }
             let zero_iv = GenericArray::from_slice(&[0u8; 16]);
             let key_slice = t!(Aes256CbcDec::new(&intermediate_key, zero_iv)
                 .decrypt_padded_mut::<NoPadding>(&mut wrapped_key)
                 .map_err(|_| PdfError::InvalidPassword));
 
-            let decoder = Decoder::new(key_slice.into(),  32, method, dict.encrypt_metadata);
+            let mut decoder = Decoder::new(key_slice.into(),  32, method, dict.encrypt_metadata);
+            decoder.owner = owner;
+            if !owner {
+                decoder.permissions = dict.permissions();
+            }
             Ok(decoder)
         } else {
             err!(format!("unsupported V value {}", level).into())
         }
     }
 
+    /// 7.6.5.3 Public-key encryption algorithms
+    pub fn from_recipient(dict: &CryptDict, recipient: &Recipient) -> Result<Decoder> {
+        let (recipients, key_bits, method, encrypt_metadata) = match dict.sub_filter.as_deref() {
+            Some("adbe.pkcs7.s4") => (&dict.recipients, dict.bits, CryptMethod::V2, true),
+            Some("adbe.pkcs7.s5") => {
+                let filter = dict
+                    .crypt_filters
+                    .get(try_opt!(dict.default_crypt_filter.as_ref()).as_str())
+                    .ok_or_else(|| other!("missing crypt filter entry {:?}", dict.default_crypt_filter.as_ref()))?;
+                // some writers give the length in bytes
+                let bits = match filter.length {
+                    Some(n) if n <= 32 => 8 * n,
+                    Some(n) => n,
+                    None => dict.bits,
+                };
+                (&filter.recipients, bits, filter.method, dict.encrypt_metadata && filter.encrypt_metadata)
+            }
+            s => err!(other!("unsupported public-key SubFilter {:?}", s)),
+        };
+
+        // envelopes for other recipients may use algorithms we don't know, so they are skipped
+        let mut seed = None;
+        for envelope in recipients {
+            match recipient.open_envelope(envelope.as_bytes()) {
+                Ok(Some(data)) => {
+                    seed = Some(data);
+                    break;
+                }
+                Ok(None) => {}
+                Err(e) => warn!("can't read recipient envelope: {:?}", e),
+            }
+        }
+        let seed = seed.ok_or(PdfError::NotRecipient)?;
+        if seed.len() < 20 {
+            bail!("recipient seed too short ({} bytes)", seed.len());
+        }
+        // bytes 20..24 hold the permissions, in big endian
+        let permissions = match seed.get(20..24) {
+            Some(p) => Permissions::from_p(i32::from_be_bytes(p.try_into().unwrap()), 4),
+            None => Permissions::all(),
+        };
+
+        fn derive<D: Digest>(seed: &[u8], recipients: &[PdfString], encrypt_metadata: bool) -> Vec<u8> {
+            let mut hash = D::new();
+            hash.update(&seed[..20]);
+            for envelope in recipients {
+                hash.update(envelope.as_bytes());
+            }
+            if !encrypt_metadata {
+                hash.update([0xff; 4]);
+            }
+            hash.finalize().to_vec()
+        }
+        let (key, key_size) = match method {
+            CryptMethod::AESV3 => (derive::<Sha256>(&seed, recipients, encrypt_metadata), 32),
+            CryptMethod::V2 | CryptMethod::AESV2 => {
+                if key_bits % 8 != 0 || !(40..=128).contains(&key_bits) {
+                    bail!("invalid key length {}", key_bits);
+                }
+                let mut key = derive::<sha1::Sha1>(&seed, recipients, encrypt_metadata);
+                key.truncate(key_bits as usize / 8);
+                (key, key_bits as usize / 8)
+            }
+            m => err!(other!("unimplemented crypt method {:?}", m)),
+        };
+
+        let mut decoder = Decoder::new(key, key_size, method, encrypt_metadata);
+        decoder.permissions = permissions;
+        Ok(decoder)
+    }
+
     fn revision_6_kdf(password: &[u8], salt: &[u8], u: &[u8]) -> [u8; 32] {
         let mut data = [0u8; (128 + 64 + 48) * 64];
         let mut data_total_len = 0;
@@ -604,7 +787,7 @@ impl Decoder {
                 }
                 let (iv, ciphertext) = data.split_at_mut(16);
                 let cipher =
-                    t!(Aes256CbcDec::new_from_slices(self.key(), iv).map_err(|_| PdfError::DecryptionFailure));
+                    t!(Aes256CbcDec::new_from_slices(&self.key[..self.key_size], iv).map_err(|_| PdfError::DecryptionFailure));
                 Ok(t!(cipher
                     .decrypt_padded_mut::<Pkcs7>(ciphertext)
                     .map_err(|_| PdfError::DecryptionFailure)))
@@ -623,6 +806,26 @@ impl fmt::Debug for Decoder {
 
 #[cfg(test)]
 mod tests {
+    use super::Permissions;
+
+    #[test]
+    fn permissions_from_p() {
+        // all bits set except 6 (annotations) and 9 (fill forms)
+        let p = Permissions::from_p(-292, 4);
+        assert!(!p.can_fill_forms());
+        assert!(p.contains(Permissions::PRINT | Permissions::MODIFY | Permissions::PRINT_HIGH_QUALITY));
+
+        // bit 9 alone allows filling in forms
+        let p = Permissions::from_p(-4 & !(1 << 5), 3);
+        assert!(p.can_fill_forms());
+        assert!(!p.contains(Permissions::MODIFY_ANNOTATIONS));
+
+        // revision 2 has no bit 9, form filling follows bit 6
+        let p = Permissions::from_p(-4 & !(1 << 5), 2);
+        assert!(!p.can_fill_forms());
+        assert_eq!(Permissions::from_p(-1, 2), Permissions::all());
+    }
+
     #[test]
     fn unencrypted_strings() {
         let data_prefix = b"%PDF-1.5\n\
@@ -9,6 +9,7 @@ use crate::object::{Object, Resolve, Stream};
 use crate::primitive::{Primitive, Dictionary};
 use std::convert::{TryFrom, TryInto};
 use std::io::{Read, Write};
+use std::borrow::Cow;
 use once_cell::sync::OnceCell;
 use datasize::DataSize;
 
@@ -25,6 +26,9 @@ pub struct LZWFlateParams {
     pub columns: i32,
     #[pdf(key="EarlyChange", default="1")]
     pub early_change: i32,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 impl Default for LZWFlateParams {
     fn default() -> LZWFlateParams {
@@ -33,7 +37,8 @@ impl Default for LZWFlateParams {
             n_components: 1,
             bits_per_component: 8,
             columns: 1,
-            early_change: 1
+            early_change: 1,
+            other: Dictionary::new(),
         }
     }
 }
@@ -47,6 +52,9 @@ pub struct DCTDecodeParams {
     //      This option is ignored if the image has one or two color components.
     #[pdf(key="ColorTransform")]
     pub color_transform: Option<i32>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
@@ -74,14 +82,21 @@ pub struct CCITTFaxDecodeParams {
 
     #[pdf(key="DamagedRowsBeforeError", default="0")]
     pub damaged_rows_before_error: u32,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
 pub struct JBIG2DecodeParams {
     #[pdf(key="JBIG2Globals")]
-    pub globals: Option<Stream<()>>
+    pub globals: Option<Stream<()>>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 #[derive(Debug, Clone, DataSize, DeepClone)]
+#[allow(clippy::large_enum_variant)]  // boxing the parameters would break matching on them
 pub enum StreamFilter {
     ASCIIHexDecode,
     ASCII85Decode,
@@ -257,121 +272,221 @@ fn encode_85(data: &[u8]) -> Vec<u8> {
     buf
 }
 
-fn inflate_bytes_zlib(data: &[u8]) -> Result<Vec<u8>> {
-    use libflate::zlib::Decoder;
-    let mut decoder = Decoder::new(data)?;
+/// Read `reader` to the end, failing once more than `limit` bytes come out.
+fn read_limited(mut reader: impl Read, limit: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::new();
-    decoder.read_to_end(&mut decoded)?;
+    (&mut reader).take((limit as u64).saturating_add(1)).read_to_end(&mut decoded)?;
+    if decoded.len() > limit {
+        return Err(PdfError::StreamTooLarge { limit });
+    }
     Ok(decoded)
 }
 
-fn inflate_bytes(data: &[u8]) -> Result<Vec<u8>> {
+fn inflate_bytes_zlib(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    use libflate::zlib::Decoder;
+    read_limited(Decoder::new(data)?, limit)
+}
+
+fn inflate_bytes(data: &[u8], limit: usize) -> Result<Vec<u8>> {
     use libflate::deflate::Decoder;
-    let mut decoder = Decoder::new(data);
-    let mut decoded = Vec::new();
-    decoder.read_to_end(&mut decoded)?;
-    Ok(decoded)
+    read_limited(Decoder::new(data), limit)
 }
 
 pub fn flate_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    flate_decode_limited(data, params, usize::MAX)
+}
 
-    let predictor = params.predictor as usize;
-    let n_components = params.n_components as usize;
-    let columns = params.columns as usize;
-    let stride = columns * n_components;
-
-
+fn flate_decode_limited(data: &[u8], params: &LZWFlateParams, limit: usize) -> Result<Vec<u8>> {
     // First flate decode
-    let decoded = {
-        if let Ok(data) = inflate_bytes_zlib(data) {
-            data
-        } else if let Ok(data) = inflate_bytes(data) {
-            data
-        } else {
-            dump_data(data);
-            bail!("can't inflate");
+    let decoded = match inflate_bytes_zlib(data, limit) {
+        Ok(data) => data,
+        Err(e @ PdfError::StreamTooLarge { .. }) => return Err(e),
+        Err(_) => match inflate_bytes(data, limit) {
+            Ok(data) => data,
+            Err(e @ PdfError::StreamTooLarge { .. }) => return Err(e),
+            Err(_) => {
+                dump_data(data);
+                bail!("can't inflate");
+            }
         }
     };
     // Then unfilter (PNG)
+    unpredict(decoded, params)
+}
+
+/// Bytes per row and bytes per pixel (at least 1) of data using a PNG predictor.
+fn predictor_layout(params: &LZWFlateParams) -> (usize, usize) {
+    let bits = params.n_components.max(1) as usize * params.bits_per_component.max(1) as usize;
+    ((params.columns.max(1) as usize * bits).div_ceil(8), bits.div_ceil(8))
+}
+
+/// Reverse the PNG predictor of each row.
+fn unpredict(decoded: Vec<u8>, params: &LZWFlateParams) -> Result<Vec<u8>> {
+    if params.predictor < 10 {
+        return Ok(decoded);
+    }
+    let (stride, bpp) = predictor_layout(params);
+
     // For this, take the old out as input, and write output to out
+    let inp = decoded; // input buffer
+    let rows = inp.len() / (stride+1);
+    if rows == 0 {
+        return Ok(Vec::new());
+    }
 
-    if predictor > 10 {
-        let inp = decoded; // input buffer
-        let rows = inp.len() / (stride+1);
-        
-        // output buffer
-        let mut out = vec![0; rows * stride];
-    
-        // Apply inverse predictor
-        let null_vec = vec![0; stride];
-        
-        let mut in_off = 0; // offset into input buffer
-        
-        let mut out_off = 0; // offset into output buffer
-        let mut last_out_off = 0; // last offset to output buffer
-        
-        while in_off + stride < inp.len() {
-            let predictor = PredictorType::from_u8(inp[in_off])?;
-            in_off += 1; // +1 because the first byte on each row is predictor
-            
-            let row_in = &inp[in_off .. in_off + stride];
-            let (prev_row, row_out) = if out_off == 0 {
-                (&null_vec[..], &mut out[out_off .. out_off+stride])
-            } else {
-                let (prev, curr) = out.split_at_mut(out_off);
-                (&prev[last_out_off ..], &mut curr[.. stride])
-            };
-            unfilter(predictor, n_components, prev_row, row_in, row_out);
-            
-            last_out_off = out_off;
-            
-            in_off += stride;
-            out_off += stride;
-        }
-        Ok(out)
-    } else {
-        Ok(decoded)
+    // output buffer
+    let mut out = vec![0; rows * stride];
+
+    // Apply inverse predictor
+    let null_vec = vec![0; stride];
+
+    let mut in_off = 0; // offset into input buffer
+
+    let mut out_off = 0; // offset into output buffer
+    let mut last_out_off = 0; // last offset to output buffer
+
+    while in_off + stride < inp.len() {
+        let predictor = PredictorType::from_u8(inp[in_off])?;
+        in_off += 1; // +1 because the first byte on each row is predictor
+
+        let row_in = &inp[in_off .. in_off + stride];
+        let (prev_row, row_out) = if out_off == 0 {
+            (&null_vec[..], &mut out[out_off .. out_off+stride])
+        } else {
+            let (prev, curr) = out.split_at_mut(out_off);
+            (&prev[last_out_off ..], &mut curr[.. stride])
+        };
+        unfilter(predictor, bpp, prev_row, row_in, row_out);
+
+        last_out_off = out_off;
+
+        in_off += stride;
+        out_off += stride;
+    }
+    Ok(out)
+}
+
+/// Apply the PNG predictor to each row and prefix it with the predictor type.
+/// Predictor 15 picks the type for each row that is likely to compress best.
+fn predict<'a>(data: &'a [u8], params: &LZWFlateParams) -> Result<Cow<'a, [u8]>> {
+    match params.predictor {
+        1 => return Ok(data.into()),
+        10 ..= 15 => {}
+        n => bail!("encoding with predictor {} is not supported", n),
+    }
+    let (stride, bpp) = predictor_layout(params);
+
+    let mut out = Vec::with_capacity(data.len() + data.len() / stride + 1);
+    let mut prev = vec![0; stride];
+    let mut current = vec![0; stride];
+    let mut row = vec![0; stride];
+    for chunk in data.chunks(stride) {
+        // a short last row is padded with zeros
+        current[.. chunk.len()].copy_from_slice(chunk);
+        current[chunk.len() ..].fill(0);
+
+        let method = if params.predictor == 15 {
+            let mut best = (PredictorType::NoFilter, usize::MAX);
+            for n in 0 ..= 4 {
+                let method = PredictorType::from_u8(n)?;
+                row.copy_from_slice(&current);
+                filter(method, bpp, &prev, &mut row);
+                let cost = row.iter().map(|&b| (b as i8).unsigned_abs() as usize).sum();
+                if cost < best.1 {
+                    best = (method, cost);
+                }
+            }
+            best.0
+        } else {
+            PredictorType::from_u8(params.predictor as u8 - 10)?
+        };
+        row.copy_from_slice(&current);
+        filter(method, bpp, &prev, &mut row);
+        out.push(method as u8);
+        out.extend_from_slice(&row);
+        std::mem::swap(&mut prev, &mut current);
     }
+    Ok(out.into())
 }
-fn flate_encode(data: &[u8]) -> Vec<u8> {
-    use libflate::deflate::Encoder;
-    let mut encoded = Vec::new();
-    let mut encoder = Encoder::new(&mut encoded);
-    encoder.write_all(data).unwrap();
-    encoded
+fn flate_encode(data: &[u8]) -> Result<Vec<u8>> {
+    use libflate::zlib::Encoder;
+    let mut encoder = Encoder::new(Vec::new())?;
+    encoder.write_all(data)?;
+    Ok(encoder.finish().into_result()?)
 }
 
-pub fn dct_decode(data: &[u8], _params: &DCTDecodeParams) -> Result<Vec<u8>> {
+pub fn dct_decode(data: &[u8], params: &DCTDecodeParams) -> Result<Vec<u8>> {
+    dct_decode_limited(data, params, usize::MAX)
+}
+
+fn dct_decode_limited(data: &[u8], _params: &DCTDecodeParams, limit: usize) -> Result<Vec<u8>> {
     use jpeg_decoder::Decoder;
     let mut decoder = Decoder::new(data);
+    decoder.read_info()?;
+    if let Some(info) = decoder.info() {
+        let size = info.width as usize * info.height as usize * info.pixel_format.pixel_bytes();
+        if size > limit {
+            return Err(PdfError::StreamTooLarge { limit });
+        }
+    }
     let pixels = decoder.decode()?;
     Ok(pixels)
 }
 
+/// Collects written bytes, but refuses to grow beyond `limit`.
+struct LimitedWriter {
+    data: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+impl Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.len() > self.limit - self.data.len() {
+            self.exceeded = true;
+            return Err(std::io::Error::other("decoded stream too large"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 pub fn lzw_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    lzw_decode_limited(data, params, usize::MAX)
+}
+
+fn lzw_decode_limited(data: &[u8], params: &LZWFlateParams, limit: usize) -> Result<Vec<u8>> {
     use weezl::{BitOrder, decode::Decoder};
-    let mut out = vec![];
+    let mut out = LimitedWriter { data: vec![], limit, exceeded: false };
 
     let mut decoder = if params.early_change != 0 {
-        Decoder::with_tiff_size_switch(BitOrder::Msb, 9)
+        Decoder::with_tiff_size_switch(BitOrder::Msb, 8)
     } else {
-        Decoder::new(BitOrder::Msb, 9)
+        Decoder::new(BitOrder::Msb, 8)
     };
 
-    decoder
+    let status = decoder
         .into_stream(&mut out)
-        .decode_all(data).status?;
-    Ok(out)
+        .decode_all(data).status;
+    if out.exceeded {
+        return Err(PdfError::StreamTooLarge { limit });
+    }
+    status?;
+    unpredict(out.data, params)
 }
 fn lzw_encode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
     use weezl::{BitOrder, encode::Encoder};
-    if params.early_change != 0 {
-        bail!("encoding early_change != 0 is not supported");
-    }
+    let mut encoder = if params.early_change != 0 {
+        Encoder::with_tiff_size_switch(BitOrder::Msb, 8)
+    } else {
+        Encoder::new(BitOrder::Msb, 8)
+    };
     let mut compressed = vec![];
-    Encoder::new(BitOrder::Msb, 9)
+    encoder
         .into_stream(&mut compressed)
-        .encode_all(data).status?;
+        .encode_all(&*t!(predict(data, params))).status?;
     Ok(compressed)
 }
 
@@ -381,28 +496,36 @@ pub fn fax_decode(data: &[u8], params: &CCITTFaxDecodeParams) -> Result<Vec<u8>>
     if params.k < 0 {
         let columns = params.columns as usize;
         let rows = params.rows as usize;
+        if columns == 0 || columns > u16::MAX as usize || rows > u16::MAX as usize {
+            bail!("unsupported fax image size {columns}∙{rows}");
+        }
 
         let height = if params.rows == 0 { None } else { Some(params.rows as u16)};
-        let mut buf = Vec::with_capacity(columns * rows);
+        let mut buf = Vec::new();
         decode_g4(data.iter().cloned(), columns as u16, height, |line| {
             buf.extend(pels(line, columns as u16).map(|c| match c {
                 Color::Black => 0,
                 Color::White => 255
             }));
-            assert_eq!(buf.len() % columns, 0, "len={}, columns={}", buf.len(), columns);
         }).ok_or(PdfError::Other { msg: "faxdecode failed".into() })?;
-        assert_eq!(buf.len() % columns, 0, "len={}, columns={}", buf.len(), columns);
+        if buf.len() % columns != 0 {
+            bail!("decoded length {} is not a multiple of {columns} columns", buf.len());
+        }
 
         if rows != 0 && buf.len() != columns * rows {
             bail!("decoded length does not match (expected {rows}∙{columns}, got {})", buf.len());
         }
         Ok(buf)
     } else {
-        unimplemented!()
+        bail!("CCITTFaxDecode with K={} (group 3) is not supported", params.k)
     }
 }
 
 pub fn run_length_decode(data: &[u8]) -> Result<Vec<u8>> {
+    run_length_decode_limited(data, usize::MAX)
+}
+
+fn run_length_decode_limited(data: &[u8], limit: usize) -> Result<Vec<u8>> {
     // Used <http://benno.id.au/refs/PDFReference15_v5.pdf> as specification
     let mut buf = Vec::new();
     let d = data;
@@ -414,21 +537,49 @@ pub fn run_length_decode(data: &[u8]) -> Result<Vec<u8>> {
             let start = c + 1;
             let end = start + length as usize + 1;
             // copy _following_ length + 1 bytes literally
-            buf.extend_from_slice(&d[start..end]);
+            buf.extend_from_slice(d.get(start..end).ok_or(PdfError::EOF)?);
             c = end; // move cursor to next run
         } else if length >= 129 {
             let copy = 257 - length as usize; // copy 2 - 128 times
-            let b = d[c + 1]; // copied byte
+            let b = *d.get(c + 1).ok_or(PdfError::EOF)?; // copied byte
             buf.extend(std::iter::repeat(b).take(copy));
             c += 2; // move cursor to next run
         } else {
             break; // EOD
         }
+        if buf.len() > limit {
+            return Err(PdfError::StreamTooLarge { limit });
+        }
     }
 
     Ok(buf)
 }
 
+fn run_length_encode(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(data.len() + data.len() / 128 + 1);
+    let mut c = 0;
+
+    while c < data.len() {
+        let run = data[c..].iter().take(128).take_while(|&&b| b == data[c]).count();
+        if run > 1 {
+            // repeat the following byte 257 - length times
+            buf.push((257 - run) as u8);
+            buf.push(data[c]);
+            c += run;
+        } else {
+            // copy bytes literally until the next run starts
+            let start = c;
+            while c < data.len() && c - start < 128 && data.get(c + 1) != Some(&data[c]) {
+                c += 1;
+            }
+            buf.push((c - start - 1) as u8);
+            buf.extend_from_slice(&data[start..c]);
+        }
+    }
+    buf.push(128); // EOD
+    buf
+}
+
 pub type DecodeFn = dyn Fn(&[u8]) -> Result<Vec<u8>> + Sync + Send + 'static;
 static JPX_DECODER: OnceCell<Box<DecodeFn>> = OnceCell::new();
 static JBIG2_DECODER: OnceCell<Box<DecodeFn>> = OnceCell::new();
@@ -461,25 +612,40 @@ pub fn jbig2_decode(data: &[u8], globals: &[u8]) -> Result<Vec<u8>> {
 }
 
 pub fn decode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
-    match *filter {
-        StreamFilter::ASCIIHexDecode => decode_hex(data),
-        StreamFilter::ASCII85Decode => decode_85(data),
-        StreamFilter::LZWDecode(ref params) => lzw_decode(data, params),
-        StreamFilter::FlateDecode(ref params) => flate_decode(data, params),
-        StreamFilter::RunLengthDecode => run_length_decode(data),
-        StreamFilter::DCTDecode(ref params) => dct_decode(data, params),
+    decode_limited(data, filter, usize::MAX)
+}
+
+/// Like [`decode`], but fails with `PdfError::StreamTooLarge` as soon as
+/// the output grows beyond `limit` bytes.
+pub fn decode_limited(data: &[u8], filter: &StreamFilter, limit: usize) -> Result<Vec<u8>> {
+    let decoded = match *filter {
+        StreamFilter::ASCIIHexDecode => decode_hex(data)?,
+        StreamFilter::ASCII85Decode => decode_85(data)?,
+        StreamFilter::LZWDecode(ref params) => lzw_decode_limited(data, params, limit)?,
+        StreamFilter::FlateDecode(ref params) => flate_decode_limited(data, params, limit)?,
+        StreamFilter::RunLengthDecode => run_length_decode_limited(data, limit)?,
+        StreamFilter::DCTDecode(ref params) => dct_decode_limited(data, params, limit)?,
 
         _ => bail!("unimplemented {filter:?}"),
+    };
+    if decoded.len() > limit {
+        return Err(PdfError::StreamTooLarge { limit });
     }
+    Ok(decoded)
 }
 
 pub fn encode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
     match *filter {
-        StreamFilter::ASCIIHexDecode => Ok(encode_hex(data)),
+        StreamFilter::ASCIIHexDecode => {
+            let mut encoded = encode_hex(data);
+            encoded.push(b'>');
+            Ok(encoded)
+        }
         StreamFilter::ASCII85Decode => Ok(encode_85(data)),
         StreamFilter::LZWDecode(ref params) => lzw_encode(data, params),
-        StreamFilter::FlateDecode (ref _params) => Ok(flate_encode(data)),
-        _ => unimplemented!(),
+        StreamFilter::FlateDecode(ref params) => flate_encode(&t!(predict(data, params))),
+        StreamFilter::RunLengthDecode => Ok(run_length_encode(data)),
+        _ => bail!("encoding {filter:?} is not supported"),
     }
 }
 
@@ -584,7 +750,6 @@ pub fn unfilter(filter: PredictorType, bpp: usize, prev: &[u8], inp: &[u8], out:
     }
 }
 
-#[allow(unused)]
 pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut [u8]) {
     use self::PredictorType::*;
     let len  = current.len();
@@ -603,7 +768,7 @@ pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut
         }
         Avg => {
             for i in (bpp..len).rev() {
-                current[i] = current[i].wrapping_sub(current[i - bpp].wrapping_add(previous[i]) / 2);
+                current[i] = current[i].wrapping_sub(((current[i - bpp] as u16 + previous[i] as u16) / 2) as u8);
             }
 
             for i in 0..bpp {
@@ -648,6 +813,63 @@ mod tests {
         */
     }
 
+    #[test]
+    fn decode_limits() {
+        let data = vec![0u8; 10_000];
+        for filter in [
+            StreamFilter::FlateDecode(LZWFlateParams::default()),
+            StreamFilter::LZWDecode(LZWFlateParams::default()),
+            StreamFilter::RunLengthDecode,
+            StreamFilter::ASCIIHexDecode,
+        ] {
+            let encoded = encode(&data, &filter).unwrap();
+            assert_eq!(decode_limited(&encoded, &filter, data.len()).unwrap(), data);
+            assert!(matches!(
+                decode_limited(&encoded, &filter, data.len() - 1),
+                Err(PdfError::StreamTooLarge { limit: 9999 })
+            ), "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn lzw_decode_test() {
+        // example from the PDF reference, section 3.3.3
+        let x = lzw_decode(&[0x80, 0x0B, 0x60, 0x50, 0x22, 0x0C, 0x0C, 0x85, 0x01], &LZWFlateParams::default()).unwrap();
+        assert_eq!(b"-----A---B", x.as_slice());
+    }
+
+    #[test]
+    fn encode_roundtrip() {
+        let image: Vec<u8> = (0 .. 30u32 * 7 * 3).map(|i| (i * i / 7 + i % 3) as u8).collect();
+        let text = b"BT /F1 12 Tf 72 712 Td (aaaaaaaaaaaaaaaaaaaa) Tj ET\n".repeat(20);
+
+        let filters = [
+            StreamFilter::ASCIIHexDecode,
+            StreamFilter::ASCII85Decode,
+            StreamFilter::RunLengthDecode,
+            StreamFilter::LZWDecode(LZWFlateParams::default()),
+            StreamFilter::FlateDecode(LZWFlateParams::default()),
+        ];
+        for filter in &filters {
+            for data in [&image[..], &text[..], &[][..], &[7][..]] {
+                let encoded = encode(data, filter).unwrap();
+                assert!(decode(&encoded, filter).unwrap() == data, "{:?}", filter);
+            }
+        }
+
+        // predictors work on whole rows of 30 pixels with 3 components
+        for predictor in 10 ..= 15 {
+            let params = LZWFlateParams { predictor, n_components: 3, columns: 30, ..Default::default() };
+            for filter in [StreamFilter::FlateDecode(params.clone()), StreamFilter::LZWDecode(params)] {
+                let encoded = encode(&image, &filter).unwrap();
+                assert!(decode(&encoded, &filter).unwrap() == image, "{:?}", filter);
+            }
+        }
+
+        let flate = StreamFilter::FlateDecode(LZWFlateParams::default());
+        assert!(encode(&text, &flate).unwrap().len() < text.len() / 4);
+    }
+
     #[test]
     fn run_length_decode_test() {
         let x = run_length_decode(&[254, b'a', 255, b'b', 2, b'c', b'b', b'c', 254, b'a', 128]).unwrap();
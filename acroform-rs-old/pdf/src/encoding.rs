@@ -100,9 +100,293 @@ impl Encoding {
             differences: HashMap::new()
         }
     }
+    /// Glyph name of `code`, from `/Differences` or the base encoding.
+    pub fn glyph_name(&self, code: u8) -> Option<&str> {
+        match self.differences.get(&(code as u32)) {
+            Some(name) => Some(name.as_str()),
+            None => self.base.glyph_name(code)
+        }
+    }
+    /// The code of `c`, if the encoding has a glyph for it.
+    pub fn encode(&self, c: char) -> Option<u8> {
+        let name = unicode_to_glyph(c)?;
+        let differences = self.differences.iter()
+            .filter(|&(_, n)| n.as_str() == name)
+            .filter_map(|(&code, _)| u8::try_from(code).ok())
+            .min();
+        differences.or_else(|| self.base.code(name).filter(|&code| !self.differences.contains_key(&(code as u32))))
+    }
+}
+impl BaseEncoding {
+    /// Glyph name of `code`. Only `StandardEncoding` and `WinAnsiEncoding` are known.
+    pub fn glyph_name(&self, code: u8) -> Option<&'static str> {
+        let table = match *self {
+            BaseEncoding::StandardEncoding => &STANDARD_ENCODING,
+            BaseEncoding::WinAnsiEncoding => &WIN_ANSI_ENCODING,
+            _ => return None
+        };
+        Some(table[code as usize]).filter(|name| !name.is_empty())
+    }
+    /// The code of the glyph `name`
+    pub fn code(&self, name: &str) -> Option<u8> {
+        (0 ..= 255).find(|&code| self.glyph_name(code) == Some(name))
+    }
+}
+
+/// The character of the glyph `name`.
+///
+/// Knows the Latin glyphs of `StandardEncoding` and `WinAnsiEncoding`
+/// and names of the form `uniXXXX` and `uXXXX`.
+pub fn glyph_to_unicode(name: &str) -> Option<char> {
+    if let Ok(i) = GLYPH_UNICODE.binary_search_by_key(&name, |&(n, _)| n) {
+        return Some(GLYPH_UNICODE[i].1);
+    }
+    let hex = name.strip_prefix("uni").filter(|h| h.len() == 4)
+        .or_else(|| name.strip_prefix('u').filter(|h| (4 ..= 6).contains(&h.len())))?;
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+/// The name of the Latin glyph for `c`, see [`glyph_to_unicode`].
+pub fn unicode_to_glyph(c: char) -> Option<&'static str> {
+    UNICODE_GLYPH.binary_search_by_key(&c, |&(c, _)| c).ok().map(|i| UNICODE_GLYPH[i].1)
+}
+
+/// The character of `code` in PDFDocEncoding, `None` for undefined codes.
+pub fn pdf_doc_to_unicode(code: u8) -> Option<char> {
+    match code {
+        b'\t' | b'\n' | b'\r' | 0x20 ..= 0x7e | 0xa1 ..= 0xac | 0xae ..= 0xff => Some(code as char),
+        0x18 ..= 0x1f => Some(PDF_DOC_ACCENTS[code as usize - 0x18]),
+        0x80 ..= 0x9e => Some(PDF_DOC_PUNCTUATION[code as usize - 0x80]),
+        0xa0 => Some('\u{20ac}'),
+        _ => None
+    }
+}
+
+/// The PDFDocEncoding code of `c`, see [`pdf_doc_to_unicode`].
+pub fn unicode_to_pdf_doc(c: char) -> Option<u8> {
+    if let Ok(code) = u8::try_from(c) {
+        if pdf_doc_to_unicode(code) == Some(c) {
+            return Some(code);
+        }
+    }
+    match c {
+        '\u{20ac}' => Some(0xa0),
+        _ => PDF_DOC_ACCENTS.iter().position(|&a| a == c).map(|i| 0x18 + i as u8)
+            .or_else(|| PDF_DOC_PUNCTUATION.iter().position(|&p| p == c).map(|i| 0x80 + i as u8))
+    }
 }
 impl DeepClone for Encoding {
     fn deep_clone(&self, cloner: &mut impl pdf::object::Cloner) -> Result<Self> {
         Ok(self.clone())
     }
-}
\ No newline at end of file
+}
+
+/// Glyph names of `StandardEncoding` by code, empty for unused codes
+static STANDARD_ENCODING: [&str; 256] = [
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quoteright",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "at", "A", "B", "C", "D", "E", "F", "G",
+    "H", "I", "J", "K", "L", "M", "N", "O",
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    "quoteleft", "a", "b", "c", "d", "e", "f", "g",
+    "h", "i", "j", "k", "l", "m", "n", "o",
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "exclamdown", "cent", "sterling", "fraction", "yen", "florin", "section",
+    "currency", "quotesingle", "quotedblleft", "guillemotleft", "guilsinglleft", "guilsinglright", "fi", "fl",
+    "", "endash", "dagger", "daggerdbl", "periodcentered", "", "paragraph", "bullet",
+    "quotesinglbase", "quotedblbase", "quotedblright", "guillemotright", "ellipsis", "perthousand", "", "questiondown",
+    "", "grave", "acute", "circumflex", "tilde", "macron", "breve", "dotaccent",
+    "dieresis", "", "ring", "cedilla", "", "hungarumlaut", "ogonek", "caron",
+    "emdash", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "AE", "", "ordfeminine", "", "", "", "",
+    "Lslash", "Oslash", "OE", "ordmasculine", "", "", "", "",
+    "", "ae", "", "", "", "dotlessi", "", "",
+    "lslash", "oslash", "oe", "germandbls", "", "", "", "",
+];
+
+/// Glyph names of `WinAnsiEncoding` by code, empty for unused codes
+static WIN_ANSI_ENCODING: [&str; 256] = [
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quotesingle",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "at", "A", "B", "C", "D", "E", "F", "G",
+    "H", "I", "J", "K", "L", "M", "N", "O",
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    "grave", "a", "b", "c", "d", "e", "f", "g",
+    "h", "i", "j", "k", "l", "m", "n", "o",
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "",
+    "Euro", "", "quotesinglbase", "florin", "quotedblbase", "ellipsis", "dagger", "daggerdbl",
+    "circumflex", "perthousand", "Scaron", "guilsinglleft", "OE", "", "Zcaron", "",
+    "", "quoteleft", "quoteright", "quotedblleft", "quotedblright", "bullet", "endash", "emdash",
+    "tilde", "trademark", "scaron", "guilsinglright", "oe", "", "zcaron", "Ydieresis",
+    "space", "exclamdown", "cent", "sterling", "currency", "yen", "brokenbar", "section",
+    "dieresis", "copyright", "ordfeminine", "guillemotleft", "logicalnot", "hyphen", "registered", "macron",
+    "degree", "plusminus", "twosuperior", "threesuperior", "acute", "mu", "paragraph", "periodcentered",
+    "cedilla", "onesuperior", "ordmasculine", "guillemotright", "onequarter", "onehalf", "threequarters", "questiondown",
+    "Agrave", "Aacute", "Acircumflex", "Atilde", "Adieresis", "Aring", "AE", "Ccedilla",
+    "Egrave", "Eacute", "Ecircumflex", "Edieresis", "Igrave", "Iacute", "Icircumflex", "Idieresis",
+    "Eth", "Ntilde", "Ograve", "Oacute", "Ocircumflex", "Otilde", "Odieresis", "multiply",
+    "Oslash", "Ugrave", "Uacute", "Ucircumflex", "Udieresis", "Yacute", "Thorn", "germandbls",
+    "agrave", "aacute", "acircumflex", "atilde", "adieresis", "aring", "ae", "ccedilla",
+    "egrave", "eacute", "ecircumflex", "edieresis", "igrave", "iacute", "icircumflex", "idieresis",
+    "eth", "ntilde", "ograve", "oacute", "ocircumflex", "otilde", "odieresis", "divide",
+    "oslash", "ugrave", "uacute", "ucircumflex", "udieresis", "yacute", "thorn", "ydieresis",
+];
+
+/// Unicode values of the Latin glyph names, sorted by name
+static GLYPH_UNICODE: [(&str, char); 229] = [
+    ("A", 'A'), ("AE", '\u{c6}'), ("Aacute", '\u{c1}'), ("Acircumflex", '\u{c2}'),
+    ("Adieresis", '\u{c4}'), ("Agrave", '\u{c0}'), ("Aring", '\u{c5}'), ("Atilde", '\u{c3}'),
+    ("B", 'B'), ("C", 'C'), ("Ccedilla", '\u{c7}'), ("D", 'D'),
+    ("E", 'E'), ("Eacute", '\u{c9}'), ("Ecircumflex", '\u{ca}'), ("Edieresis", '\u{cb}'),
+    ("Egrave", '\u{c8}'), ("Eth", '\u{d0}'), ("Euro", '\u{20ac}'), ("F", 'F'),
+    ("G", 'G'), ("H", 'H'), ("I", 'I'), ("Iacute", '\u{cd}'),
+    ("Icircumflex", '\u{ce}'), ("Idieresis", '\u{cf}'), ("Igrave", '\u{cc}'), ("J", 'J'),
+    ("K", 'K'), ("L", 'L'), ("Lslash", '\u{141}'), ("M", 'M'),
+    ("N", 'N'), ("Ntilde", '\u{d1}'), ("O", 'O'), ("OE", '\u{152}'),
+    ("Oacute", '\u{d3}'), ("Ocircumflex", '\u{d4}'), ("Odieresis", '\u{d6}'), ("Ograve", '\u{d2}'),
+    ("Oslash", '\u{d8}'), ("Otilde", '\u{d5}'), ("P", 'P'), ("Q", 'Q'),
+    ("R", 'R'), ("S", 'S'), ("Scaron", '\u{160}'), ("T", 'T'),
+    ("Thorn", '\u{de}'), ("U", 'U'), ("Uacute", '\u{da}'), ("Ucircumflex", '\u{db}'),
+    ("Udieresis", '\u{dc}'), ("Ugrave", '\u{d9}'), ("V", 'V'), ("W", 'W'),
+    ("X", 'X'), ("Y", 'Y'), ("Yacute", '\u{dd}'), ("Ydieresis", '\u{178}'),
+    ("Z", 'Z'), ("Zcaron", '\u{17d}'), ("a", 'a'), ("aacute", '\u{e1}'),
+    ("acircumflex", '\u{e2}'), ("acute", '\u{b4}'), ("adieresis", '\u{e4}'), ("ae", '\u{e6}'),
+    ("agrave", '\u{e0}'), ("ampersand", '&'), ("aring", '\u{e5}'), ("asciicircum", '^'),
+    ("asciitilde", '~'), ("asterisk", '*'), ("at", '@'), ("atilde", '\u{e3}'),
+    ("b", 'b'), ("backslash", '\\'), ("bar", '|'), ("braceleft", '{'),
+    ("braceright", '}'), ("bracketleft", '['), ("bracketright", ']'), ("breve", '\u{2d8}'),
+    ("brokenbar", '\u{a6}'), ("bullet", '\u{2022}'), ("c", 'c'), ("caron", '\u{2c7}'),
+    ("ccedilla", '\u{e7}'), ("cedilla", '\u{b8}'), ("cent", '\u{a2}'), ("circumflex", '\u{2c6}'),
+    ("colon", ':'), ("comma", ','), ("copyright", '\u{a9}'), ("currency", '\u{a4}'),
+    ("d", 'd'), ("dagger", '\u{2020}'), ("daggerdbl", '\u{2021}'), ("degree", '\u{b0}'),
+    ("dieresis", '\u{a8}'), ("divide", '\u{f7}'), ("dollar", '$'), ("dotaccent", '\u{2d9}'),
+    ("dotlessi", '\u{131}'), ("e", 'e'), ("eacute", '\u{e9}'), ("ecircumflex", '\u{ea}'),
+    ("edieresis", '\u{eb}'), ("egrave", '\u{e8}'), ("eight", '8'), ("ellipsis", '\u{2026}'),
+    ("emdash", '\u{2014}'), ("endash", '\u{2013}'), ("equal", '='), ("eth", '\u{f0}'),
+    ("exclam", '!'), ("exclamdown", '\u{a1}'), ("f", 'f'), ("fi", '\u{fb01}'),
+    ("five", '5'), ("fl", '\u{fb02}'), ("florin", '\u{192}'), ("four", '4'),
+    ("fraction", '\u{2044}'), ("g", 'g'), ("germandbls", '\u{df}'), ("grave", '`'),
+    ("greater", '>'), ("guillemotleft", '\u{ab}'), ("guillemotright", '\u{bb}'), ("guilsinglleft", '\u{2039}'),
+    ("guilsinglright", '\u{203a}'), ("h", 'h'), ("hungarumlaut", '\u{2dd}'), ("hyphen", '-'),
+    ("i", 'i'), ("iacute", '\u{ed}'), ("icircumflex", '\u{ee}'), ("idieresis", '\u{ef}'),
+    ("igrave", '\u{ec}'), ("j", 'j'), ("k", 'k'), ("l", 'l'),
+    ("less", '<'), ("logicalnot", '\u{ac}'), ("lslash", '\u{142}'), ("m", 'm'),
+    ("macron", '\u{af}'), ("minus", '\u{2212}'), ("mu", '\u{b5}'), ("multiply", '\u{d7}'),
+    ("n", 'n'), ("nine", '9'), ("ntilde", '\u{f1}'), ("numbersign", '#'),
+    ("o", 'o'), ("oacute", '\u{f3}'), ("ocircumflex", '\u{f4}'), ("odieresis", '\u{f6}'),
+    ("oe", '\u{153}'), ("ogonek", '\u{2db}'), ("ograve", '\u{f2}'), ("one", '1'),
+    ("onehalf", '\u{bd}'), ("onequarter", '\u{bc}'), ("onesuperior", '\u{b9}'), ("ordfeminine", '\u{aa}'),
+    ("ordmasculine", '\u{ba}'), ("oslash", '\u{f8}'), ("otilde", '\u{f5}'), ("p", 'p'),
+    ("paragraph", '\u{b6}'), ("parenleft", '('), ("parenright", ')'), ("percent", '%'),
+    ("period", '.'), ("periodcentered", '\u{b7}'), ("perthousand", '\u{2030}'), ("plus", '+'),
+    ("plusminus", '\u{b1}'), ("q", 'q'), ("question", '?'), ("questiondown", '\u{bf}'),
+    ("quotedbl", '"'), ("quotedblbase", '\u{201e}'), ("quotedblleft", '\u{201c}'), ("quotedblright", '\u{201d}'),
+    ("quoteleft", '\u{2018}'), ("quoteright", '\u{2019}'), ("quotesinglbase", '\u{201a}'), ("quotesingle", '\''),
+    ("r", 'r'), ("registered", '\u{ae}'), ("ring", '\u{2da}'), ("s", 's'),
+    ("scaron", '\u{161}'), ("section", '\u{a7}'), ("semicolon", ';'), ("seven", '7'),
+    ("six", '6'), ("slash", '/'), ("space", '\u{20}'), ("sterling", '\u{a3}'),
+    ("t", 't'), ("thorn", '\u{fe}'), ("three", '3'), ("threequarters", '\u{be}'),
+    ("threesuperior", '\u{b3}'), ("tilde", '\u{2dc}'), ("trademark", '\u{2122}'), ("two", '2'),
+    ("twosuperior", '\u{b2}'), ("u", 'u'), ("uacute", '\u{fa}'), ("ucircumflex", '\u{fb}'),
+    ("udieresis", '\u{fc}'), ("ugrave", '\u{f9}'), ("underscore", '_'), ("v", 'v'),
+    ("w", 'w'), ("x", 'x'), ("y", 'y'), ("yacute", '\u{fd}'),
+    ("ydieresis", '\u{ff}'), ("yen", '\u{a5}'), ("z", 'z'), ("zcaron", '\u{17e}'),
+    ("zero", '0'),
+];
+
+/// Glyph names of the Latin characters, sorted by character
+static UNICODE_GLYPH: [(char, &str); 231] = [
+    ('\u{20}', "space"), ('!', "exclam"), ('"', "quotedbl"), ('#', "numbersign"),
+    ('$', "dollar"), ('%', "percent"), ('&', "ampersand"), ('\'', "quotesingle"),
+    ('(', "parenleft"), (')', "parenright"), ('*', "asterisk"), ('+', "plus"),
+    (',', "comma"), ('-', "hyphen"), ('.', "period"), ('/', "slash"),
+    ('0', "zero"), ('1', "one"), ('2', "two"), ('3', "three"),
+    ('4', "four"), ('5', "five"), ('6', "six"), ('7', "seven"),
+    ('8', "eight"), ('9', "nine"), (':', "colon"), (';', "semicolon"),
+    ('<', "less"), ('=', "equal"), ('>', "greater"), ('?', "question"),
+    ('@', "at"), ('A', "A"), ('B', "B"), ('C', "C"),
+    ('D', "D"), ('E', "E"), ('F', "F"), ('G', "G"),
+    ('H', "H"), ('I', "I"), ('J', "J"), ('K', "K"),
+    ('L', "L"), ('M', "M"), ('N', "N"), ('O', "O"),
+    ('P', "P"), ('Q', "Q"), ('R', "R"), ('S', "S"),
+    ('T', "T"), ('U', "U"), ('V', "V"), ('W', "W"),
+    ('X', "X"), ('Y', "Y"), ('Z', "Z"), ('[', "bracketleft"),
+    ('\\', "backslash"), (']', "bracketright"), ('^', "asciicircum"), ('_', "underscore"),
+    ('`', "grave"), ('a', "a"), ('b', "b"), ('c', "c"),
+    ('d', "d"), ('e', "e"), ('f', "f"), ('g', "g"),
+    ('h', "h"), ('i', "i"), ('j', "j"), ('k', "k"),
+    ('l', "l"), ('m', "m"), ('n', "n"), ('o', "o"),
+    ('p', "p"), ('q', "q"), ('r', "r"), ('s', "s"),
+    ('t', "t"), ('u', "u"), ('v', "v"), ('w', "w"),
+    ('x', "x"), ('y', "y"), ('z', "z"), ('{', "braceleft"),
+    ('|', "bar"), ('}', "braceright"), ('~', "asciitilde"), ('\u{a0}', "space"),
+    ('\u{a1}', "exclamdown"), ('\u{a2}', "cent"), ('\u{a3}', "sterling"), ('\u{a4}', "currency"),
+    ('\u{a5}', "yen"), ('\u{a6}', "brokenbar"), ('\u{a7}', "section"), ('\u{a8}', "dieresis"),
+    ('\u{a9}', "copyright"), ('\u{aa}', "ordfeminine"), ('\u{ab}', "guillemotleft"), ('\u{ac}', "logicalnot"),
+    ('\u{ad}', "hyphen"), ('\u{ae}', "registered"), ('\u{af}', "macron"), ('\u{b0}', "degree"),
+    ('\u{b1}', "plusminus"), ('\u{b2}', "twosuperior"), ('\u{b3}', "threesuperior"), ('\u{b4}', "acute"),
+    ('\u{b5}', "mu"), ('\u{b6}', "paragraph"), ('\u{b7}', "periodcentered"), ('\u{b8}', "cedilla"),
+    ('\u{b9}', "onesuperior"), ('\u{ba}', "ordmasculine"), ('\u{bb}', "guillemotright"), ('\u{bc}', "onequarter"),
+    ('\u{bd}', "onehalf"), ('\u{be}', "threequarters"), ('\u{bf}', "questiondown"), ('\u{c0}', "Agrave"),
+    ('\u{c1}', "Aacute"), ('\u{c2}', "Acircumflex"), ('\u{c3}', "Atilde"), ('\u{c4}', "Adieresis"),
+    ('\u{c5}', "Aring"), ('\u{c6}', "AE"), ('\u{c7}', "Ccedilla"), ('\u{c8}', "Egrave"),
+    ('\u{c9}', "Eacute"), ('\u{ca}', "Ecircumflex"), ('\u{cb}', "Edieresis"), ('\u{cc}', "Igrave"),
+    ('\u{cd}', "Iacute"), ('\u{ce}', "Icircumflex"), ('\u{cf}', "Idieresis"), ('\u{d0}', "Eth"),
+    ('\u{d1}', "Ntilde"), ('\u{d2}', "Ograve"), ('\u{d3}', "Oacute"), ('\u{d4}', "Ocircumflex"),
+    ('\u{d5}', "Otilde"), ('\u{d6}', "Odieresis"), ('\u{d7}', "multiply"), ('\u{d8}', "Oslash"),
+    ('\u{d9}', "Ugrave"), ('\u{da}', "Uacute"), ('\u{db}', "Ucircumflex"), ('\u{dc}', "Udieresis"),
+    ('\u{dd}', "Yacute"), ('\u{de}', "Thorn"), ('\u{df}', "germandbls"), ('\u{e0}', "agrave"),
+    ('\u{e1}', "aacute"), ('\u{e2}', "acircumflex"), ('\u{e3}', "atilde"), ('\u{e4}', "adieresis"),
+    ('\u{e5}', "aring"), ('\u{e6}', "ae"), ('\u{e7}', "ccedilla"), ('\u{e8}', "egrave"),
+    ('\u{e9}', "eacute"), ('\u{ea}', "ecircumflex"), ('\u{eb}', "edieresis"), ('\u{ec}', "igrave"),
+    ('\u{ed}', "iacute"), ('\u{ee}', "icircumflex"), ('\u{ef}', "idieresis"), ('\u{f0}', "eth"),
+    ('\u{f1}', "ntilde"), ('\u{f2}', "ograve"), ('\u{f3}', "oacute"), ('\u{f4}', "ocircumflex"),
+    ('\u{f5}', "otilde"), ('\u{f6}', "odieresis"), ('\u{f7}', "divide"), ('\u{f8}', "oslash"),
+    ('\u{f9}', "ugrave"), ('\u{fa}', "uacute"), ('\u{fb}', "ucircumflex"), ('\u{fc}', "udieresis"),
+    ('\u{fd}', "yacute"), ('\u{fe}', "thorn"), ('\u{ff}', "ydieresis"), ('\u{131}', "dotlessi"),
+    ('\u{141}', "Lslash"), ('\u{142}', "lslash"), ('\u{152}', "OE"), ('\u{153}', "oe"),
+    ('\u{160}', "Scaron"), ('\u{161}', "scaron"), ('\u{178}', "Ydieresis"), ('\u{17d}', "Zcaron"),
+    ('\u{17e}', "zcaron"), ('\u{192}', "florin"), ('\u{2c6}', "circumflex"), ('\u{2c7}', "caron"),
+    ('\u{2d8}', "breve"), ('\u{2d9}', "dotaccent"), ('\u{2da}', "ring"), ('\u{2db}', "ogonek"),
+    ('\u{2dc}', "tilde"), ('\u{2dd}', "hungarumlaut"), ('\u{2013}', "endash"), ('\u{2014}', "emdash"),
+    ('\u{2018}', "quoteleft"), ('\u{2019}', "quoteright"), ('\u{201a}', "quotesinglbase"), ('\u{201c}', "quotedblleft"),
+    ('\u{201d}', "quotedblright"), ('\u{201e}', "quotedblbase"), ('\u{2020}', "dagger"), ('\u{2021}', "daggerdbl"),
+    ('\u{2022}', "bullet"), ('\u{2026}', "ellipsis"), ('\u{2030}', "perthousand"), ('\u{2039}', "guilsinglleft"),
+    ('\u{203a}', "guilsinglright"), ('\u{2044}', "fraction"), ('\u{20ac}', "Euro"), ('\u{2122}', "trademark"),
+    ('\u{2212}', "minus"), ('\u{fb01}', "fi"), ('\u{fb02}', "fl"),
+];
+
+/// PDFDocEncoding of the codes `0x18` to `0x1f`: breve, caron, circumflex, dotaccent,
+/// hungarumlaut, ogonek, ring and tilde
+static PDF_DOC_ACCENTS: [char; 8] = [
+    '\u{02d8}', '\u{02c7}', '\u{02c6}', '\u{02d9}', '\u{02dd}', '\u{02db}', '\u{02da}', '\u{02dc}',
+];
+
+/// PDFDocEncoding of the codes `0x80` to `0x9e`, from bullet to zcaron
+static PDF_DOC_PUNCTUATION: [char; 31] = [
+    '\u{2022}', '\u{2020}', '\u{2021}', '\u{2026}', '\u{2014}', '\u{2013}', '\u{0192}', '\u{2044}',
+    '\u{2039}', '\u{203a}', '\u{2212}', '\u{2030}', '\u{201e}', '\u{201c}', '\u{201d}', '\u{2018}',
+    '\u{2019}', '\u{201a}', '\u{2122}', '\u{fb01}', '\u{fb02}', '\u{0141}', '\u{0152}', '\u{0160}',
+    '\u{0178}', '\u{017d}', '\u{0131}', '\u{0142}', '\u{0153}', '\u{0161}', '\u{017e}',
+];
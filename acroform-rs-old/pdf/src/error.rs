@@ -1,4 +1,4 @@
-use crate::object::ObjNr;
+use crate::object::{ObjNr, PlainRef};
 use std::io;
 use std::error::Error;
 use crate::parser::ParseFlags;
@@ -81,6 +81,13 @@ pub enum PdfError {
         source: Box<PdfError>
     },
 
+    #[snafu(display("At {}, caused by\n  {}", location, source))]
+    Located {
+        location: Location,
+        #[snafu(source)]
+        source: Box<PdfError>
+    },
+
     #[snafu(display("Field /{} is missing in dictionary for type {}.", field, typ))]
     MissingEntry {
         typ: &'static str,
@@ -134,6 +141,12 @@ pub enum PdfError {
     #[snafu(display("Decryption failure"))]
     DecryptionFailure,
 
+    #[snafu(display("Not a recipient of this document"))]
+    NotRecipient,
+
+    #[snafu(display("Operation not permitted by the document, requires {:?}", required))]
+    PermissionDenied { required: crate::crypt::Permissions },
+
     #[snafu(display("JPEG Error, caused by\n  {}", source))]
     Jpeg {
         #[snafu(source)]
@@ -174,12 +187,30 @@ pub enum PdfError {
     #[snafu(display("UTF8 decode error"))]
     Utf8Decode,
 
+    #[snafu(display("PDFDocEncoding decode error: undefined code {:#04x}", byte))]
+    PdfDocDecode { byte: u8 },
+
     #[snafu(display("CID decode error"))]
     CidDecode,
 
     #[snafu(display("Max nesting depth reached"))]
     MaxDepth,
 
+    #[snafu(display("More than {} references followed in a row", limit))]
+    MaxReferenceDepth { limit: usize },
+
+    #[snafu(display("Decoded stream exceeds the limit of {} bytes", limit))]
+    StreamTooLarge { limit: usize },
+
+    #[snafu(display("Decoded streams exceed the limit of {} bytes in total", limit))]
+    DecodedTotalTooLarge { limit: usize },
+
+    #[snafu(display("Array or dictionary exceeds the limit of {} elements", limit))]
+    TooManyElements { limit: usize },
+
+    #[snafu(display("{} objects exceed the limit of {}", count, limit))]
+    TooManyObjects { count: usize, limit: usize },
+
     #[snafu(display("Invalid"))]
     Invalid,
 }
@@ -188,9 +219,137 @@ impl PdfError {
         match self {
             PdfError::EOF => true,
             PdfError::Try { ref source, .. } => source.is_eof(),
+            PdfError::Located { ref source, .. } => source.is_eof(),
             _ => false
         }
     }
+
+    /// Whether the error may go away with more data after the end of the input,
+    /// like running out of input or not finding a keyword.
+    pub fn needs_more_data(&self) -> bool {
+        match self {
+            PdfError::EOF | PdfError::ContentReadPastBoundary | PdfError::NotFound { .. } => true,
+            PdfError::Try { ref source, .. } => source.needs_more_data(),
+            PdfError::Located { ref source, .. } => source.needs_more_data(),
+            PdfError::FromPrimitive { ref source, .. } => source.needs_more_data(),
+            PdfError::Shared { ref source } => source.needs_more_data(),
+            _ => false
+        }
+    }
+
+    /// Add the dictionary `key` in front of the path of this error.
+    pub fn at_key(self, key: &str) -> PdfError {
+        self.prepend(PathSegment::Key(key.into()))
+    }
+
+    /// Add the array `index` in front of the path of this error.
+    pub fn at_index(self, index: usize) -> PdfError {
+        self.prepend(PathSegment::Index(index))
+    }
+
+    fn prepend(self, segment: PathSegment) -> PdfError {
+        match self {
+            PdfError::Located { mut location, source } => {
+                location.path.insert(0, segment);
+                PdfError::Located { location, source }
+            }
+            e => PdfError::Located {
+                location: Location { path: vec![segment], ..Location::default() },
+                source: Box::new(e)
+            }
+        }
+    }
+
+    /// Record that this error occurred while loading the object `r`, found at `offset` in the file.
+    pub fn in_object(self, r: PlainRef, offset: Option<usize>) -> PdfError {
+        match self {
+            PdfError::Located { mut location, source } if location.object.is_none() => {
+                location.object = Some(r);
+                location.offset = offset;
+                PdfError::Located { location, source }
+            }
+            e @ PdfError::Located { location: Location { object: Some(object), .. }, .. } if object == r => e,
+            e => PdfError::Located {
+                location: Location { object: Some(r), offset, path: vec![] },
+                source: Box::new(e)
+            }
+        }
+    }
+
+    /// Where in the document this error occurred, if known.
+    ///
+    /// The path starts at the object whose loading failed first (usually the trailer),
+    /// the object is the innermost one that could not be loaded.
+    pub fn location(&self) -> Option<Location> {
+        let mut found: Option<Location> = None;
+        let mut e = self;
+        loop {
+            e = match e {
+                PdfError::Located { location, source } => {
+                    let found = found.get_or_insert_with(Location::default);
+                    found.path.extend(location.path.iter().cloned());
+                    if location.object.is_some() {
+                        found.object = location.object;
+                        found.offset = location.offset;
+                    }
+                    source
+                }
+                PdfError::Try { source, .. } | PdfError::FromPrimitive { source, .. } => source,
+                PdfError::Shared { source } => source,
+                // positions of the lexer are only offsets in the file for objects read from it directly
+                PdfError::UnexpectedLexeme { pos, .. } | PdfError::UnknownType { pos, .. } => {
+                    if let Some(found) = found.as_mut().filter(|l| l.offset.is_some()) {
+                        found.offset = Some(*pos);
+                    }
+                    break;
+                }
+                _ => break
+            };
+        }
+        found
+    }
+}
+
+/// Where in the document an error occurred, see [`PdfError::location`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    /// The object that failed to load.
+    pub object: Option<PlainRef>,
+    /// Dictionary keys and array indices leading to the value, like `Root/AcroForm/Fields[3]`.
+    pub path: Vec<PathSegment>,
+    /// Byte offset in the file.
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, segment) in self.path.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => write!(f, "{}", key)?,
+                PathSegment::Key(key) => write!(f, "/{}", key)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        let mut details = vec![];
+        if let Some(PlainRef { id, gen }) = self.object {
+            details.push(format!("object {} {} R", id, gen));
+        }
+        if let Some(offset) = self.offset {
+            details.push(format!("offset {}", offset));
+        }
+        match (self.path.is_empty(), details.is_empty()) {
+            (_, true) => {}
+            (true, false) => write!(f, "{}", details.join(", "))?,
+            (false, false) => write!(f, " ({})", details.join(", "))?,
+        }
+        Ok(())
+    }
 }
 datasize::non_dynamic_const_heap_size!(PdfError, 0);
 
@@ -308,9 +467,6 @@ macro_rules! bail {
         err!($crate::PdfError::Other { msg: format!($($t)*) })
     }
 }
-macro_rules! unimplemented {
-    () => (bail!("Unimplemented @ {}:{}", file!(), line!()))
-}
 
 #[cfg(not(feature = "dump"))]
 pub fn dump_data(_data: &[u8]) {}
@@ -1,6 +1,6 @@
 //! This is kind of the entry-point of the type-safe PDF functionality.
 use std::marker::PhantomData;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::path::Path;
 use std::io::Write;
@@ -8,15 +8,17 @@ use std::io::Write;
 use crate as pdf;
 use crate::error::*;
 use crate::object::*;
-use crate::primitive::{Primitive, Dictionary, PdfString};
-use crate::backend::Backend;
+use crate::primitive::{Primitive, Dictionary, PdfString, PdfStream, StreamInner};
+use crate::backend::{Backend, Revision};
+use crate::repair;
 use crate::any::*;
 use crate::parser::{Lexer, parse_with_lexer};
 use crate::parser::{parse_indirect_object, parse, ParseFlags};
-use crate::xref::{XRef, XRefTable, XRefInfo};
+use crate::xref::{XRef, XRefTable};
 use crate::crypt::Decoder;
-use crate::crypt::CryptDict;
-use crate::enc::{StreamFilter, decode};
+use crate::crypt::{CryptDict, Permissions};
+use crate::pubsec::Recipient;
+use crate::enc::{StreamFilter, decode_limited, encode};
 use std::ops::Range;
 use datasize::DataSize;
 
@@ -29,6 +31,9 @@ pub struct PromisedRef<T> {
     _marker:    PhantomData<T>
 }
 impl<T> PromisedRef<T> {
+    pub(crate) fn new(inner: PlainRef) -> Self {
+        PromisedRef { inner, _marker: PhantomData }
+    }
     pub fn get_inner(&self) -> PlainRef {
         self.inner
     }
@@ -66,6 +71,43 @@ pub trait Log {
 pub struct NoLog;
 impl Log for NoLog {}
 
+/// The kind of cross-reference section written when saving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum XRefFormat {
+    /// A classic table for files older than PDF 1.5, a stream otherwise.
+    #[default]
+    Auto,
+    /// A classic `xref` table followed by a `trailer` dictionary.
+    Table,
+    /// A cross-reference stream (PDF 1.5).
+    Stream,
+    /// A classic table plus a cross-reference stream for the objects in object streams,
+    /// referenced by the trailer's `/XRefStm` entry. Readers older than PDF 1.5 only see the table.
+    Hybrid,
+}
+
+/// Options for writing files.
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    pub xref: XRefFormat,
+    /// Pack objects that are not streams into compressed object streams (PDF 1.5).
+    /// This requires a cross-reference stream.
+    pub object_streams: bool,
+    /// Flate compress new streams without filters whose data is longer than this many bytes.
+    pub compress_above: Option<usize>,
+}
+impl SaveOptions {
+    /// The format to write, never `Auto`
+    fn xref_format(&self, version: &str) -> Result<XRefFormat> {
+        match self.xref {
+            XRefFormat::Auto if self.object_streams || !before_1_5(version) => Ok(XRefFormat::Stream),
+            XRefFormat::Auto => Ok(XRefFormat::Table),
+            XRefFormat::Table if self.object_streams => bail!("object streams require an xref stream"),
+            format => Ok(format),
+        }
+    }
+}
+
 pub struct Storage<B, OC, SC, L> {
     // objects identical to those in the backend
     cache: OC,
@@ -78,6 +120,14 @@ pub struct Storage<B, OC, SC, L> {
 
     decoder:    Option<Decoder>,
     options:    ParseOptions,
+    save_options: SaveOptions,
+
+    // the xref table was rebuilt by scanning the file
+    repaired:   bool,
+
+    // streams decoded so far and their decoded bytes, see `ParseOptions::max_total_decoded`;
+    // each stream counts once, however often it is decoded
+    decoded: Mutex<(HashSet<PlainRef>, usize)>,
 
     backend:    B,
 
@@ -101,6 +151,9 @@ where
             refs: XRefTable::new(0),
             decoder: None,
             options: ParseOptions::strict(),
+            save_options: SaveOptions::default(),
+            repaired: false,
+            decoded: Mutex::new((HashSet::new(), 0)),
             backend: Vec::from(&b"%PDF-1.7\n"[..]),
             start_offset: 0,
             log
@@ -119,13 +172,16 @@ where
         self.backend
     }
     pub fn version(&self) -> Result<String> {
-        Ok(String::from_utf8(self.backend.read(self.start_offset+1..self.start_offset+8)?.to_owned())?)
+        Ok(String::from_utf8(self.backend.read(self.start_offset+1..self.start_offset+8)?.to_vec())?)
     }
     pub fn resolver(&self) -> impl Resolve + '_ {
         StorageResolver::new(self)
     }
     pub fn with_cache(backend: B, options: ParseOptions, object_cache: OC, stream_cache: SC, log: L) -> Result<Self> {
-        let start_offset = backend.locate_start_offset()?;
+        let start_offset = match backend.locate_start_offset() {
+            Err(_) if options.allow_garbage => backend.search_start_offset()?,
+            result => result?,
+        };
 
         Ok(Storage {
             start_offset,
@@ -136,18 +192,30 @@ where
             changes: HashMap::new(),
             decoder: None,
             options,
+            save_options: SaveOptions::default(),
+            repaired: false,
+            decoded: Mutex::new((HashSet::new(), 0)),
             log
         })
     }
     fn decode(&self, id: PlainRef, range: Range<usize>, filters: &[StreamFilter]) -> Result<Arc<[u8]>> {
-        let data = self.backend.read(range)?;
-
-        let mut data = Vec::from(data);
+        let mut data = self.backend.read(range)?.into_owned();
         if let Some(ref decoder) = self.decoder {
             data = Vec::from(t!(decoder.decrypt(id, &mut data)));
         }
         for filter in filters {
-            data = t!(decode(&data, filter), filter);
+            data = t!(decode_limited(&data, filter, self.options.max_stream_size), filter);
+        }
+        let limit = self.options.max_total_decoded;
+        if !filters.is_empty() {
+            let mut decoded = self.decoded.lock().unwrap();
+            let (ref mut streams, ref mut total) = *decoded;
+            if streams.insert(id) {
+                *total += data.len();
+            }
+            if *total > limit {
+                return Err(PdfError::DecodedTotalTooLarge { limit });
+            }
         }
         Ok(data.into())
     }
@@ -157,31 +225,62 @@ where
     }
 
     pub fn load_storage_and_trailer_password(&mut self, password: &[u8]) -> Result<Dictionary> {
+        self.load_storage_and_trailer_with(password, None)
+    }
+
+    /// Load the xref table and trailer, and set up decryption with the password
+    /// (standard security handler) or the recipient (public-key security handler).
+    ///
+    /// If the xref table can not be read and `ParseOptions::allow_xref_error` is set,
+    /// it is rebuilt by scanning the file, see [`Storage::rebuild_xref_table`].
+    pub fn load_storage_and_trailer_with(&mut self, password: &[u8], recipient: Option<&Recipient>) -> Result<Dictionary> {
 
         let resolver = StorageResolver::new(self);
-        let (refs, trailer) = t!(self.backend.read_xref_table_and_trailer(self.start_offset, &resolver));
-        self.refs = refs;
+        let trailer = match self.backend.read_xref_table_and_trailer(self.start_offset, &resolver) {
+            Ok((refs, trailer)) => {
+                self.refs = refs;
+                trailer
+            }
+            Err(e) if self.options.allow_xref_error => {
+                warn!("can't read xref table: {:?}", e);
+                t!(self.rebuild_xref_table())
+            }
+            Err(e) => return Err(e),
+        };
+        self.set_up_decryption(&trailer, password, recipient)?;
+        Ok(trailer)
+    }
 
+    fn set_up_decryption(&mut self, trailer: &Dictionary, password: &[u8], recipient: Option<&Recipient>) -> Result<()> {
         if let Some(crypt) = trailer.get("Encrypt") {
-            let key = trailer
-                .get("ID")
-                .ok_or(PdfError::MissingEntry {
-                    typ: "Trailer",
-                    field: "ID".into(),
-                })?
-                .as_array()?
-                .get(0)
-                .ok_or(PdfError::MissingEntry {
-                    typ: "Trailer",
-                    field: "ID[0]".into()
-                })?
-                .as_string()?
-                .as_bytes();
-
             let resolver = StorageResolver::new(self);
             let dict = CryptDict::from_primitive(crypt.clone(), &resolver)?;
 
-            self.decoder = Some(t!(Decoder::from_password(&dict, key, password)));
+            let decoder = match dict.filter() {
+                "Standard" => {
+                    let key = trailer
+                        .get("ID")
+                        .ok_or(PdfError::MissingEntry {
+                            typ: "Trailer",
+                            field: "ID".into(),
+                        })?
+                        .as_array()?
+                        .get(0)
+                        .ok_or(PdfError::MissingEntry {
+                            typ: "Trailer",
+                            field: "ID[0]".into()
+                        })?
+                        .as_string()?
+                        .as_bytes();
+                    t!(Decoder::from_password(&dict, key, password))
+                }
+                "Adobe.PubSec" => {
+                    let recipient = recipient.ok_or(PdfError::NotRecipient)?;
+                    t!(Decoder::from_recipient(&dict, recipient))
+                }
+                filter => bail!("unsupported security handler {}", filter),
+            };
+            self.decoder = Some(decoder);
             if let Primitive::Reference(reference) = crypt {
                 self.decoder.as_mut().unwrap().encrypt_indirect_object = Some(*reference);
             }
@@ -193,12 +292,228 @@ where
                 }
             }
         }
+        Ok(())
+    }
+
+    /// Rebuild the xref table by scanning the whole file for objects, object streams and trailers.
+    /// Returns a trailer pointing to the most recent catalog.
+    ///
+    /// This is used for damaged files whose xref table or `startxref` can not be read.
+    /// Afterwards [`Storage::repaired`] returns true.
+    pub fn rebuild_xref_table(&mut self) -> Result<Dictionary> {
+        let scan = t!(repair::scan(&self.backend, self.start_offset));
+        let len = self.backend.len() - self.start_offset;
+
+        // later definitions replace earlier ones, as in incremental updates
+        let mut refs = XRefTable::new(0);
+        let mut defined_at = HashMap::new();
+        for &(id, gen_nr, pos) in &scan.objects {
+            if id == 0 || id >= self.options.max_objects as ObjNr {
+                continue;
+            }
+            while refs.len() <= id as usize {
+                refs.push(XRef::Invalid);
+            }
+            refs.set(id, XRef::Raw { pos, gen_nr });
+            defined_at.insert(id, pos);
+        }
+        self.refs = refs;
+        self.cache.clear();
+
+        // the current definition of each object and where the next object starts
+        let current: Vec<_> = scan.objects.iter().enumerate()
+            .filter(|&(_, &(id, _, pos))| defined_at.get(&id) == Some(&pos))
+            .map(|(i, &(id, gen, pos))| {
+                let end = scan.objects.get(i + 1).map_or(len, |o| o.2);
+                (PlainRef { id, gen }, pos, end)
+            })
+            .collect();
+
+        let resolver = StorageResolver::new(self);
+        let mut compressed = vec![];
+        for &(r, pos, end) in &current {
+            if !repair::any_within(&scan.object_streams, pos, end) {
+                continue;
+            }
+            match resolver.get::<ObjectStream>(Ref::new(r)) {
+                Ok(stream) => compressed.extend(stream.object_ids().iter().enumerate()
+                    .map(|(index, &id)| (id, pos, r.id, index))),
+                Err(e) => warn!("can't read object stream {:?}: {:?}", r, e),
+            }
+        }
+        for (id, pos, stream_id, index) in compressed {
+            if id == 0 || id >= self.options.max_objects as ObjNr || defined_at.get(&id).is_some_and(|&p| p > pos) {
+                continue;
+            }
+            while self.refs.len() <= id as usize {
+                self.refs.push(XRef::Invalid);
+            }
+            self.refs.set(id, XRef::Stream { stream_id, index });
+            defined_at.insert(id, pos);
+        }
+        self.cache.clear();
+
+        // trailers and xref streams, newest last
+        let mut trailers = vec![];
+        for &pos in &scan.trailers {
+            let offset = self.start_offset + pos;
+            let trailer = self.backend.read_from(offset, |data| {
+                parse_with_lexer(&mut Lexer::with_offset(data, offset), &NoResolve, ParseFlags::DICT)
+            });
+            if let Ok(Primitive::Dictionary(dict)) = trailer {
+                trailers.push((pos, dict));
+            }
+        }
+        let resolver = StorageResolver::new(self);
+        for &(r, pos, end) in &current {
+            if repair::any_within(&scan.xref_streams, pos, end) {
+                if let Ok(Primitive::Stream(stream)) = resolver.resolve_flags(r, ParseFlags::STREAM, 0) {
+                    if stream.info.get("Type").and_then(|t| t.as_name().ok()) == Some("XRef") {
+                        trailers.push((pos, stream.info));
+                    }
+                }
+            }
+        }
+        trailers.sort_by_key(|&(pos, _)| pos);
+
+        let is_catalog = |p: &Primitive| -> bool {
+            let dict = match p {
+                &Primitive::Reference(r) => resolver.resolve(r).and_then(|p| p.into_dictionary()),
+                _ => return false,
+            };
+            dict.is_ok_and(|d| d.get("Type").and_then(|t| t.as_name().ok()) == Some("Catalog"))
+        };
+
+        let mut trailer = Dictionary::new();
+        if let Some((_, found)) = trailers.iter().rev().find(|(_, t)| t.get("Root").is_some_and(is_catalog)) {
+            for key in ["Root", "Info", "ID", "Encrypt"] {
+                if let Some(val) = found.get(key) {
+                    trailer.insert(key, val.clone());
+                }
+            }
+        } else {
+            // no usable trailer, take the most recent catalog
+            let root = self.refs.iter()
+                .map(|id| PlainRef { id: id as ObjNr, gen: self.refs.get(id as ObjNr).map_or(0, |x| x.get_gen_nr()) })
+                .filter(|&r| is_catalog(&Primitive::Reference(r)))
+                .max_by_key(|r| defined_at.get(&r.id))
+                .ok_or(PdfError::MissingEntry { typ: "Trailer", field: "Root".into() })?;
+            trailer.insert("Root", Primitive::Reference(root));
+        }
+        trailer.insert("Size", self.refs.len() as i32);
+
+        warn!("rebuilt xref table with {} objects", defined_at.len());
+        self.repaired = true;
         Ok(trailer)
     }
+
+    /// true if the xref table was damaged and had to be rebuilt by scanning the file
+    pub fn repaired(&self) -> bool {
+        self.repaired
+    }
+
+    /// The revisions of the file, oldest first. See [`Backend::read_revisions`].
+    pub fn revisions(&self) -> Result<Vec<Revision>> {
+        let resolver = StorageResolver::new(self);
+        self.backend.read_revisions(self.start_offset, &resolver)
+    }
+
+    /// Write the file followed by an incremental update with all changes to `out`.
+    ///
+    /// Unlike [`Storage::save`], this works with any backend: the original is copied
+    /// in chunks and only the update is built in memory. The backend, the xref table
+    /// and `trailer` are left unchanged, so the changes stay pending.
+    /// As with [`Storage::save`], the update of a [repaired](Storage::repaired) file
+    /// lists all objects and doesn't refer to the damaged sections.
+    pub fn save_to_writer(&mut self, trailer: &mut Trailer, out: &mut impl Write) -> Result<()> {
+        const CHUNK: usize = 1 << 20;
+
+        let version = self.effective_version(trailer)?;
+        let options = SaveOptions { xref: self.save_options.xref_format(&version)?, ..self.save_options.clone() };
+
+        let prev = trailer.prev_trailer_pos;
+        trailer.prev_trailer_pos = self.prev_xref();
+        let trailer_dict = trailer.to_dict(self);
+        trailer.prev_trailer_pos = prev;
+        let mut trailer_dict = trailer_dict?;
+        let mut changes = self.sorted_changes();
+        if options.xref != XRefFormat::Table && before_1_5(&version) {
+            self.set_version_1_5(trailer, &mut changes)?;
+        }
+
+        let len = self.backend.len();
+        for start in (0 .. len).step_by(CHUNK) {
+            let chunk = t!(self.backend.read(start .. len.min(start + CHUNK)));
+            out.write_all(&chunk)?;
+        }
+
+        let mut update = Vec::new();
+        let mut refs = self.refs.clone();
+        write_update(&mut update, len, self.start_offset, &mut refs, changes, &mut trailer_dict, &options, self.repaired)?;
+        out.write_all(&update)?;
+        Ok(())
+    }
+
+    /// The position of the last xref section, for the `/Prev` of an update.
+    /// None if the file was repaired, as the section is damaged or missing.
+    fn prev_xref(&self) -> Option<i32> {
+        if self.repaired {
+            return None;
+        }
+        self.backend.xref_offset(self.start_offset, &self.options).ok().map(|pos| pos as _)
+    }
+
+    /// The version of the file: that of the header, or the `/Version` of the catalog if it is later.
+    fn effective_version(&self, trailer: &Trailer) -> Result<String> {
+        let header = self.version()?;
+        Ok(match trailer.root.version {
+            Some(ref version) if format!("PDF-{}", version) > header => format!("PDF-{}", version),
+            _ => header,
+        })
+    }
+
+    /// Add the catalog with `/Version` set to 1.5 to the sorted `changes` of an update
+    /// that uses PDF 1.5 features, without making it a pending change of the file.
+    ///
+    /// Incremental updates raise the version this way, so the bytes of the original,
+    /// including the header covered by signatures, stay unchanged.
+    /// Returns the number and the new dictionary of the catalog.
+    fn set_version_1_5(&self, trailer: &Trailer, changes: &mut Vec<(ObjNr, GenNr, Primitive)>) -> Result<(ObjNr, Primitive)> {
+        let r = trailer.root.get_ref().get_inner();
+        let mut catalog = self.resolver().resolve(r)?.into_dictionary()?;
+        catalog.insert("Version", Primitive::Name("1.5".into()));
+        let gen = match self.refs.get(r.id)? {
+            XRef::Raw { gen_nr, .. } => gen_nr,
+            _ => 0,
+        };
+        let entry = (r.id, gen, Primitive::Dictionary(catalog.clone()));
+        match changes.binary_search_by_key(&r.id, |&(id, ..)| id) {
+            Ok(i) => changes[i] = entry,
+            Err(i) => changes.insert(i, entry),
+        }
+        Ok((r.id, Primitive::Dictionary(catalog)))
+    }
+
+    fn sorted_changes(&self) -> Vec<(ObjNr, GenNr, Primitive)> {
+        let mut changes: Vec<_> = self.changes.iter()
+            .map(|(&id, &(ref primitive, gen))| (id, gen, primitive.clone()))
+            .collect();
+        changes.sort_unstable_by_key(|&(id, ..)| id);
+        changes
+    }
+    /// true if the document is encrypted and was opened with the owner password
+    pub fn is_owner(&self) -> bool {
+        self.decoder.as_ref().map(|d| d.is_owner()).unwrap_or(false)
+    }
+    /// Parse the objects and trailers of the file in order, up to the last xref section.
+    ///
+    /// The file is read in windows, so backends like [`SeekBackend`](crate::backend::SeekBackend)
+    /// only hold the current object in memory.
     pub fn scan(&self) -> impl Iterator<Item = Result<ScanItem>> + '_ {
-        let xref_offset = self.backend.locate_xref_offset().unwrap();
-        let slice = self.backend.read(self.start_offset .. xref_offset).unwrap();
-        let mut lexer = Lexer::with_offset(slice, 0);
+        let (mut pos, mut end, mut error) = match self.backend.xref_offset(self.start_offset, &self.options) {
+            Ok(xref_offset) => (self.start_offset, self.start_offset + xref_offset, None),
+            Err(e) => (0, 0, Some(e)),
+        };
         
         fn skip_xref(lexer: &mut Lexer) -> Result<()> {
             while lexer.next()? != "trailer" {
@@ -209,43 +524,83 @@ where
 
         let resolver = StorageResolver::new(self);
         std::iter::from_fn(move || {
-            loop {
-                let pos = lexer.get_pos();
-                match parse_indirect_object(&mut lexer, &resolver, self.decoder.as_ref(), ParseFlags::all()) {
-                    Ok((r, p)) => return Some(Ok(ScanItem::Object(r, p))),
-                    Err(e) if e.is_eof() => return None,
-                    Err(e) => {
-                        lexer.set_pos(pos);
-                        if let Ok(s) = lexer.next() {
-                            debug!("next: {:?}", String::from_utf8_lossy(s.as_slice()));
-                            match &*s {
-                                b"xref" => {
-                                    if let Err(e) = skip_xref(&mut lexer) {
-                                        return Some(Err(e));
+            if let Some(e) = error.take() {
+                return Some(Err(e));
+            }
+            if pos >= end {
+                return None;
+            }
+            let result = self.backend.read_from(pos, |data| {
+                // running out of a window that ends before the xref section needs a larger one
+                let complete = pos + data.len() >= end;
+                let mut lexer = Lexer::with_offset(&data[.. data.len().min(end - pos)], pos);
+                let item = loop {
+                    let start = lexer.get_pos();
+                    match parse_indirect_object(&mut lexer, &resolver, self.decoder.as_ref(), ParseFlags::all()) {
+                        Ok((r, p)) => break Some(Ok(ScanItem::Object(r, p))),
+                        Err(e) if !complete && e.needs_more_data() => return Err(e),
+                        Err(e) if e.is_eof() => break None,
+                        Err(e) => {
+                            lexer.set_pos(start);
+                            if let Ok(s) = lexer.next() {
+                                debug!("next: {:?}", String::from_utf8_lossy(s.as_slice()));
+                                match &*s {
+                                    b"xref" => {
+                                        match skip_xref(&mut lexer) {
+                                            Err(e) if !complete && e.needs_more_data() => return Err(e),
+                                            Err(e) => break Some(Err(e)),
+                                            Ok(()) => {}
+                                        }
+                                        match parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::DICT).and_then(|p| p.into_dictionary()) {
+                                            Ok(trailer) => break Some(Ok(ScanItem::Trailer(trailer))),
+                                            Err(e) if !complete && e.needs_more_data() => return Err(e),
+                                            Err(_) => {}
+                                        }
                                     }
-                                    if let Ok(trailer) = parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::DICT).and_then(|p| p.into_dictionary()) {
-                                        return Some(Ok(ScanItem::Trailer(trailer)));
+                                    b"startxref" if lexer.next().is_ok() => {
+                                        continue;
                                     }
+                                    _ => {}
                                 }
-                                b"startxref" if lexer.next().is_ok() => {
-                                    continue;
-                                }
-                                _ => {}
                             }
+                            break Some(Err(e));
                         }
-                        return Some(Err(e));
                     }
+                };
+                Ok((item, lexer.get_pos()))
+            });
+            match result {
+                Ok((item, read)) if read > 0 => {
+                    pos += read;
+                    item
+                }
+                Ok((item, _)) => {
+                    end = pos;
+                    item
+                }
+                Err(e) => {
+                    end = pos;
+                    Some(Err(e))
                 }
             }
         })
     }
+    /// Offset of `r` in the file, unless it is in an object stream or was changed.
+    fn object_offset(&self, r: PlainRef) -> Option<usize> {
+        match self.refs.get(r.id) {
+            Ok(XRef::Raw { pos, .. }) if !self.changes.contains_key(&r.id) => Some(self.start_offset + pos),
+            _ => None
+        }
+    }
     fn resolve_ref(&self, r: PlainRef, flags: ParseFlags, resolve: &impl Resolve) -> Result<Primitive> {
         match self.changes.get(&r.id) {
             Some((p, _)) => Ok((*p).clone()),
             None => match t!(self.refs.get(r.id)) {
                 XRef::Raw {pos, ..} => {
-                    let mut lexer = Lexer::with_offset(t!(self.backend.read(self.start_offset + pos ..)), self.start_offset + pos);
-                    let p = t!(parse_indirect_object(&mut lexer, resolve, self.decoder.as_ref(), flags)).1;
+                    let pos = self.start_offset + pos;
+                    let p = t!(self.backend.read_from(pos, |data| {
+                        parse_indirect_object(&mut Lexer::with_offset(data, pos), resolve, self.decoder.as_ref(), flags)
+                    }).map_err(|e| e.in_object(r, Some(pos)))).1;
                     Ok(p)
                 }
                 XRef::Stream {stream_id, index} => {
@@ -257,16 +612,57 @@ where
 
                     let (data, range) = t!(obj_stream.get_object_slice(index, resolve));
                     let slice = data.get(range.clone()).ok_or_else(|| other!("invalid range {:?}, but only have {} bytes", range, data.len()))?;
-                    parse(slice, resolve, flags)
+                    parse(slice, resolve, flags).map_err(|e| e.in_object(r, None))
                 }
                 XRef::Free {..} => err!(PdfError::FreeObject {obj_nr: r.id}),
-                XRef::Promised => unimplemented!(),
+                XRef::Promised => bail!("object {} was promised but never written", r.id),
                 XRef::Invalid => err!(PdfError::NullRef {obj_nr: r.id}),
             }
         }
     }
 }
 
+#[cfg(feature = "threads")]
+impl<B, OC, SC, L> Storage<B, OC, SC, L>
+where
+    B: Backend,
+    OC: Cache<Result<AnySync, Arc<PdfError>>>,
+    SC: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
+    L: Log,
+    Self: Sync,
+{
+    /// Resolve `refs` on all cores.
+    ///
+    /// The object streams holding any of them are decoded in parallel first,
+    /// then the objects themselves are parsed. The results are in the order of `refs`.
+    /// With a cache, later [`Resolve::get`] calls for these objects are served from it.
+    pub fn prefetch<T>(&self, refs: &[Ref<T>]) -> Vec<Result<RcRef<T>>>
+    where
+        T: Object + DataSize + Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        let mut streams: Vec<ObjNr> = refs.iter()
+            .filter(|r| !self.changes.contains_key(&r.get_inner().id))
+            .filter_map(|r| match self.refs.get(r.get_inner().id) {
+                Ok(XRef::Stream { stream_id, .. }) => Some(stream_id),
+                _ => None
+            })
+            .collect();
+        streams.sort_unstable();
+        streams.dedup();
+
+        // errors show up again when the objects in them are resolved
+        streams.par_iter().for_each(|&id| {
+            let _ = StorageResolver::new(self).get::<ObjectStream>(Ref::from_id(id));
+        });
+
+        // every task needs its own resolver to track the references it is following
+        refs.par_iter().map(|&r| StorageResolver::new(self).get(r)).collect()
+    }
+}
+
+#[derive(Debug)]
 pub enum ScanItem {
     Object(PlainRef, Primitive),
     Trailer(Dictionary)
@@ -299,11 +695,18 @@ where
     SC: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
     L: Log
 {
-    fn resolve_flags(&self, r: PlainRef, flags: ParseFlags, _depth: usize) -> Result<Primitive> {
+    fn resolve_flags(&self, mut r: PlainRef, flags: ParseFlags, _depth: usize) -> Result<Primitive> {
         let storage = self.storage;
-        storage.log.load_object(r);
-
-        storage.resolve_ref(r, flags, self)
+        // an object may consist of nothing but a reference to another one
+        let limit = storage.options.max_reference_depth;
+        for _ in 0 .. limit {
+            storage.log.load_object(r);
+            match storage.resolve_ref(r, flags, self)? {
+                Primitive::Reference(next) => r = next,
+                p => return Ok(p),
+            }
+        }
+        Err(PdfError::MaxReferenceDepth { limit })
     }
 
     fn get<T: Object+DataSize>(&self, r: Ref<T>) -> Result<RcRef<T>> {
@@ -316,6 +719,11 @@ where
             if chain.contains(&key) {
                 bail!("Recursive reference");
             }
+            // objects loading other objects recurse, see `ParseOptions::max_reference_depth`
+            let limit = self.storage.options.max_reference_depth;
+            if chain.len() >= limit {
+                return Err(PdfError::MaxReferenceDepth { limit });
+            }
             chain.push(key);
         }
         let _defer = Defer(|| {
@@ -324,7 +732,7 @@ where
         });
         
         let res = self.storage.cache.get_or_compute(key, || {
-            match self.resolve(key).and_then(|p| T::from_primitive(p, self)) {
+            match self.resolve(key).and_then(|p| T::from_primitive(p, self).map_err(|e| e.in_object(key, self.storage.object_offset(key)))) {
                 Ok(obj) => Ok(AnySync::new(Shared::new(obj))),
                 Err(e) => {
                     let p = self.resolve(key);
@@ -380,13 +788,13 @@ where
         use std::collections::hash_map::Entry;
 
         let r = match self.refs.get(old.id)? {
-            XRef::Free { .. } => panic!(),
+            XRef::Free { .. } => err!(PdfError::FreeObject { obj_nr: old.id }),
             XRef::Raw { gen_nr, .. } => PlainRef { id: old.id, gen: gen_nr },
             // For objects in compressed streams (PDF 1.5+), we need to extract them
             // and write them as regular objects. Use generation 0 for these.
             XRef::Stream { .. } => PlainRef { id: old.id, gen: 0 },
             XRef::Promised => PlainRef { id: old.id, gen: 0 },
-            XRef::Invalid => panic!()
+            XRef::Invalid => err!(PdfError::NullRef { obj_nr: old.id })
         };
         let primitive = obj.to_primitive(self)?;
         match self.changes.entry(old.id) {
@@ -412,13 +820,7 @@ where
         
         self.refs.push(XRef::Promised);
         
-        PromisedRef {
-            inner: PlainRef {
-                id,
-                gen: 0
-            },
-            _marker:    PhantomData
-        }
+        PromisedRef::new(PlainRef { id, gen: 0 })
     }
     
     fn fulfill<T: ObjectWrite>(&mut self, promise: PromisedRef<T>, obj: T) -> Result<RcRef<T>> {
@@ -432,48 +834,299 @@ where
     SC: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
     L: Log
 {
+    /// Append an incremental update with all changes to the file.
+    ///
+    /// The cross-reference section and the use of object streams are chosen by the
+    /// [`SaveOptions`]. If the file needs PDF 1.5 features, the update sets the `/Version`
+    /// of the catalog to 1.5. The original bytes, including the header, are kept as they are.
+    ///
+    /// If the file was [repaired](Storage::repaired), the update lists all objects of the
+    /// rebuilt xref table and has no `/Prev`, so readers don't need the damaged sections.
     pub fn save(&mut self, trailer: &mut Trailer) -> Result<&[u8]> {
-        // writing the trailer generates another id for the info dictionary
-        trailer.size = (self.refs.len() + 2) as _;
-        let trailer_dict = trailer.to_dict(self)?;
-        
-        let xref_promise = self.promise::<Stream<XRefInfo>>();
+        let version = self.effective_version(trailer)?;
+        let options = SaveOptions { xref: self.save_options.xref_format(&version)?, ..self.save_options.clone() };
+
+        trailer.prev_trailer_pos = self.prev_xref();
+        let mut trailer_dict = trailer.to_dict(self)?;
+        let mut changes = self.sorted_changes();
+        let raised = if options.xref != XRefFormat::Table && before_1_5(&version) {
+            Some(self.set_version_1_5(trailer, &mut changes)?)
+        } else {
+            None
+        };
 
-        let mut changes: Vec<_> = self.changes.iter().collect();
-        changes.sort_unstable_by_key(|&(id, _)| id);
+        let repaired = self.repaired;
+        write_update(&mut self.backend, 0, self.start_offset, &mut self.refs, changes, &mut trailer_dict, &options, repaired)?;
 
-        for &(&id, &(ref primitive, gen)) in changes.iter() {
-            let pos = self.backend.len();
-            self.refs.set(id, XRef::Raw { pos: pos as _, gen_nr: gen });
-            writeln!(self.backend, "{} {} obj", id, gen)?;
-            primitive.serialize(&mut self.backend)?;
-            writeln!(self.backend, "endobj")?;
+        // a pending change of the catalog is read instead of the file, so it gets the /Version too
+        if let Some((id, catalog)) = raised {
+            if let Some((pending, _)) = self.changes.get_mut(&id) {
+                *pending = catalog;
+            }
         }
 
-        let xref_pos = self.backend.len();
-        self.refs.set(xref_promise.get_inner().id, XRef::Raw { pos: xref_pos, gen_nr: 0 });
-        // only write up to the xref stream obj id
-        let stream = self.refs.write_stream(xref_promise.get_inner().id as usize + 1)?;
+        // update trailer which may have change now.
+        self.cache.clear();
+        *trailer = Trailer::from_dict(trailer_dict, &self.resolver())?;
 
-        writeln!(self.backend, "{} {} obj", xref_promise.get_inner().id, 0)?;
-        let mut xref_and_trailer = stream.to_pdf_stream(&mut NoUpdate)?;
+        Ok(&self.backend)
+    }
+
+    /// Write a complete new file containing only the objects reachable from the trailer.
+    ///
+    /// Pending changes are included, objects are renumbered densely starting at 1
+    /// and objects that lived in object streams are written as regular objects,
+    /// unless the [`SaveOptions`] ask for object streams.
+    /// The original bytes are left untouched.
+    pub fn save_compacted(&mut self, trailer: &Trailer) -> Result<Vec<u8>> {
+        if self.decoder.is_some() {
+            bail!("compacting encrypted documents is not supported");
+        }
+        let mut version = self.effective_version(trailer)?;
+        let options = SaveOptions { xref: self.save_options.xref_format(&version)?, ..self.save_options.clone() };
+        if options.xref != XRefFormat::Table && before_1_5(&version) {
+            version = "PDF-1.5".into();
+        }
+        // the trailer creates objects for its direct members, such as /Info,
+        // which go into the new file but must not become changes of this one
+        let mut scratch = Scratch { next: self.refs.len() as ObjNr, objects: HashMap::new() };
+        let trailer_dict = trailer.to_dict(&mut scratch)?;
+
+        let resolver = self.resolver();
+        let mut numbers = HashMap::new();
+        let mut objects = vec![];
+        let mut queue = VecDeque::new();
+        trailer_dict.values().for_each(|p| p.trace(&mut |r| queue.push_back(r)));
+        while let Some(r) = queue.pop_front() {
+            if numbers.contains_key(&r) {
+                continue;
+            }
+            let primitive = match scratch.objects.get(&r).map_or_else(|| resolver.resolve(r), |p| Ok(p.clone())) {
+                Ok(p) => p,
+                // references to missing objects are treated as null
+                Err(PdfError::FreeObject { .. } | PdfError::NullRef { .. } | PdfError::UnspecifiedXRefEntry { .. }) => continue,
+                Err(e) => return Err(e),
+            };
+            match primitive {
+                // renumber writes /Length directly, so its object is not kept for it
+                Primitive::Stream(ref s) => s.info.iter()
+                    .filter(|&(k, _)| k != "Length")
+                    .for_each(|(_, p)| p.trace(&mut |r| queue.push_back(r))),
+                ref p => p.trace(&mut |r| queue.push_back(r)),
+            }
+            numbers.insert(r, objects.len() as ObjNr + 1);
+            objects.push(primitive);
+        }
+
+        let mut refs = XRefTable::new(0);
+        let objects = objects.into_iter().enumerate()
+            .map(|(i, primitive)| {
+                refs.push(XRef::Invalid);
+                Ok((i as ObjNr + 1, 0, renumber(primitive, &numbers, &resolver)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut new_trailer = Dictionary::new();
         for (k, v) in trailer_dict.iter() {
-            xref_and_trailer.info.insert(k.clone(), v.clone());
+            if k != "Size" && k != "Prev" {
+                new_trailer.insert(k.clone(), renumber(v.clone(), &numbers, &resolver)?);
+            }
         }
 
-        xref_and_trailer.serialize(&mut self.backend)?;
-        writeln!(self.backend, "endobj")?;
+        let mut out = Vec::new();
+        write!(out, "%{}\n%", version)?;
+        out.extend_from_slice(&[0xE2, 0xE3, 0xCF, 0xD3, b'\n']);
+        write_update(&mut out, 0, 0, &mut refs, objects, &mut new_trailer, &options, false)?;
 
-        let _ = self.fulfill(xref_promise, stream)?;
+        Ok(out)
+    }
+}
 
-        write!(self.backend, "\nstartxref\n{}\n%%EOF", xref_pos).unwrap();
+/// An `Updater` that keeps the objects it creates to itself, numbered from `next` on.
+struct Scratch {
+    next: ObjNr,
+    objects: HashMap<PlainRef, Primitive>,
+}
+impl Updater for Scratch {
+    fn create<T: ObjectWrite>(&mut self, obj: T) -> Result<RcRef<T>> {
+        let r = PlainRef { id: self.next, gen: 0 };
+        self.next += 1;
+        let primitive = obj.to_primitive(self)?;
+        self.objects.insert(r, primitive);
+        Ok(RcRef::new(r, Shared::new(obj)))
+    }
+    fn update<T: ObjectWrite>(&mut self, old: PlainRef, _obj: T) -> Result<RcRef<T>> {
+        bail!("can't update object {:?} here", old)
+    }
+    fn promise<T: Object>(&mut self) -> PromisedRef<T> {
+        let r = PlainRef { id: self.next, gen: 0 };
+        self.next += 1;
+        PromisedRef::new(r)
+    }
+    fn fulfill<T: ObjectWrite>(&mut self, promise: PromisedRef<T>, obj: T) -> Result<RcRef<T>> {
+        let r = promise.get_inner();
+        let primitive = obj.to_primitive(self)?;
+        self.objects.insert(r, primitive);
+        Ok(RcRef::new(r, Shared::new(obj)))
+    }
+}
 
-        // update trailer which may have change now.
-        self.cache.clear();
-        *trailer = Trailer::from_dict(trailer_dict, &self.resolver())?;
+/// Number of objects packed into one object stream.
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// Flate compress the data of a new stream without filters, if it is longer than `threshold` bytes.
+fn compress(stream: &mut PdfStream, threshold: usize) -> Result<()> {
+    let data = match stream.inner {
+        StreamInner::Pending { ref data } if data.len() > threshold => data,
+        _ => return Ok(()),
+    };
+    match stream.info.get("Filter") {
+        None | Some(Primitive::Null) => {}
+        Some(Primitive::Array(filters)) if filters.is_empty() => {}
+        Some(_) => return Ok(()),
+    }
+    let encoded = encode(data, &StreamFilter::FlateDecode(Default::default()))?;
+    if encoded.len() < data.len() {
+        stream.info.insert("Filter", Primitive::Name("FlateDecode".into()));
+        stream.info.insert("Length", encoded.len() as i32);
+        stream.inner = StreamInner::Pending { data: encoded.into() };
+    }
+    Ok(())
+}
 
-        Ok(&self.backend)
+/// `version` as returned by `Storage::version`, e.g. `PDF-1.4`.
+fn before_1_5(version: &str) -> bool {
+    version.strip_prefix("PDF-").is_some_and(|v| v < "1.5")
+}
+
+/// Append the objects, sorted by number, followed by the cross-reference section and trailer.
+///
+/// `out` starts at position `base` of the file.
+/// `refs` is updated with the new positions, relative to the header at `start_offset`.
+/// Object streams and the xref stream get new numbers at the end of `refs`,
+/// and the trailer `/Size` is set accordingly.
+/// If `complete`, the cross-reference section lists all entries of `refs`, not only the written ones.
+/// `options.xref` must not be `Auto`.
+#[allow(clippy::too_many_arguments)]
+fn write_update(
+    out: &mut Vec<u8>, base: usize, start_offset: usize, refs: &mut XRefTable, objects: Vec<(ObjNr, GenNr, Primitive)>,
+    trailer: &mut Dictionary, options: &SaveOptions, complete: bool
+) -> Result<()> {
+    let format = options.xref;
+    let pos = |out: &Vec<u8>| base + out.len() - start_offset;
+    // only valid for the section it was read from
+    trailer.remove("XRefStm");
+    let mut packed = vec![];
+    let mut written = vec![0];
+    for (id, gen, mut primitive) in objects {
+        match primitive {
+            Primitive::Stream(ref mut stream) => if let Some(threshold) = options.compress_above {
+                compress(stream, threshold)?;
+            }
+            _ if options.object_streams && gen == 0 => {
+                packed.push((id, primitive));
+                continue;
+            }
+            _ => {}
+        }
+        refs.set(id, XRef::Raw { pos: pos(out), gen_nr: gen });
+        written.push(id);
+        writeln!(out, "{} {} obj", id, gen)?;
+        primitive.serialize(out)?;
+        // numbers and references would run into the keyword
+        writeln!(out, "\nendobj")?;
+    }
+
+    for chunk in packed.chunks(OBJECTS_PER_STREAM) {
+        let stream_id = refs.len() as ObjNr;
+        refs.push(XRef::Raw { pos: pos(out), gen_nr: 0 });
+        written.push(stream_id);
+        let objects: Vec<_> = chunk.iter().map(|(id, p)| (*id, p)).collect();
+        let stream = ObjectStream::new(&objects)?;
+        for (index, &(id, _)) in chunk.iter().enumerate() {
+            refs.set(id, XRef::Stream { stream_id, index });
+        }
+        writeln!(out, "{} 0 obj", stream_id)?;
+        stream.to_primitive(&mut NoUpdate)?.serialize(out)?;
+        writeln!(out, "endobj")?;
+    }
+
+    let mut compressed: Vec<ObjNr> = packed.iter().map(|&(id, _)| id).collect();
+    if complete {
+        let in_stream = |id| matches!(refs.get(id), Ok(XRef::Stream { .. }));
+        compressed = (0 .. refs.len() as ObjNr).filter(|&id| in_stream(id)).collect();
+        written = (0 .. refs.len() as ObjNr).filter(|&id| !in_stream(id)).collect();
+        if format == XRefFormat::Table && !compressed.is_empty() {
+            bail!("the rebuilt xref table has objects in object streams, which need an xref stream");
+        }
+    }
+
+    if format == XRefFormat::Hybrid && !compressed.is_empty() {
+        let id = refs.len() as ObjNr;
+        let stream_pos = pos(out);
+        refs.push(XRef::Raw { pos: stream_pos, gen_nr: 0 });
+        written.push(id);
+        let stream = refs.write_stream_for(&compressed)?;
+
+        writeln!(out, "{} 0 obj", id)?;
+        stream.to_pdf_stream(&mut NoUpdate)?.serialize(out)?;
+        writeln!(out, "endobj")?;
+        trailer.insert("XRefStm", stream_pos as i32);
+    }
+
+    let xref_pos = pos(out);
+    if format == XRefFormat::Stream {
+        let id = refs.len();
+        refs.push(XRef::Raw { pos: xref_pos, gen_nr: 0 });
+        trailer.insert("Size", refs.len() as i32);
+        let stream = refs.write_stream(refs.len())?;
+
+        writeln!(out, "{} 0 obj", id)?;
+        let mut xref_and_trailer = stream.to_pdf_stream(&mut NoUpdate)?;
+        for (k, v) in trailer.iter() {
+            xref_and_trailer.info.insert(k.clone(), v.clone());
+        }
+        xref_and_trailer.serialize(out)?;
+        writeln!(out, "endobj")?;
+    } else {
+        trailer.insert("Size", refs.len() as i32);
+        refs.write_table(&written, out)?;
+        writeln!(out, "trailer")?;
+        Primitive::Dictionary(trailer.clone()).serialize(out)?;
     }
+
+    write!(out, "\nstartxref\n{}\n%%EOF", xref_pos)?;
+    Ok(())
+}
+
+/// Replace every reference with its new number, or null if the object was not kept.
+/// Stream data is copied as it is found in the file, without decoding it.
+fn renumber(p: Primitive, numbers: &HashMap<PlainRef, ObjNr>, resolve: &impl Resolve) -> Result<Primitive> {
+    Ok(match p {
+        Primitive::Reference(r) => match numbers.get(&r) {
+            Some(&id) => Primitive::Reference(PlainRef { id, gen: 0 }),
+            None => Primitive::Null,
+        },
+        Primitive::Array(parts) => Primitive::Array(
+            parts.into_iter().map(|p| renumber(p, numbers, resolve)).collect::<Result<_>>()?
+        ),
+        Primitive::Dictionary(dict) => Primitive::Dictionary(renumber_dict(dict, numbers, resolve)?),
+        Primitive::Stream(stream) => {
+            let data = stream.raw_data(resolve)?;
+            let mut info = renumber_dict(stream.info, numbers, resolve)?;
+            info.insert("Length", data.len() as i32);
+            Primitive::Stream(PdfStream { info, inner: StreamInner::Pending { data } })
+        }
+        p => p,
+    })
+}
+
+fn renumber_dict(dict: Dictionary, numbers: &HashMap<PlainRef, ObjNr>, resolve: &impl Resolve) -> Result<Dictionary> {
+    let mut out = Dictionary::new();
+    for (key, val) in dict.iter() {
+        out.insert(key.clone(), renumber(val.clone(), numbers, resolve)?);
+    }
+    Ok(out)
 }
 
 #[cfg(feature="cache")]
@@ -522,6 +1175,11 @@ where
     pub fn save(&mut self) -> Result<Vec<u8>> {
         Ok(self.storage.save(&mut self.trailer)?.to_vec())
     }
+
+    /// Write a new file with only the reachable objects. See [`Storage::save_compacted`].
+    pub fn save_compacted(&mut self) -> Result<Vec<u8>> {
+        self.storage.save_compacted(&self.trailer)
+    }
 }
 
 
@@ -530,6 +1188,7 @@ pub struct FileOptions<'a, OC, SC, L> {
     sc: SC,
     log: L,
     password: &'a [u8],
+    recipient: Option<&'a Recipient>,
     parse_options: ParseOptions,
 }
 impl FileOptions<'static, NoCache, NoCache, NoLog> {
@@ -538,6 +1197,7 @@ impl FileOptions<'static, NoCache, NoCache, NoLog> {
             oc: NoCache,
             sc: NoCache,
             password: b"",
+            recipient: None,
             parse_options: ParseOptions::strict(),
             log: NoLog,
         }
@@ -551,6 +1211,7 @@ impl FileOptions<'static, ObjectCache, StreamCache, NoLog> {
             oc: SyncCache::new(),
             sc: SyncCache::new(),
             password: b"",
+            recipient: None,
             parse_options: ParseOptions::strict(),
             log: NoLog
         }
@@ -568,22 +1229,31 @@ where
             .. self
         }
     }
+    /// decrypt documents encrypted with the public-key security handler (`/Adobe.PubSec`)
+    pub fn recipient(self, recipient: &'a Recipient) -> FileOptions<'a, OC, SC, L> {
+        FileOptions {
+            recipient: Some(recipient),
+            .. self
+        }
+    }
     pub fn cache<O, S>(self, oc: O, sc: S) -> FileOptions<'a, O, S, L> {
-        let FileOptions { oc: _, sc: _, password, parse_options, log } = self;
+        let FileOptions { oc: _, sc: _, password, recipient, parse_options, log } = self;
         FileOptions {
             oc,
             sc,
             password,
+            recipient,
             parse_options,
             log,
         }
     }
     pub fn log<Log>(self, log: Log) -> FileOptions<'a, OC, SC, Log> {
-        let FileOptions { oc, sc, password, parse_options, .. } = self;
+        let FileOptions { oc, sc, password, recipient, parse_options, .. } = self;
         FileOptions {
             oc,
             sc,
             password,
+            recipient,
             parse_options,
             log,
         }
@@ -604,8 +1274,29 @@ where
 
     /// load data from the given backend
     pub fn load<B: Backend>(self, backend: B) -> Result<File<B, OC, SC, L>> {
-        let FileOptions { oc, sc, password, parse_options, log } = self;
-        File::load_data(backend, password, parse_options, oc, sc, log)
+        let FileOptions { oc, sc, password, recipient, parse_options, log } = self;
+        File::load_data(backend, password, recipient, parse_options, oc, sc, log)
+    }
+
+    /// open the document as it was at an earlier revision of `file`, i.e. without the later updates
+    ///
+    /// The decryption key of `file` is reused, so the password is not needed again.
+    pub fn load_revision<B, O, S, G>(self, file: &File<B, O, S, G>, revision: &Revision) -> Result<File<Vec<u8>, OC, SC, L>>
+    where B: Backend
+    {
+        let data = t!(file.storage.backend.read(.. revision.range.end)).to_vec();
+        let decoder = match file.storage.decoder {
+            Some(ref decoder) => decoder.clone(),
+            None => return self.load(data),
+        };
+
+        let FileOptions { oc, sc, parse_options, log, .. } = self;
+        let mut storage = Storage::with_cache(data, parse_options, oc, sc, log)?;
+        let (refs, trailer) = t!(storage.backend.read_xref_table_and_trailer(storage.start_offset, &StorageResolver::new(&storage)));
+        storage.refs = refs;
+        storage.decoder = Some(decoder);
+        let trailer = t!(Trailer::from_primitive(Primitive::Dictionary(trailer), &StorageResolver::new(&storage)));
+        Ok(File { storage, trailer })
     }
 }
 
@@ -617,15 +1308,25 @@ where
     SC: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
     L: Log,
 {
-    fn load_data(backend: B, password: &[u8], options: ParseOptions, object_cache: OC, stream_cache: SC, log: L) -> Result<Self> {
+    fn load_data(backend: B, password: &[u8], recipient: Option<&Recipient>, options: ParseOptions, object_cache: OC, stream_cache: SC, log: L) -> Result<Self> {
         let mut storage = Storage::with_cache(backend, options, object_cache, stream_cache, log)?;
-        let trailer = storage.load_storage_and_trailer_password(password)?;
+        let trailer = storage.load_storage_and_trailer_with(password, recipient)?;
 
-        let resolver = StorageResolver::new(&storage);
-        let trailer = t!(Trailer::from_primitive(
+        let parsed = Trailer::from_primitive(
             Primitive::Dictionary(trailer),
-            &resolver,
-        ));
+            &StorageResolver::new(&storage),
+        );
+        let trailer = match parsed {
+            Ok(trailer) => trailer,
+            // the xref table was readable but points to the wrong places
+            Err(e) if storage.options.allow_xref_error && !storage.repaired => {
+                warn!("can't read trailer: {:?}", e);
+                let trailer = t!(storage.rebuild_xref_table());
+                storage.set_up_decryption(&trailer, password, recipient)?;
+                t!(Trailer::from_primitive(Primitive::Dictionary(trailer), &StorageResolver::new(&storage)))
+            }
+            Err(e) => return Err(e),
+        };
         Ok(File { storage, trailer })
     }
     pub fn new(storage: Storage<B, OC, SC, L>, trailer: Trailer) -> Self {
@@ -660,6 +1361,28 @@ where
         self.storage.options = options;
     }
 
+    /// true if the file was damaged and its xref table had to be rebuilt, see [`Storage::rebuild_xref_table`]
+    pub fn repaired(&self) -> bool {
+        self.storage.repaired()
+    }
+
+    /// Write the file with an incremental update to `out`, without loading it into memory.
+    /// See [`Storage::save_to_writer`].
+    pub fn save_to_writer(&mut self, mut out: impl Write) -> Result<()> {
+        self.storage.save_to_writer(&mut self.trailer, &mut out)
+    }
+
+    /// The revisions of the file, oldest first, with the objects each of them added or changed.
+    ///
+    /// Use [`FileOptions::load_revision`] to open one of them.
+    pub fn revisions(&self) -> Result<Vec<Revision>> {
+        self.storage.revisions()
+    }
+
+    pub fn set_save_options(&mut self, options: SaveOptions) {
+        self.storage.save_options = options;
+    }
+
     pub fn scan(&self) -> impl Iterator<Item = Result<ScanItem>> + '_ {
         self.storage.scan()
     }
@@ -668,6 +1391,46 @@ where
         &self.storage.log
     }
 
+    /// Resolve `refs` on all cores, see [`Storage::prefetch`].
+    #[cfg(feature = "threads")]
+    pub fn prefetch<T>(&self, refs: &[Ref<T>]) -> Vec<Result<RcRef<T>>>
+    where
+        T: Object + DataSize + Send + Sync,
+        Storage<B, OC, SC, L>: Sync,
+    {
+        self.storage.prefetch(refs)
+    }
+
+    /// Load the annotations of all pages on all cores.
+    ///
+    /// The pages keep them, so later [`Page::annotations`] loads don't parse anything.
+    #[cfg(feature = "threads")]
+    pub fn prefetch_annotations(&self) -> Result<()>
+    where
+        Storage<B, OC, SC, L>: Sync,
+    {
+        use rayon::prelude::*;
+
+        let pages = self.pages().collect::<Result<Vec<_>>>()?;
+        // loading the array loads the annotations it references
+        pages.par_iter().try_for_each(|page| page.annotations.load(&self.resolver()).map(|_| ()))?;
+        Ok(())
+    }
+
+    /// The permissions granted to the current user.
+    ///
+    /// Unencrypted documents, and encrypted documents opened with the owner password,
+    /// grant all permissions. Otherwise they are decoded from `/P` of the encryption dictionary,
+    /// or taken from the recipient envelope for the public-key security handler.
+    pub fn permissions(&self) -> Permissions {
+        self.storage.decoder.as_ref().map(|d| d.permissions()).unwrap_or(Permissions::all())
+    }
+
+    /// true if the document is encrypted and was opened with the owner password
+    pub fn is_owner(&self) -> bool {
+        self.storage.is_owner()
+    }
+
     /// the version string in the file header.
     /// if the version field in the Catalog is set, this should be used instead.
     pub fn version(&self) -> Result<String> {
@@ -694,6 +1457,9 @@ pub struct Trailer {
 
     #[pdf(key = "ID")]
     pub id:                 Vec<PdfString>,
+
+    #[pdf(other)]
+    pub other:              Dictionary,
 }
 
 /*
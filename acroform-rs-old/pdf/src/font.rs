@@ -2,7 +2,7 @@ use crate as pdf;
 use crate::object::*;
 use crate::primitive::*;
 use crate::error::*;
-use crate::encoding::Encoding;
+use crate::encoding::{Encoding, BaseEncoding, unicode_to_glyph};
 use std::collections::HashMap;
 use std::fmt::Write;
 use crate::parser::{Lexer, parse_with_lexer, ParseFlags};
@@ -10,7 +10,11 @@ use std::convert::TryInto;
 use std::sync::Arc;
 use istring::SmallString;
 use datasize::DataSize;
-use itertools::Itertools;
+
+pub mod standard;
+pub use standard::StandardFont;
+pub mod cmap;
+pub use cmap::{CMap, CMapEncoding, Code};
 
 #[allow(non_upper_case_globals, dead_code)]
 mod flags {
@@ -42,6 +46,9 @@ pub struct Font {
     pub name: Option<Name>,
     pub data: FontData,
 
+    /// The encoding of a simple font.
+    /// For Type0 fonts only set if `/Encoding` names a predefined CMap, like `Identity-H`;
+    /// the CMap itself, predefined or embedded, is [`Type0Font::encoding`].
     pub encoding: Option<Encoding>,
 
     // FIXME: Should use RcRef<Stream>
@@ -115,7 +122,14 @@ impl Object for Font {
             })
         };
 
-        let encoding = dict.remove("Encoding").map(|p| Object::from_primitive(p, resolve)).transpose()?;
+        // the encoding of a Type0 font is a CMap, kept by `Type0Font` as well
+        let encoding = match subtype {
+            FontType::Type0 => match dict.get("Encoding") {
+                Some(name @ Primitive::Name(_)) => Some(Encoding::from_primitive(name.clone(), resolve)?),
+                _ => None,
+            },
+            _ => dict.remove("Encoding").map(|p| Object::from_primitive(p, resolve)).transpose()?,
+        };
 
         let to_unicode = match dict.remove("ToUnicode") {
             Some(p) => Some(Object::from_primitive(p, resolve)?),
@@ -153,7 +167,8 @@ impl ObjectWrite for Font {
         if let Some(ref to_unicode) = self.to_unicode {
             dict.insert("ToUnicode", to_unicode.to_primitive(update)?);
         }
-        if let Some(ref encoding) = self.encoding {
+        // Type0 fonts wrote their CMap
+        if let (Some(ref encoding), None) = (&self.encoding, dict.get("Encoding")) {
             dict.insert("Encoding", encoding.to_primitive(update)?);
         }
         if let Some(ref name) = self.name {
@@ -190,6 +205,9 @@ impl Widths {
             self.values.get(cid - self.first_char).cloned().unwrap_or(self.default)
         }
     }
+    pub(crate) fn from_values(first_char: usize, values: Vec<f32>) -> Widths {
+        Widths { default: 0.0, first_char, values }
+    }
     fn new(default: f32) -> Widths {
         Widths {
             default,
@@ -271,7 +289,10 @@ impl Font {
     }
     pub fn widths(&self, resolve: &impl Resolve) -> Result<Option<Widths>> {
         match self.data {
-            FontData::Type0(ref t0) => t0.descendant_fonts[0].widths(resolve),
+            FontData::Type0(ref t0) => match t0.descendant_fonts.first() {
+                Some(font) => font.widths(resolve),
+                None => Ok(None),
+            },
             FontData::Type1(ref info) | FontData::TrueType(ref info) => {
                 match *info {
                     TFont { first_char: Some(first), ref widths, .. } => Ok(Some(Widths {
@@ -279,17 +300,23 @@ impl Font {
                         first_char: first as usize,
                         values: widths.as_ref().cloned().unwrap_or_default()
                     })),
-                    _ => Ok(None)
+                    _ => Ok(self.standard_widths())
                 }
             },
             FontData::CIDFontType0(ref cid) | FontData::CIDFontType2(ref cid) => {
                 let mut widths = Widths::new(cid.default_width);
                 let mut iter = cid.widths.iter();
+                // CIDs are limited to 16 bits
+                let check_cid = |cid: usize| if cid > 0xFFFF {
+                    Err(PdfError::Other { msg: format!("CID {} out of range in W array", cid) })
+                } else {
+                    Ok(cid)
+                };
                 while let Some(p) = iter.next() {
-                    let c1 = p.as_usize()?;
+                    let c1 = check_cid(p.as_usize()?)?;
                     match iter.next() {
                         Some(Primitive::Array(array)) => {
-                            widths.ensure_cid(c1 + array.len() - 1);
+                            widths.ensure_cid(check_cid(c1 + array.len())?);
                             for (i, w) in array.iter().enumerate() {
                                 widths.set(c1 + i, w.as_number()?);
                             }
@@ -297,7 +324,7 @@ impl Font {
                         Some(&Primitive::Reference(r)) => {
                             match resolve.resolve(r)? {
                                 Primitive::Array(array) => {
-                                    widths.ensure_cid(c1 + array.len() - 1);
+                                    widths.ensure_cid(check_cid(c1 + array.len())?);
                                     for (i, w) in array.iter().enumerate() {
                                         widths.set(c1 + i, w.as_number()?);
                                     }
@@ -306,8 +333,9 @@ impl Font {
                             }
                         }
                         Some(&Primitive::Integer(c2)) => {
+                            let c2 = check_cid(c2.max(0) as usize)?;
                             let w = try_opt!(iter.next()).as_number()?;
-                            for c in c1 ..= (c2 as usize) {
+                            for c in c1 ..= c2 {
                                 widths.set(c, w);
                             }
                         },
@@ -319,9 +347,113 @@ impl Font {
             _ => Ok(None)
         }
     }
+    /// The CMap of a composite font, `Identity-H` if it has none. `None` for simple fonts.
+    pub fn cmap(&self, resolve: &impl Resolve) -> Result<Option<CMap>> {
+        match self.data {
+            FontData::Type0(Type0Font { encoding: Some(ref encoding), .. }) => Ok(Some(encoding.load(resolve)?)),
+            _ if self.is_cid() => Ok(Some(CMap::identity(false))),
+            _ => Ok(None)
+        }
+    }
+    /// The codes of the characters of `text` in a composite font, `None` for those it can't show.
+    ///
+    /// Characters are encoded by the CMap if it is a bundled one of a known character set,
+    /// otherwise they are looked up in the `ToUnicode` map.
+    pub fn encode_composite(&self, text: &str, resolve: &impl Resolve) -> Result<Vec<Option<Code>>> {
+        let cmap = self.cmap(resolve)?.unwrap_or_else(|| CMap::identity(false));
+        self.composite_codes(&cmap, text, resolve)
+    }
+    fn composite_codes(&self, cmap: &CMap, text: &str, resolve: &impl Resolve) -> Result<Vec<Option<Code>>> {
+        let to_unicode = self.to_unicode(resolve).transpose()?.unwrap_or_default();
+        let mut codes = HashMap::new();
+        for (code, unicode) in to_unicode.iter() {
+            let mut chars = unicode.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                let entry = codes.entry(c).or_insert(code);
+                *entry = (*entry).min(code);
+            }
+        }
+        Ok(text.chars()
+            .map(|c| cmap.encode(c).or_else(|| codes.get(&c).map(|&code| cmap.code(code as u32))))
+            .collect())
+    }
     pub fn to_unicode(&self, resolve: &impl Resolve) -> Option<Result<ToUnicodeMap>> {
         self.to_unicode.as_ref().map(|s| (**s).data(resolve).and_then(|d| parse_cmap(&d)))
     }
+    /// The built-in metrics, if this is one of the standard 14 fonts (or an alias like `/Helv`).
+    pub fn standard_font(&self) -> Option<&'static StandardFont> {
+        match self.data {
+            FontData::Type1(_) | FontData::TrueType(_) => StandardFont::by_name(self.name.as_ref()?),
+            _ => None
+        }
+    }
+    /// Glyph name of `code`, from the encoding or the built-in encoding of a standard font
+    pub(crate) fn glyph_name(&self, code: u8) -> Option<&str> {
+        let builtin = || match self.standard_font() {
+            Some(font) => font.builtin_glyph(code),
+            None => BaseEncoding::StandardEncoding.glyph_name(code)
+        };
+        match self.encoding {
+            Some(ref encoding) => match encoding.differences.get(&(code as u32)) {
+                Some(name) => Some(name.as_str()),
+                None if matches!(encoding.base, BaseEncoding::None) => builtin(),
+                None => encoding.base.glyph_name(code),
+            }
+            None => builtin()
+        }
+    }
+    /// Widths of a standard font without `/Widths`, by code
+    fn standard_widths(&self) -> Option<Widths> {
+        let font = self.standard_font()?;
+        let values = (0 ..= 255u8)
+            .map(|code| self.glyph_name(code).and_then(|name| font.glyph_width(name)).unwrap_or(0.0))
+            .collect();
+        Some(Widths { default: 0.0, first_char: 0, values })
+    }
+    /// The code of `c` in a simple font, from its encoding or the built-in encoding of a standard font.
+    pub fn encode_char(&self, c: char) -> Option<u8> {
+        let builtin = || match self.standard_font() {
+            Some(font) if font.is_symbolic() => u8::try_from(c as u32).ok().filter(|&code| font.builtin_glyph(code).is_some()),
+            _ => unicode_to_glyph(c).and_then(|name| BaseEncoding::StandardEncoding.code(name))
+        };
+        match self.encoding {
+            Some(ref encoding) => encoding.encode(c).or_else(|| match encoding.base {
+                BaseEncoding::None => builtin(),
+                _ => None
+            }),
+            None => builtin()
+        }
+    }
+    pub(crate) fn missing_width(&self) -> f32 {
+        match self.data {
+            FontData::Type0(ref t0) => t0.descendant_fonts.first().map_or(0.0, |font| font.missing_width()),
+            FontData::CIDFontType0(ref cid) | FontData::CIDFontType2(ref cid) => cid.default_width,
+            FontData::Type1(ref info) | FontData::TrueType(ref info) => info.font_descriptor.as_ref().map_or(0.0, |d| d.missing_width),
+            _ => 0.0
+        }
+    }
+    /// Width of `text` set in this font at `size`, in text space units.
+    ///
+    /// Characters are mapped to codes by the encoding, or as by [`Font::encode_composite`] for composite fonts.
+    /// The widths come from `/Widths`, `/W` or the metrics of the standard 14 fonts.
+    /// Characters the font can't encode, or whose CID is not known, count with its missing width.
+    pub fn text_width(&self, text: &str, size: f32, resolve: &impl Resolve) -> Result<f32> {
+        let widths = self.widths(resolve)?;
+        let missing = self.missing_width();
+        let width_of = |code: Option<usize>| match (code, &widths) {
+            (Some(code), Some(widths)) => widths.get(code),
+            _ => missing
+        };
+
+        let total: f32 = if let Some(cmap) = self.cmap(resolve)? {
+            self.composite_codes(&cmap, text, resolve)?.into_iter()
+                .map(|code| width_of(code.and_then(|code| cmap.cid(code)).map(|cid| cid as usize)))
+                .sum()
+        } else {
+            text.chars().map(|c| width_of(self.encode_char(c).map(usize::from))).sum()
+        };
+        Ok(total * size / 1000.)
+    }
 }
 #[derive(Object, ObjectWrite, Debug, DataSize, DeepClone)]
 pub struct TFont {
@@ -340,7 +472,10 @@ pub struct TFont {
     pub widths: Option<Vec<f32>>,
 
     #[pdf(key="FontDescriptor")]
-    pub font_descriptor: Option<FontDescriptor>
+    pub font_descriptor: Option<FontDescriptor>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, DataSize, DeepClone)]
@@ -348,8 +483,14 @@ pub struct Type0Font {
     #[pdf(key="DescendantFonts")]
     pub descendant_fonts: Vec<MaybeRef<Font>>,
 
+    #[pdf(key="Encoding")]
+    pub encoding: Option<CMapEncoding>,
+
     #[pdf(key="ToUnicode")]
     pub to_unicode: Option<RcRef<Stream<()>>>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, DataSize, DeepClone)]
@@ -438,7 +579,10 @@ pub struct FontDescriptor {
     pub font_file3: Option<RcRef<Stream<FontStream3>>>,
 
     #[pdf(key="CharSet")]
-    pub char_set: Option<PdfString>
+    pub char_set: Option<PdfString>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 impl FontDescriptor {
     pub fn data(&self, resolve: &impl Resolve) -> Option<Result<Arc<[u8]>>> {
@@ -464,7 +608,10 @@ pub enum FontTypeExt {
 #[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
 pub struct FontStream3 {
     #[pdf(key="Subtype")]
-    pub subtype: FontTypeExt
+    pub subtype: FontTypeExt,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, DataSize, DeepClone)]
@@ -633,63 +780,109 @@ fn write_unicode(out: &mut String, unicode: &str) {
     }
     write!(out, ">").unwrap();
 }
+/// Write `map` as a complete ToUnicode CMap for two byte codes
 pub fn write_cmap(map: &ToUnicodeMap) -> String {
-    let mut buf = String::new();
     let mut list: Vec<(u16, &str)> = map.inner.iter().map(|(&cid, s)| (cid, s.as_str())).collect();
     list.sort();
 
-
+    // runs of consecutive codes that only differ in the last byte become ranges
+    let mut singles = vec![];
+    let mut ranges = vec![];
     let mut remaining = &list[..];
-    let blocks = std::iter::from_fn(move || {
-        if remaining.len() == 0 {
-            return None;
-        }
-        let first_cid = remaining[0].0;
-        let seq_len = remaining.iter().enumerate().take_while(|&(i, &(cid, _))| cid == first_cid + i as u16).count();
-        
+    while let Some(&(first_cid, _)) = remaining.first() {
+        let seq_len = remaining.iter().enumerate()
+            .take_while(|&(i, &(cid, _))| cid == first_cid + i as u16 && (i == 0 || cid & 0xFF != 0))
+            .count();
         let (block, tail) = remaining.split_at(seq_len);
         remaining = tail;
-        Some(block)
-    });
-
-    for (single, group) in &blocks.group_by(|b| b.len() == 1) {
-        if single {
-            writeln!(buf, "beginbfchar").unwrap();
-            for block in group {
-                for &(cid, uni) in block {
-                    write_cid(&mut buf, cid);
+        if seq_len == 1 {
+            singles.push(block[0]);
+        } else {
+            ranges.push(block);
+        }
+    }
+
+    let mut buf = String::new();
+    writeln!(buf, "/CIDInit /ProcSet findresource begin").unwrap();
+    writeln!(buf, "12 dict begin").unwrap();
+    writeln!(buf, "begincmap").unwrap();
+    writeln!(buf, "/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def").unwrap();
+    writeln!(buf, "/CMapName /Adobe-Identity-UCS def").unwrap();
+    writeln!(buf, "/CMapType 2 def").unwrap();
+    writeln!(buf, "1 begincodespacerange").unwrap();
+    writeln!(buf, "<0000> <FFFF>").unwrap();
+    writeln!(buf, "endcodespacerange").unwrap();
+
+    // at most 100 entries per section
+    for chunk in singles.chunks(100) {
+        writeln!(buf, "{} beginbfchar", chunk.len()).unwrap();
+        for &(cid, uni) in chunk {
+            write_cid(&mut buf, cid);
+            write!(buf, " ").unwrap();
+            write_unicode(&mut buf, uni);
+            writeln!(buf).unwrap();
+        }
+        writeln!(buf, "endbfchar").unwrap();
+    }
+    for chunk in ranges.chunks(100) {
+        writeln!(buf, "{} beginbfrange", chunk.len()).unwrap();
+        for block in chunk {
+            write_cid(&mut buf, block[0].0);
+            write!(buf, " ").unwrap();
+            write_cid(&mut buf, block.last().unwrap().0);
+            write!(buf, " [").unwrap();
+            for (i, &(_cid, u)) in block.iter().enumerate() {
+                if i > 0 {
                     write!(buf, " ").unwrap();
-                    write_unicode(&mut buf, uni);
-                    writeln!(buf).unwrap();
                 }
+                write_unicode(&mut buf, u);
             }
-            writeln!(buf, "endbfchar").unwrap();
-        } else {
-            writeln!(buf, "beginbfrange").unwrap();
-            for block in group {
-                write_cid(&mut buf, block[0].0);
-                write!(buf, " ").unwrap();
-                write_cid(&mut buf, block.last().unwrap().0);
-                write!(buf, " [").unwrap();
-                for (i, &(_cid, u)) in block.iter().enumerate() {
-                    if i > 0 {
-                        write!(buf, ", ").unwrap();
-                    }
-                    write_unicode(&mut buf, u);
-                }
-                writeln!(buf, "]").unwrap();
-            }    
-            writeln!(buf, "endbfrange").unwrap();
+            writeln!(buf, "]").unwrap();
         }
+        writeln!(buf, "endbfrange").unwrap();
     }
 
+    writeln!(buf, "endcmap").unwrap();
+    writeln!(buf, "CMapName currentdict /CMapResource defineresource pop").unwrap();
+    writeln!(buf, "end").unwrap();
+    writeln!(buf, "end").unwrap();
     buf
 }
 
 #[cfg(test)]
 mod tests {
 
+    use crate::font::*;
     use crate::font::{utf16be_to_string, utf16be_to_char, utf16be_to_string_lossy};
+    #[test]
+    fn write_cmap_round_trip() {
+        let mut map = ToUnicodeMap::new();
+        for (cid, c) in [(3, "a"), (4, "b"), (5, "c"), (0xFF, "x"), (0x100, "y"), (0x2000, "ffi")] {
+            map.insert(cid, c.into());
+        }
+        let parsed = parse_cmap(write_cmap(&map).as_bytes()).unwrap();
+        let mut entries: Vec<_> = parsed.iter().map(|(cid, s)| (cid, s.to_string())).collect();
+        entries.sort();
+        assert_eq!(entries, [(3, "a".into()), (4, "b".into()), (5, "c".into()), (0xFF, "x".into()), (0x100, "y".into()), (0x2000, "ffi".to_string())]);
+    }
+
+    #[test]
+    fn type0_encoding() {
+        use crate::encoding::BaseEncoding;
+        use crate::parser::{parse, ParseFlags};
+
+        let font = |encoding: &str| {
+            let dict = format!("<< /Type /Font /Subtype /Type0 /BaseFont /X /Encoding {} /DescendantFonts [] >>", encoding);
+            Font::from_primitive(parse(dict.as_bytes(), &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap()
+        };
+        let identity = font("/Identity-H");
+        assert_eq!(identity.encoding().map(|e| &e.base), Some(&BaseEncoding::IdentityH));
+        assert!(matches!(identity.data, FontData::Type0(Type0Font { encoding: Some(CMapEncoding::Predefined(_)), .. })));
+
+        let other = font("/UniJIS-UCS2-H");
+        assert_eq!(other.encoding().map(|e| &e.base), Some(&BaseEncoding::Other("UniJIS-UCS2-H".into())));
+    }
+
     #[test]
     fn utf16be_to_string_quick() {
         let v = vec![0x20, 0x09];
@@ -736,4 +929,98 @@ mod tests {
         assert_eq!(utf16be_to_string(&v[..8]).unwrap(), String::from("𝄞mu"));
         assert_eq!(utf16be_to_string_lossy(&v), lossy);
     }
+
+    fn simple_font(name: &str, encoding: Option<Encoding>) -> Font {
+        Font {
+            subtype: FontType::Type1,
+            name: Some(name.into()),
+            data: FontData::Type1(TFont {
+                base_font: Some(name.into()),
+                first_char: None,
+                last_char: None,
+                widths: None,
+                font_descriptor: None,
+                other: Dictionary::new(),
+            }),
+            encoding,
+            to_unicode: None,
+            _other: Dictionary::new(),
+        }
+    }
+
+    #[test]
+    fn standard_font_widths() {
+        let helv = simple_font("Helv", Some(Encoding { base: BaseEncoding::WinAnsiEncoding, differences: HashMap::new() }));
+        // H e l l o = 722 + 556 + 222 + 222 + 556
+        assert_eq!(helv.text_width("Hello", 10., &NoResolve).unwrap(), 22.78);
+        // WinAnsi: é and € from the Latin set
+        assert_eq!(helv.text_width("é€", 1000., &NoResolve).unwrap(), 556. + 556.);
+        let widths = helv.widths(&NoResolve).unwrap().unwrap();
+        assert_eq!(widths.get(b'W' as usize), 944.);
+
+        // StandardEncoding by default, where /Differences win
+        let mut differences = HashMap::new();
+        differences.insert(b'a' as u32, "W".into());
+        let times = simple_font("Times-Bold", Some(Encoding { base: BaseEncoding::None, differences }));
+        assert_eq!(times.widths(&NoResolve).unwrap().unwrap().get(b'a' as usize), 1000.);
+        assert_eq!(times.text_width("W", 1., &NoResolve).unwrap(), 1.);
+        assert_eq!(times.text_width("b", 1000., &NoResolve).unwrap(), 556.);
+
+        let courier = simple_font("ABCDEF+Courier", None);
+        assert_eq!(courier.text_width("iW ß", 10., &NoResolve).unwrap(), 24.);
+
+        // the check mark of checkboxes
+        let zadb = simple_font("ZaDb", None);
+        assert_eq!(zadb.text_width("4", 1000., &NoResolve).unwrap(), 846.);
+        assert!(zadb.standard_font().unwrap().is_symbolic());
+
+        let unknown = simple_font("Frutiger", None);
+        assert!(unknown.widths(&NoResolve).unwrap().is_none());
+    }
+
+    #[test]
+    fn cid_font_widths() {
+        let cid = Font {
+            subtype: FontType::CIDFontType2,
+            name: Some("NotoSans".into()),
+            data: FontData::CIDFontType2(CIDFont {
+                system_info: Dictionary::new(),
+                font_descriptor: FontDescriptor {
+                    font_name: "NotoSans".into(),
+                    font_family: None,
+                    font_stretch: None,
+                    font_weight: None,
+                    flags: 0,
+                    font_bbox: Rectangle { left: 0., bottom: 0., right: 1000., top: 1000. },
+                    italic_angle: 0.,
+                    ascent: None,
+                    descent: None,
+                    leading: 0.,
+                    cap_height: None,
+                    xheight: 0.,
+                    stem_v: 0.,
+                    stem_h: 0.,
+                    avg_width: 0.,
+                    max_width: 0.,
+                    missing_width: 0.,
+                    font_file: None,
+                    font_file2: None,
+                    font_file3: None,
+                    char_set: None,
+                    other: Dictionary::new(),
+                },
+                default_width: 1000.,
+                widths: vec![Primitive::Integer(3), Primitive::Array(vec![Primitive::Integer(500), Primitive::Integer(600)])],
+                cid_to_gid_map: None,
+                _other: Dictionary::new(),
+            }),
+            encoding: None,
+            to_unicode: None,
+            _other: Dictionary::new(),
+        };
+        // without a ToUnicode map every character has the default width
+        assert_eq!(cid.text_width("ab", 1., &NoResolve).unwrap(), 2.);
+        let widths = cid.widths(&NoResolve).unwrap().unwrap();
+        assert_eq!(widths.get(4), 600.);
+    }
 }
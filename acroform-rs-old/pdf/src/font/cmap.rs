@@ -0,0 +1,681 @@
+//! CMaps, the encodings of composite fonts
+//!
+//! A CMap splits the string of a text showing operator into codes of one to four bytes,
+//! as given by its code space ranges, and maps them to CIDs. The `/Encoding` of a Type0
+//! font names a predefined CMap or is a stream holding one.
+//!
+//! The bundled predefined CMaps are `Identity-H` and `Identity-V` and the common Adobe CMaps
+//! of the Chinese, Japanese and Korean character collections. Of the latter only the code
+//! space ranges and the character set of the codes are bundled, not their CIDs: codes are
+//! mapped to Unicode and back without the CIDs, and widths fall back to the default width.
+
+use std::collections::BTreeMap;
+
+use datasize::DataSize;
+
+use crate as pdf;
+use crate::error::*;
+use crate::object::*;
+use crate::parser::{Lexer, parse_with_lexer, ParseFlags};
+use crate::primitive::{Dictionary, Name, Primitive};
+
+/// `/UseCMap` streams nested deeper than this are ignored
+const MAX_USECMAP_DEPTH: usize = 8;
+
+/// A character code of a composite font
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Code {
+    /// The bytes of the code read as a big endian number
+    pub value: u32,
+    /// Number of bytes, 1 to 4
+    pub len: u8,
+}
+impl Code {
+    fn from_bytes(bytes: &[u8]) -> Code {
+        Code {
+            value: bytes.iter().fold(0, |value, &b| value << 8 | b as u32),
+            len: bytes.len() as u8,
+        }
+    }
+    /// The bytes of the code as they appear in a string
+    pub fn bytes(self) -> impl Iterator<Item = u8> {
+        (0 .. self.len).rev().map(move |i| (self.value >> (8 * i as u32)) as u8)
+    }
+}
+
+/// Codes of `len` bytes, each between the bytes of `low` and `high` at the same position
+#[derive(Debug, Clone, Copy)]
+struct CodespaceRange {
+    len: u8,
+    low: u32,
+    high: u32,
+}
+impl CodespaceRange {
+    fn byte_range(&self, i: u8) -> (u8, u8) {
+        let shift = 8 * (self.len - 1 - i) as u32;
+        ((self.low >> shift) as u8, (self.high >> shift) as u8)
+    }
+    fn contains(&self, bytes: &[u8]) -> bool {
+        bytes.len() == self.len as usize && bytes.iter().enumerate().all(|(i, &b)| {
+            let (low, high) = self.byte_range(i as u8);
+            low <= b && b <= high
+        })
+    }
+}
+
+/// What the codes of a bundled CMap are in Unicode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Utf16,
+    Utf8,
+    Utf32,
+    Gbk,
+    Gb18030,
+    Big5,
+    ShiftJis,
+    EucJp,
+    /// Row and cell of JIS X 0208, EUC-JP without the high bits
+    Jis,
+    EucKr,
+}
+impl Charset {
+    fn legacy(self) -> Option<&'static encoding_rs::Encoding> {
+        match self {
+            Charset::Gbk => Some(encoding_rs::GBK),
+            Charset::Gb18030 => Some(encoding_rs::GB18030),
+            Charset::Big5 => Some(encoding_rs::BIG5),
+            Charset::ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            Charset::EucJp | Charset::Jis => Some(encoding_rs::EUC_JP),
+            Charset::EucKr => Some(encoding_rs::EUC_KR),
+            Charset::Utf16 | Charset::Utf8 | Charset::Utf32 => None,
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Charset::Utf16 => crate::font::utf16be_to_string(bytes).ok().map(|s| s.to_string()),
+            Charset::Utf8 => std::str::from_utf8(bytes).ok().map(String::from),
+            Charset::Utf32 => {
+                let value = bytes.try_into().ok().map(u32::from_be_bytes)?;
+                char::from_u32(value).map(String::from)
+            }
+            Charset::Jis => {
+                let euc: Vec<u8> = bytes.iter().map(|b| b | 0x80).collect();
+                Charset::EucJp.decode(&euc)
+            }
+            _ => {
+                let decoded = self.legacy()?.decode_without_bom_handling_and_without_replacement(bytes)?;
+                Some(decoded.into_owned())
+            }
+        }
+    }
+
+    fn encode(self, c: char) -> Option<Vec<u8>> {
+        match self {
+            Charset::Utf16 => {
+                let mut buf = [0; 2];
+                Some(c.encode_utf16(&mut buf).iter().flat_map(|w| w.to_be_bytes()).collect())
+            }
+            Charset::Utf8 => Some(c.to_string().into_bytes()),
+            Charset::Utf32 => Some((c as u32).to_be_bytes().to_vec()),
+            Charset::Jis => {
+                let euc = Charset::EucJp.encode(c)?;
+                // JIS X 0208 only, no ASCII, half width katakana or JIS X 0212
+                (euc.len() == 2 && euc[0] != 0x8E).then(|| euc.iter().map(|b| b & 0x7F).collect())
+            }
+            _ => {
+                let mut buf = [0; 4];
+                let (bytes, _, unmappable) = self.legacy()?.encode(c.encode_utf8(&mut buf));
+                (!unmappable).then(|| bytes.into_owned())
+            }
+        }
+    }
+}
+
+/// A CMap, from the codes of a composite font to CIDs
+#[derive(Debug, Clone, Default)]
+pub struct CMap {
+    /// `/CMapName`
+    pub name: Option<String>,
+    /// Writing mode 1, glyphs are set from top to bottom
+    pub vertical: bool,
+    codespace: Vec<CodespaceRange>,
+    /// CID ranges by the length and first code: last code and CID of the first code.
+    /// The ranges don't overlap, see [`insert_range`].
+    cids: BTreeMap<(u8, u32), (u32, u32)>,
+    /// Undefined ranges by the length and first code: last code and CID of all codes
+    notdef: BTreeMap<(u8, u32), (u32, u32)>,
+    /// Character set of the codes, known for bundled CMaps
+    charset: Option<Charset>,
+}
+
+type Ranges = &'static [(u8, u32, u32)];
+
+const UCS2: Ranges = &[(2, 0x0000, 0xFFFF)];
+const UTF16: Ranges = &[(2, 0x0000, 0xD7FF), (4, 0xD800_DC00, 0xDBFF_DFFF), (2, 0xE000, 0xFFFF)];
+const UTF8: Ranges = &[(1, 0x00, 0x7F), (2, 0xC280, 0xDFBF), (3, 0xE0_8080, 0xEF_BFBF), (4, 0xF080_8080, 0xF7BF_BFBF)];
+const UTF32: Ranges = &[(4, 0x0000_0000, 0x0010_FFFF)];
+const EUC_CN: Ranges = &[(1, 0x00, 0x80), (2, 0xA1A1, 0xFEFE)];
+const GBK: Ranges = &[(1, 0x00, 0x80), (2, 0x8140, 0xFEFE)];
+const GBK2K: Ranges = &[(1, 0x00, 0x80), (2, 0x8140, 0xFEFE), (4, 0x8130_8130, 0xFE39_FE39)];
+const BIG5: Ranges = &[(1, 0x00, 0x80), (2, 0xA140, 0xFEFE)];
+const HKSCS: Ranges = &[(1, 0x00, 0x80), (2, 0x8740, 0xFEFE)];
+const EUC_TW: Ranges = &[(1, 0x00, 0x80), (2, 0xA1A1, 0xFEFE), (4, 0x8EA1_A1A1, 0x8EB0_FEFE)];
+const RKSJ: Ranges = &[(1, 0x00, 0x80), (2, 0x8140, 0x9FFC), (1, 0xA0, 0xDF), (2, 0xE040, 0xFCFC)];
+const EUC_JP: Ranges = &[(1, 0x00, 0x80), (2, 0x8EA0, 0x8EDF), (3, 0x8F_A1A1, 0x8F_FEFE), (2, 0xA1A1, 0xFEFE)];
+const JIS: Ranges = &[(2, 0x2121, 0x7E7E)];
+const EUC_KR: Ranges = &[(1, 0x00, 0x80), (2, 0xA1A1, 0xFEFE)];
+const UHC: Ranges = &[(1, 0x00, 0x80), (2, 0x8141, 0xFEFE)];
+
+/// The bundled CMaps by their horizontal name, each also comes as `-V`
+static PREDEFINED: &[(&str, Ranges, Option<Charset>)] = &[
+    // Adobe-GB1
+    ("GB-EUC-H", EUC_CN, Some(Charset::Gbk)),
+    ("GBpc-EUC-H", EUC_CN, Some(Charset::Gbk)),
+    ("GBK-EUC-H", GBK, Some(Charset::Gbk)),
+    ("GBKp-EUC-H", GBK, Some(Charset::Gbk)),
+    ("GBK2K-H", GBK2K, Some(Charset::Gb18030)),
+    ("UniGB-UCS2-H", UCS2, Some(Charset::Utf16)),
+    ("UniGB-UTF8-H", UTF8, Some(Charset::Utf8)),
+    ("UniGB-UTF16-H", UTF16, Some(Charset::Utf16)),
+    ("UniGB-UTF32-H", UTF32, Some(Charset::Utf32)),
+    // Adobe-CNS1
+    ("B5pc-H", BIG5, Some(Charset::Big5)),
+    ("ETen-B5-H", BIG5, Some(Charset::Big5)),
+    ("ETenms-B5-H", BIG5, Some(Charset::Big5)),
+    ("HKscs-B5-H", HKSCS, Some(Charset::Big5)),
+    ("CNS-EUC-H", EUC_TW, None),
+    ("UniCNS-UCS2-H", UCS2, Some(Charset::Utf16)),
+    ("UniCNS-UTF8-H", UTF8, Some(Charset::Utf8)),
+    ("UniCNS-UTF16-H", UTF16, Some(Charset::Utf16)),
+    ("UniCNS-UTF32-H", UTF32, Some(Charset::Utf32)),
+    // Adobe-Japan1
+    ("83pv-RKSJ-H", RKSJ, Some(Charset::ShiftJis)),
+    ("90ms-RKSJ-H", RKSJ, Some(Charset::ShiftJis)),
+    ("90msp-RKSJ-H", RKSJ, Some(Charset::ShiftJis)),
+    ("90pv-RKSJ-H", RKSJ, Some(Charset::ShiftJis)),
+    ("Add-RKSJ-H", RKSJ, Some(Charset::ShiftJis)),
+    ("Ext-RKSJ-H", RKSJ, Some(Charset::ShiftJis)),
+    ("EUC-H", EUC_JP, Some(Charset::EucJp)),
+    ("H", JIS, Some(Charset::Jis)),
+    ("UniJIS-UCS2-H", UCS2, Some(Charset::Utf16)),
+    ("UniJIS-UCS2-HW-H", UCS2, Some(Charset::Utf16)),
+    ("UniJIS-UTF8-H", UTF8, Some(Charset::Utf8)),
+    ("UniJIS-UTF16-H", UTF16, Some(Charset::Utf16)),
+    ("UniJIS-UTF32-H", UTF32, Some(Charset::Utf32)),
+    // Adobe-Korea1
+    ("KSC-EUC-H", EUC_KR, Some(Charset::EucKr)),
+    ("KSCpc-EUC-H", EUC_KR, Some(Charset::EucKr)),
+    ("KSCms-UHC-H", UHC, Some(Charset::EucKr)),
+    ("KSCms-UHC-HW-H", UHC, Some(Charset::EucKr)),
+    ("UniKS-UCS2-H", UCS2, Some(Charset::Utf16)),
+    ("UniKS-UTF8-H", UTF8, Some(Charset::Utf8)),
+    ("UniKS-UTF16-H", UTF16, Some(Charset::Utf16)),
+    ("UniKS-UTF32-H", UTF32, Some(Charset::Utf32)),
+];
+
+impl CMap {
+    /// `Identity-H`, or `Identity-V` if `vertical`: two byte codes equal to their CIDs
+    pub fn identity(vertical: bool) -> CMap {
+        let mut cmap = CMap {
+            name: Some(if vertical { "Identity-V" } else { "Identity-H" }.into()),
+            vertical,
+            ..CMap::default()
+        };
+        cmap.codespace.push(CodespaceRange { len: 2, low: 0, high: 0xFFFF });
+        cmap.cids.insert((2, 0), (0xFFFF, 0));
+        cmap
+    }
+
+    /// The bundled CMap called `name`, see the [module documentation](self)
+    pub fn predefined(name: &str) -> Option<CMap> {
+        let (horizontal, vertical) = match name {
+            "V" => ("H".to_string(), true),
+            _ => match name.strip_suffix("-V") {
+                Some(base) => (format!("{}-H", base), true),
+                None => (name.to_string(), false),
+            },
+        };
+        if horizontal == "Identity-H" {
+            return Some(CMap::identity(vertical));
+        }
+        let &(_, ranges, charset) = PREDEFINED.iter().find(|&&(n, _, _)| n == horizontal)?;
+        Some(CMap {
+            name: Some(name.into()),
+            vertical,
+            codespace: ranges.iter().map(|&(len, low, high)| CodespaceRange { len, low, high }).collect(),
+            charset,
+            ..CMap::default()
+        })
+    }
+
+    /// Parse the data of an embedded CMap stream
+    ///
+    /// A CMap it uses with `usecmap` has to be a bundled one.
+    pub fn parse(data: &[u8]) -> Result<CMap> {
+        let mut cmap = CMap::default();
+        cmap.parse_into(data)?;
+        Ok(cmap)
+    }
+
+    /// Add the definitions of `data` to this CMap, replacing those of the same codes
+    fn parse_into(&mut self, data: &[u8]) -> Result<()> {
+        let mut lexer = Lexer::new(data);
+        // operands of the next operator
+        let mut operands: Vec<Primitive> = vec![];
+        while let Ok(token) = lexer.peek() {
+            let keyword = match token.as_slice() {
+                [] => break,
+                [c, ..] if c.is_ascii_alphabetic() && !matches!(token.as_slice(), b"true" | b"false" | b"null") => {
+                    lexer.next()?;
+                    token.as_slice()
+                }
+                _ => {
+                    match parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::ANY) {
+                        Ok(p) => operands.push(p),
+                        Err(_) => {
+                            lexer.next()?;
+                        }
+                    }
+                    continue;
+                }
+            };
+            match keyword {
+                b"begincodespacerange" => {
+                    for (low, high) in t!(read_pairs(&mut lexer, b"endcodespacerange")) {
+                        let (low, high) = (Code::from_bytes(&low), Code::from_bytes(&high));
+                        self.codespace.push(CodespaceRange { len: low.len, low: low.value, high: high.value });
+                    }
+                }
+                b"begincidrange" | b"beginnotdefrange" => {
+                    let end: &[u8] = if keyword == b"begincidrange" { b"endcidrange" } else { b"endnotdefrange" };
+                    let map = if keyword == b"begincidrange" { &mut self.cids } else { &mut self.notdef };
+                    while let Some((low, high)) = t!(read_range(&mut lexer, end)) {
+                        let cid = t!(parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::INTEGER)).as_u32()?;
+                        let (low, high) = (Code::from_bytes(&low), Code::from_bytes(&high));
+                        insert_range(map, low.len, low.value, high.value, cid, keyword == b"begincidrange");
+                    }
+                }
+                b"begincidchar" | b"beginnotdefchar" => {
+                    let end: &[u8] = if keyword == b"begincidchar" { b"endcidchar" } else { b"endnotdefchar" };
+                    let map = if keyword == b"begincidchar" { &mut self.cids } else { &mut self.notdef };
+                    while !t!(at_end(&mut lexer, end)) {
+                        let code = t!(parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::STRING)).into_string()?;
+                        if !(1 ..= 4).contains(&code.as_bytes().len()) {
+                            bail!("invalid code {:?}", code.as_bytes());
+                        }
+                        let code = Code::from_bytes(code.as_bytes());
+                        let cid = t!(parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::INTEGER)).as_u32()?;
+                        insert_range(map, code.len, code.value, code.value, cid, keyword == b"begincidchar");
+                    }
+                }
+                b"usecmap" => match operands.last() {
+                    Some(Primitive::Name(name)) => match CMap::predefined(name) {
+                        Some(parent) => self.use_cmap(parent),
+                        None => warn!("CMap uses unknown CMap {}", name),
+                    },
+                    _ => bail!("usecmap without a CMap name"),
+                },
+                b"def" => match operands[..] {
+                    [.., Primitive::Name(ref key), ref value] if key == "CMapName" => {
+                        self.name = value.as_name().ok().map(String::from);
+                    }
+                    [.., Primitive::Name(ref key), ref value] if key == "WMode" => {
+                        self.vertical = value.as_integer()? == 1;
+                    }
+                    _ => {}
+                },
+                b"endcmap" => break,
+                _ => {}
+            }
+            operands.clear();
+        }
+        Ok(())
+    }
+
+    /// Take the definitions of `parent`, those made before take precedence
+    fn use_cmap(&mut self, parent: CMap) {
+        let CMap { codespace, cids, notdef, charset, vertical, .. } = parent;
+        self.codespace.extend(codespace);
+        let own = std::mem::replace(&mut self.cids, cids);
+        for ((len, low), (high, cid)) in own {
+            insert_range(&mut self.cids, len, low, high, cid, true);
+        }
+        let own = std::mem::replace(&mut self.notdef, notdef);
+        for ((len, low), (high, cid)) in own {
+            insert_range(&mut self.notdef, len, low, high, cid, false);
+        }
+        self.charset = self.charset.or(charset);
+        self.vertical |= vertical;
+    }
+
+    /// Split `bytes` into codes
+    ///
+    /// A code is the shortest run of bytes within a code space range. Bytes that start no
+    /// code take as many bytes as the shortest range their first byte fits, or one.
+    pub fn codes<'a>(&'a self, mut bytes: &'a [u8]) -> impl Iterator<Item = Code> + 'a {
+        std::iter::from_fn(move || {
+            if bytes.is_empty() {
+                return None;
+            }
+            let len = self.code_len(bytes);
+            let (code, rest) = bytes.split_at(len);
+            bytes = rest;
+            Some(Code::from_bytes(code))
+        })
+    }
+
+    fn code_len(&self, bytes: &[u8]) -> usize {
+        if self.codespace.is_empty() {
+            return bytes.len().min(2);
+        }
+        let max = bytes.len().min(4);
+        if let Some(len) = (1 ..= max).find(|&len| self.codespace.iter().any(|r| r.contains(&bytes[.. len]))) {
+            return len;
+        }
+        let partial = self.codespace.iter()
+            .filter(|r| {
+                let (low, high) = r.byte_range(0);
+                low <= bytes[0] && bytes[0] <= high
+            })
+            .map(|r| r.len as usize)
+            .min();
+        partial.unwrap_or(1).min(max)
+    }
+
+    /// The code of a `ToUnicode` map entry, with the length of the code space range it is in
+    ///
+    /// Two bytes if it is in none, as `ToUnicode` maps are written for two byte codes.
+    pub fn code(&self, value: u32) -> Code {
+        (1 ..= 4u8)
+            .map(|len| Code { value, len })
+            .filter(|code| code.len == 4 || code.value >> (8 * code.len as u32) == 0)
+            .find(|code| self.codespace.iter().any(|r| r.contains(&code.bytes().collect::<Vec<u8>>())))
+            .unwrap_or(Code { value, len: 2 })
+    }
+
+    /// The CID of `code`, `None` if it is not defined
+    ///
+    /// The bundled CMaps other than `Identity-H` and `Identity-V` define no CIDs.
+    pub fn cid(&self, code: Code) -> Option<u32> {
+        let range = |map: &BTreeMap<(u8, u32), (u32, u32)>| {
+            let (&(len, low), &(high, cid)) = map.range(..= (code.len, code.value)).next_back()?;
+            (len == code.len && code.value <= high).then_some((low, cid))
+        };
+        match range(&self.cids) {
+            Some((low, cid)) => cid.checked_add(code.value - low),
+            None => range(&self.notdef).map(|(_, cid)| cid),
+        }
+    }
+
+    /// The text of `code`, for bundled CMaps of a known character set
+    pub fn unicode(&self, code: Code) -> Option<String> {
+        self.charset?.decode(&code.bytes().collect::<Vec<u8>>())
+    }
+
+    /// The code of `c`, for bundled CMaps of a known character set
+    pub fn encode(&self, c: char) -> Option<Code> {
+        let bytes = self.charset?.encode(c)?;
+        self.codespace.iter().any(|r| r.contains(&bytes)).then(|| Code::from_bytes(&bytes))
+    }
+}
+
+/// Map the codes `low ..= high` of length `len` to `cid`, replacing what they were mapped to
+///
+/// Ranges that overlap are cut back to the codes outside, so every code is in at most one
+/// range and a lookup only has to check the range with the closest start.
+/// If `shift`, the CID grows with the code, as in `cidrange`, otherwise it is the same for all.
+fn insert_range(map: &mut BTreeMap<(u8, u32), (u32, u32)>, len: u8, low: u32, high: u32, cid: u32, shift: bool) {
+    let before = map.range(..= (len, low)).next_back()
+        .filter(|&(&(l, _), &(h, _))| l == len && h >= low)
+        .map(|(&key, &value)| (key, value));
+    let inside: Vec<_> = map.range((len, low) ..= (len, high))
+        .map(|(&key, &value)| (key, value))
+        .collect();
+    for ((_, l), (h, c)) in before.into_iter().chain(inside) {
+        map.remove(&(len, l));
+        if l < low {
+            map.insert((len, l), (low - 1, c));
+        }
+        if h > high {
+            let c = if shift { c.checked_add(high + 1 - l) } else { Some(c) };
+            if let Some(c) = c {
+                map.insert((len, high + 1), (h, c));
+            }
+        }
+    }
+    map.insert((len, low), (high, cid));
+}
+
+/// Whether the next token is `end`, which is then consumed, or the data ends
+fn at_end(lexer: &mut Lexer, end: &[u8]) -> Result<bool> {
+    let token = lexer.peek()?;
+    if token.equals(end) {
+        lexer.next()?;
+    }
+    Ok(token.equals(end) || token.as_slice().is_empty())
+}
+
+/// The next pair of codes before `end`, of the same length of one to four bytes
+fn read_range(lexer: &mut Lexer, end: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    if at_end(lexer, end)? {
+        return Ok(None);
+    }
+    let low = parse_with_lexer(lexer, &NoResolve, ParseFlags::STRING)?.into_string()?.as_bytes().to_vec();
+    let high = parse_with_lexer(lexer, &NoResolve, ParseFlags::STRING)?.into_string()?.as_bytes().to_vec();
+    if low.len() != high.len() || !(1 ..= 4).contains(&low.len()) {
+        bail!("invalid range of codes {:?} {:?}", low, high);
+    }
+    Ok(Some((low, high)))
+}
+
+/// All pairs of codes up to `end`
+fn read_pairs(lexer: &mut Lexer, end: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut pairs = vec![];
+    while let Some(pair) = read_range(lexer, end)? {
+        pairs.push(pair);
+    }
+    Ok(pairs)
+}
+
+/// The `/Encoding` of a Type0 font
+#[derive(Debug, Clone, DataSize, DeepClone)]
+pub enum CMapEncoding {
+    /// A predefined CMap like `Identity-H` or `UniJIS-UCS2-H`
+    Predefined(Name),
+    /// An embedded CMap stream
+    Embedded(RcRef<Stream<Dictionary>>),
+}
+impl Object for CMapEncoding {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p {
+            Primitive::Name(name) => Ok(CMapEncoding::Predefined(Name(name))),
+            p @ Primitive::Reference(_) => Ok(CMapEncoding::Embedded(t!(RcRef::from_primitive(p, resolve)))),
+            p => Err(PdfError::UnexpectedPrimitive {
+                expected: "Name or Reference",
+                found: p.get_debug_name()
+            })
+        }
+    }
+}
+impl ObjectWrite for CMapEncoding {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match self {
+            CMapEncoding::Predefined(name) => Ok(Primitive::Name(name.0.clone())),
+            CMapEncoding::Embedded(stream) => stream.to_primitive(update),
+        }
+    }
+}
+impl CMapEncoding {
+    /// Load the CMap
+    ///
+    /// Predefined CMaps that are not bundled read as `Identity-H`, with a warning.
+    pub fn load(&self, resolve: &impl Resolve) -> Result<CMap> {
+        match self {
+            CMapEncoding::Predefined(name) => Ok(predefined_or_identity(name)),
+            CMapEncoding::Embedded(stream) => load_embedded(stream, resolve, 0),
+        }
+    }
+}
+
+fn predefined_or_identity(name: &str) -> CMap {
+    CMap::predefined(name).unwrap_or_else(|| {
+        warn!("CMap {} is not bundled, reading two byte codes as CIDs", name);
+        CMap::identity(name.ends_with("-V"))
+    })
+}
+
+fn load_embedded(stream: &Stream<Dictionary>, resolve: &impl Resolve, depth: usize) -> Result<CMap> {
+    let mut cmap = CMap::parse(&t!(stream.data(resolve)))?;
+    let parent = match stream.info.info.get("UseCMap") {
+        Some(Primitive::Name(name)) => Some(predefined_or_identity(name)),
+        Some(&Primitive::Reference(r)) if depth < MAX_USECMAP_DEPTH => {
+            let parent: RcRef<Stream<Dictionary>> = t!(resolve.get(Ref::new(r)));
+            Some(load_embedded(&parent, resolve, depth + 1)?)
+        }
+        _ => None,
+    };
+    if let Some(parent) = parent {
+        cmap.use_cmap(parent);
+    }
+    if let Some(Primitive::Integer(1)) = stream.info.info.get("WMode") {
+        cmap.vertical = true;
+    }
+    Ok(cmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(cmap: &CMap, bytes: &[u8]) -> Vec<(u32, u8)> {
+        cmap.codes(bytes).map(|c| (c.value, c.len)).collect()
+    }
+
+    #[test]
+    fn embedded_cmap() {
+        let data = b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap
+            /CIDSystemInfo << /Registry (Adobe) /Ordering (Japan1) /Supplement 2 >> def
+            /CMapName /Test-V def /WMode 1 def
+            2 begincodespacerange <00> <80> <8140> <9FFC> endcodespacerange
+            1 begincidrange <20> <7e> 231 endcidrange
+            2 begincidchar <8140> 633 <8141> 634 endcidchar
+            1 beginnotdefrange <8150> <815f> 1 endnotdefrange
+            endcmap CMapName currentdict /CMap defineresource pop end end";
+        let cmap = CMap::parse(data).unwrap();
+        assert_eq!(cmap.name.as_deref(), Some("Test-V"));
+        assert!(cmap.vertical);
+        assert_eq!(codes(&cmap, b"A\x81\x40\x81\x55"), [(0x41, 1), (0x8140, 2), (0x8155, 2)]);
+        let cids: Vec<_> = cmap.codes(b"A\x81\x40\x81\x41\x81\x55\x81\x60").map(|c| cmap.cid(c)).collect();
+        assert_eq!(cids, [Some(231 + 0x21), Some(633), Some(634), Some(1), None]);
+        // nothing but a first byte in a code space range
+        assert_eq!(codes(&cmap, b"\x81\x20\xA0"), [(0x8120, 2), (0xA0, 1)]);
+    }
+
+    #[test]
+    fn invalid_codes() {
+        // CIDs past u32::MAX
+        let data = b"1 begincodespacerange <00000000> <FFFFFFFF> endcodespacerange
+            1 begincidrange <00000000> <FFFFFFFF> 2147483647 endcidrange";
+        let cmap = CMap::parse(data).unwrap();
+        assert_eq!(cmap.cid(Code { value: 0x8000_0000, len: 4 }), Some(u32::MAX));
+        assert_eq!(cmap.cid(Code { value: 0x8000_0001, len: 4 }), None);
+
+        assert!(CMap::parse(b"1 begincidrange <0000000000> <00000000FF> 1 endcidrange").is_err());
+        assert!(CMap::parse(b"1 begincidchar <> 1 endcidchar").is_err());
+        assert!(CMap::parse(b"1 begincidchar <0000000000> 1 endcidchar").is_err());
+    }
+
+    #[test]
+    fn usecmap() {
+        let data = b"/UniJIS-UCS2-H usecmap 1 begincidchar <0041> 34 endcidchar";
+        let cmap = CMap::parse(data).unwrap();
+        assert_eq!(codes(&cmap, b"\x30\x42\x00\x41"), [(0x3042, 2), (0x41, 2)]);
+        assert_eq!(cmap.cid(Code { value: 0x41, len: 2 }), Some(34));
+        assert_eq!(cmap.unicode(Code { value: 0x3042, len: 2 }).as_deref(), Some("\u{3042}"));
+    }
+
+    #[test]
+    fn overlapping_ranges() {
+        // the later definitions win, the rest of the ranges they fall into is kept
+        let data = b"1 begincodespacerange <0000> <FFFF> endcodespacerange
+            1 begincidrange <0020> <007E> 1 endcidrange
+            2 begincidchar <0041> 34 <007E> 200 endcidchar
+            1 begincidrange <0030> <0032> 500 endcidrange";
+        let cmap = CMap::parse(data).unwrap();
+        let cid = |value| cmap.cid(Code { value, len: 2 });
+        assert_eq!(cid(0x20), Some(1));
+        assert_eq!(cid(0x2F), Some(16));
+        assert_eq!(cid(0x31), Some(501));
+        assert_eq!(cid(0x33), Some(20));
+        assert_eq!(cid(0x41), Some(34));
+        assert_eq!(cid(0x42), Some(35));
+        assert_eq!(cid(0x7D), Some(94));
+        assert_eq!(cid(0x7E), Some(200));
+        assert_eq!(cid(0x7F), None);
+
+        // a parent range around the definitions of the CMap using it
+        let mut cmap = CMap::parse(b"1 begincidchar <0041> 34 endcidchar").unwrap();
+        cmap.use_cmap(CMap::parse(b"1 begincidrange <0020> <007E> 1 endcidrange").unwrap());
+        assert_eq!(cmap.cid(Code { value: 0x41, len: 2 }), Some(34));
+        assert_eq!(cmap.cid(Code { value: 0x42, len: 2 }), Some(35));
+        assert_eq!(cmap.cid(Code { value: 0x40, len: 2 }), Some(33));
+    }
+
+    #[test]
+    fn identity() {
+        let cmap = CMap::predefined("Identity-V").unwrap();
+        assert!(cmap.vertical);
+        assert_eq!(cmap.cid(Code { value: 0x1234, len: 2 }), Some(0x1234));
+        assert_eq!(cmap.encode('a'), None);
+        assert!(CMap::predefined("Unknown-H").is_none());
+    }
+
+    #[test]
+    fn legacy_encodings() {
+        let gbk = CMap::predefined("GBK-EUC-H").unwrap();
+        let bytes = b"\xD6\xD0\xCE\xC4A";
+        let text: String = gbk.codes(bytes).map(|c| gbk.unicode(c).unwrap()).collect();
+        assert_eq!(text, "\u{4E2D}\u{6587}A");
+        let encoded: Vec<u8> = "\u{4E2D}\u{6587}A".chars().flat_map(|c| gbk.encode(c).unwrap().bytes()).collect();
+        assert_eq!(encoded, bytes);
+
+        let sjis = CMap::predefined("90ms-RKSJ-V").unwrap();
+        assert!(sjis.vertical);
+        assert_eq!(codes(&sjis, b"\x93\xFA\xB1A"), [(0x93FA, 2), (0xB1, 1), (0x41, 1)]);
+        assert_eq!(sjis.unicode(Code { value: 0x93FA, len: 2 }).as_deref(), Some("\u{65E5}"));
+        assert_eq!(sjis.unicode(Code { value: 0xB1, len: 1 }).as_deref(), Some("\u{FF71}"));
+
+        let jis = CMap::predefined("H").unwrap();
+        assert_eq!(jis.encode('\u{65E5}'), Some(Code { value: 0x467C, len: 2 }));
+        assert_eq!(jis.encode('A'), None);
+
+        let korean = CMap::predefined("KSCms-UHC-H").unwrap();
+        assert_eq!(korean.encode('\u{D55C}').map(|c| c.value), Some(0xC7D1));
+    }
+
+    #[test]
+    fn unicode_cmaps() {
+        let utf16 = CMap::predefined("UniJIS-UTF16-H").unwrap();
+        let bytes = b"\xD8\x40\xDC\x0B\x00A";
+        assert_eq!(codes(&utf16, bytes), [(0xD840DC0B, 4), (0x41, 2)]);
+        assert_eq!(utf16.unicode(Code { value: 0xD840DC0B, len: 4 }).as_deref(), Some("\u{2000B}"));
+        assert_eq!(utf16.encode('\u{2000B}'), Some(Code { value: 0xD840DC0B, len: 4 }));
+        // UCS-2 has no surrogate pairs
+        assert_eq!(CMap::predefined("UniGB-UCS2-H").unwrap().encode('\u{2000B}'), None);
+
+        let utf8 = CMap::predefined("UniKS-UTF8-H").unwrap();
+        let text = "A\u{D55C}";
+        let codes: Vec<Code> = utf8.codes(text.as_bytes()).collect();
+        assert_eq!(codes.iter().map(|c| c.len).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(codes.iter().map(|&c| utf8.unicode(c).unwrap()).collect::<String>(), text);
+    }
+
+    #[test]
+    fn tounicode_code_length() {
+        let sjis = CMap::predefined("90ms-RKSJ-H").unwrap();
+        assert_eq!(sjis.code(0x41), Code { value: 0x41, len: 1 });
+        assert_eq!(sjis.code(0x8140), Code { value: 0x8140, len: 2 });
+        assert_eq!(CMap::identity(false).code(0x41), Code { value: 0x41, len: 2 });
+    }
+}
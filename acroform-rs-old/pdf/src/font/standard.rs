@@ -0,0 +1,381 @@
+//! Metrics of the standard 14 fonts, taken from the Adobe Font Metrics (AFM) files.
+//!
+//! Forms reference these fonts (often as `/Helv` or `/ZaDb`) without `/Widths`,
+//! so the metrics are needed to measure text set in them.
+
+use crate::encoding::BaseEncoding;
+
+/// Metrics of one of the standard 14 fonts
+///
+/// All values are in thousandths of the font size, like `/Widths`.
+#[derive(Debug)]
+pub struct StandardFont {
+    /// PostScript name, e.g. `Helvetica-Bold`
+    pub name: &'static str,
+    pub ascent: f32,
+    pub descent: f32,
+    pub cap_height: f32,
+    pub x_height: f32,
+    pub italic_angle: f32,
+    /// llx, lly, urx, ury
+    pub bbox: [f32; 4],
+    widths: Metrics,
+}
+
+#[derive(Debug)]
+enum Metrics {
+    /// every glyph of the Latin character set has this width
+    Fixed(u16),
+    /// widths of the Latin character set, in the order of `LATIN`
+    Latin(&'static [u16; LATIN_LEN]),
+    /// code, glyph name and width of a font with its own encoding
+    BuiltIn(&'static [(u8, &'static str, u16)]),
+}
+
+macro_rules! font {
+    ($name:expr, $widths:expr, asc $asc:expr, desc $desc:expr, cap $cap:expr, x $x:expr, angle $angle:expr, bbox $bbox:expr) => (
+        StandardFont {
+            name: $name,
+            ascent: $asc as f32,
+            descent: $desc as f32,
+            cap_height: $cap as f32,
+            x_height: $x as f32,
+            italic_angle: $angle as f32,
+            bbox: $bbox,
+            widths: $widths,
+        }
+    )
+}
+
+/// The standard 14 fonts
+pub static STANDARD_FONTS: [StandardFont; 14] = [
+    font!("Helvetica", Metrics::Latin(&HELVETICA_WIDTHS),
+        asc 718, desc -207, cap 718, x 523, angle 0, bbox [-166., -225., 1000., 931.]),
+    font!("Helvetica-Bold", Metrics::Latin(&HELVETICA_BOLD_WIDTHS),
+        asc 718, desc -207, cap 718, x 532, angle 0, bbox [-170., -228., 1003., 962.]),
+    font!("Helvetica-Oblique", Metrics::Latin(&HELVETICA_WIDTHS),
+        asc 718, desc -207, cap 718, x 523, angle -12, bbox [-170., -225., 1116., 931.]),
+    font!("Helvetica-BoldOblique", Metrics::Latin(&HELVETICA_BOLD_WIDTHS),
+        asc 718, desc -207, cap 718, x 532, angle -12, bbox [-174., -228., 1114., 962.]),
+    font!("Times-Roman", Metrics::Latin(&TIMES_ROMAN_WIDTHS),
+        asc 683, desc -217, cap 662, x 450, angle 0, bbox [-168., -218., 1000., 898.]),
+    font!("Times-Bold", Metrics::Latin(&TIMES_BOLD_WIDTHS),
+        asc 683, desc -217, cap 676, x 461, angle 0, bbox [-168., -218., 1000., 935.]),
+    font!("Times-Italic", Metrics::Latin(&TIMES_ITALIC_WIDTHS),
+        asc 683, desc -217, cap 653, x 441, angle -15.5, bbox [-169., -217., 1010., 883.]),
+    font!("Times-BoldItalic", Metrics::Latin(&TIMES_BOLD_ITALIC_WIDTHS),
+        asc 683, desc -217, cap 669, x 462, angle -15, bbox [-200., -218., 996., 921.]),
+    font!("Courier", Metrics::Fixed(600),
+        asc 629, desc -157, cap 562, x 426, angle 0, bbox [-23., -250., 715., 805.]),
+    font!("Courier-Bold", Metrics::Fixed(600),
+        asc 629, desc -157, cap 562, x 439, angle 0, bbox [-113., -250., 749., 801.]),
+    font!("Courier-Oblique", Metrics::Fixed(600),
+        asc 629, desc -157, cap 562, x 426, angle -12, bbox [-27., -250., 849., 805.]),
+    font!("Courier-BoldOblique", Metrics::Fixed(600),
+        asc 629, desc -157, cap 562, x 439, angle -12, bbox [-57., -250., 869., 801.]),
+    // the AFM files of the symbolic fonts have no ascender, descender and cap height
+    font!("Symbol", Metrics::BuiltIn(&SYMBOL_GLYPHS),
+        asc 1010, desc -293, cap 673, x 0, angle 0, bbox [-180., -293., 1090., 1010.]),
+    font!("ZapfDingbats", Metrics::BuiltIn(&ZAPF_DINGBATS_GLYPHS),
+        asc 820, desc -143, cap 820, x 0, angle 0, bbox [-1., -143., 981., 820.]),
+];
+
+/// Names used for the standard fonts: the abbreviations of AcroForm `/DR` dictionaries
+/// and the usual substitutes, with the index into `STANDARD_FONTS`.
+static ALIASES: [(&str, usize); 34] = [
+    ("Helv", 0), ("HeBo", 1), ("HeOb", 2), ("HeBO", 3),
+    ("TiRo", 4), ("TiBo", 5), ("TiIt", 6), ("TiBI", 7),
+    ("Cour", 8), ("CoBo", 9), ("CoOb", 10), ("CoBO", 11),
+    ("Symb", 12), ("ZaDb", 13),
+    ("Arial", 0), ("ArialMT", 0), ("Arial,Bold", 1), ("Arial-BoldMT", 1),
+    ("Arial,Italic", 2), ("Arial-ItalicMT", 2), ("Arial,BoldItalic", 3), ("Arial-BoldItalicMT", 3),
+    ("TimesNewRoman", 4), ("TimesNewRomanPSMT", 4), ("TimesNewRoman,Bold", 5), ("TimesNewRomanPS-BoldMT", 5),
+    ("TimesNewRoman,Italic", 6), ("TimesNewRomanPS-ItalicMT", 6), ("TimesNewRoman,BoldItalic", 7), ("TimesNewRomanPS-BoldItalicMT", 7),
+    ("CourierNew", 8), ("CourierNewPSMT", 8), ("CourierNew,Bold", 9), ("CourierNewPS-BoldMT", 9),
+];
+
+impl StandardFont {
+    /// Look up a standard font by its name or one of its aliases.
+    ///
+    /// A subset tag like `ABCDEF+` is ignored.
+    pub fn by_name(name: &str) -> Option<&'static StandardFont> {
+        let name = match name.split_once('+') {
+            Some((tag, rest)) if tag.len() == 6 && tag.bytes().all(|b| b.is_ascii_uppercase()) => rest,
+            _ => name
+        };
+        STANDARD_FONTS.iter().find(|f| f.name == name)
+            .or_else(|| ALIASES.iter().find(|&&(alias, _)| alias == name).map(|&(_, i)| &STANDARD_FONTS[i]))
+    }
+
+    /// true for Symbol and ZapfDingbats, which use their own encoding
+    pub fn is_symbolic(&self) -> bool {
+        matches!(self.widths, Metrics::BuiltIn(_))
+    }
+
+    /// Glyph name of `code` in the font's built-in encoding.
+    ///
+    /// That is `StandardEncoding` for all but the symbolic fonts.
+    pub fn builtin_glyph(&self, code: u8) -> Option<&'static str> {
+        match self.widths {
+            Metrics::BuiltIn(glyphs) => glyphs.binary_search_by_key(&code, |&(c, _, _)| c).ok().map(|i| glyphs[i].1),
+            _ => BaseEncoding::StandardEncoding.glyph_name(code),
+        }
+    }
+
+    /// Width of the glyph `name`, if the font has it.
+    pub fn glyph_width(&self, name: &str) -> Option<f32> {
+        let width = match self.widths {
+            Metrics::BuiltIn(glyphs) => glyphs.iter().find(|&&(_, n, _)| n == name)?.2,
+            Metrics::Fixed(width) => {
+                latin_index(name)?;
+                width
+            }
+            Metrics::Latin(widths) => widths[latin_index(name)?],
+        };
+        Some(width as f32)
+    }
+}
+
+fn latin_index(name: &str) -> Option<usize> {
+    let name = match ACCENTED.binary_search_by_key(&name, |&(accented, _)| accented) {
+        Ok(i) => ACCENTED[i].1,
+        Err(_) => name
+    };
+    LATIN.binary_search(&name).ok()
+}
+
+const LATIN_LEN: usize = 171;
+
+/// Glyph names of the Latin character set, sorted
+static LATIN: [&str; LATIN_LEN] = [
+    "A", "AE", "B", "C", "D", "E", "Eth", "Euro",
+    "F", "G", "H", "I", "J", "K", "L", "Lslash",
+    "M", "N", "O", "OE", "Oslash", "P", "Q", "R",
+    "S", "T", "Thorn", "U", "V", "W", "X", "Y",
+    "Z", "a", "acute", "ae", "ampersand", "asciicircum", "asciitilde", "asterisk",
+    "at", "b", "backslash", "bar", "braceleft", "braceright", "bracketleft", "bracketright",
+    "breve", "brokenbar", "bullet", "c", "caron", "cedilla", "cent", "circumflex",
+    "colon", "comma", "copyright", "currency", "d", "dagger", "daggerdbl", "degree",
+    "dieresis", "divide", "dollar", "dotaccent", "dotlessi", "e", "eight", "ellipsis",
+    "emdash", "endash", "equal", "eth", "exclam", "exclamdown", "f", "fi",
+    "five", "fl", "florin", "four", "fraction", "g", "germandbls", "grave",
+    "greater", "guillemotleft", "guillemotright", "guilsinglleft", "guilsinglright", "h", "hungarumlaut", "hyphen",
+    "i", "j", "k", "l", "less", "logicalnot", "lslash", "m",
+    "macron", "minus", "mu", "multiply", "n", "nine", "numbersign", "o",
+    "oe", "ogonek", "one", "onehalf", "onequarter", "onesuperior", "ordfeminine", "ordmasculine",
+    "oslash", "p", "paragraph", "parenleft", "parenright", "percent", "period", "periodcentered",
+    "perthousand", "plus", "plusminus", "q", "question", "questiondown", "quotedbl", "quotedblbase",
+    "quotedblleft", "quotedblright", "quoteleft", "quoteright", "quotesinglbase", "quotesingle", "r", "registered",
+    "ring", "s", "section", "semicolon", "seven", "six", "slash", "space",
+    "sterling", "t", "thorn", "three", "threequarters", "threesuperior", "tilde", "trademark",
+    "two", "twosuperior", "u", "underscore", "v", "w", "x", "y",
+    "yen", "z", "zero",
+];
+
+/// Accented glyphs, sorted, with the glyph they share the width with
+static ACCENTED: [(&str, &str); 58] = [
+    ("Aacute", "A"), ("Acircumflex", "A"), ("Adieresis", "A"), ("Agrave", "A"),
+    ("Aring", "A"), ("Atilde", "A"), ("Ccedilla", "C"), ("Eacute", "E"),
+    ("Ecircumflex", "E"), ("Edieresis", "E"), ("Egrave", "E"), ("Iacute", "I"),
+    ("Icircumflex", "I"), ("Idieresis", "I"), ("Igrave", "I"), ("Ntilde", "N"),
+    ("Oacute", "O"), ("Ocircumflex", "O"), ("Odieresis", "O"), ("Ograve", "O"),
+    ("Otilde", "O"), ("Scaron", "S"), ("Uacute", "U"), ("Ucircumflex", "U"),
+    ("Udieresis", "U"), ("Ugrave", "U"), ("Yacute", "Y"), ("Ydieresis", "Y"),
+    ("Zcaron", "Z"), ("aacute", "a"), ("acircumflex", "a"), ("adieresis", "a"),
+    ("agrave", "a"), ("aring", "a"), ("atilde", "a"), ("ccedilla", "c"),
+    ("eacute", "e"), ("ecircumflex", "e"), ("edieresis", "e"), ("egrave", "e"),
+    ("iacute", "dotlessi"), ("icircumflex", "dotlessi"), ("idieresis", "dotlessi"), ("igrave", "dotlessi"),
+    ("ntilde", "n"), ("oacute", "o"), ("ocircumflex", "o"), ("odieresis", "o"),
+    ("ograve", "o"), ("otilde", "o"), ("scaron", "s"), ("uacute", "u"),
+    ("ucircumflex", "u"), ("udieresis", "u"), ("ugrave", "u"), ("yacute", "y"),
+    ("ydieresis", "y"), ("zcaron", "z"),
+];
+
+static HELVETICA_WIDTHS: [u16; LATIN_LEN] = [
+    667, 1000, 667, 722, 722, 667, 722, 556, 611, 778, 722, 278, 500, 667, 556, 556,
+    833, 722, 778, 1000, 778, 667, 778, 722, 667, 611, 667, 722, 667, 944, 667, 667,
+    611, 556, 333, 889, 667, 469, 584, 389, 1015, 556, 278, 260, 334, 334, 278, 278,
+    333, 260, 350, 500, 333, 333, 556, 333, 278, 278, 737, 556, 556, 556, 556, 400,
+    333, 584, 556, 333, 278, 556, 556, 1000, 1000, 556, 584, 556, 278, 333, 278, 500,
+    556, 500, 556, 556, 167, 556, 611, 333, 584, 556, 556, 333, 333, 556, 333, 333,
+    222, 222, 500, 222, 584, 584, 222, 833, 333, 584, 556, 584, 556, 556, 556, 556,
+    944, 333, 556, 834, 834, 333, 370, 365, 611, 556, 537, 333, 333, 889, 278, 278,
+    1000, 584, 584, 556, 556, 611, 355, 333, 333, 333, 222, 222, 222, 191, 333, 737,
+    333, 500, 556, 278, 556, 556, 278, 278, 556, 278, 556, 556, 834, 333, 333, 1000,
+    556, 333, 556, 556, 500, 722, 500, 500, 556, 500, 556,
+];
+
+static HELVETICA_BOLD_WIDTHS: [u16; LATIN_LEN] = [
+    722, 1000, 722, 722, 722, 667, 722, 556, 611, 778, 722, 278, 556, 722, 611, 611,
+    833, 722, 778, 1000, 778, 667, 778, 722, 667, 611, 667, 722, 667, 944, 667, 667,
+    611, 556, 333, 889, 722, 584, 584, 389, 975, 611, 278, 280, 389, 389, 333, 333,
+    333, 280, 350, 556, 333, 333, 556, 333, 333, 278, 737, 556, 611, 556, 556, 400,
+    333, 584, 556, 333, 278, 556, 556, 1000, 1000, 556, 584, 611, 333, 333, 333, 611,
+    556, 611, 556, 556, 167, 611, 611, 333, 584, 556, 556, 333, 333, 611, 333, 333,
+    278, 278, 556, 278, 584, 584, 278, 889, 333, 584, 611, 584, 611, 556, 556, 611,
+    944, 333, 556, 834, 834, 333, 370, 365, 611, 611, 556, 333, 333, 889, 278, 278,
+    1000, 584, 584, 611, 611, 611, 474, 500, 500, 500, 278, 278, 278, 238, 389, 737,
+    333, 556, 556, 333, 556, 556, 278, 278, 556, 333, 611, 556, 834, 333, 333, 1000,
+    556, 333, 611, 556, 556, 778, 556, 556, 556, 500, 556,
+];
+
+static TIMES_ROMAN_WIDTHS: [u16; LATIN_LEN] = [
+    722, 889, 667, 667, 722, 611, 722, 500, 556, 722, 722, 333, 389, 722, 611, 611,
+    889, 722, 722, 889, 722, 556, 722, 667, 556, 611, 556, 722, 722, 944, 722, 722,
+    611, 444, 333, 667, 778, 469, 541, 500, 921, 500, 278, 200, 480, 480, 333, 333,
+    333, 200, 350, 444, 333, 333, 500, 333, 278, 250, 760, 500, 500, 500, 500, 400,
+    333, 564, 500, 333, 278, 444, 500, 1000, 1000, 500, 564, 500, 333, 333, 333, 556,
+    500, 556, 500, 500, 167, 500, 500, 333, 564, 500, 500, 333, 333, 500, 333, 333,
+    278, 278, 500, 278, 564, 564, 278, 778, 333, 564, 500, 564, 500, 500, 500, 500,
+    722, 333, 500, 750, 750, 300, 276, 310, 500, 500, 453, 333, 333, 833, 250, 250,
+    1000, 564, 564, 500, 444, 444, 408, 444, 444, 444, 333, 333, 333, 180, 333, 760,
+    333, 389, 500, 278, 500, 500, 278, 250, 500, 278, 500, 500, 750, 300, 333, 980,
+    500, 300, 500, 500, 500, 722, 500, 500, 500, 444, 500,
+];
+
+static TIMES_BOLD_WIDTHS: [u16; LATIN_LEN] = [
+    722, 1000, 667, 722, 722, 667, 722, 500, 611, 778, 778, 389, 500, 778, 667, 667,
+    944, 722, 778, 1000, 778, 611, 778, 722, 556, 667, 611, 722, 722, 1000, 722, 722,
+    667, 500, 333, 722, 833, 581, 520, 500, 930, 556, 278, 220, 394, 394, 333, 333,
+    333, 220, 350, 444, 333, 333, 500, 333, 333, 250, 747, 500, 556, 500, 500, 400,
+    333, 570, 500, 333, 278, 444, 500, 1000, 1000, 500, 570, 500, 333, 333, 333, 556,
+    500, 556, 500, 500, 167, 500, 556, 333, 570, 500, 500, 333, 333, 556, 333, 333,
+    278, 333, 556, 278, 570, 570, 278, 833, 333, 570, 556, 570, 556, 500, 500, 500,
+    722, 333, 500, 750, 750, 300, 300, 330, 500, 556, 540, 333, 333, 1000, 250, 250,
+    1000, 570, 570, 556, 500, 500, 555, 500, 500, 500, 333, 333, 333, 278, 444, 747,
+    333, 389, 500, 333, 500, 500, 278, 250, 500, 333, 556, 500, 750, 300, 333, 1000,
+    500, 300, 556, 500, 500, 722, 500, 500, 500, 444, 500,
+];
+
+static TIMES_ITALIC_WIDTHS: [u16; LATIN_LEN] = [
+    611, 889, 611, 667, 722, 611, 722, 500, 611, 722, 722, 333, 444, 667, 556, 556,
+    833, 667, 722, 944, 722, 611, 722, 611, 500, 556, 611, 722, 611, 833, 611, 556,
+    556, 500, 333, 667, 778, 422, 541, 500, 920, 500, 278, 275, 400, 400, 389, 389,
+    333, 275, 350, 444, 333, 333, 500, 333, 333, 250, 760, 500, 500, 500, 500, 400,
+    333, 675, 500, 333, 278, 444, 500, 889, 889, 500, 675, 500, 333, 389, 278, 500,
+    500, 500, 500, 500, 167, 500, 500, 333, 675, 500, 500, 333, 333, 500, 333, 333,
+    278, 278, 444, 278, 675, 675, 278, 722, 333, 675, 500, 675, 500, 500, 500, 500,
+    667, 333, 500, 750, 750, 300, 276, 310, 500, 500, 523, 333, 333, 833, 250, 250,
+    1000, 675, 675, 500, 500, 500, 420, 556, 556, 556, 333, 333, 333, 214, 389, 760,
+    333, 389, 500, 333, 500, 500, 278, 250, 500, 278, 500, 500, 750, 300, 333, 980,
+    500, 300, 500, 500, 444, 667, 444, 444, 500, 389, 500,
+];
+
+static TIMES_BOLD_ITALIC_WIDTHS: [u16; LATIN_LEN] = [
+    667, 944, 667, 667, 722, 667, 722, 500, 667, 722, 778, 389, 500, 667, 611, 611,
+    889, 722, 722, 944, 722, 611, 722, 667, 556, 611, 611, 722, 667, 889, 667, 611,
+    611, 500, 333, 722, 778, 570, 570, 500, 832, 500, 278, 220, 348, 348, 333, 333,
+    333, 220, 350, 444, 333, 333, 500, 333, 333, 250, 747, 500, 500, 500, 500, 400,
+    333, 570, 500, 333, 278, 444, 500, 1000, 1000, 500, 570, 500, 389, 389, 333, 556,
+    500, 556, 500, 500, 167, 500, 500, 333, 570, 500, 500, 333, 333, 556, 333, 333,
+    278, 278, 500, 278, 570, 606, 278, 778, 333, 606, 576, 570, 556, 500, 500, 500,
+    722, 333, 500, 750, 750, 300, 266, 300, 500, 500, 500, 333, 333, 833, 250, 250,
+    1000, 570, 570, 500, 500, 500, 555, 500, 500, 500, 333, 333, 333, 278, 389, 747,
+    333, 389, 500, 333, 500, 500, 278, 250, 500, 278, 500, 500, 750, 300, 333, 1000,
+    500, 300, 556, 500, 444, 667, 500, 444, 500, 389, 500,
+];
+
+static SYMBOL_GLYPHS: [(u8, &str, u16); 189] = [
+    (32, "space", 250), (33, "exclam", 333), (34, "universal", 713), (35, "numbersign", 500),
+    (36, "existential", 549), (37, "percent", 833), (38, "ampersand", 778), (39, "suchthat", 439),
+    (40, "parenleft", 333), (41, "parenright", 333), (42, "asteriskmath", 500), (43, "plus", 549),
+    (44, "comma", 250), (45, "minus", 549), (46, "period", 250), (47, "slash", 278),
+    (48, "zero", 500), (49, "one", 500), (50, "two", 500), (51, "three", 500),
+    (52, "four", 500), (53, "five", 500), (54, "six", 500), (55, "seven", 500),
+    (56, "eight", 500), (57, "nine", 500), (58, "colon", 278), (59, "semicolon", 278),
+    (60, "less", 549), (61, "equal", 549), (62, "greater", 549), (63, "question", 444),
+    (64, "congruent", 549), (65, "Alpha", 722), (66, "Beta", 667), (67, "Chi", 722),
+    (68, "Delta", 612), (69, "Epsilon", 611), (70, "Phi", 763), (71, "Gamma", 603),
+    (72, "Eta", 722), (73, "Iota", 333), (74, "theta1", 631), (75, "Kappa", 722),
+    (76, "Lambda", 686), (77, "Mu", 889), (78, "Nu", 722), (79, "Omicron", 722),
+    (80, "Pi", 768), (81, "Theta", 741), (82, "Rho", 556), (83, "Sigma", 592),
+    (84, "Tau", 611), (85, "Upsilon", 690), (86, "sigma1", 439), (87, "Omega", 768),
+    (88, "Xi", 645), (89, "Psi", 795), (90, "Zeta", 611), (91, "bracketleft", 333),
+    (92, "therefore", 863), (93, "bracketright", 333), (94, "perpendicular", 658), (95, "underscore", 500),
+    (96, "radicalex", 500), (97, "alpha", 631), (98, "beta", 549), (99, "chi", 549),
+    (100, "delta", 494), (101, "epsilon", 439), (102, "phi", 521), (103, "gamma", 411),
+    (104, "eta", 603), (105, "iota", 329), (106, "phi1", 603), (107, "kappa", 549),
+    (108, "lambda", 549), (109, "mu", 576), (110, "nu", 521), (111, "omicron", 549),
+    (112, "pi", 549), (113, "theta", 521), (114, "rho", 549), (115, "sigma", 603),
+    (116, "tau", 439), (117, "upsilon", 576), (118, "omega1", 713), (119, "omega", 686),
+    (120, "xi", 493), (121, "psi", 686), (122, "zeta", 494), (123, "braceleft", 480),
+    (124, "bar", 200), (125, "braceright", 480), (126, "similar", 549), (160, "Euro", 750),
+    (161, "Upsilon1", 620), (162, "minute", 247), (163, "lessequal", 549), (164, "fraction", 167),
+    (165, "infinity", 713), (166, "florin", 500), (167, "club", 753), (168, "diamond", 753),
+    (169, "heart", 753), (170, "spade", 753), (171, "arrowboth", 1042), (172, "arrowleft", 987),
+    (173, "arrowup", 603), (174, "arrowright", 987), (175, "arrowdown", 603), (176, "degree", 400),
+    (177, "plusminus", 549), (178, "second", 411), (179, "greaterequal", 549), (180, "multiply", 549),
+    (181, "proportional", 713), (182, "partialdiff", 494), (183, "bullet", 460), (184, "divide", 549),
+    (185, "notequal", 549), (186, "equivalence", 549), (187, "approxequal", 549), (188, "ellipsis", 1000),
+    (189, "arrowvertex", 603), (190, "arrowhorizex", 1000), (191, "carriagereturn", 658), (192, "aleph", 823),
+    (193, "Ifraktur", 686), (194, "Rfraktur", 795), (195, "weierstrass", 987), (196, "circlemultiply", 768),
+    (197, "circleplus", 768), (198, "emptyset", 823), (199, "intersection", 768), (200, "union", 768),
+    (201, "propersuperset", 713), (202, "reflexsuperset", 713), (203, "notsubset", 713), (204, "propersubset", 713),
+    (205, "reflexsubset", 713), (206, "element", 713), (207, "notelement", 713), (208, "angle", 768),
+    (209, "gradient", 713), (210, "registerserif", 790), (211, "copyrightserif", 790), (212, "trademarkserif", 890),
+    (213, "product", 823), (214, "radical", 549), (215, "dotmath", 250), (216, "logicalnot", 713),
+    (217, "logicaland", 603), (218, "logicalor", 603), (219, "arrowdblboth", 1042), (220, "arrowdblleft", 987),
+    (221, "arrowdblup", 603), (222, "arrowdblright", 987), (223, "arrowdbldown", 603), (224, "lozenge", 494),
+    (225, "angleleft", 329), (226, "registersans", 790), (227, "copyrightsans", 790), (228, "trademarksans", 786),
+    (229, "summation", 713), (230, "parenlefttp", 384), (231, "parenleftex", 384), (232, "parenleftbt", 384),
+    (233, "bracketlefttp", 384), (234, "bracketleftex", 384), (235, "bracketleftbt", 384), (236, "bracelefttp", 494),
+    (237, "braceleftmid", 494), (238, "braceleftbt", 494), (239, "braceex", 494), (241, "angleright", 329),
+    (242, "integral", 274), (243, "integraltp", 686), (244, "integralex", 686), (245, "integralbt", 686),
+    (246, "parenrighttp", 384), (247, "parenrightex", 384), (248, "parenrightbt", 384), (249, "bracketrighttp", 384),
+    (250, "bracketrightex", 384), (251, "bracketrightbt", 384), (252, "bracerighttp", 494), (253, "bracerightmid", 494),
+    (254, "bracerightbt", 494),
+];
+
+static ZAPF_DINGBATS_GLYPHS: [(u8, &str, u16); 202] = [
+    (32, "space", 278), (33, "a1", 974), (34, "a2", 961), (35, "a202", 974),
+    (36, "a3", 980), (37, "a4", 719), (38, "a5", 789), (39, "a119", 790),
+    (40, "a118", 791), (41, "a117", 690), (42, "a11", 960), (43, "a12", 939),
+    (44, "a13", 549), (45, "a14", 855), (46, "a15", 911), (47, "a16", 933),
+    (48, "a105", 911), (49, "a17", 945), (50, "a18", 974), (51, "a19", 755),
+    (52, "a20", 846), (53, "a21", 762), (54, "a22", 761), (55, "a23", 571),
+    (56, "a24", 677), (57, "a25", 763), (58, "a26", 760), (59, "a27", 759),
+    (60, "a28", 754), (61, "a6", 494), (62, "a7", 552), (63, "a8", 537),
+    (64, "a9", 577), (65, "a10", 692), (66, "a29", 786), (67, "a30", 788),
+    (68, "a31", 788), (69, "a32", 790), (70, "a33", 793), (71, "a34", 794),
+    (72, "a35", 816), (73, "a36", 823), (74, "a37", 789), (75, "a38", 841),
+    (76, "a39", 823), (77, "a40", 833), (78, "a41", 816), (79, "a42", 831),
+    (80, "a43", 923), (81, "a44", 744), (82, "a45", 723), (83, "a46", 749),
+    (84, "a47", 790), (85, "a48", 792), (86, "a49", 695), (87, "a50", 776),
+    (88, "a51", 768), (89, "a52", 792), (90, "a53", 759), (91, "a54", 707),
+    (92, "a55", 708), (93, "a56", 682), (94, "a57", 701), (95, "a58", 826),
+    (96, "a59", 815), (97, "a60", 789), (98, "a61", 789), (99, "a62", 707),
+    (100, "a63", 687), (101, "a64", 696), (102, "a65", 689), (103, "a66", 786),
+    (104, "a67", 787), (105, "a68", 713), (106, "a69", 791), (107, "a70", 785),
+    (108, "a71", 791), (109, "a72", 873), (110, "a73", 761), (111, "a74", 762),
+    (112, "a203", 762), (113, "a75", 759), (114, "a204", 759), (115, "a76", 892),
+    (116, "a77", 892), (117, "a78", 788), (118, "a79", 784), (119, "a81", 438),
+    (120, "a82", 138), (121, "a83", 277), (122, "a84", 415), (123, "a97", 392),
+    (124, "a98", 392), (125, "a99", 668), (126, "a100", 668), (128, "a89", 390),
+    (129, "a90", 390), (130, "a93", 317), (131, "a94", 317), (132, "a91", 276),
+    (133, "a92", 276), (134, "a205", 509), (135, "a85", 509), (136, "a206", 410),
+    (137, "a86", 410), (138, "a87", 234), (139, "a88", 234), (140, "a95", 334),
+    (141, "a96", 334), (161, "a101", 732), (162, "a102", 544), (163, "a103", 544),
+    (164, "a104", 910), (165, "a106", 667), (166, "a107", 760), (167, "a108", 760),
+    (168, "a112", 776), (169, "a111", 595), (170, "a110", 694), (171, "a109", 626),
+    (172, "a120", 788), (173, "a121", 788), (174, "a122", 788), (175, "a123", 788),
+    (176, "a124", 788), (177, "a125", 788), (178, "a126", 788), (179, "a127", 788),
+    (180, "a128", 788), (181, "a129", 788), (182, "a130", 788), (183, "a131", 788),
+    (184, "a132", 788), (185, "a133", 788), (186, "a134", 788), (187, "a135", 788),
+    (188, "a136", 788), (189, "a137", 788), (190, "a138", 788), (191, "a139", 788),
+    (192, "a140", 788), (193, "a141", 788), (194, "a142", 788), (195, "a143", 788),
+    (196, "a144", 788), (197, "a145", 788), (198, "a146", 788), (199, "a147", 788),
+    (200, "a148", 788), (201, "a149", 788), (202, "a150", 788), (203, "a151", 788),
+    (204, "a152", 788), (205, "a153", 788), (206, "a154", 788), (207, "a155", 788),
+    (208, "a156", 788), (209, "a157", 788), (210, "a158", 788), (211, "a159", 788),
+    (212, "a160", 894), (213, "a161", 838), (214, "a163", 1016), (215, "a164", 458),
+    (216, "a196", 748), (217, "a165", 924), (218, "a192", 748), (219, "a166", 918),
+    (220, "a167", 927), (221, "a168", 928), (222, "a169", 928), (223, "a170", 834),
+    (224, "a171", 873), (225, "a172", 828), (226, "a173", 924), (227, "a162", 924),
+    (228, "a174", 917), (229, "a175", 930), (230, "a176", 931), (231, "a177", 463),
+    (232, "a178", 883), (233, "a179", 836), (234, "a193", 836), (235, "a180", 867),
+    (236, "a199", 867), (237, "a181", 696), (238, "a200", 696), (239, "a182", 874),
+    (241, "a201", 874), (242, "a183", 760), (243, "a184", 946), (244, "a197", 771),
+    (245, "a185", 865), (246, "a194", 771), (247, "a198", 888), (248, "a186", 967),
+    (249, "a195", 888), (250, "a187", 831), (251, "a188", 873), (252, "a189", 927),
+    (253, "a190", 970), (254, "a191", 918),
+];
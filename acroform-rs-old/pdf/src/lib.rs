@@ -19,10 +19,13 @@ pub mod font;
 pub mod any;
 pub mod encoding;
 pub mod build;
+pub mod text;
 
 // mod content;
 pub mod enc;
 pub mod crypt;
+pub mod pubsec;
+mod repair;
 
 // pub use content::*;
 pub use crate::error::PdfError;
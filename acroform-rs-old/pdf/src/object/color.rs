@@ -16,6 +16,9 @@ pub struct IccInfo {
 
     #[pdf(key="Metadata")]
     pub metadata: Option<Stream<()>>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Debug, Clone, DeepClone)]
@@ -171,9 +174,31 @@ impl ObjectWrite for ColorSpace {
                 };
                 Ok(Primitive::Array(vec![Primitive::name("Indexed"), base, hival, lookup]))
             }
-            ref p => {
-                dbg!(p);
-                unimplemented!()
+            ColorSpace::DeviceGray => Ok(Primitive::name("DeviceGray")),
+            ColorSpace::Pattern => Ok(Primitive::name("Pattern")),
+            ColorSpace::Named(ref name) => name.to_primitive(update),
+            ColorSpace::CalGray(ref dict) => Ok(Primitive::Array(vec![Primitive::name("CalGray"), dict.clone().into()])),
+            ColorSpace::CalRGB(ref dict) => Ok(Primitive::Array(vec![Primitive::name("CalRGB"), dict.clone().into()])),
+            ColorSpace::CalCMYK(ref dict) => Ok(Primitive::Array(vec![Primitive::name("CalCMYK"), dict.clone().into()])),
+            ColorSpace::Icc(ref stream) => Ok(Primitive::Array(vec![Primitive::name("ICCBased"), stream.to_primitive(update)?])),
+            ColorSpace::Other(ref arr) => Ok(Primitive::Array(arr.clone())),
+            ColorSpace::Separation(ref name, ref alt, ref tint) => Ok(Primitive::Array(vec![
+                Primitive::name("Separation"),
+                name.to_primitive(update)?,
+                alt.to_primitive(update)?,
+                tint.to_primitive(update)?,
+            ])),
+            ColorSpace::DeviceN { ref names, ref alt, ref tint, ref attr } => {
+                let mut arr = vec![
+                    Primitive::name("DeviceN"),
+                    names.to_primitive(update)?,
+                    alt.to_primitive(update)?,
+                    tint.to_primitive(update)?,
+                ];
+                if let Some(attr) = attr {
+                    arr.push(attr.clone().into());
+                }
+                Ok(Primitive::Array(arr))
             }
         }
     }
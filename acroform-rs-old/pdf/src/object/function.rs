@@ -73,18 +73,22 @@ impl Function {
             _ => bail!("unimplemted function {:?}", self)
         }
     }
+    /// Number of inputs, 0 if unknown.
     pub fn input_dim(&self) -> usize {
         match *self {
             Function::PostScript { ref domain, .. } => domain.len() / 2,
             Function::Sampled(ref f) => f.input.len(),
-            _ => panic!()
+            Function::Interpolated(_) => 1,
+            Function::Stiching | Function::Calculator => 0,
         }
     }
+    /// Number of outputs, 0 if unknown.
     pub fn output_dim(&self) -> usize {
         match *self {
             Function::PostScript { ref range, .. } => range.len() / 2,
             Function::Sampled(ref f) => f.output.len(),
-            _ => panic!()
+            Function::Interpolated(ref parts) => parts.len(),
+            Function::Stiching | Function::Calculator => 0,
         }
     }
 }
@@ -103,7 +107,10 @@ impl FromDict for Function {
                     _ => bail!("unknown dimensions")
                 };
                 let mut parts = Vec::with_capacity(n_dim);
-                let input_range = (raw.domain[0], raw.domain[1]);
+                let input_range = match raw.domain[..] {
+                    [a, b, ..] => (a, b),
+                    _ => bail!("function domain needs two values, found {:?}", raw.domain)
+                };
                 for dim in 0 .. n_dim {
                     let output_range = (
                         raw.range.as_ref().and_then(|r| r.get(2*dim).cloned()).unwrap_or(-INFINITY),
@@ -118,10 +125,7 @@ impl FromDict for Function {
                 }
                 Ok(Function::Interpolated(parts))
             },
-            i => {
-                dbg!(raw);
-                bail!("unsupported function type {}", i)
-            }
+            i => bail!("unsupported function type {}", i)
         }
     }
 }
@@ -137,7 +141,8 @@ impl Object for Function {
                         let s = std::str::from_utf8(&data)?;
                         let func = PsFunc::parse(s)?;
                         let info = stream.info.info;
-                        Ok(Function::PostScript { func, domain: info.domain, range: info.range.unwrap() })
+                        let range = info.range.ok_or_else(|| PdfError::MissingEntry { typ: "Function", field: "Range".into() })?;
+                        Ok(Function::PostScript { func, domain: info.domain, range })
                     },
                     0 => {
                         let info = stream.info.info;
@@ -149,7 +154,7 @@ impl Object for Function {
 
                         let size = try_opt!(info.size);
                         let range = try_opt!(info.range);
-                        let encode = info.encode.unwrap_or_else(|| size.iter().flat_map(|&n| [0.0, (n-1) as f32]).collect());
+                        let encode = info.encode.unwrap_or_else(|| size.iter().flat_map(|&n| [0.0, n.saturating_sub(1) as f32]).collect());
                         let decode = info.decode.unwrap_or_else(|| range.clone());
 
                         Ok(Function::Sampled(SampledFunction {
@@ -180,7 +185,7 @@ impl Object for Function {
 }
 impl ObjectWrite for Function {
     fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
-        unimplemented!()
+        bail!("writing functions is not supported")
         /*
         let dict = match self {
             Function::Interpolated(parts) => {
@@ -221,6 +226,8 @@ impl SampledFunctionInput {
     fn map(&self, x: f32) -> (usize, usize, f32) {
         let x = x.clamp(self.domain.0, self.domain.1);
         let y = x.mul_add(self.encode_scale, self.encode_offset);
+        // samples outside of the table are clipped
+        let y = y.clamp(0.0, self.size.saturating_sub(1) as f32);
         (y.floor() as usize, self.size, y.fract())
     }
 }
@@ -265,16 +272,17 @@ impl SampledFunction {
                 match self.order {
                     Interpolation::Linear => {
                         let (i, _, s) = self.input[0].map(x[0]);
-                        let idx = i * n_out;
+                        let idx = i.saturating_mul(n_out);
 
-                        for (o, &a) in out.iter_mut().zip(&self.data[idx..]) {
+                        out.fill(0.0);
+                        for (o, &a) in out.iter_mut().zip(self.data.get(idx..).unwrap_or_default()) {
                             *o = a as f32 * (1. - s);
                         }
-                        for (o, &b) in out.iter_mut().zip(&self.data[idx + n_out..]) {
+                        for (o, &b) in out.iter_mut().zip(self.data.get(idx.saturating_add(n_out)..).unwrap_or_default()) {
                             *o += b as f32 * s;
                         }
                     }
-                    _ => unimplemented!()
+                    ref o => bail!("unsupported interpolation {:?} for sampled function", o)
                 }
             }
             2 => match self.order {
@@ -285,10 +293,10 @@ impl SampledFunction {
                     let (g0, g1) = (1. - f0, 1. - f1);
                     
                     out.fill(0.0);
-                    let mut add = |i0, i1, f| {
-                        let idx = (i0 + s0 * i1) * n_out;
+                    let mut add = |i0: usize, i1: usize, f| {
+                        let idx = s0.checked_mul(i1).and_then(|n| n.checked_add(i0)).and_then(|n| n.checked_mul(n_out));
                         
-                        if let Some(part) = self.data.get(idx .. idx+n_out) {
+                        if let Some(part) = idx.and_then(|idx| self.data.get(idx .. idx.checked_add(n_out)?)) {
                             for (o, &b) in out.iter_mut().zip(part) {
                                 *o += f * b as f32;
                             }
@@ -300,7 +308,7 @@ impl SampledFunction {
                     add(i0, j1, g0 * f1);
                     add(j0, j1, f0 * f1);
                 }
-                _ => unimplemented!()
+                ref o => bail!("unsupported interpolation {:?} for sampled function", o)
             }
             3 => match self.order {
                 Interpolation::Linear => {
@@ -311,10 +319,12 @@ impl SampledFunction {
                     let (g0, g1, g2) = (1. - f0, 1. - f1, 1. - f2);
                     
                     out.fill(0.0);
-                    let mut add = |i0, i1, i2, f| {
-                        let idx = (i0 + s0 * (i1 + s1 * i2)) * n_out;
+                    let mut add = |i0: usize, i1: usize, i2: usize, f| {
+                        let idx = s1.checked_mul(i2).and_then(|n| n.checked_add(i1))
+                            .and_then(|n| n.checked_mul(s0)).and_then(|n| n.checked_add(i0))
+                            .and_then(|n| n.checked_mul(n_out));
                         
-                        if let Some(part) = self.data.get(idx .. idx+n_out) {
+                        if let Some(part) = idx.and_then(|idx| self.data.get(idx .. idx.checked_add(n_out)?)) {
                             for (o, &b) in out.iter_mut().zip(part) {
                                 *o += f * b as f32;
                             }
@@ -331,7 +341,7 @@ impl SampledFunction {
                     add(i0, j1, j2, g0 * f1 * f2);
                     add(j0, j1, j2, f0 * f1 * f2);
                 }
-                _ => unimplemented!()
+                ref o => bail!("unsupported interpolation {:?} for sampled function", o)
             }
             n => bail!("Order {}", n)
         }
@@ -391,12 +401,12 @@ impl PsFunc {
                 PsOp::Roll => {
                     let j = stack.pop().ok_or(PostScriptError::StackUnderflow)? as isize;
                     let n = stack.pop().ok_or(PostScriptError::StackUnderflow)? as usize;
+                    if n > stack.len() { return Err(PostScriptError::StackUnderflow); }
                     let start = stack.len() - n;
                     let slice = &mut stack[start..];
-                    if j > 0 {
-                        slice.rotate_right(j as usize);
-                    } else {
-                        slice.rotate_left(-j as usize);
+                    if n > 0 {
+                        // rolling left by j is rolling right by n - j
+                        slice.rotate_right(j.rem_euclid(n as isize) as usize);
                     }
                 }
                 PsOp::Index => {
@@ -430,7 +440,8 @@ impl PsFunc {
         let start = s.find('{').ok_or(PdfError::PostScriptParse)?;
         let end = s.rfind('}').ok_or(PdfError::PostScriptParse)?;
 
-        let ops: Result<Vec<_>, _> = s[start + 1 .. end].split_ascii_whitespace().map(PsOp::parse).collect();
+        let body = s.get(start + 1 .. end).ok_or(PdfError::PostScriptParse)?;
+        let ops: Result<Vec<_>, _> = body.split_ascii_whitespace().map(PsOp::parse).collect();
         Ok(PsFunc { ops: ops? })
     }
 }
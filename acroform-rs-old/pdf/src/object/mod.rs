@@ -37,6 +37,24 @@ pub struct ParseOptions {
     pub allow_xref_error: bool,
     pub allow_invalid_ops: bool,
     pub allow_missing_endobj: bool,
+    /// Look for the `%PDF-` header anywhere in the file and skip garbage after `%%EOF`.
+    pub allow_garbage: bool,
+    /// Find the end of a stream by scanning for `endstream` when `/Length` is wrong or missing.
+    pub allow_wrong_length: bool,
+    /// Maximum size of a single decoded stream, in bytes.
+    pub max_stream_size: usize,
+    /// Maximum number of bytes decoded from all streams of a document together.
+    pub max_total_decoded: usize,
+    /// Maximum number of elements in an array or entries in a dictionary.
+    pub max_collection_len: usize,
+    /// Maximum nesting depth of arrays and dictionaries within one object.
+    pub max_depth: usize,
+    /// Maximum number of references followed in a row: objects that are nothing but a
+    /// reference to another one, and objects loaded while loading others, such as a
+    /// page loading its parent page tree nodes.
+    pub max_reference_depth: usize,
+    /// Maximum number of objects (the `/Size` of the xref table).
+    pub max_objects: usize,
 }
 impl ParseOptions {
     pub const fn tolerant() -> Self {
@@ -45,6 +63,9 @@ impl ParseOptions {
             allow_xref_error: true,
             allow_invalid_ops: true,
             allow_missing_endobj: true,
+            allow_garbage: true,
+            allow_wrong_length: true,
+            ..ParseOptions::strict()
         }
     }
     pub const fn strict() -> Self {
@@ -53,6 +74,14 @@ impl ParseOptions {
             allow_xref_error: false,
             allow_invalid_ops: true,
             allow_missing_endobj: false,
+            allow_garbage: false,
+            allow_wrong_length: false,
+            max_stream_size: 256 << 20,
+            max_total_decoded: 1 << 30,
+            max_collection_len: 1 << 20,
+            max_depth: 20,
+            max_reference_depth: 20,
+            max_objects: crate::backend::MAX_ID as usize,
         }
     }
 }
@@ -113,12 +142,24 @@ pub trait Updater {
     fn fulfill<T: ObjectWrite>(&mut self, promise: PromisedRef<T>, obj: T) -> Result<RcRef<T>>;
 }
 
+/// An `Updater` for objects that must not create new objects.
+///
+/// `create`, `update` and `fulfill` fail, `promise` hands out object 0, which can never be fulfilled.
 pub struct NoUpdate;
 impl Updater for NoUpdate {
-    fn create<T: ObjectWrite>(&mut self, _obj: T) -> Result<RcRef<T>> { panic!() }
-    fn update<T: ObjectWrite>(&mut self, _old: PlainRef, _obj: T) -> Result<RcRef<T>> { panic!() }
-    fn promise<T: Object>(&mut self) -> PromisedRef<T> { panic!() }
-    fn fulfill<T: ObjectWrite>(&mut self, _promise: PromisedRef<T>, _obj: T) -> Result<RcRef<T>> { panic!() }
+    fn create<T: ObjectWrite>(&mut self, _obj: T) -> Result<RcRef<T>> {
+        bail!("can't create objects here")
+    }
+    fn update<T: ObjectWrite>(&mut self, old: PlainRef, _obj: T) -> Result<RcRef<T>> {
+        bail!("can't update object {:?} here", old)
+    }
+    /// The returned ref is `0 0 R`, which is never a valid object; passing it to `fulfill` fails.
+    fn promise<T: Object>(&mut self) -> PromisedRef<T> {
+        PromisedRef::new(PlainRef { id: 0, gen: 0 })
+    }
+    fn fulfill<T: ObjectWrite>(&mut self, promise: PromisedRef<T>, _obj: T) -> Result<RcRef<T>> {
+        bail!("can't fulfill promise {:?} here", promise.get_inner())
+    }
 }
 
 pub trait ObjectWrite {
@@ -608,7 +649,8 @@ impl<T: Object> Object for Vec<T> {
             Primitive::Array(_) => {
                 p.resolve(r)?.into_array()?
                     .into_iter()
-                    .map(|p| T::from_primitive(p, r))
+                    .enumerate()
+                    .map(|(i, p)| T::from_primitive(p, r).map_err(|e| e.at_index(i)))
                     .collect::<Result<Vec<T>>>()?
             },
             Primitive::Null => {
@@ -694,6 +736,7 @@ impl Trace for Primitive {
             Primitive::Reference(r) => cb(r),
             Primitive::Array(ref parts) => parts.iter().for_each(|p| p.trace(cb)),
             Primitive::Dictionary(ref dict) => dict.values().for_each(|p| p.trace(cb)),
+            Primitive::Stream(ref s) => s.info.values().for_each(|p| p.trace(cb)),
             _ => ()
         }
     }
@@ -706,7 +749,7 @@ impl<V: Object> Object for HashMap<Name, V> {
             Primitive::Dictionary (dict) => {
                 let mut new = Self::new();
                 for (key, val) in dict.iter() {
-                    new.insert(key.clone(), V::from_primitive(val.clone(), resolve)?);
+                    new.insert(key.clone(), V::from_primitive(val.clone(), resolve).map_err(|e| e.at_key(key))?);
                 }
                 Ok(new)
             }
@@ -5,7 +5,7 @@ use crate::object::*;
 use crate::primitive::*;
 use crate::error::*;
 use crate::parser::Lexer;
-use crate::enc::{StreamFilter, decode};
+use crate::enc::{StreamFilter, LZWFlateParams, decode_limited, encode};
 
 use std::ops::{Deref, Range};
 use std::fmt;
@@ -80,7 +80,7 @@ impl<I: Object> Stream<I> {
                     use std::borrow::Cow;
                     let mut data: Cow<[u8]> = (&**data).into();
                     for filter in filters {
-                        data = t!(decode(&data, filter), filter).into();
+                        data = t!(decode_limited(&data, filter, resolve.options().max_stream_size), filter).into();
                     }
                     Ok(data.into())
                 }
@@ -91,6 +91,19 @@ impl<I: Object> Stream<I> {
         }
     }
 
+    /// Encode the data with `filter`, which becomes the first entry of `/Filter`.
+    ///
+    /// Only streams created in memory can be encoded.
+    pub fn encode(&mut self, filter: StreamFilter) -> Result<()> {
+        let data = match self.inner_data {
+            StreamData::Generated(ref data) => t!(encode(data, &filter), filter),
+            StreamData::Original(_, id) => bail!("stream {:?} is read from the file and can not be encoded", id),
+        };
+        self.info.filters.insert(0, filter);
+        self.inner_data = StreamData::Generated(data.into());
+        Ok(())
+    }
+
     pub fn len(&self) -> usize {
         match self.inner_data {
             StreamData::Generated(ref data) => data.len(),
@@ -119,20 +132,17 @@ impl<I: ObjectWrite> Stream<I> {
             Primitive::Null => Dictionary::new(),
             p => bail!("stream info has to be a dictionary (found {:?})", p)
         };
-        let mut params = None;
         if self.info.filters.len() > 0 {
+            let mut params = Vec::with_capacity(self.info.filters.len());
             for f in self.info.filters.iter() {
-                if let Some(para) = match f {
+                params.push(match f {
                     StreamFilter::LZWDecode(ref p) => Some(p.to_primitive(update)?),
                     StreamFilter::FlateDecode(ref p) => Some(p.to_primitive(update)?),
                     StreamFilter::DCTDecode(ref p) => Some(p.to_primitive(update)?),
                     StreamFilter::CCITTFaxDecode(ref p) => Some(p.to_primitive(update)?),
                     StreamFilter::JBIG2Decode(ref p) => Some(p.to_primitive(update)?),
                     _ => None
-                } {
-                    assert!(params.is_none());
-                    params = Some(para);
-                }
+                });
             }
             let mut filters = self.info.filters.iter().map(|filter| match filter {
                 StreamFilter::ASCIIHexDecode => "ASCIIHexDecode",
@@ -151,15 +161,20 @@ impl<I: ObjectWrite> Stream<I> {
                 0 => {},
                 1 => {
                     info.insert("Filter", filters.next().unwrap().to_primitive(update)?);
+                    if let Some(para) = params.pop().flatten() {
+                        info.insert("DecodeParms", para);
+                    }
                 }
                 _ => {
                     info.insert("Filter", Primitive::array::<Primitive, _, _, _>(filters, update)?);
+                    // one entry per filter, null where a filter has no parameters
+                    if params.iter().any(Option::is_some) {
+                        let params = params.into_iter().map(|p| p.unwrap_or(Primitive::Null)).collect();
+                        info.insert("DecodeParms", Primitive::Array(params));
+                    }
                 }
             }
         }
-        if let Some(para) = params {
-            info.insert("DecodeParms", para);
-        }
 
         let inner = match self.inner_data {
             StreamData::Generated(ref data) => {
@@ -246,14 +261,6 @@ impl<I: Default> Default for StreamInfo<I> {
     }
 }
 impl<T> StreamInfo<T> {
-/*
-    /// If the stream is not encoded, this is a no-op. `decode()` should be called whenever it's uncertain
-    /// whether the stream is encoded.
-    pub fn encode(&mut self, _filter: StreamFilter) {
-        // TODO this should add the filter to `self.filters` and encode the data with the given
-        // filter
-        unimplemented!();
-    }*/
     pub fn get_filters(&self) -> &[StreamFilter] {
         &self.filters
     }
@@ -316,7 +323,7 @@ impl<T: Object> Object for StreamInfo<T> {
     }
 }
 
-#[derive(Object, Default, Debug, DataSize)]
+#[derive(Object, ObjectWrite, Default, Debug, DataSize)]
 #[pdf(Type = "ObjStm")]
 pub struct ObjStmInfo {
     #[pdf(key = "N")]
@@ -330,10 +337,15 @@ pub struct ObjStmInfo {
     #[pdf(key = "Extends")]
     /// A reference to an eventual ObjectStream which this ObjectStream extends.
     pub extends: Option<Ref<Stream<()>>>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(DataSize)]
 pub struct ObjectStream {
+    /// Object number of each object.
+    ids:        Vec<ObjNr>,
     /// Byte offset of each object. Index is the object number.
     offsets:    Vec<usize>,
     /// The object number of this object.
@@ -346,19 +358,21 @@ impl Object for ObjectStream {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<ObjectStream> {
         let stream: Stream<ObjStmInfo> = Stream::from_primitive(p, resolve)?;
 
+        let mut ids = Vec::new();
         let mut offsets = Vec::new();
         {
             debug!("parsing stream");
             let data = stream.data(resolve)?;
             let mut lexer = Lexer::new(&data);
             for _ in 0..(stream.info.num_objects as ObjNr) {
-                let _obj_nr = lexer.next()?.to::<ObjNr>()?;
+                ids.push(lexer.next()?.to::<ObjNr>()?);
                 let offset = lexer.next()?.to::<usize>()?;
                 offsets.push(offset);
             }
         }
 
         Ok(ObjectStream {
+            ids,
             offsets,
             _id: 0, // TODO
             inner: stream
@@ -366,7 +380,49 @@ impl Object for ObjectStream {
     }
 }
 
+impl ObjectWrite for ObjectStream {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        self.inner.to_primitive(update)
+    }
+}
+
 impl ObjectStream {
+    /// Pack the given objects into a new, flate compressed object stream.
+    ///
+    /// The objects must not be streams and are stored in the given order.
+    pub fn new(objects: &[(ObjNr, &Primitive)]) -> Result<ObjectStream> {
+        let mut body = Vec::new();
+        let mut ids = Vec::with_capacity(objects.len());
+        let mut offsets = Vec::with_capacity(objects.len());
+        let mut header = String::new();
+        for &(id, primitive) in objects {
+            if let Primitive::Stream(_) = primitive {
+                bail!("streams can not be stored in an object stream");
+            }
+            ids.push(id);
+            offsets.push(body.len());
+            header += &format!("{} {} ", id, body.len());
+            primitive.serialize(&mut body)?;
+            body.push(b'\n');
+        }
+        let info = ObjStmInfo {
+            num_objects: objects.len(),
+            first: header.len(),
+            extends: None,
+            other: Dictionary::new(),
+        };
+        let mut data = header.into_bytes();
+        data.extend_from_slice(&body);
+
+        let filter = StreamFilter::FlateDecode(LZWFlateParams::default());
+        let data = encode(&data, &filter)?;
+        Ok(ObjectStream {
+            ids,
+            offsets,
+            _id: 0,
+            inner: Stream::from_compressed(info, data, vec![filter]),
+        })
+    }
     pub fn get_object_slice(&self, index: usize, resolve: &impl Resolve) -> Result<(Arc<[u8]>, Range<usize>)> {
         if index >= self.offsets.len() {
             err!(PdfError::ObjStmOutOfBounds {index, max: self.offsets.len()});
@@ -385,6 +441,10 @@ impl ObjectStream {
     pub fn n_objects(&self) -> usize {
         self.offsets.len()
     }
+    /// The object numbers of the contained objects, in the order they are stored
+    pub fn object_ids(&self) -> &[ObjNr] {
+        &self.ids
+    }
     pub fn _data(&self, resolve: &impl Resolve) -> Result<Arc<[u8]>> {
         self.inner.data(resolve)
     }
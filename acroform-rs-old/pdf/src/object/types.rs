@@ -170,6 +170,9 @@ pub struct Catalog {
 // Requirements: array
 // Collection: dict
 // NeedsRendering: bool
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, Default, Clone, DataSize)]
@@ -192,6 +195,9 @@ pub struct PageTree {
     
     #[pdf(key="CropBox")]
     pub crop_box:   Option<Rectangle>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 impl PageTree {
     pub fn page(&self, resolve: &impl Resolve, page_nr: u32) -> Result<PageRc> {
@@ -365,7 +371,10 @@ pub struct PageLabel {
     pub prefix: Option<PdfString>,
     
     #[pdf(key="St")]
-    pub start:  Option<usize>
+    pub start:  Option<usize>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, DataSize, Default, DeepClone, Clone)]
@@ -388,6 +397,9 @@ pub struct Resources {
 
     #[pdf(key="Properties")]
     pub properties: HashMap<Name, MaybeRef<Dictionary>>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 
@@ -413,6 +425,9 @@ pub struct PatternDict {
 
     #[pdf(key="Matrix")]
     pub matrix: Option<Matrix>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Debug, DataSize)]
@@ -466,7 +481,7 @@ impl DeepClone for Pattern {
                 let ops: Vec<Op> = ops.iter().map(|op| deep_clone_op(op, cloner, &old_resources, &mut resources)).collect::<Result<Vec<_>>>()?;
                 let dict = PatternDict {
                     resources: cloner.create(resources)?.get_ref(),
-                    .. *dict
+                    .. dict.clone()
                 };
                 Ok(Pattern::Stream(dict, ops))
             }
@@ -852,6 +867,9 @@ pub struct InteractiveFormDictionary {
 
     #[pdf(key="XFA")]
     pub xfa: Option<Primitive>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, Copy, Clone, PartialEq, DataSize)]
@@ -1052,6 +1070,9 @@ pub struct AppearanceStreams {
 
     #[pdf(key="D")]
     pub down: Option<MaybeRef<AppearanceStreamEntry>>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 /// An appearance stream entry can be either:
@@ -1194,8 +1215,25 @@ impl<T: Object> Object for NameTree<T> {
 }
 
 impl<T: ObjectWrite> ObjectWrite for NameTree<T> {
-    fn to_primitive(&self, _update: &mut impl Updater) -> Result<Primitive> {
-        todo!("impl ObjectWrite for NameTree")
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        let mut dict = Dictionary::new();
+        if let Some((ref min, ref max)) = self.limits {
+            dict.insert("Limits", vec![Primitive::String(min.clone()), Primitive::String(max.clone())]);
+        }
+        match self.node {
+            NameTreeNode::Leaf(ref items) => {
+                let mut names = Vec::with_capacity(items.len() * 2);
+                for (name, val) in items {
+                    names.push(Primitive::String(name.clone()));
+                    names.push(val.to_primitive(update)?);
+                }
+                dict.insert("Names", names);
+            }
+            NameTreeNode::Intermediate(ref kids) => {
+                dict.insert("Kids", kids.iter().map(|r| r.get_inner().into()).collect_vec());
+            }
+        }
+        Ok(dict.into())
     }
 }
 
@@ -1314,6 +1352,9 @@ pub struct LageLabel {
 
     #[pdf(key="St")]
     start: Option<i32>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Debug, Clone, DataSize)]
@@ -1499,6 +1540,9 @@ pub struct NameDictionary {
     #[pdf(key="Renditions")]
     renditions: NameTree<Rendition>,
     */
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 /* Embedded file streams can be associated with the document as a whole through
@@ -1516,6 +1560,9 @@ pub struct FileSpec {
     #[pdf(key="RF")]
     rf: Option<Files<RelatedFilesArray>>,
     */
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 /// Used only as elements in `FileSpec`
@@ -1531,6 +1578,9 @@ pub struct Files<T> {
     pub mac: Option<T>,
     #[pdf(key="Unix")]
     pub unix: Option<T>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 impl<T: DataSize> DataSize for Files<T> {
     const IS_DYNAMIC: bool = T::IS_DYNAMIC;
@@ -1554,6 +1604,9 @@ pub struct EmbeddedFile {
     
     #[pdf(key="Params")]
     pub params: Option<EmbeddedFileParamDict>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, Debug, Clone, DataSize, DeepClone, ObjectWrite)]
@@ -1572,6 +1625,9 @@ pub struct EmbeddedFileParamDict {
 
     #[pdf(key="CheckSum")]
     checksum: Option<PdfString>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, Debug, Clone, DataSize)]
@@ -1653,6 +1709,8 @@ pub struct Outlines {
     #[pdf(key="Last")]
     pub last: Option<Ref<OutlineItem>>,
 
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 /// ISO 32000-2:2020(E) 7.9.5 Rectangles (Pg 134)
@@ -1708,6 +1766,9 @@ pub struct MarkInformation { // TODO no /Type
     /// Indicating the presence of tag suspects
     #[pdf(key="Suspects", default="false")]
     pub suspects: bool,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, DataSize)]
@@ -1715,6 +1776,9 @@ pub struct MarkInformation { // TODO no /Type
 pub struct StructTreeRoot {
     #[pdf(key="K")]
     pub children: Vec<StructElem>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 #[derive(Object, ObjectWrite, Debug, DataSize)]
 pub struct StructElem {
@@ -1730,6 +1794,9 @@ pub struct StructElem {
     /// `Pg`: A page object representing a page on which some or all of the content items designated by the K entry are rendered.
     #[pdf(key="Pg")]
     pub page: Option<Ref<Page>>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[derive(Object, ObjectWrite, Debug, DataSize)]
@@ -1823,6 +1890,9 @@ pub struct InfoDict {
 
     #[pdf(key="Trapped")]
     pub trapped: Option<Trapped>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 #[cfg(test)]
@@ -40,7 +40,7 @@ fn boundary(data: &[u8], pos: usize, condition: impl Fn(u8) -> bool) -> usize {
 }
 
 #[inline]
-fn is_whitespace(b: u8) -> bool {
+pub(crate) fn is_whitespace(b: u8) -> bool {
     matches!(b, 0 | b' ' | b'\r' | b'\n' | b'\t')
 }
 #[inline]
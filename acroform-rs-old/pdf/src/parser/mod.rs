@@ -16,7 +16,6 @@ use crate::crypt::Decoder;
 use bitflags::bitflags;
 use istring::{SmallBytes, SmallString, IBytes};
 
-const MAX_DEPTH: usize = 20;
 
 
 bitflags! {
@@ -68,7 +67,7 @@ pub fn parse(data: &[u8], r: &impl Resolve, flags: ParseFlags) -> Result<Primiti
 /// Recursive. Can parse stream but only if its dictionary does not contain indirect references.
 /// Use `parse_stream` if this is not sufficient.
 pub fn parse_with_lexer(lexer: &mut Lexer, r: &impl Resolve, flags: ParseFlags) -> Result<Primitive> {
-    parse_with_lexer_ctx(lexer, r, None, flags, MAX_DEPTH)
+    parse_with_lexer_ctx(lexer, r, None, flags, r.options().max_depth)
 }
 
 fn parse_dictionary_object(lexer: &mut Lexer, r: &impl Resolve, ctx: Option<&Context>, max_depth: usize) -> Result<Dictionary> {
@@ -80,6 +79,9 @@ fn parse_dictionary_object(lexer: &mut Lexer, r: &impl Resolve, ctx: Option<&Con
             let key = token.reslice(1..).to_name()?;
             let obj = t!(parse_with_lexer_ctx(lexer, r, ctx, ParseFlags::ANY, max_depth));
             dict.insert(key, obj);
+            if dict.len() > r.options().max_collection_len {
+                return Err(PdfError::TooManyElements { limit: r.options().max_collection_len });
+            }
         } else if token.equals(b">>") {
             break;
         } else {
@@ -89,16 +91,44 @@ fn parse_dictionary_object(lexer: &mut Lexer, r: &impl Resolve, ctx: Option<&Con
     Ok(dict)
 }
 
-fn parse_stream_object(dict: Dictionary, lexer: &mut Lexer, r: &impl Resolve, ctx: &Context) -> Result<PdfStream> {
+fn parse_stream_object(mut dict: Dictionary, lexer: &mut Lexer, r: &impl Resolve, ctx: &Context) -> Result<PdfStream> {
     t!(lexer.next_stream());
 
     let length = match dict.get("Length") {
-        Some(&Primitive::Integer(n)) if n >= 0 => n as usize,
-        Some(&Primitive::Reference(reference)) => t!(t!(r.resolve_flags(reference, ParseFlags::INTEGER, 1)).as_usize()),
-        Some(other) => err!(PdfError::UnexpectedPrimitive { expected: "unsigned Integer or Reference", found: other.get_debug_name() }),
-        None => err!(PdfError::MissingEntry { typ: "<Stream>", field: "Length".into() }),
+        Some(&Primitive::Integer(n)) if n >= 0 => Ok(n as usize),
+        Some(&Primitive::Reference(reference)) => r.resolve_flags(reference, ParseFlags::INTEGER, 1).and_then(|p| p.as_usize()),
+        Some(other) => Err(PdfError::UnexpectedPrimitive { expected: "unsigned Integer or Reference", found: other.get_debug_name() }),
+        None => Err(PdfError::MissingEntry { typ: "<Stream>", field: "Length".into() }),
+    };
+
+    let start = lexer.get_pos();
+    let stream_substr = match read_stream_data(lexer, length) {
+        Ok(substr) => substr,
+        Err(e) if r.options().allow_wrong_length => {
+            lexer.set_pos(start);
+            match scan_stream_data(lexer) {
+                Some(substr) => {
+                    dict.insert("Length", Primitive::Integer(substr.len() as _));
+                    substr
+                }
+                None => return Err(e),
+            }
+        }
+        Err(e) => return Err(e),
     };
 
+    Ok(PdfStream {
+        inner: StreamInner::InFile {
+            id: ctx.id,
+            file_range: stream_substr.file_range(),
+        },
+        info: dict,
+    })
+}
+
+/// Read `length` bytes of stream data followed by `endstream`.
+fn read_stream_data<'a>(lexer: &mut Lexer<'a>, length: Result<usize>) -> Result<Substr<'a>> {
+    let length = t!(length);
     let stream_substr = lexer.read_n(length);
 
     if stream_substr.len() != length {
@@ -107,14 +137,26 @@ fn parse_stream_object(dict: Dictionary, lexer: &mut Lexer, r: &impl Resolve, ct
 
     // Finish
     t!(lexer.next_expect("endstream"));
+    Ok(stream_substr)
+}
 
-    Ok(PdfStream {
-        inner: StreamInner::InFile {
-            id: ctx.id,
-            file_range: stream_substr.file_range(),
-        },
-        info: dict,
-    })
+/// Find the stream data by scanning for `endstream`, for when `/Length` can't be trusted.
+/// The end-of-line marker in front of `endstream` is not part of the data.
+fn scan_stream_data<'a>(lexer: &mut Lexer<'a>) -> Option<Substr<'a>> {
+    const KEYWORD: &[u8] = b"endstream";
+    let start = lexer.get_pos();
+    let remaining = lexer.get_remaining_slice();
+    let found = remaining.windows(KEYWORD.len()).position(|w| w == KEYWORD)?;
+
+    let mut end = found;
+    if remaining[.. end].ends_with(b"\n") {
+        end -= 1;
+    }
+    if remaining[.. end].ends_with(b"\r") {
+        end -= 1;
+    }
+    lexer.set_pos(start + found + KEYWORD.len());
+    Some(lexer.new_substr(start .. start + end))
 }
 
 #[inline]
@@ -163,27 +205,24 @@ fn _parse_with_lexer_ctx(lexer: &mut Lexer, r: &impl Resolve, ctx: Option<&Conte
         // First backup position
         let pos_bk = lexer.get_pos();
 
-        let second_lexeme = t!(lexer.next());
-        if second_lexeme.is_integer() {
-            let third_lexeme = t!(lexer.next());
-            if third_lexeme.equals(b"R") {
+        // the number may end the data, as the last object of an object stream does
+        let second_lexeme = lexer.next();
+        let third_lexeme = lexer.next();
+        match (second_lexeme, third_lexeme) {
+            (Ok(second_lexeme), Ok(third_lexeme)) if second_lexeme.is_integer() && third_lexeme.equals(b"R") => {
                 // It is indeed a reference to an indirect object
                 check(flags, ParseFlags::REF)?;
                 Primitive::Reference (PlainRef {
                     id: t!(first_lexeme.to::<ObjNr>()),
                     gen: t!(second_lexeme.to::<GenNr>()),
                 })
-            } else {
+            }
+            _ => {
                 check(flags, ParseFlags::INTEGER)?;
-                // We are probably in an array of numbers - it's not a reference anyway
+                // It is but a number, maybe in an array of numbers
                 lexer.set_pos(pos_bk); // (roll back the lexer first)
                 Primitive::Integer(t!(first_lexeme.to::<i32>()))
             }
-        } else {
-            check(flags, ParseFlags::INTEGER)?;
-            // It is but a number
-            lexer.set_pos(pos_bk); // (roll back the lexer first)
-            Primitive::Integer(t!(first_lexeme.to::<i32>()))
         }
     } else if let Some(s) = first_lexeme.real_number() {
         check(flags, ParseFlags::NUMBER)?;
@@ -228,6 +267,9 @@ fn _parse_with_lexer_ctx(lexer: &mut Lexer, r: &impl Resolve, ctx: Option<&Conte
                 break;
             }
 
+            if array.len() == r.options().max_collection_len {
+                return Err(PdfError::TooManyElements { limit: r.options().max_collection_len });
+            }
             let element = t!(parse_with_lexer_ctx(lexer, r, ctx, ParseFlags::ANY, max_depth-1));
             array.push(element);
         }
@@ -299,7 +341,7 @@ fn parse_stream_with_lexer(lexer: &mut Lexer, r: &impl Resolve, ctx: &Context) -
     let first_lexeme = t!(lexer.next());
 
     let obj = if first_lexeme.equals(b"<<") {
-        let dict = t!(parse_dictionary_object(lexer, r, None, MAX_DEPTH));
+        let dict = t!(parse_dictionary_object(lexer, r, None, r.options().max_depth));
         // It might just be the dictionary in front of a stream.
         if t!(lexer.peek()).equals(b"stream") {
             let ctx = Context {
@@ -402,4 +444,14 @@ mod tests {
         assert_eq!(lx.next().unwrap().as_str().unwrap(), "TJ");
         assert!(lx.next().unwrap_err().is_eof());
     }
+
+    #[test]
+    fn number_at_end() {
+        use crate::object::{NoResolve, PlainRef};
+        use crate::primitive::Primitive;
+        use super::*;
+        assert_eq!(parse(b"42", &NoResolve, ParseFlags::ANY).unwrap(), Primitive::Integer(42));
+        assert_eq!(parse(b"12 0", &NoResolve, ParseFlags::ANY).unwrap(), Primitive::Integer(12));
+        assert_eq!(parse(b"12 0 R", &NoResolve, ParseFlags::ANY).unwrap(), Primitive::Reference(PlainRef { id: 12, gen: 0 }));
+    }
 }
@@ -1,11 +1,12 @@
 use crate::error::*;
 use crate::parser::lexer::Lexer;
 use crate::xref::{XRef, XRefSection, XRefInfo};
-use crate::primitive::{Primitive, Dictionary};
+use crate::primitive::{Primitive, Dictionary, PdfStream};
 use crate::object::*;
 use crate::parser::{parse_with_lexer, ParseFlags};
 use crate::parser::parse_object::{parse_indirect_stream};
 use std::convert::TryInto;
+use std::collections::BTreeMap;
 
 // Just the part of Parser which reads xref sections from xref stream.
 /// Takes `&mut &[u8]` so that it can "consume" data as it reads
@@ -71,9 +72,25 @@ pub fn parse_xref_stream_and_trailer(lexer: &mut Lexer, resolve: &impl Resolve)
         let trailer = t!(parse_with_lexer(lexer, resolve, ParseFlags::DICT));
         t!(trailer.into_dictionary())
     } else {
-        xref_stream.info.clone()
+        // keep only the trailer entries, the rest describes the xref stream itself
+        let mut trailer = xref_stream.info.clone();
+        for key in ["Type", "W", "Index", "Length", "Filter", "DecodeParms", "F", "FFilter", "FDecodeParms", "DL"] {
+            trailer.remove(key);
+        }
+        trailer
     };
 
+    let sections = t!(xref_stream_sections(xref_stream, resolve));
+    Ok((sections, trailer))
+}
+
+/// Reads the xref sections of the stream that the `/XRefStm` entry of a hybrid-reference file points to.
+pub fn parse_xref_stream(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<Vec<XRefSection>> {
+    let xref_stream = t!(parse_indirect_stream(lexer, resolve, None)).1;
+    xref_stream_sections(xref_stream, resolve)
+}
+
+fn xref_stream_sections(xref_stream: PdfStream, resolve: &impl Resolve) -> Result<Vec<XRefSection>> {
     let xref_stream = t!(Stream::<XRefInfo>::from_primitive(Primitive::Stream(xref_stream), resolve));
     let mut data_left = &*t!(xref_stream.data(resolve));
     
@@ -91,7 +108,37 @@ pub fn parse_xref_stream_and_trailer(lexer: &mut Lexer, resolve: &impl Resolve)
         sections.push(section);
     }
 
-    Ok((sections, trailer))
+    Ok(sections)
+}
+
+
+/// Combine the sections of the table and of the `/XRefStm` stream of a hybrid-reference file.
+///
+/// The stream is only consulted for objects that the table does not list as in use.
+pub fn merge_hybrid_sections(table: Vec<XRefSection>, stream: Vec<XRefSection>) -> Vec<XRefSection> {
+    let mut entries = BTreeMap::new();
+    for section in &table {
+        for (id, &entry) in section.entries() {
+            entries.insert(id, entry);
+        }
+    }
+    for section in &stream {
+        for (id, &entry) in section.entries() {
+            match entries.get(&id) {
+                Some(XRef::Raw { .. }) => {}
+                _ => { entries.insert(id, entry); }
+            }
+        }
+    }
+
+    let mut sections: Vec<XRefSection> = vec![];
+    for (id, entry) in entries {
+        match sections.last_mut() {
+            Some(last) if last.first_id as usize + last.entries.len() == id => last.entries.push(entry),
+            _ => sections.push(XRefSection { first_id: id as u32, entries: vec![entry] }),
+        }
+    }
+    sections
 }
 
 
@@ -100,13 +100,11 @@ fn serialize_list(arr: &[Primitive], out: &mut impl io::Write) -> Result<()> {
 
 pub fn serialize_name(s: &str, out: &mut impl io::Write) -> Result<()> {
     write!(out, "/")?;
-    for b in s.chars() {
+    for &b in s.as_bytes() {
         match b {
-            '\\' | '(' | ')' => write!(out, r"\")?,
-            c if c > '~' => panic!("only ASCII"),
-            _ => ()
+            b'!' ..= b'~' if !b"()<>[]{}/%#".contains(&b) => out.write_all(&[b])?,
+            _ => write!(out, "#{:02X}", b)?,
         }
-        write!(out, "{}", b)?;
     }
     Ok(())
 }
@@ -277,8 +275,8 @@ impl PdfStream {
 
         writeln!(out, "stream")?;
         match self.inner {
-            StreamInner::InFile { .. } => {
-                unimplemented!()
+            StreamInner::InFile { id, .. } => {
+                bail!("can't serialize stream {:?} whose data is still in the file", id)
             }
             StreamInner::Pending { ref data } => {
                 out.write_all(data)?;
@@ -456,31 +454,61 @@ impl PdfString {
     pub fn into_bytes(self) -> IBytes {
         self.data
     }
-    /// without encoding information the PdfString cannot be decoded into a String
-    /// therefore only lossy decoding is possible replacing unknown characters.
-    /// For decoding correctly see
-    /// pdf_tools/src/lib.rs
+    /// Encode `text` as a text string: PDFDocEncoding if it has all the characters,
+    /// UTF-16BE with a byte order mark otherwise.
+    pub fn from_text(text: &str) -> PdfString {
+        let pdf_doc: Option<Vec<u8>> = text.chars().map(crate::encoding::unicode_to_pdf_doc).collect();
+        // codes that read as UTF-8 would be decoded as such
+        let pdf_doc = pdf_doc.filter(|data| data.is_ascii() || std::str::from_utf8(data).is_err());
+        let data = pdf_doc.unwrap_or_else(|| {
+            [0xfe, 0xff].into_iter().chain(text.encode_utf16().flat_map(u16::to_be_bytes)).collect()
+        });
+        PdfString { data: data.into() }
+    }
+    /// Decode the string as a text string, replacing what can't be decoded.
+    ///
+    /// UTF-16BE and UTF-8 are recognized by their byte order mark, other strings are
+    /// PDFDocEncoding unless they are valid UTF-8 with non-ASCII characters,
+    /// which some writers produce.
     pub fn to_string_lossy(&self) -> String {
         if self.data.starts_with(&[0xfe, 0xff]) {
             crate::font::utf16be_to_string_lossy(&self.data[2..])
         }
+        else if let Some(utf8) = self.data.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+            String::from_utf8_lossy(utf8).into()
+        }
+        else if let Some(s) = self.legacy_utf8() {
+            s.into()
+        }
         else {
-            String::from_utf8_lossy(&self.data).into()
+            self.data.iter()
+                .map(|&b| crate::encoding::pdf_doc_to_unicode(b).unwrap_or(std::char::REPLACEMENT_CHARACTER))
+                .collect()
         }
     }
-    /// without encoding information the PdfString cannot be sensibly decoded into a String
-    /// converts to a Rust String but only works for valid UTF-8, UTF-16BE and ASCII characters
-    /// if invalid bytes found an Error is returned
+    /// Decode the string as a text string like [`to_string_lossy`](Self::to_string_lossy),
+    /// but fail on invalid UTF-16BE or UTF-8 and on undefined PDFDocEncoding codes.
     pub fn to_string(&self) -> Result<String> {
         if self.data.starts_with(&[0xfe, 0xff]) {
-            Ok(String::from(std::str::from_utf8(crate::font::utf16be_to_string(&self.data[2..])?.as_bytes())
-                .map_err(|_| PdfError::Utf8Decode)?))
+            Ok(crate::font::utf16be_to_string(&self.data[2..])?.to_string())
+        }
+        else if let Some(utf8) = self.data.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+            Ok(String::from(std::str::from_utf8(utf8).map_err(|_| PdfError::Utf8Decode)?))
+        }
+        else if let Some(s) = self.legacy_utf8() {
+            Ok(s.into())
         }
         else {
-            Ok(String::from(std::str::from_utf8(&self.data)
-                .map_err(|_| PdfError::Utf8Decode)?))
+            self.data.iter()
+                .map(|&b| crate::encoding::pdf_doc_to_unicode(b).ok_or(PdfError::PdfDocDecode { byte: b }))
+                .collect()
         }
     }
+    /// The string if it is UTF-8 without a byte order mark, told apart from PDFDocEncoding
+    /// by its non-ASCII characters
+    fn legacy_utf8(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok().filter(|s| !s.is_ascii())
+    }
 }
 impl<'a> From<&'a str> for PdfString {
     fn from(value: &'a str) -> Self {
@@ -869,11 +897,58 @@ mod tests {
         let s = PdfString::new([b'm', b'i', b't', 0xc3, 0xa4 /*ä*/].as_slice().into());
         assert_eq!(s.to_string_lossy(), "mitä");
         assert_eq!(s.to_string().unwrap(), "mitä");
-        // verify valid ISO-8859-1 bytestream with umlaut fails
+        // verify PDFDocEncoding, which matches ISO-8859-1 for the umlaut
         let s = PdfString::new([b'm', b'i', b't', 0xe4/*ä in latin1*/].as_slice().into());
+        assert_eq!(s.to_string_lossy(), "mitä");
+        assert_eq!(s.to_string().unwrap(), "mitä");
+        // verify undefined PDFDocEncoding codes fail
+        let s = PdfString::new([b'm', b'i', b't', 0x7f].as_slice().into());
         let repl_ch = ['m', 'i', 't', std::char::REPLACEMENT_CHARACTER].iter().collect::<String>();
         assert_eq!(s.to_string_lossy(), repl_ch);
-        assert!(s.to_string().is_err()); // FIXME verify it is a PdfError::Utf16Decode
+        assert!(matches!(s.to_string(), Err(crate::error::PdfError::PdfDocDecode { byte: 0x7f })));
+    }
+
+    #[test]
+    fn pdf_doc_string() {
+        let s = PdfString::new([0x80, b' ', 0x84, b' ', 0x92, b' ', 0xa0, b' ', 0x18].as_slice().into());
+        assert_eq!(s.to_string().unwrap(), "\u{2022} \u{2014} \u{2122} \u{20ac} \u{02d8}");
+    }
+
+    #[test]
+    fn utf8_bom_string() {
+        let s = PdfString::new([0xef, 0xbb, 0xbf, b'a', 0xce, 0xb1].as_slice().into());
+        assert_eq!(s.to_string_lossy(), "a\u{3b1}");
+        assert_eq!(s.to_string().unwrap(), "a\u{3b1}");
+        let s = PdfString::new([0xef, 0xbb, 0xbf, 0xce].as_slice().into());
+        assert!(s.to_string().is_err());
+    }
+
+    #[test]
+    fn text_string_encoding() {
+        assert_eq!(PdfString::from_text("Caf\u{e9} \u{2013} \u{20ac}5").as_bytes(), b"Caf\xe9 \x85 \xa05");
+        let s = PdfString::from_text("\u{3b1}\u{3b2}");
+        assert_eq!(s.as_bytes(), [0xfe, 0xff, 0x03, 0xb1, 0x03, 0xb2]);
+        for text in ["plain", "\u{2022} \u{0141}\u{fb01} \u{a0}", "\u{3b1}\u{1f600}", "\u{c3}\u{a9}"] {
+            assert_eq!(PdfString::from_text(text).to_string().unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn name_escapes() {
+        use crate::parser::{parse, ParseFlags};
+
+        let mut out = vec![];
+        super::serialize_name("Adobe Acrobat (32-bit)#1", &mut out).unwrap();
+        assert_eq!(out, b"/Adobe#20Acrobat#20#2832-bit#29#231");
+        let p = parse(&out, &NoResolve, ParseFlags::NAME).unwrap();
+        assert_eq!(p.as_name().unwrap(), "Adobe Acrobat (32-bit)#1");
+
+        // names are bytes, UTF-8 is written escaped
+        let mut out = vec![];
+        super::serialize_name("Gr\u{f6}\u{df}e/A", &mut out).unwrap();
+        assert_eq!(out, b"/Gr#C3#B6#C3#9Fe#2FA");
+        let p = parse(&out, &NoResolve, ParseFlags::NAME).unwrap();
+        assert_eq!(p.as_name().unwrap(), "Gr\u{f6}\u{df}e/A");
     }
 
     #[test]
@@ -0,0 +1,452 @@
+//! Public-key security handler (`/Adobe.PubSec`), 7.6.5
+//!
+//! The file key is derived from a seed that is encrypted for each recipient
+//! in a PKCS#7 / CMS `EnvelopedData` structure stored in `/Recipients`.
+//! Only key transport recipients (RSA) are supported.
+
+use aes::cipher::{BlockDecryptMut, KeyIvInit};
+use aes::cipher::block_padding::Pkcs7;
+use rsa::{RsaPrivateKey, Pkcs1v15Encrypt, Oaep};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use sha1::Sha1;
+use crate::error::{PdfError, Result};
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xa0;
+const TAG_CONTEXT_0_PRIMITIVE: u8 = 0x80;
+
+// 1.2.840.113549.1.7.3
+const OID_ENVELOPED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x03];
+// 1.2.840.113549.1.1.1
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+// 1.2.840.113549.1.1.7
+const OID_RSAES_OAEP: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x07];
+// 2.16.840.1.101.3.4.1.{2, 22, 42}
+const OID_AES128_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x02];
+const OID_AES192_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x16];
+const OID_AES256_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+// 1.2.840.113549.3.7
+const OID_DES_EDE3_CBC: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x03, 0x07];
+
+/// Minimal DER reader, just enough for CMS envelopes and X.509 certificates.
+struct Der<'a> {
+    data: &'a [u8],
+}
+impl<'a> Der<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Der { data }
+    }
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+    /// Read the next element. Returns the tag, the content and the complete encoding.
+    fn read(&mut self) -> Result<(u8, &'a [u8], &'a [u8])> {
+        let data = self.data;
+        let (&tag, rest) = data.split_first().ok_or(PdfError::EOF)?;
+        let (&first, rest) = rest.split_first().ok_or(PdfError::EOF)?;
+        let (len, rest) = match first {
+            n @ 0 ..= 0x7f => (n as usize, rest),
+            0x80 => bail!("indefinite length encoding is not supported"),
+            n => {
+                let n = (n & 0x7f) as usize;
+                if n > 4 || rest.len() < n {
+                    bail!("invalid DER length");
+                }
+                let len = rest[..n].iter().fold(0, |len, &b| len << 8 | b as usize);
+                (len, &rest[n..])
+            }
+        };
+        if rest.len() < len {
+            bail!("DER element exceeds its container");
+        }
+        let header_len = data.len() - rest.len();
+        self.data = &rest[len..];
+        Ok((tag, &rest[..len], &data[..header_len + len]))
+    }
+    /// Read the next element and return its content, which must have the given tag.
+    fn expect(&mut self, expected: u8) -> Result<&'a [u8]> {
+        let (tag, content, _) = self.read()?;
+        if tag != expected {
+            bail!("unexpected DER tag {:#04x}, expected {:#04x}", tag, expected);
+        }
+        Ok(content)
+    }
+}
+
+/// Strip the sign padding of a DER INTEGER
+fn unsigned(int: &[u8]) -> &[u8] {
+    match int {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        _ => int,
+    }
+}
+
+/// The private key and certificate of a recipient of a document encrypted
+/// with the public-key security handler.
+pub struct Recipient {
+    key: RsaPrivateKey,
+    /// DER encoding of the certificate issuer name
+    issuer: Vec<u8>,
+    serial: Vec<u8>,
+}
+impl Recipient {
+    /// Create a recipient from a RSA key and the DER encoded certificate.
+    pub fn new(key: RsaPrivateKey, certificate: &[u8]) -> Result<Recipient> {
+        let mut cert = Der::new(t!(Der::new(certificate).expect(TAG_SEQUENCE)));
+        let mut tbs = Der::new(t!(cert.expect(TAG_SEQUENCE)));
+        if tbs.peek_tag() == Some(TAG_CONTEXT_0) {
+            // version
+            tbs.read()?;
+        }
+        let serial = unsigned(t!(tbs.expect(TAG_INTEGER))).to_vec();
+        // signature algorithm
+        t!(tbs.expect(TAG_SEQUENCE));
+        let (tag, _, issuer) = t!(tbs.read());
+        if tag != TAG_SEQUENCE {
+            bail!("invalid certificate issuer");
+        }
+        Ok(Recipient { key, issuer: issuer.to_vec(), serial })
+    }
+
+    /// Create a recipient from a DER encoded private key (PKCS#8 or PKCS#1)
+    /// and a DER encoded X.509 certificate.
+    pub fn from_der(private_key: &[u8], certificate: &[u8]) -> Result<Recipient> {
+        let key = RsaPrivateKey::from_pkcs8_der(private_key)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_der(private_key))
+            .map_err(|e| other!("invalid private key: {}", e))?;
+        Recipient::new(key, certificate)
+    }
+
+    /// Create a recipient from a PEM encoded private key (PKCS#8 or PKCS#1)
+    /// and a PEM encoded X.509 certificate.
+    pub fn from_pem(private_key: &str, certificate: &str) -> Result<Recipient> {
+        let key = RsaPrivateKey::from_pkcs8_pem(private_key)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(private_key))
+            .map_err(|e| other!("invalid private key: {}", e))?;
+        let (label, certificate) = rsa::pkcs8::der::pem::decode_vec(certificate.as_bytes())
+            .map_err(|e| other!("invalid certificate: {}", e))?;
+        if label != "CERTIFICATE" {
+            bail!("expected a CERTIFICATE, found {}", label);
+        }
+        Recipient::new(key, &certificate)
+    }
+
+    fn decrypt_key(&self, algorithm: &[u8], encrypted_key: &[u8]) -> Result<Vec<u8>> {
+        let mut alg = Der::new(algorithm);
+        let oid = t!(alg.expect(TAG_OID));
+        let key = if oid == OID_RSA_ENCRYPTION {
+            self.key.decrypt(Pkcs1v15Encrypt, encrypted_key)
+        } else if oid == OID_RSAES_OAEP {
+            // only the default parameters (SHA-1, MGF1 with SHA-1) are supported
+            if !alg.is_empty() && !t!(alg.expect(TAG_SEQUENCE)).is_empty() {
+                bail!("unsupported RSAES-OAEP parameters");
+            }
+            self.key.decrypt(Oaep::new::<Sha1>(), encrypted_key)
+        } else {
+            bail!("unsupported key encryption algorithm {:02x?}", oid);
+        };
+        key.map_err(|_| PdfError::DecryptionFailure)
+    }
+
+    /// Decrypt one entry of `/Recipients`.
+    ///
+    /// Returns `None` if the envelope is not addressed to this recipient.
+    pub fn open_envelope(&self, envelope: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut content_info = Der::new(t!(Der::new(envelope).expect(TAG_SEQUENCE)));
+        if t!(content_info.expect(TAG_OID)) != OID_ENVELOPED_DATA {
+            bail!("recipient is not a CMS EnvelopedData");
+        }
+        let mut enveloped = Der::new(t!(Der::new(t!(content_info.expect(TAG_CONTEXT_0))).expect(TAG_SEQUENCE)));
+        // version
+        t!(enveloped.expect(TAG_INTEGER));
+        if enveloped.peek_tag() == Some(TAG_CONTEXT_0) {
+            // originatorInfo
+            enveloped.read()?;
+        }
+
+        let mut content_key = None;
+        let mut infos = Der::new(t!(enveloped.expect(TAG_SET)));
+        while !infos.is_empty() {
+            let (tag, info, _) = t!(infos.read());
+            if tag != TAG_SEQUENCE {
+                // not a KeyTransRecipientInfo
+                continue;
+            }
+            let mut info = Der::new(info);
+            t!(info.expect(TAG_INTEGER));
+            let (tag, rid, _) = t!(info.read());
+            let matches = match tag {
+                TAG_SEQUENCE => {
+                    let mut rid = Der::new(rid);
+                    let (_, _, issuer) = t!(rid.read());
+                    let serial = unsigned(t!(rid.expect(TAG_INTEGER)));
+                    Some(issuer == self.issuer && serial == self.serial)
+                }
+                // subjectKeyIdentifier, we have to try
+                _ => None,
+            };
+            if matches == Some(false) {
+                continue;
+            }
+            let algorithm = t!(info.expect(TAG_SEQUENCE));
+            let encrypted_key = t!(info.expect(TAG_OCTET_STRING));
+            match self.decrypt_key(algorithm, encrypted_key) {
+                Ok(key) => {
+                    content_key = Some(key);
+                    break;
+                }
+                Err(e) if matches == Some(true) => return Err(e),
+                Err(_) => continue,
+            }
+        }
+        let content_key = match content_key {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+
+        let mut encrypted_content_info = Der::new(t!(enveloped.expect(TAG_SEQUENCE)));
+        t!(encrypted_content_info.expect(TAG_OID));
+        let mut alg = Der::new(t!(encrypted_content_info.expect(TAG_SEQUENCE)));
+        let cipher = t!(alg.expect(TAG_OID));
+        let iv = t!(alg.expect(TAG_OCTET_STRING));
+        let mut data = match t!(encrypted_content_info.read()) {
+            (TAG_CONTEXT_0_PRIMITIVE, content, _) => content.to_vec(),
+            (TAG_CONTEXT_0, parts, _) => {
+                let mut parts = Der::new(parts);
+                let mut data = vec![];
+                while !parts.is_empty() {
+                    data.extend_from_slice(t!(parts.expect(TAG_OCTET_STRING)));
+                }
+                data
+            }
+            (tag, _, _) => bail!("unexpected encrypted content tag {:#04x}", tag),
+        };
+
+        fn decrypt<C: KeyIvInit + BlockDecryptMut>(key: &[u8], iv: &[u8], data: &mut [u8]) -> Result<usize> {
+            let cipher = C::new_from_slices(key, iv).map_err(|_| PdfError::DecryptionFailure)?;
+            let plain = cipher.decrypt_padded_mut::<Pkcs7>(data).map_err(|_| PdfError::DecryptionFailure)?;
+            Ok(plain.len())
+        }
+        let len = match cipher {
+            OID_AES128_CBC => decrypt::<cbc::Decryptor<aes::Aes128>>(&content_key, iv, &mut data)?,
+            OID_AES192_CBC => decrypt::<cbc::Decryptor<aes::Aes192>>(&content_key, iv, &mut data)?,
+            OID_AES256_CBC => decrypt::<cbc::Decryptor<aes::Aes256>>(&content_key, iv, &mut data)?,
+            OID_DES_EDE3_CBC => decrypt::<cbc::Decryptor<des::TdesEde3>>(&content_key, iv, &mut data)?,
+            oid => bail!("unsupported content encryption algorithm {:02x?}", oid),
+        };
+        data.truncate(len);
+        Ok(Some(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The fixtures in `files/pubsec` were generated with
+    //!
+    //! ```sh
+    //! openssl req -x509 -newkey rsa:2048 -nodes -keyout recipient.key -out recipient.crt -days 36500 -subj "/CN=acroform test recipient"
+    //! openssl req -x509 -newkey rsa:2048 -nodes -keyout other.key -out other.crt -days 36500 -subj "/CN=acroform other recipient"
+    //! python3 -c "import sys; sys.stdout.buffer.write(bytes(range(1,21)) + (-292).to_bytes(4,'big',signed=True))" > seed.bin
+    //! openssl cms -encrypt -binary -outform DER -aes256 -in seed.bin -out envelope_aes256.der recipient.crt
+    //! openssl cms -encrypt -binary -outform DER -des3 -in seed.bin -out envelope_des3.der recipient.crt
+    //! openssl cms -encrypt -binary -outform DER -aes128 -in seed.bin -out envelope_oaep.der -recip recipient.crt -keyopt rsa_padding_mode:oaep
+    //! openssl cms -encrypt -binary -outform DER -aes256 -in seed.bin -out envelope_both.der other.crt recipient.crt
+    //! ```
+    use super::Recipient;
+    use crate::crypt::{Rc4, Permissions};
+    use crate::error::PdfError;
+    use aes::cipher::{BlockEncryptMut, KeyIvInit};
+    use aes::cipher::block_padding::Pkcs7;
+    use sha1::{Sha1, Digest};
+    use sha2::Sha256;
+
+    const SEED: &[u8] = include_bytes!("../../files/pubsec/seed.bin");
+
+    fn recipient() -> Recipient {
+        Recipient::from_pem(
+            include_str!("../../files/pubsec/recipient.key"),
+            include_str!("../../files/pubsec/recipient.crt"),
+        ).unwrap()
+    }
+    fn other() -> Recipient {
+        Recipient::from_pem(
+            include_str!("../../files/pubsec/other.key"),
+            include_str!("../../files/pubsec/other.crt"),
+        ).unwrap()
+    }
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    #[test]
+    fn open_envelopes() {
+        let recipient = recipient();
+        for envelope in [
+            &include_bytes!("../../files/pubsec/envelope_aes256.der")[..],
+            &include_bytes!("../../files/pubsec/envelope_des3.der")[..],
+            &include_bytes!("../../files/pubsec/envelope_oaep.der")[..],
+            &include_bytes!("../../files/pubsec/envelope_both.der")[..],
+        ] {
+            assert_eq!(recipient.open_envelope(envelope).unwrap().as_deref(), Some(SEED));
+        }
+        let other = other();
+        assert_eq!(other.open_envelope(include_bytes!("../../files/pubsec/envelope_aes256.der")).unwrap(), None);
+        assert_eq!(other.open_envelope(include_bytes!("../../files/pubsec/envelope_both.der")).unwrap().as_deref(), Some(SEED));
+    }
+
+    /// Build a document with the given (already encrypted) `/Title` in the info dictionary, object 3.
+    fn document(encrypt_dict: &str, title: &[u8]) -> Vec<u8> {
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [] /Count 0 >>".to_string(),
+            format!("<< /Title <{}> >>", hex(title)),
+            encrypt_dict.to_string(),
+        ];
+        let mut data = b"%PDF-1.7\n".to_vec();
+        let mut offsets = vec![];
+        for (i, obj) in objects.iter().enumerate() {
+            offsets.push(data.len());
+            data.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, obj).as_bytes());
+        }
+        let xref_pos = data.len();
+        data.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f\r\n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            data.extend_from_slice(format!("{:010} 00000 n\r\n", offset).as_bytes());
+        }
+        data.extend_from_slice(format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R /Encrypt 4 0 R >>\nstartxref\n{}\n%%EOF",
+            objects.len() + 1, xref_pos
+        ).as_bytes());
+        data
+    }
+
+    fn object_key(key: &[u8], salt: &[u8]) -> Vec<u8> {
+        // object 3, generation 0
+        let mut data = key.to_vec();
+        data.extend_from_slice(&[3, 0, 0, 0, 0]);
+        data.extend_from_slice(salt);
+        let hash = md5::compute(&data);
+        hash[..(key.len() + 5).min(16)].to_vec()
+    }
+
+    /// IV followed by the ciphertext, as stored in PDF strings
+    fn encrypt_cbc<C: KeyIvInit + BlockEncryptMut>(key: &[u8], plain: &[u8]) -> Vec<u8> {
+        let iv = [7u8; 16];
+        let mut buf = plain.to_vec();
+        buf.resize(plain.len() + 16, 0);
+        let len = C::new_from_slices(key, &iv).unwrap()
+            .encrypt_padded_mut::<Pkcs7>(&mut buf, plain.len()).unwrap()
+            .len();
+        buf.truncate(len);
+        [&iv[..], &buf].concat()
+    }
+
+    fn open(data: Vec<u8>, recipient: &Recipient) -> crate::error::Result<(String, Permissions)> {
+        let file = crate::file::FileOptions::uncached().recipient(recipient).load(data)?;
+        let title = file.trailer.info_dict.as_ref().unwrap().title.as_ref().unwrap().to_string_lossy();
+        Ok((title, file.permissions()))
+    }
+
+    #[test]
+    fn s4_rc4() {
+        let envelope = include_bytes!("../../files/pubsec/envelope_aes256.der");
+        let mut hash = Sha1::new();
+        hash.update(&SEED[..20]);
+        hash.update(envelope);
+        let key = &hash.finalize()[..16];
+
+        let mut title = b"pubsec rc4".to_vec();
+        Rc4::encrypt(&object_key(key, b""), &mut title);
+        let dict = format!(
+            "<< /Filter /Adobe.PubSec /SubFilter /adbe.pkcs7.s4 /V 2 /Length 128 /Recipients [<{}>] >>",
+            hex(envelope)
+        );
+        let (title, permissions) = open(document(&dict, &title), &recipient()).unwrap();
+        assert_eq!(title, "pubsec rc4");
+        assert_eq!(permissions, Permissions::from_p(-292, 4));
+    }
+
+    #[test]
+    fn s5_aesv2() {
+        let envelopes = [
+            &include_bytes!("../../files/pubsec/envelope_des3.der")[..],
+            &include_bytes!("../../files/pubsec/envelope_both.der")[..],
+        ];
+        let mut hash = Sha1::new();
+        hash.update(&SEED[..20]);
+        for envelope in envelopes {
+            hash.update(envelope);
+        }
+        let key = &hash.finalize()[..16];
+
+        let title = encrypt_cbc::<cbc::Encryptor<aes::Aes128>>(&object_key(key, b"sAlT"), b"pubsec aes-128");
+        let dict = format!(
+            "<< /Filter /Adobe.PubSec /SubFilter /adbe.pkcs7.s5 /V 4 /Length 128 /StmF /DefaultCryptFilter /StrF /DefaultCryptFilter \
+            /CF << /DefaultCryptFilter << /CFM /AESV2 /Length 128 /Recipients [<{}> <{}>] >> >> >>",
+            hex(envelopes[0]), hex(envelopes[1])
+        );
+        let data = document(&dict, &title);
+        assert_eq!(title_of(data.clone(), &recipient()), "pubsec aes-128");
+        assert_eq!(title_of(data, &other()), "pubsec aes-128");
+    }
+
+    #[test]
+    fn broken_envelopes_are_skipped() {
+        let broken = b"\x30\x03\x02\x01\x00";
+        let envelope = include_bytes!("../../files/pubsec/envelope_aes256.der");
+        let mut hash = Sha1::new();
+        hash.update(&SEED[..20]);
+        hash.update(broken);
+        hash.update(envelope);
+        let key = &hash.finalize()[..16];
+
+        let mut title = b"pubsec rc4".to_vec();
+        Rc4::encrypt(&object_key(key, b""), &mut title);
+        let dict = format!(
+            "<< /Filter /Adobe.PubSec /SubFilter /adbe.pkcs7.s4 /V 2 /Length 128 /Recipients [<{}> <{}>] >>",
+            hex(broken), hex(envelope)
+        );
+        let data = document(&dict, &title);
+        assert_eq!(title_of(data.clone(), &recipient()), "pubsec rc4");
+        let mut err = open(data, &other()).unwrap_err();
+        while let PdfError::Try { source, .. } = err {
+            err = *source;
+        }
+        assert!(matches!(err, PdfError::NotRecipient));
+    }
+
+    fn title_of(data: Vec<u8>, recipient: &Recipient) -> String {
+        open(data, recipient).unwrap().0
+    }
+
+    #[test]
+    fn s5_aesv3() {
+        let envelope = include_bytes!("../../files/pubsec/envelope_oaep.der");
+        let mut hash = Sha256::new();
+        hash.update(&SEED[..20]);
+        hash.update(envelope);
+        let key = hash.finalize();
+
+        let title = encrypt_cbc::<cbc::Encryptor<aes::Aes256>>(&key, b"pubsec aes-256");
+        let dict = format!(
+            "<< /Filter /Adobe.PubSec /SubFilter /adbe.pkcs7.s5 /V 5 /Length 256 /StmF /DefaultCryptFilter /StrF /DefaultCryptFilter \
+            /CF << /DefaultCryptFilter << /CFM /AESV3 /Length 256 /Recipients [<{}>] >> >> >>",
+            hex(envelope)
+        );
+        let data = document(&dict, &title);
+        assert_eq!(title_of(data.clone(), &recipient()), "pubsec aes-256");
+        let mut err = open(data, &other()).unwrap_err();
+        while let PdfError::Try { source, .. } = err {
+            err = *source;
+        }
+        assert!(matches!(err, PdfError::NotRecipient));
+    }
+}
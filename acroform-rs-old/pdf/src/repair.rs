@@ -1,42 +1,214 @@
+//! Recovery of files whose cross-reference information can not be read.
+//!
+//! The file is scanned for `N G obj` headers and `trailer` keywords.
+//! Stream data is skipped, as it may contain anything, even other PDF files.
+//! `Storage` rebuilds the xref table from them when `ParseOptions::allow_xref_error` is set.
+//!
+//! The file is read in chunks, so only the positions found are kept in memory.
 
-fn build_xref_table() {
-    warn!("can't read xref table: {:?}", e);
-    let start_offset = t!(backend.locate_start_offset());
-    let mut lexer = Lexer::new(t!(backend.read(..)));
-    let mut objects = Vec::new();
-
-    (|| -> Result<()> { loop {
-        let offset = lexer.get_pos();
-        let w1 = t!(lexer.next());
-        let w2 = t!(lexer.next());
-        let w3 = t!(lexer.next_expect("obj"));
-        try_opt!(lexer.seek_substr("endobj"));
-
-        objects.push((t!(w1.to::<ObjNr>()), t!(w2.to::<GenNr>()), offset));
-    }})();
-
-    objects.sort_unstable();
-    let mut first_id = objects.first().map(|&(n, _, _)| n).unwrap_or(0);
-    let mut last_id = objects.last().map(|&(n, _, _)| n).unwrap_or(0);
-    let mut xref = XRefTable::new(1 + last_id - first_id);
-    for &(obj_nr, gen_nr, offset) in objects.iter() {
-        for n in first_id + 1 .. obj_nr {
-            xref.push(XRef::Free { next_obj_nr: obj_nr, gen_nr: 0 });
-        }
-        if obj_nr == last_id {
-            warn!("duplicate obj_nr {}", obj_nr);
-            continue;
+use crate::backend::Backend;
+use crate::error::Result;
+use crate::object::{ObjNr, GenNr};
+
+/// Bytes scanned at once
+const CHUNK_SIZE: usize = 1 << 20;
+/// Bytes a chunk is read past the part that is scanned, for keywords crossing its end
+const LOOKAHEAD: usize = 16;
+/// Bytes a chunk is read before the part that is scanned, for the `N G` in front of `obj`
+const LOOKBEHIND: usize = 256;
+
+/// What looks like indirect objects and trailers in a file.
+#[derive(Debug, Default)]
+pub struct Scan {
+    /// Object number, generation number and position of each `N G obj` header, in file order.
+    pub objects: Vec<(ObjNr, GenNr, usize)>,
+    /// Position right after each `trailer` keyword, in file order.
+    pub trailers: Vec<usize>,
+    /// Position of each `/ObjStm` name outside of stream data, in file order.
+    pub object_streams: Vec<usize>,
+    /// Position of each `/XRef` name outside of stream data, in file order.
+    pub xref_streams: Vec<usize>,
+}
+
+#[inline]
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, 0 | b' ' | b'\r' | b'\n' | b'\t' | 0x0c)
+}
+
+#[inline]
+fn is_delimiter(b: u8) -> bool {
+    b"()<>[]{}/%".contains(&b)
+}
+
+/// true if a keyword may end right before `pos`
+fn keyword_ends(data: &[u8], pos: usize) -> bool {
+    data.get(pos).is_none_or(|&b| is_whitespace(b) || is_delimiter(b))
+}
+
+/// true if a keyword may start at `pos`
+fn keyword_starts(data: &[u8], pos: usize) -> bool {
+    pos == 0 || is_whitespace(data[pos - 1]) || is_delimiter(data[pos - 1])
+}
+
+/// Move `pos` back over whitespace. Returns false if there was none.
+fn skip_whitespace_back(data: &[u8], pos: &mut usize) -> bool {
+    let end = *pos;
+    while *pos > 0 && is_whitespace(data[*pos - 1]) {
+        *pos -= 1;
+    }
+    *pos < end
+}
+
+/// Move `pos` back over a decimal number and return it.
+fn number_back(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let end = *pos;
+    while *pos > 0 && data[*pos - 1].is_ascii_digit() {
+        *pos -= 1;
+    }
+    std::str::from_utf8(&data[*pos .. end]).ok()?.parse().ok()
+}
+
+/// Read `N G` backwards from the `obj` keyword at `pos`.
+fn object_header(data: &[u8], mut pos: usize) -> Option<(ObjNr, GenNr, usize)> {
+    if !skip_whitespace_back(data, &mut pos) {
+        return None;
+    }
+    let gen = number_back(data, &mut pos)?;
+    if !skip_whitespace_back(data, &mut pos) {
+        return None;
+    }
+    let id = number_back(data, &mut pos)?;
+    if !keyword_starts(data, pos) {
+        return None;
+    }
+    Some((id, gen, pos))
+}
+
+/// Find everything that looks like an object header or a trailer in `backend`, from `start` on.
+/// Positions are relative to `start`.
+///
+/// The data of streams, from the `stream` keyword to the next `endstream`, is skipped.
+pub fn scan(backend: &impl Backend, start: usize) -> Result<Scan> {
+    scan_chunks(backend, start, CHUNK_SIZE)
+}
+
+fn scan_chunks(backend: &impl Backend, start: usize, chunk_size: usize) -> Result<Scan> {
+    let len = backend.len().saturating_sub(start);
+    let mut result = Scan::default();
+    let mut pos = 0;
+    // the `stream` keyword whose data is skipped
+    let mut stream = None;
+    // without an end, the rest of the file is scanned as usual, and no later stream has an end either
+    let mut skip_streams = true;
+    'chunks: while pos < len {
+        let offset = pos.saturating_sub(LOOKBEHIND);
+        let end = (pos + chunk_size).min(len);
+        let data = t!(backend.read(start + offset .. start + end));
+        let limit = match end == len {
+            true => data.len(),
+            false => data.len() - LOOKAHEAD,
+        };
+
+        let mut i = pos - offset;
+        while i < limit {
+            if let Some(stream_pos) = stream {
+                match data[i ..].windows(9).position(|w| w == b"endstream") {
+                    Some(n) if i + n < limit => {
+                        stream = None;
+                        i += n + 9;
+                    }
+                    _ if end < len => i = limit,
+                    _ => {
+                        stream = None;
+                        skip_streams = false;
+                        pos = stream_pos + 1;
+                        continue 'chunks;
+                    }
+                }
+                continue;
+            }
+            let rest = &data[i ..];
+            if rest.starts_with(b"obj") && keyword_ends(&data, i + 3) {
+                match object_header(&data, i) {
+                    // the header may be cut off by the start of the chunk
+                    Some((_, _, 0)) if offset > 0 => {}
+                    Some((id, gen, header)) => result.objects.push((id, gen, offset + header)),
+                    None => {}
+                }
+            } else if rest.starts_with(b"trailer") && keyword_starts(&data, i) && keyword_ends(&data, i + 7) {
+                result.trailers.push(offset + i + 7);
+            } else if rest.starts_with(b"/ObjStm") && keyword_ends(&data, i + 7) {
+                result.object_streams.push(offset + i);
+            } else if rest.starts_with(b"/XRef") && keyword_ends(&data, i + 5) {
+                result.xref_streams.push(offset + i);
+            } else if skip_streams && rest.starts_with(b"stream") && keyword_starts(&data, i) && keyword_ends(&data, i + 6) {
+                stream = Some(offset + i);
+                i += 6;
+                continue;
+            }
+            i += 1;
         }
-        xref.push(XRef::Raw {
-            pos: offset - start_offset,
-            gen_nr
-        });
-        last_id = obj_nr;
+        pos = offset + i;
     }
+    Ok(result)
+}
 
-    return t!(Err(e));
+/// true if one of the sorted `positions` is in `start .. end`
+pub fn any_within(positions: &[usize], start: usize, end: usize) -> bool {
+    let i = positions.partition_point(|&p| p < start);
+    positions.get(i).is_some_and(|&p| p < end)
 }
 
-fn build_catalog() {
-    
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_objects() {
+        let data = b"%PDF-1.4\n1 0 obj\n<</Type/Catalog>>\nendobj\n12 3 obj[1 0 R]endobj\n\
+            x1 0 obj 7 obj\ntrailer\n<</Root 1 0 R>>\ntrailers";
+        let scan = scan(&&data[..], 0).unwrap();
+        assert_eq!(scan.objects, [(1, 0, 9), (12, 3, 42)]);
+        assert_eq!(scan.trailers.len(), 1);
+        assert!(data[scan.trailers[0] ..].starts_with(b"\n<</Root"));
+    }
+
+    #[test]
+    fn scan_skips_stream_data() {
+        let data = b"1 0 obj\n<</Length 30>>stream\n2 0 obj\n<<>>\nendobj\ntrailer\nendstream\nendobj\n\
+            3 0 obj\n<<>>stream\n4 0 obj";
+        let scan = scan(&&data[..], 0).unwrap();
+        // the stream without an end is scanned
+        assert_eq!(scan.objects, [(1, 0, 0), (3, 0, 74), (4, 0, 93)]);
+        assert!(scan.trailers.is_empty());
+    }
+
+    #[test]
+    fn scan_in_chunks() {
+        let data = b"%PDF-1.5\n1 0 obj\n<</Type/ObjStm/Length 20>>stream\n2 0 obj\n<<>>\nendobj\nendstream\nendobj\n\
+            123 0 obj\n<</Type/XRef>>\nendobj\n   \n  45  6   obj\ntrailer\n<</Root 1 0 R>>\n\
+            7 0 obj\n<<>>stream\n8 0 obj";
+        let data = &data[..];
+        let whole = scan_chunks(&data, 0, data.len()).unwrap();
+        assert_eq!(whole.objects, [(1, 0, 9), (123, 0, 87), (45, 6, 125), (7, 0, 161), (8, 0, 180)]);
+        assert_eq!(whole.object_streams, [24]);
+        assert_eq!(whole.xref_streams, [104]);
+        assert_eq!(whole.trailers, [144]);
+
+        // keywords and headers across the edges of chunks are found once,
+        // also when chunks start in the middle of the file
+        let padded = [&[b'%'; LOOKBEHIND * 2][..], b"\n", data].concat();
+        let padded = &padded[..];
+        let shift = LOOKBEHIND * 2 + 1;
+        assert_eq!(scan_chunks(&padded, shift, padded.len()).unwrap().objects, whole.objects);
+        let whole = scan_chunks(&padded, 0, padded.len()).unwrap();
+        assert_eq!(whole.objects[0], (1, 0, shift + 9));
+        for chunk_size in LOOKAHEAD + 1 .. data.len() {
+            let chunked = scan_chunks(&padded, 0, chunk_size).unwrap();
+            assert_eq!(chunked.objects, whole.objects, "chunk size {}", chunk_size);
+            assert_eq!(chunked.trailers, whole.trailers, "chunk size {}", chunk_size);
+            assert_eq!(chunked.object_streams, whole.object_streams, "chunk size {}", chunk_size);
+            assert_eq!(chunked.xref_streams, whole.xref_streams, "chunk size {}", chunk_size);
+        }
+    }
 }
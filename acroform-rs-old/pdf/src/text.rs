@@ -0,0 +1,595 @@
+//! Text extraction from content streams
+//!
+//! [`page_text`] runs the content of a page through a small interpreter that tracks the
+//! graphics and text state and returns one [`TextRun`] per text showing operator, with the
+//! text mapped to Unicode and its position on the page. Form XObjects are followed.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::content::{Matrix, Op, TextDrawAdjusted, TextMode};
+use crate::encoding::glyph_to_unicode;
+use crate::error::*;
+use crate::font::{CMap, Code, Font, FontData, ToUnicodeMap, Widths};
+use crate::object::*;
+use crate::primitive::{Dictionary, Name, PdfString};
+
+/// Form XObjects nested deeper than this are skipped
+const MAX_FORM_DEPTH: usize = 32;
+
+/// Operators interpreted in total, including those of form XObjects, before the rest is skipped.
+/// Forms that draw other forms several times expand exponentially.
+const MAX_OPS: usize = 1_000_000;
+
+/// A `TJ` adjustment of more than this, in thousandths of the font size, reads as a space
+const WORD_GAP: f32 = 250.0;
+
+/// Text shown by one `Tj`, `TJ`, `'` or `"` operator
+#[derive(Debug, Clone)]
+pub struct TextRun {
+    /// The text, mapped to Unicode by the `ToUnicode` map, the glyph names of the encoding
+    /// or the character set of a bundled CMap.
+    /// Codes that can't be mapped become U+FFFD.
+    pub text: String,
+    /// Name of the font in the resources, or of the graphics state parameters that set it
+    pub font: Name,
+    /// The font size set by `Tf`
+    pub size: f32,
+    /// Text rendering matrix at the start of the run, from text space to default user space.
+    /// Its scale is the effective font size.
+    pub matrix: Matrix,
+    /// Bounds of the run in default user space, from the descent to the ascent of the font
+    pub bbox: Rectangle,
+    /// The text rendering mode, `Invisible` for the text layer of scans
+    pub mode: TextMode,
+}
+
+/// The text runs of `page` in content order
+pub fn page_text(page: &Page, resolve: &impl Resolve) -> Result<Vec<TextRun>> {
+    let contents = match page.contents {
+        Some(ref contents) => contents,
+        None => return Ok(vec![]),
+    };
+    let ops = t!(contents.operations(resolve));
+    let resources = t!(page.resources());
+    content_text(&ops, resources, resolve)
+}
+
+/// The text runs of `ops`, with fonts and form XObjects looked up in `resources`
+pub fn content_text(ops: &[Op], resources: &Resources, resolve: &impl Resolve) -> Result<Vec<TextRun>> {
+    let mut interpreter = Interpreter { resolve, runs: vec![], fonts: HashMap::new(), forms: vec![], ops_left: MAX_OPS };
+    let state = GraphicsState { ctm: Matrix::default(), text: TextState::default() };
+    interpreter.run(ops, resources, state)?;
+    Ok(interpreter.runs)
+}
+
+#[derive(Clone)]
+struct TextState {
+    font: Option<(Name, Arc<TextFont>)>,
+    size: f32,
+    char_space: f32,
+    word_space: f32,
+    /// `Tz` divided by 100
+    horiz_scale: f32,
+    leading: f32,
+    rise: f32,
+    mode: TextMode,
+}
+impl Default for TextState {
+    fn default() -> Self {
+        TextState {
+            font: None,
+            size: 0.0,
+            char_space: 0.0,
+            word_space: 0.0,
+            horiz_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            mode: TextMode::Fill,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    text: TextState,
+}
+
+struct Interpreter<'a, R> {
+    resolve: &'a R,
+    runs: Vec<TextRun>,
+    /// Fonts already looked at, the keys keep them from being freed and reused
+    fonts: HashMap<MaybeRef<Font>, Arc<TextFont>>,
+    /// The form XObjects being interpreted, a form that draws itself is skipped
+    forms: Vec<PlainRef>,
+    ops_left: usize,
+}
+
+impl<'a, R: Resolve> Interpreter<'a, R> {
+    /// Interpret `ops`, starting with `state`, which forms inherit from the operator that draws them
+    fn run(&mut self, ops: &[Op], resources: &Resources, mut state: GraphicsState) -> Result<()> {
+        let mut stack = vec![];
+        // text matrix and text line matrix
+        let mut tm = Matrix::default();
+        let mut tlm = Matrix::default();
+
+        for op in ops {
+            if self.ops_left == 0 {
+                break;
+            }
+            self.ops_left -= 1;
+            match *op {
+                Op::Save => stack.push(state.clone()),
+                Op::Restore => if let Some(saved) = stack.pop() {
+                    state = saved;
+                },
+                Op::Transform { matrix } => state.ctm = concat(matrix, state.ctm),
+                Op::GraphicsState { ref name } => {
+                    if let Some(&(font, size)) = resources.graphics_states.get(name).and_then(|gs| gs.font.as_ref()) {
+                        let font = self.text_font(t!(self.resolve.get(font)).into())?;
+                        state.text.font = Some((name.clone(), font));
+                        state.text.size = size;
+                    }
+                }
+                Op::BeginText => {
+                    tm = Matrix::default();
+                    tlm = tm;
+                }
+                Op::EndText => {}
+                Op::CharSpacing { char_space } => state.text.char_space = char_space,
+                Op::WordSpacing { word_space } => state.text.word_space = word_space,
+                Op::TextScaling { horiz_scale } => state.text.horiz_scale = horiz_scale / 100.0,
+                Op::Leading { leading } => state.text.leading = leading,
+                Op::TextRise { rise } => state.text.rise = rise,
+                Op::TextRenderMode { mode } => state.text.mode = mode,
+                Op::TextFont { ref name, size } => {
+                    state.text.font = match resources.fonts.get(name) {
+                        Some(font) => Some((name.clone(), self.text_font(t!(font.load(self.resolve)))?)),
+                        None => None,
+                    };
+                    state.text.size = size;
+                }
+                Op::MoveTextPosition { translation } => {
+                    tlm = concat(translate(translation.x, translation.y), tlm);
+                    tm = tlm;
+                }
+                Op::SetTextMatrix { matrix } => {
+                    tm = matrix;
+                    tlm = matrix;
+                }
+                Op::TextNewline => {
+                    tlm = concat(translate(0.0, -state.text.leading), tlm);
+                    tm = tlm;
+                }
+                Op::TextDraw { ref text } => {
+                    self.show(&state, &mut tm, std::slice::from_ref(&TextDrawAdjusted::Text(text.clone())));
+                }
+                Op::TextDrawAdjusted { ref array } => self.show(&state, &mut tm, array),
+                Op::XObject { ref name } if self.forms.len() < MAX_FORM_DEPTH => {
+                    let r = match resources.xobjects.get(name) {
+                        Some(&r) if !self.forms.contains(&r.get_inner()) => r,
+                        _ => continue,
+                    };
+                    let xobject = t!(self.resolve.get(r));
+                    if let XObject::Form(ref form) = *xobject {
+                        let dict = form.dict();
+                        let matrix = match dict.matrix {
+                            Some(ref m) => t!(Matrix::from_primitive(m.clone(), self.resolve)),
+                            None => Matrix::default(),
+                        };
+                        let ops = t!(form.operations(self.resolve));
+                        let form_resources = dict.resources.as_deref().unwrap_or(resources);
+                        let form_state = GraphicsState { ctm: concat(matrix, state.ctm), text: state.text.clone() };
+                        self.forms.push(r.get_inner());
+                        let result = self.run(&ops, form_resources, form_state);
+                        self.forms.pop();
+                        result?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn text_font(&mut self, font: MaybeRef<Font>) -> Result<Arc<TextFont>> {
+        if let Some(text_font) = self.fonts.get(&font) {
+            return Ok(text_font.clone());
+        }
+        let text_font = Arc::new(t!(TextFont::new(&font, self.resolve)));
+        self.fonts.insert(font, text_font.clone());
+        Ok(text_font)
+    }
+
+    /// Show the strings of `array`, moving the text matrix past them
+    fn show(&mut self, state: &GraphicsState, tm: &mut Matrix, array: &[TextDrawAdjusted]) {
+        let ts = &state.text;
+        let (name, font) = match ts.font {
+            Some((ref name, ref font)) => (name, font),
+            None => return,
+        };
+        let start = *tm;
+        let mut text = String::new();
+        // along the line: x, or y for vertical fonts, which goes down
+        let mut advance = 0.0;
+        // horizontal scaling does not apply to vertical fonts
+        let scale = if font.vertical { 1.0 } else { ts.horiz_scale };
+        for part in array {
+            match *part {
+                TextDrawAdjusted::Text(ref s) => {
+                    for code in font.codes(s) {
+                        font.push_unicode(code, &mut text);
+                        let mut step = font.advance(code) * ts.size + ts.char_space;
+                        if code == (Code { value: 32, len: 1 }) {
+                            step += ts.word_space;
+                        }
+                        advance += step * scale;
+                    }
+                }
+                TextDrawAdjusted::Spacing(adjustment) => {
+                    advance -= adjustment / 1000.0 * ts.size * scale;
+                    // positive adjustments move vertical text down, away from the previous glyph
+                    let gap = if font.vertical { adjustment } else { -adjustment };
+                    if gap > WORD_GAP && !text.is_empty() && !text.ends_with(' ') {
+                        text.push(' ');
+                    }
+                }
+            }
+        }
+
+        let to_user = concat(start, state.ctm);
+        let corners = if font.vertical {
+            *tm = concat(translate(0.0, advance), start);
+            // glyphs are centered on the line and hang from their origin, with the default `/DW2`
+            let (left, right) = (-ts.size / 2.0, ts.size / 2.0);
+            [(left, advance), (right, advance), (left, 0.0), (right, 0.0)].map(|(x, y)| apply(to_user, x, y + ts.rise))
+        } else {
+            *tm = concat(translate(advance, 0.0), start);
+            let bottom = font.descent * ts.size + ts.rise;
+            let top = font.ascent * ts.size + ts.rise;
+            [(0.0, bottom), (advance, bottom), (0.0, top), (advance, top)].map(|(x, y)| apply(to_user, x, y))
+        };
+        let bbox = Rectangle {
+            left: corners.iter().map(|p| p.0).fold(f32::INFINITY, f32::min),
+            bottom: corners.iter().map(|p| p.1).fold(f32::INFINITY, f32::min),
+            right: corners.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max),
+            top: corners.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max),
+        };
+        let scale = Matrix { a: ts.size * ts.horiz_scale, d: ts.size, f: ts.rise, ..Matrix::default() };
+        self.runs.push(TextRun {
+            text,
+            font: name.clone(),
+            size: ts.size,
+            matrix: concat(scale, to_user),
+            bbox,
+            mode: ts.mode,
+        });
+    }
+}
+
+/// What the interpreter needs of a font
+struct TextFont {
+    /// The CMap of composite fonts, simple fonts have single byte codes
+    cmap: Option<CMap>,
+    /// Glyphs are set from top to bottom
+    vertical: bool,
+    to_unicode: Option<ToUnicodeMap>,
+    /// Unicode of the single byte codes of simple fonts, from the glyph names
+    glyph_unicode: Vec<Option<char>>,
+    widths: Option<Widths>,
+    missing_width: f32,
+    /// From glyph space to text space, 1/1000 except for Type3 fonts
+    glyph_scale: f32,
+    /// Ascent and descent in text space for a font size of 1
+    ascent: f32,
+    descent: f32,
+}
+
+impl TextFont {
+    fn new(font: &Font, resolve: &impl Resolve) -> Result<TextFont> {
+        let cmap = t!(font.cmap(resolve));
+        let to_unicode = font.to_unicode(resolve).transpose()?;
+        let glyph_unicode = match cmap {
+            Some(_) => vec![],
+            None => (0 ..= 255u8).map(|code| font.glyph_name(code).and_then(glyph_to_unicode)).collect(),
+        };
+        let (ascent, descent) = font_extent(font);
+        let mut text_font = TextFont {
+            vertical: cmap.as_ref().is_some_and(|cmap| cmap.vertical),
+            cmap,
+            to_unicode,
+            glyph_unicode,
+            widths: font.widths(resolve)?,
+            missing_width: font.missing_width(),
+            glyph_scale: 0.001,
+            ascent: ascent / 1000.0,
+            descent: descent / 1000.0,
+        };
+        if let FontData::Other(ref dict) = font.data {
+            if matches!(font.subtype, crate::font::FontType::Type3) {
+                text_font.type3(dict, resolve)?;
+            }
+        }
+        Ok(text_font)
+    }
+
+    /// Widths and extent of a Type3 font, in the glyph space given by `/FontMatrix`
+    fn type3(&mut self, dict: &Dictionary, resolve: &impl Resolve) -> Result<()> {
+        let matrix = match dict.get("FontMatrix") {
+            Some(p) => Matrix::from_primitive(p.clone(), resolve)?,
+            None => Matrix { a: 0.001, d: 0.001, ..Matrix::default() },
+        };
+        self.glyph_scale = matrix.a;
+        if let (Some(first), Some(widths)) = (dict.get("FirstChar"), dict.get("Widths")) {
+            let first_char = first.as_integer()?.max(0) as usize;
+            let values = widths.clone().resolve(resolve)?.into_array()?.iter()
+                .map(|w| w.as_number())
+                .collect::<Result<Vec<f32>>>()?;
+            self.widths = Some(Widths::from_values(first_char, values));
+        }
+        if let Some(bbox) = dict.get("FontBBox") {
+            let bbox = Rectangle::from_primitive(bbox.clone(), resolve)?;
+            let (bottom, top) = (apply(matrix, 0.0, bbox.bottom).1, apply(matrix, 0.0, bbox.top).1);
+            if top > bottom {
+                self.ascent = top;
+                self.descent = bottom;
+            }
+        }
+        Ok(())
+    }
+
+    /// The codes of `s`, split by the CMap of composite fonts
+    fn codes<'s>(&'s self, s: &'s PdfString) -> Box<dyn Iterator<Item = Code> + 's> {
+        match self.cmap {
+            Some(ref cmap) => Box::new(cmap.codes(s.as_bytes())),
+            None => Box::new(s.as_bytes().iter().map(|&b| Code { value: b as u32, len: 1 })),
+        }
+    }
+
+    /// How far `code` moves the text position in text space for a font size of 1
+    ///
+    /// The width, or the default vertical displacement of `/DW2` for vertical fonts.
+    fn advance(&self, code: Code) -> f32 {
+        if self.vertical {
+            return -1.0;
+        }
+        // composite fonts have widths by CID
+        let index = match self.cmap {
+            Some(ref cmap) => cmap.cid(code),
+            None => Some(code.value),
+        };
+        let width = match (index, &self.widths) {
+            (Some(index), Some(widths)) => widths.get(index as usize),
+            _ => self.missing_width,
+        };
+        width * self.glyph_scale
+    }
+
+    fn push_unicode(&self, code: Code, text: &mut String) {
+        let mapped = u16::try_from(code.value).ok().and_then(|value| self.to_unicode.as_ref()?.get(value));
+        if let Some(unicode) = mapped {
+            text.push_str(unicode);
+        } else if let Some(unicode) = self.cmap.as_ref().and_then(|cmap| cmap.unicode(code)) {
+            text.push_str(&unicode);
+        } else if let Some(&Some(c)) = self.glyph_unicode.get(code.value as usize) {
+            text.push(c);
+        } else {
+            text.push(std::char::REPLACEMENT_CHARACTER);
+        }
+    }
+}
+
+/// Ascent and descent in glyph space, from the font descriptor or the standard 14 font metrics
+fn font_extent(font: &Font) -> (f32, f32) {
+    let descriptor = match font.data {
+        FontData::Type1(ref t) | FontData::TrueType(ref t) => t.font_descriptor.as_ref(),
+        FontData::CIDFontType0(ref c) | FontData::CIDFontType2(ref c) => Some(&c.font_descriptor),
+        FontData::Type0(ref t) => t.descendant_fonts.first().and_then(|f| match f.data {
+            FontData::CIDFontType0(ref c) | FontData::CIDFontType2(ref c) => Some(&c.font_descriptor),
+            _ => None,
+        }),
+        FontData::Other(_) => None,
+    };
+    match (descriptor.and_then(|d| d.ascent), descriptor.and_then(|d| d.descent)) {
+        (Some(ascent), Some(descent)) if ascent > descent => (ascent, descent),
+        _ => match font.standard_font() {
+            Some(standard) => (standard.ascent, standard.descent),
+            None => (750.0, -250.0),
+        }
+    }
+}
+
+/// `a` followed by `b`
+fn concat(a: Matrix, b: Matrix) -> Matrix {
+    Matrix {
+        a: a.a * b.a + a.b * b.c,
+        b: a.a * b.b + a.b * b.d,
+        c: a.c * b.a + a.d * b.c,
+        d: a.c * b.b + a.d * b.d,
+        e: a.e * b.a + a.f * b.c + b.e,
+        f: a.e * b.b + a.f * b.d + b.f,
+    }
+}
+
+fn translate(x: f32, y: f32) -> Matrix {
+    Matrix { e: x, f: y, ..Matrix::default() }
+}
+
+fn apply(m: Matrix, x: f32, y: f32) -> (f32, f32) {
+    (m.a * x + m.c * y + m.e, m.b * x + m.d * y + m.f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::parse_ops;
+    use crate::file::FileOptions;
+    use crate::font::write_cmap;
+    use crate::parser::{parse, ParseFlags};
+
+    fn resources(dict: &str, resolve: &impl Resolve) -> Resources {
+        let p = parse(dict.as_bytes(), resolve, ParseFlags::ANY).unwrap();
+        Resources::from_primitive(p, resolve).unwrap()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn concat_order() {
+        let scale = Matrix { a: 2.0, d: 2.0, ..Matrix::default() };
+        let m = concat(translate(10.0, 0.0), scale);
+        assert_eq!(apply(m, 1.0, 1.0), (22.0, 2.0));
+        let m = concat(scale, translate(10.0, 0.0));
+        assert_eq!(apply(m, 1.0, 1.0), (12.0, 2.0));
+    }
+
+    #[test]
+    fn simple_font_text_state() {
+        let resources = resources("<< /Font << /F1 << /Type /Font /Subtype /Type1 /BaseFont /Helvetica >> >> >>", &NoResolve);
+        let ops = parse_ops(b"BT /F1 10 Tf 2 Tc 5 Tw 50 Tz 12 TL 100 700 Td (A B) Tj T* [(A) -1000 (B)] TJ 3 Ts (x) ' ET", &NoResolve).unwrap();
+        let runs = content_text(&ops, &resources, &NoResolve).unwrap();
+        let texts: Vec<_> = runs.iter().map(|r| r.text.as_str()).collect();
+        assert_eq!(texts, ["A B", "A B", "x"]);
+
+        // A and B are 667 wide, the space 278; Tc adds 2 to each, Tw 5 to the space; Tz halves
+        let first = &runs[0];
+        assert_eq!(first.font.as_str(), "F1");
+        assert_close(first.bbox.left, 100.0);
+        assert_close(first.bbox.right, 100.0 + (8.67 + 9.78 + 8.67) / 2.0);
+        assert_close(first.bbox.bottom, 700.0 - 2.07);
+        assert_close(first.bbox.top, 700.0 + 7.18);
+        assert_close(first.matrix.a, 5.0);
+        assert_close(first.matrix.d, 10.0);
+
+        let second = &runs[1];
+        assert_close(second.bbox.right, 100.0 + (8.67 + 10.0 + 8.67) / 2.0);
+        assert_close(second.bbox.bottom, 688.0 - 2.07);
+
+        let third = &runs[2];
+        assert_close(third.bbox.bottom, 676.0 - 2.07 + 3.0);
+    }
+
+    #[test]
+    fn forms_inherit_text_state() {
+        let mut storage = FileOptions::cached().storage();
+        let form = Stream::new(FormDict { bbox: Rectangle { left: 0.0, bottom: 0.0, right: 100.0, top: 100.0 }, ..Default::default() }, b"BT (AB) Tj ET".to_vec());
+        let form = storage.create(form).unwrap().get_ref().get_inner().id;
+        let resolve = storage.resolver();
+        let resources = resources(&format!("<< /Font << /F1 << /Type /Font /Subtype /Type1 /BaseFont /Helvetica >> >>
+            /XObject << /X {} 0 R >> >>", form), &resolve);
+        let ops = parse_ops(b"BT /F1 10 Tf 2 Tc ET /X Do", &resolve).unwrap();
+        let runs = content_text(&ops, &resources, &resolve).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].text, "AB");
+        assert_eq!(runs[0].font.as_str(), "F1");
+        assert_close(runs[0].bbox.right, 6.67 * 2.0 + 2.0 * 2.0);
+    }
+
+    #[test]
+    fn self_drawing_form() {
+        let mut storage = FileOptions::cached().storage();
+        let promise = storage.promise::<Stream<FormDict>>();
+        let id = promise.get_inner().id;
+        let form_resources = resources(&format!("<< /Font << /F1 << /Type /Font /Subtype /Type1 /BaseFont /Helvetica >> >>
+            /XObject << /X {} 0 R >> >>", id), &NoResolve);
+        let form = FormDict {
+            bbox: Rectangle { left: 0.0, bottom: 0.0, right: 100.0, top: 100.0 },
+            resources: Some(MaybeRef::Direct(Arc::new(form_resources))),
+            ..Default::default()
+        };
+        storage.fulfill(promise, Stream::new(form, b"/X Do /X Do BT /F1 10 Tf (a) Tj ET /X Do".to_vec())).unwrap();
+        let resolve = storage.resolver();
+        let resources = resources(&format!("<< /XObject << /X {} 0 R >> >>", id), &resolve);
+        let ops = parse_ops(b"/X Do /X Do", &resolve).unwrap();
+        let runs = content_text(&ops, &resources, &resolve).unwrap();
+        // the form draws itself, but only the outermost drawing is followed
+        assert_eq!(runs.len(), 2);
+    }
+
+    #[test]
+    fn type0_font() {
+        let mut storage = FileOptions::cached().storage();
+        let to_unicode = ToUnicodeMap::create([(3, "\u{3a3}".into()), (4, "\u{3c9}".into())].into_iter());
+        let to_unicode = storage.create(Stream::new((), write_cmap(&to_unicode).into_bytes())).unwrap();
+        let font = format!("<< /Type /Font /Subtype /Type0 /BaseFont /X /Encoding /Identity-H /ToUnicode {} 0 R
+            /DescendantFonts [<< /Type /Font /Subtype /CIDFontType2 /BaseFont /X
+                /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >>
+                /FontDescriptor << /Type /FontDescriptor /FontName /X /Flags 4 /FontBBox [0 0 1000 1000]
+                    /ItalicAngle 0 /Ascent 800 /Descent -200 >>
+                /DW 500 /W [3 [600]] >>] >>", to_unicode.get_ref().get_inner().id);
+        let resolve = storage.resolver();
+        let resources = resources(&format!("<< /Font << /F1 {} >> >>", font), &resolve);
+        let ops = parse_ops(b"2 0 0 2 10 10 cm BT /F1 20 Tf <00030004> Tj ET", &resolve).unwrap();
+        let runs = content_text(&ops, &resources, &resolve).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].text, "\u{3a3}\u{3c9}");
+        assert_close(runs[0].bbox.left, 10.0);
+        assert_close(runs[0].bbox.right, 10.0 + (0.6 + 0.5) * 20.0 * 2.0);
+        assert_close(runs[0].bbox.bottom, 10.0 - 0.2 * 20.0 * 2.0);
+        assert_close(runs[0].bbox.top, 10.0 + 0.8 * 20.0 * 2.0);
+    }
+
+    fn cid_font(encoding: &str) -> String {
+        format!("<< /Type /Font /Subtype /Type0 /BaseFont /STSong-Light /Encoding {}
+            /DescendantFonts [<< /Type /Font /Subtype /CIDFontType0 /BaseFont /STSong-Light
+                /CIDSystemInfo << /Registry (Adobe) /Ordering (GB1) /Supplement 4 >>
+                /FontDescriptor << /Type /FontDescriptor /FontName /STSong-Light /Flags 6 /FontBBox [0 -200 1000 900]
+                    /ItalicAngle 0 /Ascent 880 /Descent -120 >>
+                /W [34 [500]] >>] >>", encoding)
+    }
+
+    #[test]
+    fn predefined_cmap() {
+        let gbk = resources(&format!("<< /Font << /F1 {} >> >>", cid_font("/GBK-EUC-H")), &NoResolve);
+        let ops = parse_ops(b"BT /F1 10 Tf <D6D0CEC441> Tj ET", &NoResolve).unwrap();
+        let runs = content_text(&ops, &gbk, &NoResolve).unwrap();
+        assert_eq!(runs[0].text, "\u{4E2D}\u{6587}A");
+        // the CIDs are not known, all codes are as wide as the default width
+        assert_close(runs[0].bbox.right, 30.0);
+
+        let vertical = resources(&format!("<< /Font << /F1 {} >> >>", cid_font("/UniGB-UCS2-V")), &NoResolve);
+        let ops = parse_ops(b"BT /F1 10 Tf 100 700 Td 1 Tc [<4E2D> -500 <6587>] TJ <0041> Tj ET", &NoResolve).unwrap();
+        let runs = content_text(&ops, &vertical, &NoResolve).unwrap();
+        assert_eq!(runs[0].text, "\u{4E2D}\u{6587}");
+        // down by the size less the character spacing, the adjustment moves up
+        assert_close(runs[0].bbox.left, 95.0);
+        assert_close(runs[0].bbox.right, 105.0);
+        assert_close(runs[0].bbox.top, 700.0);
+        assert_close(runs[0].bbox.bottom, 700.0 - 9.0 + 5.0 - 9.0);
+        assert_close(runs[1].bbox.top, 700.0 - 13.0);
+    }
+
+    #[test]
+    fn embedded_cmap() {
+        let mut storage = FileOptions::cached().storage();
+        let cmap = b"begincmap 2 begincodespacerange <00> <80> <8140> <FEFE> endcodespacerange
+            1 begincidrange <20> <7E> 1 endcidrange endcmap";
+        let cmap = storage.create(Stream::new((), cmap.to_vec())).unwrap();
+        let to_unicode = ToUnicodeMap::create([(0x41, "A".into()), (0xD6D0, "\u{4E2D}".into())].into_iter());
+        let to_unicode = storage.create(Stream::new((), write_cmap(&to_unicode).into_bytes())).unwrap();
+        let font = cid_font(&format!("{} 0 R /ToUnicode {} 0 R", cmap.get_ref().get_inner().id, to_unicode.get_ref().get_inner().id));
+        let resolve = storage.resolver();
+        let resources = resources(&format!("<< /Font << /F1 {} >> >>", font), &resolve);
+        let ops = parse_ops(b"BT /F1 10 Tf <41D6D0> Tj ET", &resolve).unwrap();
+        let runs = content_text(&ops, &resources, &resolve).unwrap();
+        assert_eq!(runs[0].text, "A\u{4E2D}");
+        // A is CID 34, the two byte code is not mapped
+        assert_close(runs[0].bbox.right, 5.0 + 10.0);
+    }
+
+    #[test]
+    fn type3_font() {
+        let resources = resources("<< /Font << /T3 << /Type /Font /Subtype /Type3 /FontBBox [0 0 500 1000]
+            /FontMatrix [0.002 0 0 0.002 0 0] /CharProcs << >> /Resources << >>
+            /Encoding << /Type /Encoding /Differences [65 /Aacute /uni0394] >>
+            /FirstChar 65 /LastChar 66 /Widths [250 500] >> >> >>", &NoResolve);
+        let ops = parse_ops(b"BT /T3 10 Tf (AB) Tj ET", &NoResolve).unwrap();
+        let runs = content_text(&ops, &resources, &NoResolve).unwrap();
+        assert_eq!(runs[0].text, "\u{c1}\u{394}");
+        assert_close(runs[0].bbox.right, (0.5 + 1.0) * 10.0);
+        assert_close(runs[0].bbox.bottom, 0.0);
+        assert_close(runs[0].bbox.top, 20.0);
+    }
+}
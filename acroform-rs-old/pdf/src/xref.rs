@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Formatter};
 use crate::error::*;
 use crate::object::*;
+use crate::primitive::Dictionary;
 use crate as pdf;
 use datasize::DataSize;
 
@@ -33,12 +34,13 @@ pub enum XRef {
 }
 
 impl XRef {
+    /// The generation number. Objects in streams, promised and invalid entries have generation 0.
     pub fn get_gen_nr(&self) -> GenNr {
         match *self {
             XRef::Free {gen_nr, ..}
             | XRef::Raw {gen_nr, ..} => gen_nr,
             XRef::Stream { .. } => 0, // TODO I think these always have gen nr 0?
-            _ => panic!()
+            XRef::Promised | XRef::Invalid => 0,
         }
     }
 }
@@ -110,12 +112,16 @@ impl XRefTable {
         for (i, &entry) in section.entries() {
             if let Some(dst) = self.entries.get_mut(i) {
                 // Early return if the entry we have has larger or equal generation number
-                let should_be_updated = match *dst {
-                    XRef::Raw { gen_nr: gen, .. } | XRef::Free { gen_nr: gen, .. }
+                let should_be_updated = match (*dst, entry) {
+                    // an object is not deleted by an older free entry,
+                    // like those of hybrid-reference files for objects in object streams
+                    (XRef::Raw { .. }, XRef::Free { .. }) => false,
+                    (XRef::Raw { gen_nr: gen, .. } | XRef::Free { gen_nr: gen, .. }, _)
                         => entry.get_gen_nr() > gen,
-                    XRef::Stream { .. } | XRef::Invalid
-                        => true,
-                    x => bail!("found {:?}", x)
+                    // sections are added newest first
+                    (XRef::Stream { .. }, _) => false,
+                    (XRef::Invalid, _) => true,
+                    (x, _) => bail!("found {:?}", x)
                 };
                 if should_be_updated {
                     *dst = entry;
@@ -148,10 +154,78 @@ impl XRefTable {
             index: vec![0, size as u32],
             prev: None,
             w: vec![1, a_w, b_w],
+            other: Dictionary::new(),
         };
         
         Ok(Stream::new(info, data))
     }
+
+    /// Write an xref stream listing only the given object numbers, which have to be sorted,
+    /// as used for the `/XRefStm` of hybrid-reference files.
+    pub fn write_stream_for(&self, ids: &[ObjNr]) -> Result<Stream<XRefInfo>> {
+        let (max_a, max_b) = self.max_field_widths();
+        let a_w = byte_len(max_a);
+        let b_w = byte_len(max_b);
+
+        let mut data = Vec::with_capacity((1 + a_w + b_w) * ids.len());
+        let mut index = vec![];
+        for run in runs(ids) {
+            index.extend([run[0] as u32, run.len() as u32]);
+            for &id in run {
+                let (t, a, b) = match self.get(id)? {
+                    XRef::Free { next_obj_nr, gen_nr } => (0, next_obj_nr, gen_nr),
+                    XRef::Raw { pos, gen_nr } => (1, pos as u64, gen_nr),
+                    XRef::Stream { stream_id, index } => (2, stream_id, index as u64),
+                    XRef::Invalid | XRef::Promised => (0, 0, 0),
+                };
+                data.push(t);
+                data.extend_from_slice(&a.to_be_bytes()[8 - a_w ..]);
+                data.extend_from_slice(&b.to_be_bytes()[8 - b_w ..]);
+            }
+        }
+        let info = XRefInfo {
+            size: self.len() as u32,
+            index,
+            prev: None,
+            w: vec![1, a_w, b_w],
+            other: Dictionary::new(),
+        };
+
+        Ok(Stream::new(info, data))
+    }
+
+    /// Write a classic `xref` section listing the given object numbers, which have to be sorted.
+    /// Runs of consecutive numbers are written as one subsection.
+    pub fn write_table(&self, ids: &[ObjNr], out: &mut impl std::io::Write) -> Result<()> {
+        writeln!(out, "xref")?;
+        for run in runs(ids) {
+            writeln!(out, "{} {}", run[0], run.len())?;
+            for &id in run {
+                let (a, b, kind) = match self.get(id)? {
+                    XRef::Free { next_obj_nr, gen_nr } => (next_obj_nr, gen_nr, 'f'),
+                    XRef::Raw { pos, gen_nr } => (pos as u64, gen_nr, 'n'),
+                    XRef::Stream { .. } => bail!("object {} is in an object stream and needs an xref stream", id),
+                    XRef::Invalid | XRef::Promised => (0, 0, 'f'),
+                };
+                // every entry is exactly 20 bytes long
+                write!(out, "{:010} {:05} {}\r\n", a, b, kind)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split sorted object numbers into runs of consecutive numbers
+fn runs(mut ids: &[ObjNr]) -> impl Iterator<Item=&[ObjNr]> {
+    std::iter::from_fn(move || {
+        let &first = ids.first()?;
+        let len = ids.iter().enumerate()
+            .take_while(|&(i, &id)| id == first + i as ObjNr)
+            .count();
+        let (run, rest) = ids.split_at(len);
+        ids = rest;
+        Some(run)
+    })
 }
 
 fn byte_len(n: u64) -> usize {
@@ -228,6 +302,9 @@ pub struct XRefInfo {
 
     #[pdf(key = "W")]
     pub w: Vec<usize>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
 }
 
 // read_xref_table
@@ -1,7 +1,8 @@
 use std::str;
 use std::path::{Path, PathBuf};
-use pdf::file::FileOptions;
+use pdf::file::{FileOptions, SaveOptions, XRefFormat};
 use pdf::object::*;
+use pdf::primitive::{PdfString, Primitive};
 use pdf::parser::{parse, ParseFlags};
 use glob::glob;
 
@@ -90,22 +91,56 @@ fn owner_password() {
 #[test]
 fn invalid_pdfs() {
     for path in dir_pdfs(file_path("invalid")) {
-        let path = path.to_str().unwrap();
-        println!("\n\n == Now testing `{}` ==\n", path);
+        println!("\n\n == Now testing `{}` ==\n", path.display());
+        let data = std::fs::read(&path).unwrap();
 
-        match FileOptions::cached().open(path) {
-            Ok(file) => {
-                for i in 0 .. file.num_pages() {
-                    let _ = file.get_page(i);
-                }
-            }
-            Err(_) => {
+        for options in [ParseOptions::strict(), ParseOptions::tolerant()] {
+            let Ok(mut file) = FileOptions::cached().parse_options(options).load(data.clone()) else {
                 continue;
+            };
+            let resolver = file.resolver();
+            for i in 0 .. file.num_pages() {
+                let Ok(page) = file.get_page(i) else { continue };
+                if let Some(ref contents) = page.contents {
+                    let _ = contents.operations(&resolver);
+                }
+                let Ok(resources) = page.resources() else { continue };
+                for font in resources.fonts.values() {
+                    if let Ok(font) = font.load(&resolver) {
+                        let _ = font.widths(&resolver);
+                        let _ = font.to_unicode(&resolver);
+                    }
+                }
+                for &xobject in resources.xobjects.values() {
+                    if let Ok(XObject::Image(image)) = resolver.get(xobject).as_deref() {
+                        let _ = image.image_data(&resolver);
+                    }
+                }
             }
+            drop(resolver);
+            let _ = file.revisions();
+            let _ = file.save();
         }
     }
 }
 
+#[test]
+fn unsupported_features_are_errors() {
+    use pdf::enc::{fax_decode, CCITTFaxDecodeParams};
+    use pdf::primitive::{Dictionary, Primitive};
+
+    // /K defaults to 0, which selects group 3 coding
+    let params = run!(CCITTFaxDecodeParams::from_dict(Dictionary::new(), &NoResolve));
+    assert_eq!(params.k, 0);
+    assert!(fax_decode(&[0; 16], &params).is_err());
+
+    // type 3 (stitching) functions are not supported
+    let mut dict = Dictionary::new();
+    dict.insert("FunctionType", 3);
+    dict.insert("Domain", vec![Primitive::Number(0.0), Primitive::Number(1.0)]);
+    assert!(Function::from_primitive(Primitive::Dictionary(dict), &NoResolve).is_err());
+}
+
 #[cfg(feature="cache")]
 #[test]
 fn parse_objects_from_stream() {
@@ -123,4 +158,813 @@ fn parse_objects_from_stream() {
     }
 }
 
+#[test]
+fn save_compacted() {
+    for path in dir_pdfs(files()) {
+        println!("\n == Now testing `{}` ==", path.to_str().unwrap());
+
+        let original = std::fs::read(&path).unwrap();
+        let mut file = run!(FileOptions::uncached().load(original.clone()));
+        if file.trailer.encrypt_dict.is_some() {
+            assert!(file.save_compacted().is_err());
+            continue;
+        }
+        let compacted = run!(file.save_compacted());
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+
+        let reopened = run!(FileOptions::uncached().load(compacted));
+        assert_eq!(reopened.num_pages(), file.num_pages());
+        for i in 0 .. reopened.num_pages() {
+            run!(reopened.get_page(i));
+        }
+
+        file.set_save_options(SaveOptions { object_streams: true, ..Default::default() });
+        let packed = run!(file.save_compacted());
+        assert!(packed.starts_with(b"%PDF-1.") && &packed[5..8] >= b"1.5".as_slice());
+        let reopened = run!(FileOptions::uncached().load(packed));
+        assert_eq!(reopened.num_pages(), file.num_pages());
+    }
+}
+
+// Compacting leaves the file alone, so a later incremental update is the same.
+#[test]
+fn save_after_save_compacted() {
+    for path in dir_pdfs(files()) {
+        println!("\n == Now testing `{}` ==", path.to_str().unwrap());
+        let original = std::fs::read(&path).unwrap();
+        let mut file = run!(FileOptions::uncached().load(original.clone()));
+        if file.trailer.encrypt_dict.is_some() {
+            continue;
+        }
+        let has_info = file.trailer.info_dict.is_some();
+        let compacted = run!(file.save_compacted());
+        let reopened = run!(FileOptions::uncached().load(compacted));
+        assert_eq!(reopened.trailer.info_dict.is_some(), has_info);
+
+        let saved = run!(file.save());
+        let mut fresh = run!(FileOptions::uncached().load(original));
+        assert_eq!(saved, run!(fresh.save()));
+    }
+}
+
+// Names with spaces, delimiters or non-ASCII characters, as found in the catalogs
+// of many files, have to be written escaped when objects are saved again.
+#[test]
+fn save_escapes_names() {
+    use pdf::primitive::{Dictionary, Primitive};
+
+    let names = ["Adobe Acrobat (32-bit)", "Gr\u{f6}\u{df}e", "a/b#c"];
+    let mut file = run!(FileOptions::uncached().open(file_path("example.pdf")));
+    let mut dict = Dictionary::new();
+    for (i, name) in names.iter().enumerate() {
+        dict.insert(format!("N{}", i), Primitive::Name((*name).into()));
+    }
+    let r = run!(file.create(Primitive::Dictionary(dict))).get_ref().get_inner();
+    let saved = run!(file.save());
+
+    let reopened = run!(FileOptions::uncached().load(saved));
+    let dict = run!(run!(reopened.resolver().resolve(r)).into_dictionary());
+    for (i, name) in names.iter().enumerate() {
+        assert_eq!(run!(dict[format!("N{}", i).as_str()].as_name()), *name);
+    }
+}
+
+// Objects that are not dictionaries or streams, such as numbers and references, read back the same.
+#[test]
+fn save_plain_objects() {
+    for options in [SaveOptions::default(), SaveOptions { object_streams: true, ..Default::default() }] {
+        let mut file = run!(FileOptions::uncached().open(file_path("example.pdf")));
+        let number = run!(file.create(Primitive::Integer(42))).get_ref().get_inner();
+        let reference = run!(file.create(Primitive::Reference(number))).get_ref().get_inner();
+        let name = run!(file.create(Primitive::Name("Name".into()))).get_ref().get_inner();
+        file.set_save_options(options);
+        let saved = run!(file.save());
+
+        let reopened = run!(FileOptions::uncached().load(saved));
+        let resolver = reopened.resolver();
+        assert_eq!(run!(resolver.resolve(number)), Primitive::Integer(42));
+        assert_eq!(run!(resolver.resolve(reference)), Primitive::Integer(42));
+        assert_eq!(run!(resolver.resolve(name)), Primitive::Name("Name".into()));
+    }
+}
+
+#[test]
+fn save_xref_formats() {
+    let options = [
+        SaveOptions::default(),
+        SaveOptions { xref: XRefFormat::Table, ..Default::default() },
+        SaveOptions { xref: XRefFormat::Stream, ..Default::default() },
+        SaveOptions { object_streams: true, ..Default::default() },
+        SaveOptions { xref: XRefFormat::Hybrid, object_streams: true, ..Default::default() },
+    ];
+    for path in dir_pdfs(files()) {
+        for options in options.iter() {
+            println!("\n == Now testing `{}` with {:?} ==", path.to_str().unwrap(), options);
+
+            let original = std::fs::read(&path).unwrap();
+            let mut file = run!(FileOptions::uncached().load(original.clone()));
+            if file.trailer.encrypt_dict.is_some() {
+                continue;
+            }
+            let old_version = run!(file.version());
+            let old_catalog_version = file.trailer.root.version.clone();
+            let root = file.trailer.root.get_ref().get_inner();
+            let catalog = run!(file.resolver().resolve(root));
+            run!(file.update(root, catalog));
+
+            file.set_save_options(options.clone());
+            let saved = run!(file.save());
+            // the original, header included, is kept as it is
+            assert!(saved.starts_with(&original));
+            let update = &saved[original.len() ..];
+
+            let (table, xref_stream) = match options.xref {
+                XRefFormat::Auto => {
+                    let stream = options.object_streams || old_version.as_str() >= "PDF-1.5";
+                    (!stream, stream)
+                }
+                XRefFormat::Table => (true, false),
+                XRefFormat::Stream => (false, true),
+                XRefFormat::Hybrid => (true, true),
+            };
+            assert_eq!(update.windows(6).any(|w| w == b"\nxref\n"), table);
+            assert_eq!(update.windows(8).any(|w| w == b"/XRefStm"), table && xref_stream);
+            assert_eq!(update.windows(13).any(|w| w == b"/Type /ObjStm"), options.object_streams);
+            assert_eq!(run!(file.version()), old_version);
+            let catalog_version = file.trailer.root.version.as_ref().map(|v| v.as_str());
+            if xref_stream && old_version.as_str() < "PDF-1.5" && old_catalog_version.is_none() {
+                assert_eq!(catalog_version, Some("1.5"));
+            } else {
+                assert_eq!(catalog_version, old_catalog_version.as_ref().map(|v| v.as_str()));
+            }
+
+            let reopened = run!(FileOptions::uncached().load(saved));
+            assert_eq!(reopened.num_pages(), file.num_pages());
+            for i in 0 .. reopened.num_pages() {
+                run!(reopened.get_page(i));
+            }
+        }
+    }
+}
+
+// Objects of an object stream that are saved again, packed or not, have to be read from the newest section.
+#[test]
+fn save_over_object_stream_entries() {
+
+    let mut data = std::fs::read(file_path("xelatex.pdf")).unwrap();
+    let file = run!(FileOptions::uncached().load(data.clone()));
+    // .. we know that object 13 of that file is an ObjectStream
+    let obj_stream: RcRef<ObjectStream> = run!(file.resolver().get(Ref::new(PlainRef {id: 13, gen: 0})));
+    let id = obj_stream.object_ids().iter().copied()
+        .find(|&id| matches!(file.resolver().resolve(PlainRef { id, gen: 0 }), Ok(Primitive::Dictionary(_))))
+        .unwrap();
+    let r = PlainRef { id, gen: 0 };
+
+    let options = [
+        SaveOptions { object_streams: true, ..Default::default() },
+        SaveOptions { object_streams: true, ..Default::default() },
+        SaveOptions { xref: XRefFormat::Table, ..Default::default() },
+        SaveOptions { object_streams: true, ..Default::default() },
+    ];
+    for (i, options) in options.into_iter().enumerate() {
+        let mut file = run!(FileOptions::uncached().load(data));
+        let mut dict = run!(run!(file.resolver().resolve(r)).into_dictionary());
+        dict.insert("Saved", i as i32);
+        run!(file.update(r, Primitive::Dictionary(dict)));
+        file.set_save_options(options);
+        data = run!(file.save());
+
+        let reopened = run!(FileOptions::uncached().load(data.clone()));
+        let dict = run!(run!(reopened.resolver().resolve(r)).into_dictionary());
+        assert_eq!(dict.get("Saved"), Some(&Primitive::Integer(i as i32)), "save {}", i);
+    }
+}
+
+#[test]
+fn save_compressed_streams() {
+    use pdf::enc::{StreamFilter, LZWFlateParams};
+
+    let content = b"BT /F1 12 Tf 72 712 Td (Hello) Tj ET\n".repeat(100);
+    let original = std::fs::read(file_path("example.pdf")).unwrap();
+
+    let mut sizes = vec![];
+    for compress_above in [None, Some(1024), Some(content.len())] {
+        let mut file = run!(FileOptions::uncached().load(original.clone()));
+        let small = run!(file.create(Stream::new((), &b"q Q"[..])));
+        let large = run!(file.create(Stream::new((), content.clone())));
+        let mut encoded = Stream::new((), content.clone());
+        run!(encoded.encode(StreamFilter::FlateDecode(LZWFlateParams { predictor: 12, columns: 37, ..Default::default() })));
+        run!(encoded.encode(StreamFilter::ASCII85Decode));
+        let encoded = run!(file.create(encoded));
+
+        file.set_save_options(SaveOptions { compress_above, ..Default::default() });
+        let saved = run!(file.save());
+        sizes.push(saved.len());
+
+        let reopened = run!(FileOptions::uncached().load(saved));
+        let resolver = reopened.resolver();
+        let small: RcRef<Stream<()>> = run!(resolver.get(small.get_ref()));
+        let large: RcRef<Stream<()>> = run!(resolver.get(large.get_ref()));
+        let encoded: RcRef<Stream<()>> = run!(resolver.get(encoded.get_ref()));
+        assert!(small.filters.is_empty());
+        assert_eq!(large.filters.is_empty(), compress_above != Some(1024));
+        assert_eq!(encoded.filters.len(), 2);
+        assert_eq!(&*run!(Stream::data(&small, &resolver)), b"q Q");
+        assert_eq!(&*run!(Stream::data(&large, &resolver)), &content[..]);
+        assert_eq!(&*run!(Stream::data(&encoded, &resolver)), &content[..]);
+    }
+    assert!(sizes[1] + content.len() / 2 < sizes[0]);
+    assert_eq!(sizes[2], sizes[0]);
+}
+
+#[test]
+fn repair_damaged_xref() {
+    fn rfind(data: &[u8], needle: &[u8]) -> usize {
+        data.windows(needle.len()).rposition(|w| w == needle).unwrap()
+    }
+
+    for path in dir_pdfs(files()) {
+        if path.file_name().unwrap().to_str().unwrap().starts_with("encrypted") {
+            continue;
+        }
+        println!("{:?}", path);
+        let original = std::fs::read(&path).unwrap();
+        let expected = run!(FileOptions::uncached().load(original.clone())).num_pages();
+
+        // startxref points into nowhere
+        let mut bad_offset = original.clone();
+        let start = rfind(&bad_offset, b"startxref") + b"startxref".len();
+        let digits = bad_offset[start ..].iter().skip_while(|b| b.is_ascii_whitespace()).take_while(|b| b.is_ascii_digit()).count();
+        let start = start + bad_offset[start ..].iter().take_while(|b| b.is_ascii_whitespace()).count();
+        bad_offset[start .. start + digits].fill(b'7');
+
+        assert!(FileOptions::uncached().load(bad_offset.clone()).is_err());
+
+        // everything after the last object is missing,
+        // an earlier startxref of an incremental update may still be usable
+        let truncated = original[.. rfind(&original, b"endobj") + b"endobj".len()].to_vec();
+
+        for damaged in [bad_offset, truncated] {
+            let readable = FileOptions::uncached().load(damaged.clone()).is_ok();
+            let file = run!(FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(damaged));
+            assert_eq!(file.repaired(), !readable);
+            assert_eq!(file.num_pages(), expected);
+            for page in file.pages() {
+                run!(page);
+            }
+        }
+
+        let file = run!(FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(original));
+        assert!(!file.repaired());
+    }
+}
+
+// Objects and trailers inside stream data are not taken for real ones.
+#[test]
+fn repair_ignores_stream_data() {
+    let data = std::fs::read(file_path("repair/stream_with_objects.pdf")).unwrap();
+    assert!(FileOptions::uncached().load(data.clone()).is_err());
+    let file = run!(FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(data));
+    assert!(file.repaired());
+    assert_eq!(file.num_pages(), 1);
+    run!(file.get_page(0));
+}
+
+// An update of a repaired file doesn't need the damaged xref sections.
+#[test]
+fn save_repaired() {
+    for name in ["example.pdf", "xelatex.pdf"] {
+        println!("{}", name);
+        let mut data = std::fs::read(file_path(name)).unwrap();
+        let start = data.windows(9).rposition(|w| w == b"startxref").unwrap() + b"startxref\n".len();
+        data[start] = b'9';
+        assert!(FileOptions::uncached().load(data.clone()).is_err());
+
+        let mut file = run!(FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(data));
+        assert!(file.repaired());
+        let page = run!(file.get_page(0)).get_ref().get_inner();
+        let mut dict = run!(run!(file.resolver().resolve(page)).into_dictionary());
+        dict.insert("Rotate", 90);
+        run!(file.update(page, Primitive::Dictionary(dict)));
+        let saved = run!(file.save());
+
+        let reopened = run!(FileOptions::uncached().parse_options(ParseOptions::strict()).load(saved));
+        assert_eq!(reopened.num_pages(), file.num_pages());
+        assert_eq!(run!(reopened.get_page(0)).rotate, 90);
+        for page in reopened.pages() {
+            run!(page);
+        }
+    }
+}
+
+#[test]
+fn revisions() {
+    let plain = dir_pdfs(files()).filter(|p| !p.file_name().unwrap().to_str().unwrap().starts_with("encrypted"));
+    for path in plain.chain(dir_pdfs(file_path("password_protected"))) {
+        println!("{:?}", path);
+        let file = run!(FileOptions::uncached().password(b"userpassword").open(&path));
+        let revisions = run!(file.revisions());
+        assert!(!revisions.is_empty());
+        assert_eq!(revisions[0].range.start, 0);
+        for pair in revisions.windows(2) {
+            assert_eq!(pair[0].range.end, pair[1].range.start);
+        }
+        for revision in &revisions {
+            assert!(revision.range.start < revision.range.end);
+            // no password needed
+            let old = run!(FileOptions::uncached().load_revision(&file, revision));
+            assert!(old.trailer.size <= file.trailer.size);
+        }
+        let last = run!(FileOptions::uncached().load_revision(&file, revisions.last().unwrap()));
+        assert_eq!(last.num_pages(), file.num_pages());
+    }
+
+    let original = std::fs::read(file_path("example.pdf")).unwrap();
+    let base = run!(run!(FileOptions::uncached().load(original.clone())).revisions()).len();
+
+    let mut file = run!(FileOptions::uncached().load(original));
+    let created = run!(file.create(PdfString::from("first"))).get_ref().get_inner();
+    let saved = run!(file.save());
+    let mut file = run!(FileOptions::uncached().load(saved));
+    run!(file.update(created, PdfString::from("second")));
+    let saved = run!(file.save());
+
+    let file = run!(FileOptions::uncached().load(saved.clone()));
+    let revisions = run!(file.revisions());
+    assert_eq!(revisions.len(), base + 2);
+    assert_eq!(revisions.last().unwrap().range.end, saved.len());
+    let first = &revisions[base];
+    let second = &revisions[base + 1];
+    assert!(first.added.contains(&created));
+    assert!(second.changed.contains(&created));
+    assert!(!second.added.contains(&created));
+    assert_eq!(second.trailer.get("Prev").unwrap().as_usize().unwrap(), first.xref_offset);
+
+    for (revision, value) in [(first, "first"), (second, "second")] {
+        let old = run!(FileOptions::uncached().load_revision(&file, revision));
+        let s: RcRef<PdfString> = run!(old.resolver().get(Ref::new(created)));
+        assert_eq!(s.to_string_lossy(), value);
+    }
+    let old = run!(FileOptions::uncached().load_revision(&file, &revisions[base - 1]));
+    assert!(old.resolver().get::<PdfString>(Ref::new(created)).is_err());
+}
+
+#[cfg(feature="cache")]
+#[test]
+fn hybrid_reference_file() {
+    // a classic table whose compressed objects are listed in the `/XRefStm` stream
+    let file = run!(FileOptions::cached().open(file_path("ep.pdf")));
+    let revisions = run!(file.revisions());
+    let last = revisions.last().unwrap();
+    assert!(last.trailer.get("XRefStm").is_some());
+    assert!(last.added.len() > 2000);
+
+    let resolver = file.resolver();
+    for &r in &last.added {
+        run!(resolver.resolve(r));
+    }
+    for i in 0 .. file.num_pages() {
+        run!(file.get_page(i));
+    }
+}
+
+#[test]
+fn seek_backend() {
+    use pdf::backend::SeekBackend;
+    use std::io::Cursor;
+
+    let plain = dir_pdfs(files()).filter(|p| !p.file_name().unwrap().to_str().unwrap().starts_with("encrypted"));
+    for path in plain.chain(dir_pdfs(file_path("password_protected"))) {
+        println!("{:?}", path);
+        let data = std::fs::read(&path).unwrap();
+        let in_memory = run!(FileOptions::uncached().password(b"userpassword").load(data.clone()));
+        // a single page of cache
+        let backend = run!(SeekBackend::with_cache_size(Cursor::new(data.clone()), 0));
+        let mut file = run!(FileOptions::uncached().password(b"userpassword").load(backend));
+
+        assert_eq!(file.num_pages(), in_memory.num_pages());
+        for (a, b) in file.pages().zip(in_memory.pages()) {
+            let (a, b) = (run!(a), run!(b));
+            assert_eq!(a.get_ref(), b.get_ref());
+            assert_eq!(
+                a.contents.as_ref().map(|c| c.parts.len()),
+                b.contents.as_ref().map(|c| c.parts.len())
+            );
+        }
+        if file.trailer.encrypt_dict.is_some() {
+            continue;
+        }
+
+        // scanning reads windows of the file, the same objects as from memory
+        let items = |items: Vec<pdf::error::Result<pdf::file::ScanItem>>| items.into_iter()
+            .map(|item| format!("{:?}", run!(item)))
+            .collect::<Vec<_>>();
+        assert_eq!(items(file.scan().collect()), items(in_memory.scan().collect()));
+
+        // the streamed update is the same as an in-memory save
+        let mut in_memory = run!(FileOptions::uncached().load(data.clone()));
+        let created = run!(file.create(PdfString::from("new"))).get_ref();
+        run!(in_memory.create(PdfString::from("new")));
+        let mut streamed = vec![];
+        run!(file.save_to_writer(&mut streamed));
+        assert_eq!(streamed, run!(in_memory.save()));
+
+        let reopened = run!(FileOptions::uncached().load(streamed));
+        let s: RcRef<PdfString> = run!(reopened.resolver().get(created));
+        assert_eq!(s.to_string_lossy(), "new");
+        assert_eq!(run!(file.revisions()).len() + 1, run!(reopened.revisions()).len());
+    }
+}
+
+#[test]
+fn save_to_writer_after_garbage() {
+    // the original is copied in chunks of 1 MiB, with the header across the first two
+    let original = std::fs::read(file_path("libreoffice.pdf")).unwrap();
+    assert!(original.starts_with(b"%PDF-1.4"));
+    let mut data = vec![b'x'; (1 << 20) - 6];
+    data.extend_from_slice(&original);
+
+    assert!(FileOptions::uncached().load(data.clone()).is_err());
+    let mut file = run!(FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(data.clone()));
+    file.set_save_options(SaveOptions { xref: XRefFormat::Stream, ..Default::default() });
+    let created = run!(file.create(PdfString::from("new"))).get_ref();
+    let mut saved = vec![];
+    run!(file.save_to_writer(&mut saved));
+    assert!(saved.starts_with(&data));
+
+    // the xref stream needs PDF 1.5, which the catalog says instead of the header
+    let reopened = run!(FileOptions::uncached().parse_options(ParseOptions::tolerant()).load(saved));
+    assert_eq!(run!(reopened.version()), "PDF-1.4");
+    assert_eq!(reopened.get_root().version.as_ref().map(|v| v.as_str()), Some("1.5"));
+    let s: RcRef<PdfString> = run!(reopened.resolver().get(created));
+    assert_eq!(s.to_string_lossy(), "new");
+}
+
 // TODO test decoding
+
+#[test]
+fn seek_backend_window() {
+    use pdf::backend::{Backend, SeekBackend};
+    use pdf::error::PdfError;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Counting(Cursor<Vec<u8>>, Arc<AtomicUsize>);
+    impl Read for Counting {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.read(buf)?;
+            self.1.fetch_add(n, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+    impl Seek for Counting {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    let page = SeekBackend::<Cursor<Vec<u8>>>::PAGE_SIZE;
+    let data = b"word ".repeat(4 * page);
+    let read = Arc::new(AtomicUsize::new(0));
+    let backend = run!(SeekBackend::with_cache_size(Counting(Cursor::new(data.clone()), read.clone()), 0));
+
+    // a parse error is not retried with more data
+    let result: Result<(), _> = backend.read_from(0, |_| Err(PdfError::Other { msg: "bad".into() }));
+    assert!(result.is_err());
+    assert_eq!(read.load(Ordering::Relaxed), page);
+
+    // running out of data is, and windows end after whitespace
+    read.store(0, Ordering::Relaxed);
+    let len = run!(backend.read_from(0, |data| {
+        assert!(data.ends_with(b" "));
+        match data.len() < 2 * page {
+            true => Err(PdfError::EOF),
+            false => Ok(data.len()),
+        }
+    }));
+    // the second window of four pages, up to its last space
+    assert_eq!(len, 4 * page - 4);
+
+    // but only up to the largest window
+    let backend = run!(SeekBackend::with_cache_size(Cursor::new(data), 0)).max_window(2 * page);
+    let mut largest = 0;
+    let result: Result<(), _> = backend.read_from(0, |data| {
+        largest = data.len();
+        Err(PdfError::EOF)
+    });
+    assert!(result.is_err());
+    assert!(largest <= 2 * page);
+}
+
+#[test]
+fn resource_limits() {
+    use pdf::enc::{StreamFilter, LZWFlateParams};
+    use pdf::error::PdfError;
+
+    fn innermost(e: &PdfError) -> &PdfError {
+        match e {
+            PdfError::Try { source, .. } | PdfError::FromPrimitive { source, .. } => innermost(source),
+            PdfError::Shared { source } => innermost(source),
+            e => e,
+        }
+    }
+
+    let original = std::fs::read(file_path("example.pdf")).unwrap();
+    let mut file = run!(FileOptions::uncached().load(original.clone()));
+    let mut bomb = Stream::new((), vec![0u8; 1 << 20]);
+    run!(bomb.encode(StreamFilter::FlateDecode(LZWFlateParams::default())));
+    let bomb = run!(file.create(bomb));
+    // five objects, each only a reference to the next one
+    let mut chain = run!(file.create(Primitive::Integer(1))).get_ref().get_inner();
+    for _ in 0 .. 4 {
+        chain = run!(file.create(Primitive::Reference(chain))).get_ref().get_inner();
+    }
+    let saved = run!(file.save());
+
+    let load = |options: ParseOptions| FileOptions::uncached().parse_options(options).load(saved.clone());
+
+    let file = run!(load(ParseOptions { max_stream_size: 1 << 16, ..ParseOptions::strict() }));
+    let stream: RcRef<Stream<()>> = run!(file.resolver().get(bomb.get_ref()));
+    let e = Stream::data(&stream, &file.resolver()).unwrap_err();
+    assert!(matches!(innermost(&e), PdfError::StreamTooLarge { limit: 65536 }), "{:?}", e);
+
+    let file = run!(load(ParseOptions { max_total_decoded: 1 << 16, ..ParseOptions::strict() }));
+    let stream: RcRef<Stream<()>> = run!(file.resolver().get(bomb.get_ref()));
+    let e = Stream::data(&stream, &file.resolver()).unwrap_err();
+    assert!(matches!(innermost(&e), PdfError::DecodedTotalTooLarge { limit: 65536 }), "{:?}", e);
+
+    // decoding the same stream again does not count again
+    let file = run!(load(ParseOptions { max_total_decoded: (1 << 20) + 1, ..ParseOptions::strict() }));
+    for _ in 0 .. 3 {
+        let stream: RcRef<Stream<()>> = run!(file.resolver().get(bomb.get_ref()));
+        assert_eq!(run!(Stream::data(&stream, &file.resolver())).len(), 1 << 20);
+    }
+
+    let e = load(ParseOptions { max_objects: 3, ..ParseOptions::strict() }).err().unwrap();
+    assert!(matches!(innermost(&e), PdfError::TooManyObjects { limit: 3, .. }), "{:?}", e);
+
+    let mut file = run!(load(ParseOptions::strict()));
+    file.set_options(ParseOptions { max_depth: 3, max_collection_len: 4, ..ParseOptions::strict() });
+    {
+        let resolver = file.resolver();
+        run!(parse(b"[[[1]] 2 3 4]", &resolver, ParseFlags::ANY));
+        let e = parse(b"[[[[1]]]]", &resolver, ParseFlags::ANY).unwrap_err();
+        assert!(matches!(innermost(&e), PdfError::MaxDepth), "{:?}", e);
+        let e = parse(b"[1 2 3 4 5]", &resolver, ParseFlags::ANY).unwrap_err();
+        assert!(matches!(innermost(&e), PdfError::TooManyElements { limit: 4 }), "{:?}", e);
+        let e = parse(b"<</A 1 /B 2 /C 3 /D 4 /E 5>>", &resolver, ParseFlags::ANY).unwrap_err();
+        assert!(matches!(innermost(&e), PdfError::TooManyElements { limit: 4 }), "{:?}", e);
+    }
+    // references are limited on their own
+    assert_eq!(run!(file.resolver().resolve(chain)), Primitive::Integer(1));
+
+    file.set_options(ParseOptions { max_reference_depth: 4, ..ParseOptions::strict() });
+    let e = file.resolver().resolve(chain).unwrap_err();
+    assert!(matches!(innermost(&e), PdfError::MaxReferenceDepth { limit: 4 }), "{:?}", e);
+}
+
+#[test]
+fn garbage_and_wrong_lengths() {
+    fn content<B: pdf::backend::Backend>(data: B, options: ParseOptions) -> pdf::error::Result<Vec<u8>> {
+        let file = FileOptions::uncached().parse_options(options).load(data)?;
+        let resolver = file.resolver();
+        let page = file.get_page(0)?;
+        let contents = page.contents.as_ref().ok_or(pdf::error::PdfError::Invalid)?;
+        Ok(Stream::data(&contents.parts[0], &resolver)?.to_vec())
+    }
+    let replace = |data: &[u8], from: &[u8], to: &[u8]| -> Vec<u8> {
+        let pos = data.windows(from.len()).position(|w| w == from).unwrap();
+        [&data[.. pos], to, &data[pos + from.len() ..]].concat()
+    };
+
+    let original = std::fs::read(file_path("example.pdf")).unwrap();
+    let expected = run!(content(original.clone(), ParseOptions::strict()));
+
+    // mail headers longer than the 1 KB the header is normally searched in
+    let mut mail = b"From: someone@example.com\r\nContent-Type: application/pdf\r\n".to_vec();
+    mail.extend(std::iter::repeat_n(b"X-Padding: garbage\r\n", 100).flatten());
+    mail.extend_from_slice(b"\r\n");
+    let with_header = [&mail[..], &original].concat();
+    assert!(content(with_header.clone(), ParseOptions::strict()).is_err());
+    assert_eq!(run!(content(with_header, ParseOptions::tolerant())), expected);
+
+    // ... or longer than the first window of a SeekBackend
+    let mut junk = mail.clone();
+    junk.extend(std::iter::repeat_n(b"X-Padding: garbage\r\n", 4000).flatten());
+    assert!(junk.len() > pdf::backend::SeekBackend::<std::io::Cursor<Vec<u8>>>::PAGE_SIZE);
+    let with_junk = [&junk[..], &original].concat();
+    let backend = run!(pdf::backend::SeekBackend::new(std::io::Cursor::new(with_junk)));
+    assert_eq!(run!(content(backend, ParseOptions::tolerant())), expected);
+
+    // trailing garbage after %%EOF that looks like the end of a file
+    let with_trailer = [&original[..], b"\r\n--boundary--\r\nstartxref\r\n123\r\n%%EOF\r\n"].concat();
+    assert!(content(with_trailer.clone(), ParseOptions::strict()).is_err());
+    assert_eq!(run!(content(with_trailer, ParseOptions::tolerant())), expected);
+
+    // the indirect /Length of the content stream is wrong.
+    // the file counts the end of line in front of `endstream` as data, the recovered stream doesn't.
+    let expected = &expected[.. expected.len() - 1];
+    let wrong_length = replace(&original, b"4 0 obj\n132", b"4 0 obj\n100");
+    assert!(content(wrong_length.clone(), ParseOptions::strict()).is_err());
+    assert_eq!(run!(content(wrong_length, ParseOptions::tolerant())), expected);
+
+    // ... or points to a missing object
+    let missing_length = replace(&original, b"/Length 4 0 R", b"/Length 8 0 R");
+    assert!(content(missing_length.clone(), ParseOptions::strict()).is_err());
+    assert_eq!(run!(content(missing_length, ParseOptions::tolerant())), expected);
+}
+
+#[test]
+fn error_location() {
+    use pdf::error::{Location, PathSegment};
+    use pdf::object::NoResolve;
+
+    // an entry of the wrong type in the page
+    let original = std::fs::read(file_path("example.pdf")).unwrap();
+    let pos = original.windows(5).position(|w| w == b"240 ]").unwrap();
+    let mut data = original.clone();
+    data[pos .. pos + 3].copy_from_slice(b"(4)");
+
+    let file = run!(FileOptions::uncached().load(data));
+    let e = file.get_page(0).err().unwrap();
+    assert_eq!(e.location(), Some(Location {
+        object: Some(PlainRef { id: 6, gen: 0 }),
+        path: vec![PathSegment::Key("MediaBox".into())],
+        offset: Some(317),
+    }));
+
+    // ... or in a form field, found from the trailer
+    let mut file = run!(FileOptions::uncached().load(original));
+    let field = |s: &[u8]| run!(parse(s, &NoResolve, ParseFlags::ANY));
+    let good = run!(file.create(field(b"<</FT/Tx/T(good)>>"))).get_ref().get_inner();
+    let bad = run!(file.create(field(b"<</FT/Tx/T(bad)/Ff 1234>>"))).get_ref().get_inner();
+    let catalog = format!("<</Type/Catalog/Pages 2 0 R/AcroForm<</Fields[{} {} R {} {} R]>>>>", good.id, good.gen, bad.id, bad.gen);
+    run!(file.update(PlainRef { id: 1, gen: 0 }, field(catalog.as_bytes())));
+    let mut saved = run!(file.save());
+    let pos = saved.windows(4).position(|w| w == b"1234").unwrap();
+    saved[pos .. pos + 4].copy_from_slice(b"(12)");
+
+    let e = FileOptions::uncached().load(saved).err().unwrap();
+    let location = e.location().unwrap();
+    assert_eq!(location.object, Some(bad));
+    assert!(location.offset.is_some());
+    assert_eq!(location.to_string().split(" (").next(), Some("Root/AcroForm/Fields[1]/Ff"));
+}
+
+// Every entry of a dictionary has to survive parsing and writing it as the matching type.
+#[test]
+fn round_trip_keeps_unknown_keys() {
+    use pdf::primitive::{Dictionary, Primitive};
+    use pdf::font::FontDescriptor;
+
+    // writing empty values is optional
+    fn is_empty(p: &Primitive, r: &impl Resolve) -> bool {
+        match p {
+            Primitive::Null => true,
+            Primitive::Dictionary(d) => d.is_empty(),
+            Primitive::Array(a) => a.is_empty(),
+            Primitive::Reference(id) => r.resolve(*id).is_ok_and(|p| !matches!(p, Primitive::Reference(_)) && is_empty(&p, r)),
+            _ => false,
+        }
+    }
+
+    // the keys of `dict` that are lost when writing it back as `$ty`
+    macro_rules! lost_keys {
+        ($ty:ty, $dict:expr, $file:expr, $path:expr, $checked:expr) => ({
+            let parsed = <$ty as Object>::from_primitive(Primitive::Dictionary($dict.clone()), &$file.resolver())
+                .unwrap_or_else(|e| panic!("{:?}: can't read {}: {:?}", $path, stringify!($ty), e));
+            let written = ObjectWrite::to_primitive(&parsed, &mut $file).and_then(|p| p.into_dictionary())
+                .unwrap_or_else(|e| panic!("{:?}: can't write {}: {:?}", $path, stringify!($ty), e));
+            $checked += 1;
+            $dict.iter()
+                .filter(|&(key, val)| !is_empty(val, &$file.resolver()) && !written.contains_key(key.as_str()))
+                .map(|(key, _)| format!("{} {}", stringify!($ty), key))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    let paths = dir_pdfs(files()).chain(dir_pdfs(files().parent().unwrap().join("acroform_files")));
+    let mut checked = 0;
+    for path in paths {
+        // files that can't be opened are covered by other tests
+        let Ok(mut file) = FileOptions::uncached().open(&path) else { continue };
+        let mut lost = vec![];
+
+        let mut dicts: Vec<Dictionary> = vec![];
+        for id in 1 .. file.trailer.size.max(0) as u64 {
+            if let Ok(Primitive::Dictionary(dict)) = file.resolver().resolve(PlainRef { id, gen: 0 }) {
+                dicts.push(dict);
+            }
+        }
+        let resolve_dict = |file: &pdf::file::File<_, _, _, _>, p: &Primitive| match p {
+            Primitive::Dictionary(d) => Some(d.clone()),
+            Primitive::Reference(r) => file.resolver().resolve(*r).ok().and_then(|p| p.into_dictionary().ok()),
+            _ => None,
+        };
+
+        for dict in dicts {
+            let typ = dict.get("Type").and_then(|t| t.as_name().ok()).map(|t| t.to_string());
+            match typ.as_deref() {
+                Some("Catalog") => {
+                    lost.extend(lost_keys!(Catalog, dict, file, path, checked));
+                    if let Some(forms) = dict.get("AcroForm").and_then(|p| resolve_dict(&file, p)) {
+                        lost.extend(lost_keys!(InteractiveFormDictionary, forms, file, path, checked));
+                    }
+                    if let Some(names) = dict.get("Names").and_then(|p| resolve_dict(&file, p)) {
+                        lost.extend(lost_keys!(NameDictionary, names, file, path, checked));
+                        let trees: Vec<_> = names.values().filter_map(|p| resolve_dict(&file, p)).collect();
+                        for tree in trees {
+                            lost.extend(lost_keys!(NameTree<Primitive>, tree, file, path, checked));
+                        }
+                    }
+                    if let Some(mark_info) = dict.get("MarkInfo").and_then(|p| resolve_dict(&file, p)) {
+                        lost.extend(lost_keys!(MarkInformation, mark_info, file, path, checked));
+                    }
+                }
+                Some("Pages") => lost.extend(lost_keys!(PageTree, dict, file, path, checked)),
+                Some("Page") => {
+                    lost.extend(lost_keys!(Page, dict, file, path, checked));
+                    if let Some(resources) = dict.get("Resources").and_then(|p| resolve_dict(&file, p)) {
+                        lost.extend(lost_keys!(Resources, resources, file, path, checked));
+                    }
+                }
+                Some("Outlines") => lost.extend(lost_keys!(Outlines, dict, file, path, checked)),
+                Some("FontDescriptor") => lost.extend(lost_keys!(FontDescriptor, dict, file, path, checked)),
+                _ => {}
+            }
+            if typ.as_deref() == Some("Annot") || dict.contains_key("FT") {
+                if dict.contains_key("FT") {
+                    lost.extend(lost_keys!(FieldDictionary, dict, file, path, checked));
+                }
+                if let Some(ap) = dict.get("AP").and_then(|p| resolve_dict(&file, p)) {
+                    lost.extend(lost_keys!(AppearanceStreams, ap, file, path, checked));
+                }
+            }
+        }
+        lost.sort();
+        lost.dedup();
+        assert!(lost.is_empty(), "{:?}: lost {:?}", path, lost);
+    }
+    assert!(checked > 100, "only {} dictionaries compared", checked);
+}
+
+#[test]
+fn save_keeps_unknown_trailer_keys() {
+
+    for name in ["example.pdf", "xelatex.pdf"] {
+        let mut file = run!(FileOptions::uncached().open(file_path(name)));
+        file.trailer.other.insert("PieceInfo", Primitive::Name("kept".into()));
+        let mut data = vec![];
+        run!(file.save_to_writer(&mut data));
+
+        let file = run!(FileOptions::uncached().load(data));
+        assert_eq!(file.trailer.other.get("PieceInfo"), Some(&Primitive::Name("kept".into())), "{}", name);
+        for key in ["Type", "W", "Index", "Length", "Filter", "XRefStm"] {
+            assert!(!file.trailer.other.contains_key(key), "{}: {} in trailer", name, key);
+        }
+    }
+}
+
+#[cfg(all(feature="threads", feature="cache"))]
+#[test]
+fn prefetch() {
+
+    // most objects of this file are in object streams
+    let file = run!(FileOptions::cached().open(file_path("xelatex.pdf")));
+    let refs: Vec<Ref<Primitive>> = (1 .. file.trailer.size as u64)
+        .map(|id| Ref::new(PlainRef { id, gen: 0 }))
+        .collect();
+    let prefetched = file.prefetch(&refs);
+    assert_eq!(prefetched.len(), refs.len());
+
+    let sequential = run!(FileOptions::uncached().open(file_path("xelatex.pdf")));
+    let resolver = sequential.resolver();
+    for (r, p) in refs.iter().zip(prefetched) {
+        match (p, resolver.get(*r)) {
+            (Ok(p), Ok(s)) => assert_eq!(*p, *s, "{:?}", r),
+            (Err(_), Err(_)) => {}
+            (p, s) => panic!("{:?}: prefetched {:?}, sequential {:?}", r, p.is_ok(), s.is_ok()),
+        }
+    }
+
+    run!(file.prefetch_annotations());
+}
+
+#[test]
+fn page_text_through_form_xobject() {
+    use pdf::text::page_text;
+
+    // the same text, once in the page content and once in a form XObject
+    for name in ["example.pdf", "formxobject.pdf"] {
+        let file = run!(FileOptions::cached().open(file_path(name)));
+        let page = run!(file.get_page(0));
+        let runs = run!(page_text(&page, &file.resolver()));
+        assert_eq!(runs.len(), 1, "{}", name);
+        assert_eq!(runs[0].text, "Hello World!");
+        assert_eq!(runs[0].font.as_str(), "F0");
+        assert_eq!(runs[0].size, 24.0);
+        assert!(runs[0].bbox.left > 25.0 && runs[0].bbox.right < 155.0, "{:?}", runs[0].bbox);
+        assert!(runs[0].bbox.bottom > 190.0 && runs[0].bbox.top < 220.0, "{:?}", runs[0].bbox);
+    }
+}
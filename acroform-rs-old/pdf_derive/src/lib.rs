@@ -598,9 +598,8 @@ fn impl_object_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
             return quote! {}
         }
         if attrs.other {
-            return quote! {
-                let #name = dict;
-            };
+            // takes whatever is left, see `other_part`
+            return quote! {}
         }
 
         let key = attrs.key();
@@ -617,7 +616,7 @@ fn impl_object_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
                                 typ: #typ,
                                 field: stringify!(#name),
                                 source: Box::new(e)
-                            })?,
+                            }.at_key(#key))?,
                         None => #default,
                     };
                     x
@@ -634,7 +633,7 @@ fn impl_object_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
                                     typ: stringify!(#ty),
                                     field: stringify!(#name),
                                     source: Box::new(e)
-                                })
+                                }.at_key(#key))
                             }
                         None =>  // Try to construct T from Primitive::Null
                             match <#ty as pdf::object::Object>::from_primitive(pdf::primitive::Primitive::Null, resolve) {
@@ -652,6 +651,14 @@ fn impl_object_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
         }
     });
 
+    // the unrecognized entries, after all known keys have been removed
+    let other_part = fields.iter()
+        .find(|field| FieldAttrs::parse(&field.attrs).other)
+        .map(|field| {
+            let name = &field.ident;
+            quote! { let #name = dict; }
+        });
+
     let field_parts = fields.iter().map(|field| {
         let name = &field.ident;
         quote! { #name: #name, }
@@ -676,6 +683,7 @@ fn impl_object_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
                 #ty_check
                 #( #checks )*
                 #( #let_parts )*
+                #other_part
                 Ok(#id {
                     #( #field_parts )*
                 })